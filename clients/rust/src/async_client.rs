@@ -1,18 +1,25 @@
 //! Asynchronous MerkleKV client implementation
 
+use crate::client::{numeric_command, string_command};
 use crate::error::{Error, Result};
+use crate::response::Response;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
 use tokio::time::timeout;
-use log::{debug, info};
+use log::{debug, info, warn};
 
 /// Connection pool for managing multiple TCP connections
+/// 
+/// At most `max_size` connections are checked out at once: each checkout holds
+/// a semaphore permit, and callers wait for one to free up instead of opening
+/// more sockets.
 #[derive(Debug)]
 struct ConnectionPool {
-    connections: Vec<TcpStream>,
+    connections: Mutex<Vec<TcpStream>>,
+    permits: Arc<Semaphore>,
     addr: String,
     max_size: usize,
 }
@@ -20,38 +27,56 @@ struct ConnectionPool {
 impl ConnectionPool {
     fn new(addr: String, max_size: usize) -> Self {
         ConnectionPool {
-            connections: Vec::new(),
+            connections: Mutex::new(Vec::new()),
+            permits: Arc::new(Semaphore::new(max_size)),
             addr,
             max_size,
         }
     }
     
-    async fn get_connection(&mut self) -> Result<TcpStream> {
-        if let Some(stream) = self.connections.pop() {
-            debug!("Reusing existing connection from pool");
-            Ok(stream)
-        } else {
-            debug!("Creating new connection to {}", self.addr);
-            let stream = TcpStream::connect(&self.addr)
-                .await
-                .map_err(|e| Error::connection(format!("Failed to connect to {}: {}", self.addr, e)))?;
-            
-            // Enable TCP_NODELAY for performance optimization
-            stream.set_nodelay(true)
-                .map_err(|e| Error::connection(format!("Failed to set TCP_NODELAY: {}", e)))?;
-            
-            Ok(stream)
-        }
+    /// Wait for a free slot, then take an idle connection or open a new one
+    async fn get_connection(&self) -> Result<(TcpStream, OwnedSemaphorePermit)> {
+        let permit = Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::connection("Connection pool closed"))?;
+        let idle = self.connections.lock().await.pop();
+        let stream = match idle {
+            Some(stream) => {
+                debug!("Reusing existing connection from pool");
+                stream
+            }
+            None => self.connect().await?,
+        };
+        Ok((stream, permit))
     }
     
-    async fn return_connection(&mut self, stream: TcpStream) {
-        if self.connections.len() < self.max_size {
+    /// Open a new connection, bypassing any idle ones in the pool
+    async fn connect(&self) -> Result<TcpStream> {
+        debug!("Creating new connection to {}", self.addr);
+        let stream = TcpStream::connect(&self.addr)
+            .await
+            .map_err(|e| Error::connection(format!("Failed to connect to {}: {}", self.addr, e)))?;
+        
+        // Enable TCP_NODELAY for performance optimization
+        stream.set_nodelay(true)
+            .map_err(|e| Error::connection(format!("Failed to set TCP_NODELAY: {}", e)))?;
+        
+        Ok(stream)
+    }
+    
+    /// Put a healthy connection back and release its slot. A connection that
+    /// failed is simply dropped along with its permit instead.
+    async fn return_connection(&self, stream: TcpStream, permit: OwnedSemaphorePermit) {
+        let mut connections = self.connections.lock().await;
+        if connections.len() < self.max_size {
             debug!("Returning connection to pool");
-            self.connections.push(stream);
+            connections.push(stream);
         } else {
             debug!("Pool is full, dropping connection");
             // Connection will be dropped automatically
         }
+        drop(permit);
     }
 }
 
@@ -78,7 +103,7 @@ impl ConnectionPool {
 /// }
 /// ```
 pub struct AsyncClient {
-    pool: Arc<Mutex<ConnectionPool>>,
+    pool: Arc<ConnectionPool>,
     timeout_duration: Duration,
 }
 
@@ -110,13 +135,17 @@ impl AsyncClient {
     /// # Arguments
     /// 
     /// * `addr` - Server address in format "host:port"
-    /// * `pool_size` - Maximum number of connections to maintain in the pool
+    /// * `pool_size` - Maximum number of connections open at once; operations beyond
+    ///   it wait for a connection to be returned. Must be at least 1
     /// * `timeout` - Operation timeout duration
     pub async fn connect_with_options(
         addr: &str, 
         pool_size: usize, 
         timeout_duration: Duration
     ) -> Result<Self> {
+        if pool_size == 0 {
+            return Err(Error::invalid_parameter("Pool size must be at least 1"));
+        }
         info!("Connecting to MerkleKV server at {} with pool size {}", addr, pool_size);
         
         // Test connection to ensure server is reachable
@@ -133,7 +162,7 @@ impl AsyncClient {
         info!("Connected to MerkleKV server at {}", addr);
         
         Ok(AsyncClient {
-            pool: Arc::new(Mutex::new(pool)),
+            pool: Arc::new(pool),
             timeout_duration,
         })
     }
@@ -172,14 +201,10 @@ impl AsyncClient {
         
         debug!("Received async response: {}", response);
         
-        if response == "NOT_FOUND" {
-            Err(Error::key_not_found(key))
-        } else if let Some(value) = response.strip_prefix("VALUE ") {
-            Ok(value.to_string())
-        } else if let Some(error) = response.strip_prefix("ERROR ") {
-            Err(Error::protocol(error))
-        } else {
-            Err(Error::invalid_response(response))
+        match Response::parse(&response)? {
+            Response::Value(value) => Ok(value),
+            Response::NotFound => Err(Error::key_not_found(key)),
+            other => Err(other.unexpected()),
         }
     }
     
@@ -215,12 +240,9 @@ impl AsyncClient {
         
         debug!("Received async response: {}", response);
         
-        if response == "OK" {
-            Ok(())
-        } else if let Some(error) = response.strip_prefix("ERROR ") {
-            Err(Error::protocol(error))
-        } else {
-            Err(Error::invalid_response(response))
+        match Response::parse(&response)? {
+            Response::Ok => Ok(()),
+            other => Err(other.unexpected()),
         }
     }
     
@@ -257,14 +279,10 @@ impl AsyncClient {
         
         debug!("Received async response: {}", response);
         
-        if response == "OK" {
-            Ok(true)
-        } else if response == "NOT_FOUND" {
-            Ok(false)
-        } else if let Some(error) = response.strip_prefix("ERROR ") {
-            Err(Error::protocol(error))
-        } else {
-            Err(Error::invalid_response(response))
+        match Response::parse(&response)? {
+            Response::Ok => Ok(true),
+            Response::NotFound => Ok(false),
+            other => Err(other.unexpected()),
         }
     }
     
@@ -294,14 +312,10 @@ impl AsyncClient {
                 .await
                 .map_err(|_| Error::timeout("GET operation timed out"))??;
                 
-                if response == "NOT_FOUND" {
-                    Err(Error::key_not_found(&key_str))
-                } else if let Some(value) = response.strip_prefix("VALUE ") {
-                    Ok(value.to_string())
-                } else if let Some(error) = response.strip_prefix("ERROR ") {
-                    Err(Error::protocol(error))
-                } else {
-                    Err(Error::invalid_response(response))
+                match Response::parse(&response)? {
+                    Response::Value(value) => Ok(value),
+                    Response::NotFound => Err(Error::key_not_found(&key_str)),
+                    other => Err(other.unexpected()),
                 }
             });
             
@@ -349,12 +363,9 @@ impl AsyncClient {
                 .await
                 .map_err(|_| Error::timeout("SET operation timed out"))??;
                 
-                if response == "OK" {
-                    Ok(())
-                } else if let Some(error) = response.strip_prefix("ERROR ") {
-                    Err(Error::protocol(error))
-                } else {
-                    Err(Error::invalid_response(response))
+                match Response::parse(&response)? {
+                    Response::Ok => Ok(()),
+                    other => Err(other.unexpected()),
                 }
             });
             
@@ -372,10 +383,90 @@ impl AsyncClient {
         results
     }
     
+    /// Increment the integer stored at a key
+    /// 
+    /// A missing key counts as 0.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `key` - The key to increment
+    /// * `amount` - Amount to add, 1 if `None`
+    /// 
+    /// # Returns
+    /// 
+    /// The value after the increment
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Timeout` if operation times out
+    /// * `Error::Io` if network communication fails
+    /// * `Error::Protocol` if the value is not an integer or would overflow
+    pub async fn increment<S: AsRef<str>>(&mut self, key: S, amount: Option<i64>) -> Result<i64> {
+        let command = numeric_command("INC", key.as_ref(), amount)?;
+        let response = self.send_with_timeout(&command, "INC").await?;
+        Response::parse(&response)?.into_integer()
+    }
+    
+    /// Decrement the integer stored at a key
+    /// 
+    /// A missing key counts as 0.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `key` - The key to decrement
+    /// * `amount` - Amount to subtract, 1 if `None`
+    /// 
+    /// # Returns
+    /// 
+    /// The value after the decrement
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Timeout` if operation times out
+    /// * `Error::Io` if network communication fails
+    /// * `Error::Protocol` if the value is not an integer or would overflow
+    pub async fn decrement<S: AsRef<str>>(&mut self, key: S, amount: Option<i64>) -> Result<i64> {
+        let command = numeric_command("DEC", key.as_ref(), amount)?;
+        let response = self.send_with_timeout(&command, "DEC").await?;
+        Response::parse(&response)?.into_integer()
+    }
+    
+    /// Append to the string stored at a key, creating it if missing
+    /// 
+    /// # Returns
+    /// 
+    /// The full value after appending
+    pub async fn append<S: AsRef<str>, V: AsRef<str>>(&mut self, key: S, value: V) -> Result<String> {
+        let command = string_command("APPEND", key.as_ref(), value.as_ref())?;
+        let response = self.send_with_timeout(&command, "APPEND").await?;
+        Response::parse(&response)?.into_value()
+    }
+    
+    /// Prepend to the string stored at a key, creating it if missing
+    /// 
+    /// # Returns
+    /// 
+    /// The full value after prepending
+    pub async fn prepend<S: AsRef<str>, V: AsRef<str>>(&mut self, key: S, value: V) -> Result<String> {
+        let command = string_command("PREPEND", key.as_ref(), value.as_ref())?;
+        let response = self.send_with_timeout(&command, "PREPEND").await?;
+        Response::parse(&response)?.into_value()
+    }
+    
+    /// Check whether a key exists
+    pub async fn exists<S: AsRef<str>>(&mut self, key: S) -> Result<bool> {
+        let key = key.as_ref();
+        if key.is_empty() {
+            return Err(Error::invalid_parameter("Key cannot be empty"));
+        }
+        let command = format!("EXISTS {}", key);
+        let response = self.send_with_timeout(&command, "EXISTS").await?;
+        Response::parse(&response)?.into_exists()
+    }
+    
     /// Get the server address this client is connected to
     pub async fn server_addr(&self) -> String {
-        let pool = self.pool.lock().await;
-        pool.addr.clone()
+        self.pool.addr.clone()
     }
     
     /// Send a command using the connection pool
@@ -383,20 +474,27 @@ impl AsyncClient {
         let pool = Arc::clone(&self.pool);
         send_command_with_pool(pool, command).await
     }
+    
+    /// Send a command, failing with `Error::Timeout` after the client timeout
+    async fn send_with_timeout(&mut self, command: &str, name: &str) -> Result<String> {
+        debug!("Sending async command: {}", command);
+        let response = timeout(self.timeout_duration, self.send_command(command))
+            .await
+            .map_err(|_| Error::timeout(format!("{} operation timed out", name)))??;
+        debug!("Received async response: {}", response);
+        Ok(response)
+    }
 }
 
 // Helper function to send command with connection pool
-async fn send_command_with_pool(pool: Arc<Mutex<ConnectionPool>>, command: &str) -> Result<String> {
-    let mut pool_guard = pool.lock().await;
-    let stream = pool_guard.get_connection().await?;
-    drop(pool_guard);
+async fn send_command_with_pool(pool: Arc<ConnectionPool>, command: &str) -> Result<String> {
+    let (stream, permit) = pool.get_connection().await?;
     
     let result = send_command_on_stream(stream, command).await;
     
     match result {
         Ok((response, stream)) => {
-            let mut pool_guard = pool.lock().await;
-            pool_guard.return_connection(stream).await;
+            pool.return_connection(stream, permit).await;
             Ok(response)
         }
         Err(e) => {
@@ -460,50 +558,53 @@ impl AsyncClient {
     /// * `Error::Io` if network communication fails
     /// * `Error::Protocol` if any command returns an error
     pub async fn pipeline(&mut self, commands: Vec<String>) -> Result<Vec<String>> {
+        self.pipeline_with_retry(commands, false).await
+    }
+
+    /// Execute a pipeline, optionally retrying it once if the connection breaks
+    /// 
+    /// A connection that fails mid-pipeline is never returned to the pool. Some
+    /// of its commands may already have been applied, so with `retry_idempotent`
+    /// set the whole pipeline is resent once on a fresh connection, but only if
+    /// every command is safe to repeat (reads, `SET`, `MSET`, `DEL`). A retried
+    /// `DEL` may report `NOT_FOUND` for a key the broken attempt removed.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `commands` - Vector of command strings to execute
+    /// * `retry_idempotent` - Retry once after a connection failure if all commands are idempotent
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Io` or `Error::Connection` if the connection fails (and cannot be retried)
+    /// * `Error::Protocol` if any command returns an error
+    pub async fn pipeline_with_retry(
+        &mut self,
+        commands: Vec<String>,
+        retry_idempotent: bool,
+    ) -> Result<Vec<String>> {
         if commands.is_empty() {
             return Ok(Vec::new());
         }
 
         debug!("Executing async pipeline with {} commands", commands.len());
 
-        let mut pool = self.pool.lock().await;
-        let stream = pool.get_connection().await?;
-        let (reader, writer) = stream.into_split();
-        let mut buf_reader = BufReader::new(reader);
-        let mut buf_writer = BufWriter::new(writer);
-
-        // Write all commands with CRLF termination
-        for command in &commands {
-            buf_writer.write_all(format!("{}\r\n", command).as_bytes()).await
-                .map_err(Error::io)?;
-        }
-
-        // Flush all commands at once
-        buf_writer.flush().await
-            .map_err(Error::io)?;
-
-        // Read responses in order
-        let mut responses = Vec::with_capacity(commands.len());
-        for (i, command) in commands.iter().enumerate() {
-            let mut response = String::new();
-            buf_reader.read_line(&mut response).await
-                .map_err(Error::io)?;
-            
-            let response = response.trim().to_string();
-            debug!("Async pipeline response {}: {}", i, response);
-
-            // Check for protocol errors
-            if let Some(error) = response.strip_prefix("ERROR ") {
-                return Err(Error::protocol(format!("Command '{}' failed: {}", command, error)));
+        let (stream, permit) = self.pool.get_connection().await?;
+        let (responses, stream) = match run_pipeline(stream, &commands).await {
+            Ok(done) => done,
+            Err(e) if retry_idempotent
+                && matches!(e, Error::Io { .. } | Error::Connection { .. })
+                && commands.iter().all(|command| is_idempotent(command)) =>
+            {
+                warn!("Pipeline connection failed ({}), retrying once on a fresh connection", e);
+                // The failed attempt's slot carries over to the retry
+                let stream = self.pool.connect().await?;
+                run_pipeline(stream, &commands).await?
             }
+            Err(e) => return Err(e),
+        };
 
-            responses.push(response);
-        }
-
-        // Reunite the stream and return it to the pool
-        let stream = buf_reader.into_inner().reunite(buf_writer.into_inner())?;
-        pool.return_connection(stream).await;
-
+        self.pool.return_connection(stream, permit).await;
         Ok(responses)
     }
 
@@ -540,6 +641,62 @@ impl AsyncClient {
     }
 }
 
+/// Write all `commands` at once and read their responses in order, handing
+/// the stream back only if every response arrived
+async fn run_pipeline(stream: TcpStream, commands: &[String]) -> Result<(Vec<String>, TcpStream)> {
+    let (reader, writer) = stream.into_split();
+    let mut buf_reader = BufReader::new(reader);
+    let mut buf_writer = BufWriter::new(writer);
+
+    // Write all commands with CRLF termination
+    for command in commands {
+        buf_writer.write_all(format!("{}\r\n", command).as_bytes()).await
+            .map_err(Error::io)?;
+    }
+
+    // Flush all commands at once
+    buf_writer.flush().await
+        .map_err(Error::io)?;
+
+    // Read responses in order
+    let mut responses = Vec::with_capacity(commands.len());
+    for (i, command) in commands.iter().enumerate() {
+        let mut response = String::new();
+        let read = buf_reader.read_line(&mut response).await
+            .map_err(Error::io)?;
+        if read == 0 {
+            return Err(Error::connection(format!(
+                "Server closed connection after {} of {} pipelined responses",
+                i,
+                commands.len()
+            )));
+        }
+        
+        let response = response.trim().to_string();
+        debug!("Async pipeline response {}: {}", i, response);
+
+        // Check for protocol errors
+        if let Some(error) = response.strip_prefix("ERROR ") {
+            return Err(Error::protocol(format!("Command '{}' failed: {}", command, error)));
+        }
+
+        responses.push(response);
+    }
+
+    let stream = buf_reader.into_inner().reunite(buf_writer.into_inner())?;
+    Ok((responses, stream))
+}
+
+/// Whether sending `command` twice leaves the store as sending it once
+fn is_idempotent(command: &str) -> bool {
+    let name = command.split_whitespace().next().unwrap_or("").to_uppercase();
+    matches!(
+        name.as_str(),
+        "GET" | "MGET" | "EXISTS" | "SCAN" | "HASH" | "PING" | "ECHO" | "DBSIZE"
+            | "HGET" | "HGETALL" | "HLEN" | "SET" | "MSET" | "DEL" | "DELETE"
+    )
+}
+
 // Implement Send and Sync for AsyncClient
 unsafe impl Send for AsyncClient {}
 unsafe impl Sync for AsyncClient {}
@@ -547,6 +704,7 @@ unsafe impl Sync for AsyncClient {}
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[tokio::test]
     async fn test_async_command_formatting() {
@@ -687,4 +845,127 @@ mod tests {
             assert!(result.contains(&format!("mkey{}", i + 1)));
         }
     }
+
+    /// Connection counts seen by `scripted_server`
+    #[derive(Default)]
+    struct ServerCounters {
+        accepted: AtomicUsize,
+        open: AtomicUsize,
+        max_open: AtomicUsize,
+    }
+
+    /// Accept connections and answer every command line with `OK` after a
+    /// short delay. The n-th accepted connection closes after `answers[n]`
+    /// replies (unlimited past the end of `answers`).
+    async fn scripted_server(answers: Vec<usize>) -> (String, Arc<ServerCounters>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let counters = Arc::new(ServerCounters::default());
+        let server_counters = Arc::clone(&counters);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let counters = Arc::clone(&server_counters);
+                let limit = answers.get(counters.accepted.fetch_add(1, Ordering::SeqCst)).copied().unwrap_or(usize::MAX);
+                let open = counters.open.fetch_add(1, Ordering::SeqCst) + 1;
+                counters.max_open.fetch_max(open, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    for _ in 0..limit {
+                        match lines.next_line().await {
+                            Ok(Some(_)) => {
+                                tokio::time::sleep(Duration::from_millis(5)).await;
+                                writer.write_all(b"OK\r\n").await.unwrap();
+                            }
+                            _ => break,
+                        }
+                    }
+                    counters.open.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        (addr, counters)
+    }
+
+    /// Connect and wait until the server has seen the reachability probe close
+    async fn connect_after_probe(addr: &str, pool_size: usize, counters: &ServerCounters) -> AsyncClient {
+        let client = AsyncClient::connect_with_options(addr, pool_size, Duration::from_secs(5)).await.unwrap();
+        while counters.open.load(Ordering::SeqCst) > 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        client
+    }
+
+    fn commands(list: &[&str]) -> Vec<String> {
+        list.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_disconnect_drops_dead_connection() {
+        // Connection 0 is the reachability probe in `connect_with_options`
+        let (addr, counters) = scripted_server(vec![usize::MAX, 1]).await;
+        let mut client = connect_after_probe(&addr, 4, &counters).await;
+
+        let result = client.pipeline(commands(&["SET a 1", "SET b 2", "SET c 3"])).await;
+        assert!(matches!(result, Err(Error::Connection { .. }) | Err(Error::Io { .. })));
+        assert_eq!(client.pool.connections.lock().await.len(), 0);
+
+        // The next pipeline opens a fresh connection instead of reusing the dead one
+        let responses = client.pipeline(commands(&["SET a 1", "SET b 2"])).await.unwrap();
+        assert_eq!(responses, vec!["OK", "OK"]);
+        assert_eq!(counters.accepted.load(Ordering::SeqCst), 3);
+        assert_eq!(client.pool.connections.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_retry_is_opt_in_and_idempotent_only() {
+        let (addr, counters) = scripted_server(vec![usize::MAX, 1]).await;
+        let mut client = connect_after_probe(&addr, 4, &counters).await;
+        let responses = client.pipeline_with_retry(commands(&["SET a 1", "GET a"]), true).await.unwrap();
+        assert_eq!(responses, vec!["OK", "OK"]);
+        assert_eq!(counters.accepted.load(Ordering::SeqCst), 3);
+
+        // INC is not safe to repeat, so the failure is returned as is
+        let (addr, counters) = scripted_server(vec![usize::MAX, 1]).await;
+        let mut client = connect_after_probe(&addr, 4, &counters).await;
+        assert!(client.pipeline_with_retry(commands(&["INC n", "GET n"]), true).await.is_err());
+        assert_eq!(counters.accepted.load(Ordering::SeqCst), 2);
+
+        // Without the flag nothing is retried
+        let (addr, counters) = scripted_server(vec![usize::MAX, 1]).await;
+        let mut client = connect_after_probe(&addr, 4, &counters).await;
+        assert!(client.pipeline_with_retry(commands(&["SET a 1", "GET a"]), false).await.is_err());
+        assert_eq!(counters.accepted.load(Ordering::SeqCst), 2);
+
+        // The retry happens at most once
+        let (addr, counters) = scripted_server(vec![usize::MAX, 1, 1]).await;
+        let mut client = connect_after_probe(&addr, 4, &counters).await;
+        assert!(client.pipeline_with_retry(commands(&["SET a 1", "GET a"]), true).await.is_err());
+        assert_eq!(counters.accepted.load(Ordering::SeqCst), 3);
+        assert_eq!(client.pool.connections.lock().await.len(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_pool_caps_concurrent_connections() {
+        let (addr, counters) = scripted_server(Vec::new()).await;
+        let mut client = connect_after_probe(&addr, 3, &counters).await;
+        counters.max_open.store(0, Ordering::SeqCst);
+
+        let pairs: Vec<(String, String)> = (0..20).map(|i| (format!("k{}", i), "v".to_string())).collect();
+        let results = client.mset(pairs).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+
+        // Callers beyond the pool size waited for a connection instead of opening one
+        assert!(counters.max_open.load(Ordering::SeqCst) <= 3);
+        assert_eq!(client.pool.connections.lock().await.len(), counters.open.load(Ordering::SeqCst));
+        assert!(client.pool.connections.lock().await.len() <= 3);
+    }
+
+    #[tokio::test]
+    async fn test_pool_size_must_be_positive() {
+        let (addr, _) = scripted_server(Vec::new()).await;
+        let result = AsyncClient::connect_with_options(&addr, 0, Duration::from_secs(5)).await;
+        assert!(matches!(result, Err(Error::InvalidParameter { .. })));
+    }
 }
@@ -1,7 +1,8 @@
 //! Synchronous MerkleKV client implementation
 
 use crate::error::{Error, Result};
-use std::io::{BufRead, BufReader, Write, BufWriter};
+use crate::response::{decode_compressed, Response};
+use std::io::{BufRead, BufReader, Read, Write, BufWriter};
 use std::net::TcpStream;
 use std::time::Duration;
 use log::{debug, info};
@@ -111,14 +112,36 @@ impl Client {
         let response = self.send_command(&command)?;
         debug!("Received response: {}", response);
         
-        if response == "NOT_FOUND" {
-            Err(Error::key_not_found(key))
-        } else if let Some(value) = response.strip_prefix("VALUE ") {
-            Ok(value.to_string())
-        } else if let Some(error) = response.strip_prefix("ERROR ") {
-            Err(Error::protocol(error))
-        } else {
-            Err(Error::invalid_response(response))
+        if let Some(header) = response.strip_prefix("VALUEZ ") {
+            return self.read_compressed(header);
+        }
+        match Response::parse(&response)? {
+            Response::Value(value) => Ok(value),
+            Response::NotFound => Err(Error::key_not_found(key)),
+            other => Err(other.unexpected()),
+        }
+    }
+    
+    /// Ask the server to send large `get` values compressed (`HELLO 1 COMPRESS`)
+    /// 
+    /// `get` decodes them transparently. Returns whether the server will
+    /// compress at all; a server with compression turned off reports `none`.
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Io` if network communication fails
+    /// * `Error::Protocol` if the server rejects the handshake
+    pub fn enable_compression(&mut self) -> Result<bool> {
+        let response = self.send_command("HELLO 1 COMPRESS")?;
+        if response != "HELLO" {
+            return Err(Response::parse(&response)?.unexpected());
+        }
+        // `key:value` lines follow, `compress:<algo>` last
+        loop {
+            let line = self.read_line()?;
+            if let Some(algo) = line.strip_prefix("compress:") {
+                return Ok(algo != "none");
+            }
         }
     }
     
@@ -147,12 +170,9 @@ impl Client {
         let response = self.send_command(&command)?;
         debug!("Received response: {}", response);
         
-        if response == "OK" {
-            Ok(())
-        } else if let Some(error) = response.strip_prefix("ERROR ") {
-            Err(Error::protocol(error))
-        } else {
-            Err(Error::invalid_response(response))
+        match Response::parse(&response)? {
+            Response::Ok => Ok(()),
+            other => Err(other.unexpected()),
         }
     }
     
@@ -182,15 +202,110 @@ impl Client {
         let response = self.send_command(&command)?;
         debug!("Received response: {}", response);
         
-        if response == "OK" {
-            Ok(true)
-        } else if response == "NOT_FOUND" {
-            Ok(false)
-        } else if let Some(error) = response.strip_prefix("ERROR ") {
-            Err(Error::protocol(error))
-        } else {
-            Err(Error::invalid_response(response))
+        match Response::parse(&response)? {
+            Response::Ok => Ok(true),
+            Response::NotFound => Ok(false),
+            other => Err(other.unexpected()),
+        }
+    }
+    
+    /// Increment the integer stored at a key
+    /// 
+    /// A missing key counts as 0.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `key` - The key to increment
+    /// * `amount` - Amount to add, 1 if `None`
+    /// 
+    /// # Returns
+    /// 
+    /// The value after the increment
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Io` if network communication fails
+    /// * `Error::Protocol` if the value is not an integer or would overflow
+    pub fn increment<S: AsRef<str>>(&mut self, key: S, amount: Option<i64>) -> Result<i64> {
+        let command = numeric_command("INC", key.as_ref(), amount)?;
+        debug!("Sending command: {}", command);
+        let response = self.send_command(&command)?;
+        Response::parse(&response)?.into_integer()
+    }
+    
+    /// Decrement the integer stored at a key
+    /// 
+    /// A missing key counts as 0.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `key` - The key to decrement
+    /// * `amount` - Amount to subtract, 1 if `None`
+    /// 
+    /// # Returns
+    /// 
+    /// The value after the decrement
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Io` if network communication fails
+    /// * `Error::Protocol` if the value is not an integer or would overflow
+    pub fn decrement<S: AsRef<str>>(&mut self, key: S, amount: Option<i64>) -> Result<i64> {
+        let command = numeric_command("DEC", key.as_ref(), amount)?;
+        debug!("Sending command: {}", command);
+        let response = self.send_command(&command)?;
+        Response::parse(&response)?.into_integer()
+    }
+    
+    /// Append to the string stored at a key, creating it if missing
+    /// 
+    /// # Returns
+    /// 
+    /// The full value after appending
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Io` if network communication fails
+    /// * `Error::Protocol` if server returns an error
+    pub fn append<S: AsRef<str>, V: AsRef<str>>(&mut self, key: S, value: V) -> Result<String> {
+        let command = string_command("APPEND", key.as_ref(), value.as_ref())?;
+        debug!("Sending command: APPEND {} <value>", key.as_ref());
+        let response = self.send_command(&command)?;
+        Response::parse(&response)?.into_value()
+    }
+    
+    /// Prepend to the string stored at a key, creating it if missing
+    /// 
+    /// # Returns
+    /// 
+    /// The full value after prepending
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Io` if network communication fails
+    /// * `Error::Protocol` if server returns an error
+    pub fn prepend<S: AsRef<str>, V: AsRef<str>>(&mut self, key: S, value: V) -> Result<String> {
+        let command = string_command("PREPEND", key.as_ref(), value.as_ref())?;
+        debug!("Sending command: PREPEND {} <value>", key.as_ref());
+        let response = self.send_command(&command)?;
+        Response::parse(&response)?.into_value()
+    }
+    
+    /// Check whether a key exists
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::Io` if network communication fails
+    /// * `Error::Protocol` if server returns an error
+    pub fn exists<S: AsRef<str>>(&mut self, key: S) -> Result<bool> {
+        let key = key.as_ref();
+        if key.is_empty() {
+            return Err(Error::invalid_parameter("Key cannot be empty"));
         }
+        let command = format!("EXISTS {}", key);
+        debug!("Sending command: {}", command);
+        let response = self.send_command(&command)?;
+        Response::parse(&response)?.into_exists()
     }
     
     /// Get the server address this client is connected to
@@ -204,7 +319,22 @@ impl Client {
         writeln!(&mut self.writer, "{}", command)?;
         self.writer.flush()?;
         
-        // Read response
+        self.read_line()
+    }
+    
+    /// Read the `<len>` bytes and CRLF following a `VALUEZ <algo> <len>` header
+    fn read_compressed(&mut self, header: &str) -> Result<String> {
+        let (algo, len) = header
+            .split_once(' ')
+            .and_then(|(algo, len)| Some((algo, len.parse::<usize>().ok()?)))
+            .ok_or_else(|| Error::invalid_response(format!("VALUEZ {}", header)))?;
+        let mut body = vec![0u8; len + 2];
+        self.reader.read_exact(&mut body)?;
+        decode_compressed(algo, &body[..len])
+    }
+    
+    /// Read one response line, without its line terminator
+    fn read_line(&mut self) -> Result<String> {
         let mut response = String::new();
         self.reader.read_line(&mut response)?;
         
@@ -311,6 +441,25 @@ impl Client {
     }
 }
 
+/// Format `INC`/`DEC`, leaving out the amount when it is the server default
+pub(crate) fn numeric_command(name: &str, key: &str, amount: Option<i64>) -> Result<String> {
+    if key.is_empty() {
+        return Err(Error::invalid_parameter("Key cannot be empty"));
+    }
+    Ok(match amount {
+        Some(amount) => format!("{} {} {}", name, key, amount),
+        None => format!("{} {}", name, key),
+    })
+}
+
+/// Format `APPEND`/`PREPEND`
+pub(crate) fn string_command(name: &str, key: &str, value: &str) -> Result<String> {
+    if key.is_empty() {
+        return Err(Error::invalid_parameter("Key cannot be empty"));
+    }
+    Ok(format!("{} {} {}", name, key, value))
+}
+
 impl Drop for Client {
     fn drop(&mut self) {
         debug!("Closing connection to {}", self.addr);
@@ -360,6 +509,18 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_numeric_and_string_command_formatting() {
+        assert_eq!(numeric_command("INC", "hits", None).unwrap(), "INC hits");
+        assert_eq!(numeric_command("INC", "hits", Some(5)).unwrap(), "INC hits 5");
+        assert_eq!(numeric_command("DEC", "stock", Some(-2)).unwrap(), "DEC stock -2");
+        assert!(matches!(numeric_command("INC", "", None), Err(Error::InvalidParameter { .. })));
+
+        assert_eq!(string_command("APPEND", "log", "more text").unwrap(), "APPEND log more text");
+        assert_eq!(string_command("PREPEND", "log", "head").unwrap(), "PREPEND log head");
+        assert!(matches!(string_command("APPEND", "", "x"), Err(Error::InvalidParameter { .. })));
+    }
+
     #[test]
     fn test_parameter_validation() {
         // Test empty key validation
@@ -66,8 +66,10 @@
 pub mod error;
 mod client;
 mod async_client;
+mod response;
 
 // Re-export main types
 pub use error::{Error, Result};
+pub use response::Response;
 pub use client::Client;
 pub use async_client::AsyncClient;
@@ -0,0 +1,216 @@
+//! Typed server responses shared by the sync and async clients
+
+use crate::error::{Error, Result};
+use std::fmt;
+
+/// One response from a MerkleKV server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// `OK`, or `DELETED` for a successful delete
+    Ok,
+    /// `VALUE <data>`
+    Value(String),
+    /// `NOT_FOUND`
+    NotFound,
+    /// A named count such as `EXISTS <n>` or `DBSIZE <n>`
+    Count(u64),
+    /// `ERROR <message>`
+    Error(String),
+    /// The `key value` / `key NOT_FOUND` lines following an `MGET` header
+    Values(Vec<(String, Option<String>)>),
+}
+
+impl Response {
+    /// Parse a single-line response (without its line terminator)
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::InvalidResponse` if the line matches no known response form
+    pub fn parse(line: &str) -> Result<Response> {
+        match line {
+            "OK" | "DELETED" => return Ok(Response::Ok),
+            "NOT_FOUND" => return Ok(Response::NotFound),
+            _ => {}
+        }
+        if let Some(value) = line.strip_prefix("VALUE ") {
+            return Ok(Response::Value(value.to_string()));
+        }
+        if let Some(message) = line.strip_prefix("ERROR ") {
+            return Ok(Response::Error(message.to_string()));
+        }
+        match line.split_once(' ') {
+            Some((name, count)) if is_response_name(name) => count
+                .parse()
+                .map(Response::Count)
+                .map_err(|_| Error::invalid_response(line)),
+            _ => Err(Error::invalid_response(line)),
+        }
+    }
+
+    /// Parse the per-key lines of an `MGET` response, one per requested key
+    /// 
+    /// # Errors
+    /// 
+    /// * `Error::InvalidResponse` if a line has no key
+    pub fn parse_values<S: AsRef<str>>(lines: &[S]) -> Result<Response> {
+        lines
+            .iter()
+            .map(|line| match line.as_ref().split_once(' ') {
+                Some((key, "NOT_FOUND")) if !key.is_empty() => Ok((key.to_string(), None)),
+                Some((key, value)) if !key.is_empty() => Ok((key.to_string(), Some(value.to_string()))),
+                _ => Err(Error::invalid_response(line.as_ref())),
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Response::Values)
+    }
+
+    /// The data of a `VALUE <data>` response
+    pub(crate) fn into_value(self) -> Result<String> {
+        match self {
+            Response::Value(value) => Ok(value),
+            other => Err(other.unexpected()),
+        }
+    }
+
+    /// The integer carried by a `VALUE <n>` response
+    pub(crate) fn into_integer(self) -> Result<i64> {
+        let value = self.into_value()?;
+        value
+            .parse()
+            .map_err(|_| Error::invalid_response(format!("VALUE {}", value)))
+    }
+
+    /// Whether a single-key `EXISTS <n>` response reports the key present
+    pub(crate) fn into_exists(self) -> Result<bool> {
+        match self {
+            Response::Count(count) => Ok(count > 0),
+            other => Err(other.unexpected()),
+        }
+    }
+
+    /// The error to return when a command got this response instead of the
+    /// one it expects: the server's message for `Error`, otherwise an
+    /// invalid-response error quoting the response
+    pub fn unexpected(self) -> Error {
+        match self {
+            Response::Error(message) => Error::protocol(message),
+            other => Error::invalid_response(other.to_string()),
+        }
+    }
+}
+
+/// Decode the body of a `VALUEZ <algo> <len>` response. The only algorithm
+/// the server sends is `zstd`: the body is one standard zstd frame.
+///
+/// # Errors
+///
+/// * `Error::InvalidResponse` for another algorithm or a corrupt body
+pub(crate) fn decode_compressed(algo: &str, body: &[u8]) -> Result<String> {
+    if algo != "zstd" {
+        return Err(Error::invalid_response(format!("VALUEZ {} {}", algo, body.len())));
+    }
+    let bytes = zstd::stream::decode_all(body)
+        .map_err(|e| Error::invalid_response(format!("corrupt VALUEZ body: {}", e)))?;
+    String::from_utf8(bytes).map_err(|_| Error::invalid_response("VALUEZ body is not UTF-8"))
+}
+
+/// Whether `name` looks like a response keyword (`EXISTS`, `DBSIZE`, ...)
+fn is_response_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+}
+
+impl fmt::Display for Response {
+    /// Formats the response in its wire form; counts and `MGET` blocks are
+    /// shown by their totals only
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Response::Ok => write!(f, "OK"),
+            Response::Value(value) => write!(f, "VALUE {}", value),
+            Response::NotFound => write!(f, "NOT_FOUND"),
+            Response::Count(count) => write!(f, "COUNT {}", count),
+            Response::Error(message) => write!(f, "ERROR {}", message),
+            Response::Values(values) => write!(f, "VALUES {}", values.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_compressed_zstd_frames() {
+        let value = "merkle-tree;".repeat(100);
+        let frame = zstd::bulk::compress(value.as_bytes(), 3).unwrap();
+        assert_eq!(decode_compressed("zstd", &frame).unwrap(), value);
+
+        assert!(decode_compressed("zstd", b"not a frame").is_err());
+        assert!(decode_compressed("lz4", &frame).is_err());
+    }
+
+    #[test]
+    fn test_parse_single_line_forms() {
+        assert_eq!(Response::parse("OK").unwrap(), Response::Ok);
+        assert_eq!(Response::parse("DELETED").unwrap(), Response::Ok);
+        assert_eq!(Response::parse("NOT_FOUND").unwrap(), Response::NotFound);
+        assert_eq!(Response::parse("VALUE hello world").unwrap(), Response::Value("hello world".to_string()));
+        assert_eq!(Response::parse("VALUE ").unwrap(), Response::Value(String::new()));
+        assert_eq!(Response::parse("VALUE 42").unwrap(), Response::Value("42".to_string()));
+        assert_eq!(Response::parse("EXISTS 2").unwrap(), Response::Count(2));
+        assert_eq!(Response::parse("DBSIZE 0").unwrap(), Response::Count(0));
+        assert_eq!(
+            Response::parse("ERROR value is not an integer").unwrap(),
+            Response::Error("value is not an integer".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_forms() {
+        for line in ["", "PONG", "EXISTS many", "exists 1", "EXISTS -1", "OK extra"] {
+            assert!(matches!(Response::parse(line), Err(Error::InvalidResponse { .. })), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let lines = ["a 1", "b NOT_FOUND", "c two words"];
+        assert_eq!(
+            Response::parse_values(&lines).unwrap(),
+            Response::Values(vec![
+                ("a".to_string(), Some("1".to_string())),
+                ("b".to_string(), None),
+                ("c".to_string(), Some("two words".to_string())),
+            ])
+        );
+        assert!(Response::parse_values(&["novalue"]).is_err());
+        assert_eq!(Response::parse_values::<&str>(&[]).unwrap(), Response::Values(Vec::new()));
+    }
+
+    #[test]
+    fn test_typed_conversions() {
+        assert_eq!(Response::parse("VALUE 42").unwrap().into_integer().unwrap(), 42);
+        assert_eq!(Response::parse("VALUE -7").unwrap().into_integer().unwrap(), -7);
+        assert!(matches!(
+            Response::parse("VALUE abc").unwrap().into_integer(),
+            Err(Error::InvalidResponse { .. })
+        ));
+        assert!(matches!(
+            Response::parse("ERROR value is not an integer").unwrap().into_integer(),
+            Err(Error::Protocol { .. })
+        ));
+        assert_eq!(Response::parse("VALUE a b").unwrap().into_value().unwrap(), "a b");
+        assert!(matches!(Response::Ok.into_value(), Err(Error::InvalidResponse { .. })));
+        assert!(Response::parse("EXISTS 1").unwrap().into_exists().unwrap());
+        assert!(!Response::parse("EXISTS 0").unwrap().into_exists().unwrap());
+        assert!(matches!(Response::parse("VALUE 1").unwrap().into_exists(), Err(Error::InvalidResponse { .. })));
+    }
+
+    #[test]
+    fn test_unexpected_response_errors() {
+        assert!(matches!(Response::Error("boom".to_string()).unexpected(), Error::Protocol { .. }));
+        match Response::Value("x".to_string()).unexpected() {
+            Error::InvalidResponse { message } => assert_eq!(message, "VALUE x"),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
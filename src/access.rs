@@ -0,0 +1,159 @@
+//! # Key Access Tracking
+//!
+//! This module keeps per-key access metadata for diagnostics such as
+//! `OBJECT IDLETIME` and for last-write-wins checks in `RESTORE`. Tracking
+//! lives beside the storage engines rather than inside them, so every engine
+//! gets the same behavior without changing the `KVEngineStoreTrait` interface.
+//!
+//! Keys are recorded when the server reads or writes them. A key that exists
+//! in the store but has never been touched since startup (e.g. loaded from a
+//! persistent engine or applied by replication) is reported as idle since the
+//! tracker was created and has no known write timestamp.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Access metadata for a single key.
+#[derive(Debug, Clone, Copy)]
+struct KeyAccess {
+    /// Last read or write
+    accessed: Instant,
+
+    /// Timestamp (unix nanos) of the last write, if one was seen
+    written: Option<u64>,
+}
+
+/// Records the last time each key was read or written.
+#[derive(Debug)]
+pub struct AccessTracker {
+    /// Access metadata per key
+    keys: Mutex<HashMap<String, KeyAccess>>,
+
+    /// Creation time, used as the last access of untracked keys
+    created: Instant,
+}
+
+impl Default for AccessTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Current wall-clock time in unix nanoseconds, as used for write timestamps.
+pub fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+impl AccessTracker {
+    /// Create an empty tracker.
+    pub fn new() -> Self {
+        Self {
+            keys: Mutex::new(HashMap::new()),
+            created: Instant::now(),
+        }
+    }
+
+    /// Record a read of `key` at the current time.
+    pub fn touch(&self, key: &str) {
+        let mut map = self.keys.lock().unwrap();
+        map.entry(key.to_string())
+            .and_modify(|a| a.accessed = Instant::now())
+            .or_insert(KeyAccess { accessed: Instant::now(), written: None });
+    }
+
+    /// Record a write of `key` at the current time.
+    pub fn touch_write(&self, key: &str) {
+        self.touch_write_at(key, unix_nanos());
+    }
+
+    /// Record a write of `key` carrying an explicit timestamp (unix nanos),
+    /// e.g. one restored from a dump.
+    pub fn touch_write_at(&self, key: &str, ts: u64) {
+        let mut map = self.keys.lock().unwrap();
+        map.insert(key.to_string(), KeyAccess { accessed: Instant::now(), written: Some(ts) });
+    }
+
+    /// Forget a key (called after it is deleted).
+    pub fn remove(&self, key: &str) {
+        self.keys.lock().unwrap().remove(key);
+    }
+
+    /// Forget every key (called after the store is cleared).
+    pub fn clear(&self) {
+        self.keys.lock().unwrap().clear();
+    }
+
+    /// Number of whole seconds since `key` was last accessed.
+    pub fn idle_seconds(&self, key: &str) -> u64 {
+        let map = self.keys.lock().unwrap();
+        map.get(key)
+            .map_or(self.created, |a| a.accessed)
+            .elapsed()
+            .as_secs()
+    }
+
+    /// `keys` ordered from least to most recently accessed.
+    pub fn least_recently_used(&self, mut keys: Vec<String>) -> Vec<String> {
+        let map = self.keys.lock().unwrap();
+        keys.sort_by_key(|key| map.get(key).map_or(self.created, |a| a.accessed));
+        keys
+    }
+
+    /// Timestamp (unix nanos) of the last write to `key`, if known.
+    pub fn last_write(&self, key: &str) -> Option<u64> {
+        self.keys.lock().unwrap().get(key).and_then(|a| a.written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_touch_resets_idle_time() {
+        let tracker = AccessTracker::new();
+        tracker.touch("k");
+        std::thread::sleep(Duration::from_millis(1100));
+        assert!(tracker.idle_seconds("k") >= 1);
+
+        tracker.touch("k");
+        assert_eq!(tracker.idle_seconds("k"), 0);
+
+        // Forgotten keys fall back to the tracker's creation time
+        tracker.remove("k");
+        assert!(tracker.idle_seconds("k") >= 1);
+    }
+
+    #[test]
+    fn test_last_write_survives_reads() {
+        let tracker = AccessTracker::new();
+        tracker.touch("k");
+        assert_eq!(tracker.last_write("k"), None);
+
+        tracker.touch_write_at("k", 42);
+        tracker.touch("k");
+        assert_eq!(tracker.last_write("k"), Some(42));
+
+        tracker.touch_write("k");
+        assert!(tracker.last_write("k").unwrap() > 42);
+
+        tracker.clear();
+        assert_eq!(tracker.last_write("k"), None);
+    }
+
+    #[test]
+    fn test_least_recently_used_order() {
+        let tracker = AccessTracker::new();
+        tracker.touch("a");
+        tracker.touch_write("b");
+        tracker.touch("a");
+
+        let keys = vec!["a".to_string(), "b".to_string(), "untracked".to_string()];
+        assert_eq!(tracker.least_recently_used(keys), vec!["untracked", "b", "a"]);
+    }
+}
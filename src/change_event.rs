@@ -41,6 +41,23 @@ pub enum OpKind {
     Append,
     /// String prepend; event value contains the resulting string as bytes
     Prepend,
+    /// Remove every key (TRUNCATE/FLUSHDB); `key` is empty and `val` is None
+    Clear,
+}
+
+impl OpKind {
+    /// Lowercase name, the same tag used in serialized events.
+    pub fn name(self) -> &'static str {
+        match self {
+            OpKind::Set => "set",
+            OpKind::Del => "del",
+            OpKind::Incr => "incr",
+            OpKind::Decr => "decr",
+            OpKind::Append => "append",
+            OpKind::Prepend => "prepend",
+            OpKind::Clear => "clear",
+        }
+    }
 }
 
 /// Canonical change-event structure used to replicate writes.
@@ -49,13 +66,13 @@ pub enum OpKind {
 /// - `op`: What kind of mutation this is (set/del/incr/decr/append/prepend).
 /// - `key`: The logical key being mutated.
 /// - `val`: The resulting value as raw bytes (UTF-8 for string values, ASCII
-///          digits for numeric results); `None` for deletions.
+///   digits for numeric results); `None` for deletions.
 /// - `ts`: A timestamp for conflict resolution. We allow Unix nanoseconds or a
-///         logical clock; the comparison is the only semantic the system needs.
+///   logical clock; the comparison is the only semantic the system needs.
 /// - `src`: The originating node identifier, used for loop prevention.
 /// - `op_id`: A 128-bit identifier (UUID v4) for idempotency/deduplication.
 /// - `prev`: Optional 32-byte Merkle root (or leaf) hash to assist anti-entropy.
-/// - `ttl`: Optional TTL-in-seconds hint (not enforced by the in-memory engine).
+/// - `ttl`: Optional TTL in seconds for `SETEX`/`PSETEX` writes, counted from `ts`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ChangeEvent {
     /// Schema version (allows additive, backward-compatible upgrades)
@@ -74,7 +91,7 @@ pub struct ChangeEvent {
     pub op_id: [u8; 16],
     /// Optional Merkle hash (32 bytes). Useful for anti-entropy proofs.
     pub prev: Option<[u8; 32]>,
-    /// Optional TTL in seconds (advisory in this prototype)
+    /// Optional TTL in seconds, counted from `ts`
     pub ttl: Option<u64>,
 }
 
@@ -85,6 +102,7 @@ impl ChangeEvent {
     /// under at-least-once delivery. Timestamps should be monotonic within a
     /// node for LWW to be meaningful. Using `unix_nanos` is sufficient for a
     /// prototype; Lamport clocks would improve causality ordering across nodes.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         v: u16,
         op: OpKind,
@@ -110,6 +128,7 @@ impl ChangeEvent {
     }
 
     /// Helper: Build an event whose value is a UTF-8 string.
+    #[allow(clippy::too_many_arguments)]
     pub fn with_str_value(
         v: u16,
         op: OpKind,
@@ -174,6 +193,7 @@ impl ChangeEvent {
 
 /// Preferred encoding for on-wire messages.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
 pub enum ChangeCodec {
     Json,
     Cbor,
@@ -181,6 +201,15 @@ pub enum ChangeCodec {
 }
 
 impl ChangeCodec {
+    /// Lowercase codec name, as reported by `NODE`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChangeCodec::Json => "json",
+            ChangeCodec::Cbor => "cbor",
+            ChangeCodec::Bincode => "bincode",
+        }
+    }
+
     /// Serialize according to the selected codec.
     pub fn encode(self, ev: &ChangeEvent) -> Result<Vec<u8>, String> {
         match self {
@@ -194,6 +223,7 @@ impl ChangeCodec {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base64::Engine;
     use std::collections::{HashMap, HashSet};
 
     /// A minimal local applier used for unit tests without MQTT.
@@ -248,7 +278,7 @@ mod tests {
                 }
                 _ => {
                     if let Some(bytes) = &ev.val {
-                        let s = String::from_utf8(bytes.clone()).unwrap_or_else(|_| base64::encode(bytes));
+                        let s = String::from_utf8(bytes.clone()).unwrap_or_else(|_| base64::engine::general_purpose::STANDARD.encode(bytes));
                         self.store.insert(ev.key.clone(), s);
                     }
                 }
@@ -278,7 +308,7 @@ mod tests {
     #[test]
     fn idempotency_duplicate_event() {
         let mut applier = LocalApplier::new();
-        let mut ev = sample_event(OpKind::Set, "x", Some("1"), 10);
+        let ev = sample_event(OpKind::Set, "x", Some("1"), 10);
         // Ensure same op id for duplicate
         let op_id = ev.op_id;
         applier.apply(&ev);
@@ -296,7 +326,7 @@ mod tests {
         let mut applier = LocalApplier::new();
         // Newer event arrives first
         let ev_new = sample_event(OpKind::Set, "c", Some("new"), 200);
-        let mut ev_old = sample_event(OpKind::Set, "c", Some("old"), 100);
+        let ev_old = sample_event(OpKind::Set, "c", Some("old"), 100);
         applier.apply(&ev_new);
         applier.apply(&ev_old); // should be ignored by LWW
         assert_eq!(applier.store.get("c").cloned(), Some("new".into()));
@@ -308,6 +338,7 @@ mod tests {
         applier2.apply(&ev_new2);
         assert_eq!(applier2.store.get("d").cloned(), Some("new".into()));
     }
+    #[test]
     fn mixed_codec_interop() {
     let ev = sample_event(OpKind::Set, "k", Some("v"), 1_000);
     let j = ev.to_json().unwrap();
@@ -327,17 +358,17 @@ fn corrupted_payload_rejected() {
 #[test]
 fn non_utf8_value_safe_handling() {
     let mut applier = LocalApplier::new();
-    let mut bytes = vec![0, 159, 146, 150]; // invalid UTF-8
+    let bytes = vec![0, 159, 146, 150]; // invalid UTF-8
     let ev = ChangeEvent::new(1, OpKind::Set, "bin", Some(bytes.clone()), 5, "A", None, None);
     applier.apply(&ev);
     // LocalApplier stringify non-UTF8 via base64 fallback
     let got = applier.store.get("bin").unwrap();
-    assert_eq!(got, &base64::encode(&bytes));
+    assert_eq!(got, &base64::engine::general_purpose::STANDARD.encode(&bytes));
 }
 #[test]
 fn idempotency_burst_duplicates() {
     let mut applier = LocalApplier::new();
-    let mut ev = sample_event(OpKind::Set, "dup", Some("1"), 10);
+    let ev = sample_event(OpKind::Set, "dup", Some("1"), 10);
     let op_id = ev.op_id;
     for _ in 0..10 { applier.apply(&ev); }
     assert_eq!(applier.store.get("dup").cloned(), Some("1".into()));
@@ -358,8 +389,8 @@ fn lww_clock_skew_no_overwrite() {
 #[test]
 fn same_timestamp_tie_break_by_op_id() {
     let mut applier = LocalApplier::new();
-    let mut ev1 = sample_event(OpKind::Set, "tie", Some("A"), 500);
-    let mut ev2 = sample_event(OpKind::Set, "tie", Some("B"), 500);
+    let ev1 = sample_event(OpKind::Set, "tie", Some("A"), 500);
+    let ev2 = sample_event(OpKind::Set, "tie", Some("B"), 500);
     // Definition: choose the event with the lexicographically larger op_id
     let winner_is_ev2 = ev2.op_id > ev1.op_id;
     applier.apply(&ev1);
@@ -391,14 +422,14 @@ fn delete_lww_behavior() {
     let mut a = LocalApplier::new();
     a.apply(&sample_event(OpKind::Set, "z", Some("keep"), 10));
     a.apply(&sample_event(OpKind::Del, "z", None, 11));
-    assert!(a.store.get("z").is_none());
+    assert!(!a.store.contains_key("z"));
 }
 #[test]
 fn missing_value_for_non_del_is_ignored() {
     let mut a = LocalApplier::new();
     let ev = ChangeEvent::new(1, OpKind::Set, "m", None, 5, "node", None, None);
     a.apply(&ev);
-    assert!(a.store.get("m").is_none());
+    assert!(!a.store.contains_key("m"));
 }
 #[test]
 fn large_payload_roundtrip() {
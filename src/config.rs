@@ -13,6 +13,23 @@
 //! port = 7379
 //! storage_path = "data"
 //! sync_interval_seconds = 60
+//! max_value_bytes = 1048576
+//! max_key_bytes = 1024
+//! read_only = false
+//! databases = 16
+//! max_connections = 1000
+//! tcp_nodelay = true
+//! tcp_keepalive_secs = 60
+//! rate_limit_per_sec = 0
+//! max_keys = 0
+//! maxmemory_bytes = 0
+//! eviction_policy = "noeviction"
+//!
+//! [sync]
+//! enabled = true
+//! interval_secs = 30
+//! peers = ["10.0.0.2:7379", "10.0.0.3:7379"]
+//! timeout_ms = 5000
 //!
 //! [replication]
 //! enabled = true
@@ -22,9 +39,11 @@
 //! client_id = "node1"
 //! ```
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use config::{Config as ConfigLib, File};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::path::Path;
 
 /// Configuration for anti-entropy synchronization.
@@ -37,9 +56,98 @@ pub struct AntiEntropyConfig {
     #[serde(default)]
     pub peer_list: Vec<String>,
 }
+/// Configuration for the periodic background sync task.
+///
+/// When enabled, the server pulls from each peer in turn every
+/// `interval_secs` (plus a small random jitter) using the same Merkle
+/// reconciliation as the `SYNC` command.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// Whether the background sync task runs at all
+    pub enabled: bool,
+
+    /// Base interval (in seconds) between sync cycles
+    pub interval_secs: u64,
+
+    /// Peers to reconcile with, as "host:port"
+    pub peers: Vec<String>,
+
+    /// How long (in milliseconds) connecting to a peer or waiting for one of
+    /// its replies may take before the sync fails with `sync timeout`;
+    /// 0 waits forever. Applies to `SYNC`, `SYNC PEERS` and `DIFF` as well.
+    pub timeout_ms: u64,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 60,
+            peers: vec![],
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// What to do when a write would push memory use past `maxmemory_bytes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvictionPolicy {
+    /// Refuse the write with `ERROR OOM`
+    #[default]
+    NoEviction,
+    /// Delete the least recently accessed keys until the write fits
+    Lru,
+}
+
+/// A group of commands a user may be allowed to run, matching the flags
+/// `COMMAND` lists: `read` for readonly commands, `write` for commands that
+/// change keys and `admin` for server management.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AclCategory {
+    Read,
+    Write,
+    Admin,
+}
+
+/// A user that can log in with `AUTH <name> <password>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserConfig {
+    pub name: String,
+    pub password: String,
+    /// Categories of commands this user may run
+    #[serde(default)]
+    pub categories: Vec<AclCategory>,
+}
+
+fn default_databases() -> usize {
+    1
+}
+
+fn default_tcp_nodelay() -> bool {
+    true
+}
+
+fn default_slowlog_max_len() -> usize {
+    128
+}
+
+fn default_response_compress_threshold_bytes() -> usize {
+    1024
+}
+
+fn default_replication_qos() -> u8 {
+    1
+}
+
 fn ae_is_disabled(ae: &AntiEntropyConfig) -> bool {
     !ae.enabled && ae.peer_list.is_empty()
 }
+
+/// What `Config::settings` reports in place of a password.
+pub const REDACTED: &str = "****";
 /// Main configuration structure for the MerkleKV server.
 ///
 /// Contains all settings needed to run a node, including network configuration,
@@ -52,6 +160,19 @@ pub struct Config {
     /// Port number for the TCP server to listen on (e.g., 7379)
     pub port: u16,
 
+    /// Addresses to listen on, e.g. `["0.0.0.0:7379", "[::]:7379"]`.
+    /// When empty, the server listens on `host:port` only.
+    #[serde(default)]
+    pub listen: Vec<String>,
+
+    /// Disable Nagle's algorithm on client connections (on by default).
+    #[serde(default = "default_tcp_nodelay")]
+    pub tcp_nodelay: bool,
+
+    /// Idle seconds before TCP keepalive probes are sent to a client; 0 disables keepalive.
+    #[serde(default)]
+    pub tcp_keepalive_secs: u64,
+
     /// Path where data files should be stored (currently unused as storage is in-memory)
     /// TODO: Implement persistent storage using this path
     pub storage_path: String,
@@ -71,6 +192,140 @@ pub struct Config {
     /// Configuration for anti-entropy synchronization
     #[serde(default, skip_serializing_if = "ae_is_disabled")]
     pub anti_entropy: AntiEntropyConfig,
+
+    /// Start in read-only mode: writes are rejected, reads still served.
+    /// Can be toggled at runtime with `CONFIG SET read-only on|off`.
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// Number of logical databases selectable with `SELECT` (at least 1).
+    #[serde(default = "default_databases")]
+    pub databases: usize,
+
+    /// Maximum number of simultaneous client connections; 0 means unlimited.
+    #[serde(default)]
+    pub max_connections: usize,
+
+    /// Maximum commands per second accepted on a single connection; 0 means unlimited.
+    #[serde(default)]
+    pub rate_limit_per_sec: u64,
+
+    /// Periodic background sync with peers
+    #[serde(default)]
+    pub sync: SyncConfig,
+
+    /// Maximum size (in bytes) of a stored value; 0 means unlimited.
+    /// Writes exceeding this limit are rejected with `ERROR value too large`.
+    #[serde(default)]
+    pub max_value_bytes: usize,
+
+    /// Maximum size (in bytes) of a key; 0 means unlimited.
+    /// Commands naming a longer key are rejected with `ERROR key too long`.
+    #[serde(default)]
+    pub max_key_bytes: usize,
+
+    /// Most keys a single MGET, MSET or EXISTS may name, and most lines one
+    /// IMPORT may send; 0 means unlimited. Longer commands are rejected with
+    /// `ERROR too many keys`.
+    #[serde(default)]
+    pub max_bulk_keys: usize,
+
+    /// Values longer than this many bytes are kept compressed in memory by
+    /// the `rwlock` engine; 0 disables compression.
+    #[serde(default)]
+    pub compress_threshold_bytes: usize,
+
+    /// Keep integer values as numbers rather than strings in the `rwlock`
+    /// engine, saving memory and string conversions for counters. Reads
+    /// return the same text either way.
+    #[serde(default)]
+    pub int_encoding: bool,
+
+    /// GET values longer than this many bytes are sent compressed
+    /// (`VALUEZ`) to connections that asked for it with `HELLO ... COMPRESS`;
+    /// 0 never compresses replies.
+    #[serde(default = "default_response_compress_threshold_bytes")]
+    pub response_compress_threshold_bytes: usize,
+
+    /// Apply SETs through a per-database writer task that drains queued
+    /// writes in batches, so many concurrent writers hand off to one task
+    /// instead of contending on the engine's write lock.
+    #[serde(default)]
+    pub write_batching: bool,
+
+    /// Commands taking at least this many milliseconds are recorded in the
+    /// slow log (`SLOWLOG GET`); 0 disables the slow log.
+    #[serde(default)]
+    pub slowlog_threshold_ms: u64,
+
+    /// Number of entries the slow log keeps; older entries are evicted.
+    #[serde(default = "default_slowlog_max_len")]
+    pub slowlog_max_len: usize,
+
+    /// Recent writes kept per key for `HISTORY`; 0 (the default) keeps none,
+    /// since every written key then holds its recent values in memory. Only
+    /// the 10,000 most recently written keys keep a history.
+    #[serde(default)]
+    pub key_history: usize,
+
+    /// Count GET and SET hits per key for `HOTKEYS`. Off by default, since
+    /// every accessed key then keeps a counter in memory.
+    #[serde(default)]
+    pub track_key_stats: bool,
+
+    /// Most keys a database may hold; 0 means unlimited. Writes that would
+    /// create a key beyond it are rejected with `ERROR key limit reached`,
+    /// while updates to existing keys are always allowed.
+    #[serde(default)]
+    pub max_keys: usize,
+
+    /// Memory budget per database (as reported by `MEMORY`); 0 means unlimited.
+    /// Writes that would exceed it are handled per `eviction_policy`.
+    #[serde(default)]
+    pub maxmemory_bytes: usize,
+
+    /// `noeviction` (default) or `lru`.
+    #[serde(default)]
+    pub eviction_policy: EvictionPolicy,
+
+    /// Log every command (client id, command, key, outcome) at info level.
+    #[serde(default)]
+    pub command_log: bool,
+
+    /// Also include written values in the command log. Off by default so
+    /// values do not end up in log files.
+    #[serde(default)]
+    pub command_log_values: bool,
+
+    /// Tokio worker threads; 0 uses one per CPU core.
+    #[serde(default)]
+    pub worker_threads: usize,
+
+    /// Allow `MONITOR`, which shows every command (values included) to the
+    /// monitoring client. Off by default.
+    #[serde(default)]
+    pub enable_monitor: bool,
+
+    /// Allow `DEBUG`, whose `SLEEP` stalls a connection on purpose. Off by
+    /// default.
+    #[serde(default)]
+    pub enable_debug: bool,
+
+    /// Extra command names, e.g. `{ KEYS = "SCAN" }`; names are
+    /// case-insensitive and an empty target disables the name.
+    #[serde(default)]
+    pub command_aliases: HashMap<String, String>,
+
+    /// Commands rejected as unknown, e.g. `["FLUSHDB", "SHUTDOWN"]`. An alias
+    /// in `command_aliases` can still reach a disabled command.
+    #[serde(default)]
+    pub rename_commands: Vec<String>,
+
+    /// Users for `AUTH`. When any are configured, a connection must log in
+    /// before running other commands, and may then only run commands in its
+    /// user's categories. Empty (the default) leaves every command open.
+    #[serde(default)]
+    pub users: Vec<UserConfig>,
 }
 
 /// Configuration for MQTT-based replication.
@@ -103,6 +358,42 @@ pub struct ReplicationConfig {
     #[serde(default)]
     pub client_password: Option<String>,
 
+    /// Connect to the broker over TLS instead of plain TCP.
+    #[serde(default)]
+    pub mqtt_tls: bool,
+
+    /// PEM file with the CA certificate(s) the broker is verified against.
+    /// Without it the platform's trusted roots are used.
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+
+    /// PEM client certificate for brokers requiring mutual TLS; needs
+    /// `client_key_path` and `ca_cert_path`.
+    #[serde(default)]
+    pub client_cert_path: Option<String>,
+
+    /// PEM private key for `client_cert_path`.
+    #[serde(default)]
+    pub client_key_path: Option<String>,
+
+    /// MQTT QoS level (0, 1 or 2) for replication publishes and the
+    /// subscription. Events are deduplicated by op_id, so 1 (at-least-once)
+    /// is safe; 0 trades lost events for less overhead.
+    #[serde(default = "default_replication_qos")]
+    pub qos: u8,
+
+    /// Only writes to keys starting with one of these prefixes are published.
+    /// Empty publishes every write. When set, TRUNCATE/FLUSHDB are not
+    /// published, since they would clear peers' keys outside the prefixes.
+    #[serde(default)]
+    pub publish_prefixes: Vec<String>,
+
+    /// Only received events for keys starting with one of these prefixes are
+    /// applied. Empty applies every event. When set, a received clear only
+    /// removes keys under these prefixes.
+    #[serde(default)]
+    pub subscribe_prefixes: Vec<String>,
+
     /// List of peer nodes (host:port) for replication
     #[serde(default)]
     pub peer_list: Vec<String>,
@@ -126,9 +417,42 @@ impl Config {
         let settings = ConfigLib::builder().add_source(File::from(path)).build()?;
 
         let config: Config = settings.try_deserialize()?;
+        if config.replication.qos > 2 {
+            return Err(anyhow!("replication.qos must be 0, 1 or 2, got {}", config.replication.qos));
+        }
+        for (i, user) in config.users.iter().enumerate() {
+            if config.users[..i].iter().any(|u| u.name == user.name) {
+                return Err(anyhow!("duplicate user {:?}", user.name));
+            }
+        }
         Ok(config)
     }
+    /// Addresses the server binds to: `listen` if set, otherwise `host:port`.
+    pub fn listen_addrs(&self) -> Vec<String> {
+        if self.listen.is_empty() {
+            vec![format!("{}:{}", self.host, self.port)]
+        } else {
+            self.listen.clone()
+        }
+    }
+    /// Check whether a value of `len` bytes exceeds the configured `max_value_bytes`.
+    pub fn value_too_large(&self, len: usize) -> bool {
+        self.max_value_bytes != 0 && len > self.max_value_bytes
+    }
+    /// Every setting as `(name, value)` pairs sorted by name (`CONFIG GETALL`).
+    ///
+    /// Sections are flattened to dotted names (`replication.mqtt_port`,
+    /// `users.0.name`), lists of plain values are comma-joined and unset or
+    /// empty values read `-`. Passwords are reported as `****`.
+    pub fn settings(&self) -> Vec<(String, String)> {
+        let mut settings = Vec::new();
+        let value = serde_json::to_value(self).expect("config always serializes");
+        flatten_setting(String::new(), &value, &mut settings);
+        settings.sort();
+        settings
+    }
     /// Get the number of peers configured for anti-entropy synchronization.
+    #[allow(dead_code)]
     pub fn peer_list_len(&self) -> usize {
         self.anti_entropy.peer_list.len()
     }
@@ -143,10 +467,14 @@ impl Config {
     ///
     /// # Returns
     /// * `Config` - Configuration with default values
+    #[allow(dead_code)]
     pub fn default() -> Self {
         Self {
             host: "127.0.0.1".to_string(),
             port: 7379,
+            listen: vec![],
+            tcp_nodelay: true,
+            tcp_keepalive_secs: 0,
             storage_path: "data".to_string(),
             engine: "sled".to_string(),
             replication: ReplicationConfig {
@@ -156,6 +484,13 @@ impl Config {
                 topic_prefix: "merkle_kv".to_string(),
                 client_id: "node1".to_string(),
                 client_password: None,
+                mqtt_tls: false,
+                ca_cert_path: None,
+                client_cert_path: None,
+                client_key_path: None,
+                qos: 1,
+                publish_prefixes: vec![],
+                subscribe_prefixes: vec![],
                 peer_list: vec![], 
             },
             sync_interval_seconds: 60,
@@ -164,10 +499,141 @@ impl Config {
                 interval_seconds: 60,
                 peer_list: vec![],
             },
+            read_only: false,
+            databases: 1,
+            max_connections: 0,
+            rate_limit_per_sec: 0,
+            sync: SyncConfig::default(),
+            max_value_bytes: 0,
+            max_key_bytes: 0,
+            max_bulk_keys: 0,
+            write_batching: false,
+            compress_threshold_bytes: 0,
+            int_encoding: false,
+            response_compress_threshold_bytes: 1024,
+            slowlog_threshold_ms: 0,
+            slowlog_max_len: 128,
+            key_history: 0,
+            track_key_stats: false,
+            max_keys: 0,
+            maxmemory_bytes: 0,
+            eviction_policy: EvictionPolicy::NoEviction,
+            command_log: false,
+            command_log_values: false,
+            worker_threads: 0,
+            enable_monitor: false,
+            enable_debug: false,
+            command_aliases: HashMap::new(),
+            rename_commands: vec![],
+            users: vec![],
         }
     }
 }
 
+fn flatten_setting(name: String, value: &Value, settings: &mut Vec<(String, String)>) {
+    let child = |key: String| if name.is_empty() { key } else { format!("{}.{}", name, key) };
+    let secret = name.rsplit('.').next().is_some_and(|field| field == "password" || field.ends_with("_password"));
+    let text = match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten_setting(child(key.clone()), value, settings);
+            }
+            return;
+        }
+        Value::Array(items) if items.iter().any(Value::is_object) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten_setting(child(i.to_string()), item, settings);
+            }
+            return;
+        }
+        Value::Null => String::new(),
+        _ if secret => REDACTED.to_string(),
+        Value::Array(items) => items.iter().map(setting_text).collect::<Vec<_>>().join(","),
+        scalar => setting_text(scalar),
+    };
+    settings.push((name, if text.is_empty() { "-".to_string() } else { text }));
+}
+
+fn setting_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Settings that can be changed while the server is running via `CONFIG SET`.
+///
+/// The server shares one instance across all connections behind an
+/// `Arc<RwLock<..>>`; changes apply to subsequent connections and commands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeConfig {
+    /// Maximum simultaneous connections (0 = unlimited)
+    pub max_connections: usize,
+
+    /// Maximum commands per second per connection (0 = unlimited)
+    pub rate_limit_per_sec: u64,
+
+    /// Reject writes while set
+    pub read_only: bool,
+}
+
+impl RuntimeConfig {
+    /// Names accepted by `CONFIG GET` / `CONFIG SET`.
+    pub const PARAMS: &'static [&'static str] = &["max_connections", "rate_limit_per_sec", "read_only"];
+
+    /// Seed the runtime settings from the startup configuration.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_connections: config.max_connections,
+            rate_limit_per_sec: config.rate_limit_per_sec,
+            read_only: config.read_only,
+        }
+    }
+
+    /// Current value of `param`, formatted as `CONFIG GET` reports it.
+    pub fn get(&self, param: &str) -> Result<String> {
+        match Self::canonical(param)? {
+            "max_connections" => Ok(self.max_connections.to_string()),
+            "rate_limit_per_sec" => Ok(self.rate_limit_per_sec.to_string()),
+            _ => Ok(if self.read_only { "on" } else { "off" }.to_string()),
+        }
+    }
+
+    /// Parse and apply a new value for `param`.
+    pub fn set(&mut self, param: &str, value: &str) -> Result<()> {
+        match Self::canonical(param)? {
+            "max_connections" => {
+                self.max_connections = value
+                    .parse()
+                    .map_err(|_| anyhow!("{} must be a non-negative integer", param))?;
+            }
+            "rate_limit_per_sec" => {
+                self.rate_limit_per_sec = value
+                    .parse()
+                    .map_err(|_| anyhow!("{} must be a non-negative integer", param))?;
+            }
+            _ => {
+                self.read_only = match value.to_ascii_lowercase().as_str() {
+                    "on" | "yes" | "true" => true,
+                    "off" | "no" | "false" => false,
+                    _ => return Err(anyhow!("{} must be on or off", param)),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Map a user-supplied name onto an entry of `PARAMS` (`-` and `_` are interchangeable).
+    fn canonical(param: &str) -> Result<&'static str> {
+        let normalized = param.to_ascii_lowercase().replace('-', "_");
+        Self::PARAMS
+            .iter()
+            .copied()
+            .find(|p| *p == normalized)
+            .ok_or_else(|| anyhow!("Unsupported CONFIG parameter: {}", param))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -217,11 +683,211 @@ client_id = "node1"
         assert_eq!(config.port, 7379);
         assert_eq!(config.storage_path, "data");
         assert_eq!(config.sync_interval_seconds, 60);
-        assert_eq!(config.replication.enabled, true);
+        assert!(config.replication.enabled);
         assert_eq!(config.replication.mqtt_broker, "localhost");
         assert_eq!(config.replication.mqtt_port, 1883);
         assert_eq!(config.replication.topic_prefix, "merkle_kv");
         assert_eq!(config.replication.client_id, "node1");
         assert_eq!(config.replication.client_password, None);
     }
+
+    #[test]
+    fn test_users_from_file() {
+        let load = |users: &str| {
+            let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+            writeln!(
+                file.as_file_mut(),
+                r#"
+host = "127.0.0.1"
+port = 7379
+storage_path = "data"
+engine = "rwlock"
+sync_interval_seconds = 60
+{}
+[replication]
+enabled = false
+mqtt_broker = "localhost"
+mqtt_port = 1883
+topic_prefix = "merkle_kv"
+client_id = "node1"
+                "#,
+                users
+            )
+            .unwrap();
+            Config::load(file.path())
+        };
+
+        let config = load(
+            r#"
+[[users]]
+name = "reader"
+password = "r"
+categories = ["read"]
+
+[[users]]
+name = "ops"
+password = "o"
+categories = ["read", "write", "admin"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.users.len(), 2);
+        assert_eq!(config.users[0].categories, vec![AclCategory::Read]);
+        assert_eq!(config.users[1].name, "ops");
+        assert!(Config::default().users.is_empty());
+
+        let duplicate = "[[users]]\nname = \"a\"\npassword = \"1\"\n[[users]]\nname = \"a\"\npassword = \"2\"\n";
+        assert!(load(duplicate).is_err());
+        assert!(load("[[users]]\nname = \"a\"\npassword = \"1\"\ncategories = [\"root\"]\n").is_err());
+    }
+
+    #[test]
+    fn test_settings_redact_passwords() {
+        let mut config = Config::default();
+        config.replication.client_password = Some("mqtt-secret".to_string());
+        config.sync.peers = vec!["a:1".to_string(), "b:2".to_string()];
+        config.users.push(UserConfig {
+            name: "ops".to_string(),
+            password: "hunter2".to_string(),
+            categories: vec![AclCategory::Read, AclCategory::Admin],
+        });
+
+        let settings: HashMap<String, String> = config.settings().into_iter().collect();
+        assert_eq!(settings["engine"], "sled");
+        assert_eq!(settings["port"], "7379");
+        assert_eq!(settings["replication.client_password"], REDACTED);
+        assert_eq!(settings["replication.ca_cert_path"], "-");
+        assert_eq!(settings["sync.peers"], "a:1,b:2");
+        assert_eq!(settings["users.0.name"], "ops");
+        assert_eq!(settings["users.0.password"], REDACTED);
+        assert_eq!(settings["users.0.categories"], "read,admin");
+        assert!(settings.values().all(|v| !v.contains("secret") && !v.contains("hunter2")));
+
+        // No MQTT password configured reads as unset, not redacted
+        let defaults: HashMap<String, String> = Config::default().settings().into_iter().collect();
+        assert_eq!(defaults["replication.client_password"], "-");
+    }
+
+    #[test]
+    fn test_worker_threads_from_file() {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        writeln!(
+            file.as_file_mut(),
+            r#"
+host = "127.0.0.1"
+port = 7379
+storage_path = "data"
+engine = "rwlock"
+sync_interval_seconds = 60
+worker_threads = 2
+
+[replication]
+enabled = false
+mqtt_broker = "localhost"
+mqtt_port = 1883
+topic_prefix = "merkle_kv"
+client_id = "node1"
+            "#
+        )
+        .unwrap();
+
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(config.worker_threads, 2);
+        assert_eq!(Config::default().worker_threads, 0);
+    }
+
+    #[test]
+    fn test_replication_qos_from_file() {
+        let load = |qos: Option<u8>| {
+            let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+            writeln!(
+                file.as_file_mut(),
+                r#"
+host = "127.0.0.1"
+port = 7379
+storage_path = "data"
+engine = "rwlock"
+sync_interval_seconds = 60
+
+[replication]
+enabled = false
+mqtt_broker = "localhost"
+mqtt_port = 1883
+topic_prefix = "merkle_kv"
+client_id = "node1"
+{}
+                "#,
+                qos.map(|q| format!("qos = {}", q)).unwrap_or_default()
+            )
+            .unwrap();
+            Config::load(file.path())
+        };
+
+        assert_eq!(load(None).unwrap().replication.qos, 1);
+        assert_eq!(load(Some(0)).unwrap().replication.qos, 0);
+        assert_eq!(load(Some(2)).unwrap().replication.qos, 2);
+        assert_eq!(
+            load(Some(3)).unwrap_err().to_string(),
+            "replication.qos must be 0, 1 or 2, got 3"
+        );
+    }
+
+    #[test]
+    fn test_command_aliases_from_file() {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        writeln!(
+            file.as_file_mut(),
+            r#"
+host = "127.0.0.1"
+port = 7379
+storage_path = "data"
+engine = "rwlock"
+sync_interval_seconds = 60
+rename_commands = ["FLUSHDB", "SHUTDOWN"]
+
+[command_aliases]
+KEYS = "SCAN"
+
+[replication]
+enabled = false
+mqtt_broker = "localhost"
+mqtt_port = 1883
+topic_prefix = "merkle_kv"
+client_id = "node1"
+            "#
+        )
+        .unwrap();
+
+        let config = Config::load(file.path()).unwrap();
+        // Names are matched case-insensitively, so the loader's casing does not matter
+        let aliases: Vec<(String, String)> = config
+            .command_aliases
+            .iter()
+            .map(|(name, target)| (name.to_uppercase(), target.clone()))
+            .collect();
+        assert_eq!(aliases, vec![("KEYS".to_string(), "SCAN".to_string())]);
+        assert_eq!(config.rename_commands, vec!["FLUSHDB", "SHUTDOWN"]);
+        assert!(Config::default().command_aliases.is_empty());
+    }
+
+    #[test]
+    fn test_runtime_config_get_set() {
+        let mut config = Config::default();
+        config.max_connections = 10;
+        let mut rt = RuntimeConfig::from_config(&config);
+
+        assert_eq!(rt.get("max_connections").unwrap(), "10");
+        assert_eq!(rt.get("rate_limit_per_sec").unwrap(), "0");
+        assert_eq!(rt.get("read-only").unwrap(), "off");
+
+        rt.set("rate_limit_per_sec", "50").unwrap();
+        rt.set("READ_ONLY", "on").unwrap();
+        assert_eq!(rt.rate_limit_per_sec, 50);
+        assert!(rt.read_only);
+
+        assert!(rt.set("max_connections", "-1").is_err());
+        assert!(rt.set("read_only", "maybe").is_err());
+        assert!(rt.get("maxmemory").is_err());
+        assert!(rt.set("maxmemory", "1").is_err());
+    }
 }
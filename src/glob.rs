@@ -0,0 +1,108 @@
+//! # Glob Patterns
+//!
+//! Redis-style glob matching for key patterns (`DBSIZE <pattern>`,
+//! `MEMORY USAGE <pattern>`, `SCAN <prefix> MATCH <pattern>`):
+//! - `*` matches any run of characters, including none
+//! - `?` matches exactly one character
+//! - `[abc]`, `[a-z]` match one character from the set; `[!a]` or `[^a]` negate it
+//! - `\x` matches `x` literally
+
+/// Check whether `text` matches the glob `pattern` in full.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it is currently matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match_class(&pattern, p, text[t]),
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == text[t]).then_some(p + 2),
+            Some(&c) => (c == text[t]).then_some(p + 1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                t += 1;
+            }
+            // Let the last `*` swallow one more character and retry
+            (None, Some((star, matched))) => {
+                p = star + 1;
+                t = matched + 1;
+                backtrack = Some((star, matched + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the `[...]` class opening at `pattern[start]`.
+///
+/// Returns the index just past the class on a match. An unterminated `[`
+/// is treated as a literal character.
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<usize> {
+    let mut i = start + 1;
+    let negate = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() && (pattern[i] != ']' || first) {
+        first = false;
+        let lo = if pattern[i] == '\\' && i + 1 < pattern.len() {
+            i += 1;
+            pattern[i]
+        } else {
+            pattern[i]
+        };
+        if pattern.get(i + 1) == Some(&'-') && i + 2 < pattern.len() && pattern[i + 2] != ']' {
+            matched |= (lo..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= lo == c;
+            i += 1;
+        }
+    }
+    if i >= pattern.len() {
+        return (c == '[').then_some(start + 1);
+    }
+    (matched != negate).then_some(i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("user:*", "user:1"));
+        assert!(!glob_match("user:*", "order:1"));
+        assert!(glob_match("*:admin:*", "tenant:admin:42"));
+        assert!(glob_match("user:?", "user:7"));
+        assert!(!glob_match("user:?", "user:42"));
+        assert!(glob_match("h[ae]llo", "hallo"));
+        assert!(!glob_match("h[ae]llo", "hillo"));
+        assert!(glob_match("h[^e]llo", "hallo"));
+        assert!(glob_match("h[!e]llo", "hallo"));
+        assert!(!glob_match("h[!e]llo", "hello"));
+        assert!(glob_match("id[0-9]", "id5"));
+        assert!(!glob_match("id[0-9]", "idx"));
+        assert!(glob_match(r"a\*b", "a*b"));
+        assert!(!glob_match(r"a\*b", "axb"));
+        assert!(glob_match("a*b*c", "aXXbYYbc"));
+        assert!(glob_match("[", "["));
+        assert!(glob_match("ключ:*", "ключ:значение"));
+    }
+}
@@ -0,0 +1,152 @@
+//! # Per-Key Change History
+//!
+//! Keeps the most recent writes to each key as `ChangeEvent`s for `HISTORY`,
+//! so an operator can see how a key reached its current value, e.g. which of
+//! two racing writes won under last-writer-wins. Each key has a bounded ring
+//! buffer of `key_history` entries; with 0 (the default) nothing is recorded.
+//!
+//! History is fed with the writes made through this node's clients. Events
+//! applied from replication or anti-entropy sync are not recorded, and a
+//! deleted key keeps its history so the delete itself can be inspected. At
+//! most `MAX_TRACKED_KEYS` keys are tracked, the least recently written one
+//! being dropped first, and TRUNCATE/FLUSHDB forget every key.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::change_event::{ChangeEvent, OpKind};
+
+/// Most keys with a history at once.
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Bounded log of the most recent writes per key.
+#[derive(Debug)]
+pub struct KeyHistory {
+    capacity: usize,
+    max_keys: usize,
+    keys: Mutex<TrackedKeys>,
+}
+
+/// Histories by key, with the keys ordered by their latest write.
+#[derive(Debug, Default)]
+struct TrackedKeys {
+    /// Key → sequence number of its latest write and its events
+    events: HashMap<String, (u64, VecDeque<ChangeEvent>)>,
+    /// Sequence number of each key's latest write → key, oldest first
+    by_write: BTreeMap<u64, String>,
+    next_seq: u64,
+}
+
+impl KeyHistory {
+    /// Create a history holding at most `capacity` events per key.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_max_keys(capacity, MAX_TRACKED_KEYS)
+    }
+
+    fn with_max_keys(capacity: usize, max_keys: usize) -> Self {
+        Self {
+            capacity,
+            max_keys,
+            keys: Mutex::new(TrackedKeys::default()),
+        }
+    }
+
+    /// Whether writes are being recorded at all.
+    pub fn enabled(&self) -> bool {
+        self.capacity != 0
+    }
+
+    /// Record a write. A `Clear` forgets every key.
+    pub fn record(&self, ev: ChangeEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut keys = self.keys.lock().unwrap();
+        if ev.op == OpKind::Clear {
+            *keys = TrackedKeys::default();
+            return;
+        }
+        let keys = &mut *keys;
+        let seq = keys.next_seq;
+        keys.next_seq += 1;
+        match keys.events.get_mut(&ev.key) {
+            Some((last, _)) => {
+                keys.by_write.remove(last);
+                *last = seq;
+            }
+            None => {
+                if keys.events.len() == self.max_keys {
+                    if let Some((_, oldest)) = keys.by_write.pop_first() {
+                        keys.events.remove(&oldest);
+                    }
+                }
+                keys.events.insert(ev.key.clone(), (seq, VecDeque::new()));
+            }
+        }
+        keys.by_write.insert(seq, ev.key.clone());
+        let (_, events) = keys.events.get_mut(&ev.key).expect("inserted above");
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(ev);
+    }
+
+    /// Up to `count` of the latest events for `key`, oldest first.
+    pub fn recent(&self, key: &str, count: usize) -> Vec<ChangeEvent> {
+        let keys = self.keys.lock().unwrap();
+        let Some((_, events)) = keys.events.get(key) else {
+            return Vec::new();
+        };
+        events.iter().skip(events.len().saturating_sub(count)).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(op: OpKind, key: &str, value: Option<&str>, ts: u64) -> ChangeEvent {
+        ChangeEvent::with_str_value(1, op, key, value, ts, "node1", None, None)
+    }
+
+    #[test]
+    fn test_keeps_latest_events_per_key() {
+        let history = KeyHistory::new(2);
+        for ts in 1..=3 {
+            history.record(event(OpKind::Set, "a", Some(&ts.to_string()), ts));
+        }
+        history.record(event(OpKind::Del, "b", None, 4));
+
+        let a: Vec<_> = history.recent("a", 10).into_iter().map(|ev| ev.ts).collect();
+        assert_eq!(a, vec![2, 3]);
+        assert_eq!(history.recent("b", 1)[0].op, OpKind::Del);
+        assert!(history.recent("missing", 10).is_empty());
+
+        // A clear forgets every key
+        history.record(event(OpKind::Clear, "", None, 5));
+        assert!(history.recent("a", 10).is_empty());
+        assert!(history.recent("b", 10).is_empty());
+    }
+
+    #[test]
+    fn test_drops_least_recently_written_key() {
+        let history = KeyHistory::with_max_keys(2, 2);
+        history.record(event(OpKind::Set, "a", Some("1"), 1));
+        history.record(event(OpKind::Set, "b", Some("1"), 2));
+        history.record(event(OpKind::Set, "a", Some("2"), 3));
+        history.record(event(OpKind::Set, "c", Some("1"), 4));
+
+        assert!(history.recent("b", 10).is_empty());
+        assert_eq!(history.recent("a", 10).len(), 2);
+        assert_eq!(history.recent("c", 10).len(), 1);
+        assert_eq!(history.keys.lock().unwrap().by_write.len(), 2);
+    }
+
+    #[test]
+    fn test_zero_capacity_records_nothing() {
+        let history = KeyHistory::new(0);
+        assert!(!history.enabled());
+        history.record(event(OpKind::Set, "a", Some("1"), 1));
+        assert!(history.recent("a", 10).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+//! # Hot Key Tracking
+//!
+//! Counts how often each key is read with `GET` or written with `SET`, so
+//! `HOTKEYS` can name the keys taking most of the traffic. Counting is exact:
+//! one map entry per key touched since startup (or the last flush), which is
+//! why it only runs when `track_key_stats` is set.
+//!
+//! Counts outlive deletes, so a key that was hot and then removed still shows
+//! up until the database is flushed. Only hits are counted; a `GET` of a
+//! missing key leaves the map untouched.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// GET/SET hit counts per key.
+#[derive(Debug)]
+pub struct KeyStats {
+    enabled: bool,
+    counts: Mutex<HashMap<String, u64>>,
+}
+
+impl KeyStats {
+    /// Create an empty counter; a disabled one ignores every access.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether accesses are being counted at all.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Count one access to `key`.
+    pub fn record(&self, key: &str) {
+        if !self.enabled {
+            return;
+        }
+        let mut counts = self.counts.lock().unwrap();
+        match counts.get_mut(key) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(key.to_string(), 1);
+            }
+        }
+    }
+
+    /// Forget every count (called after the store is cleared).
+    pub fn clear(&self) {
+        self.counts.lock().unwrap().clear();
+    }
+
+    /// The `n` most accessed keys with their counts, busiest first; ties are
+    /// broken by key so the order is stable.
+    pub fn top(&self, n: usize) -> Vec<(String, u64)> {
+        let counts = self.counts.lock().unwrap();
+        let mut keys: Vec<(String, u64)> = counts.iter().map(|(k, c)| (k.clone(), *c)).collect();
+        keys.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        keys.truncate(n);
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_orders_by_count_then_key() {
+        let stats = KeyStats::new(true);
+        for key in ["b", "a", "c", "a", "b", "a"] {
+            stats.record(key);
+        }
+        assert_eq!(
+            stats.top(10),
+            vec![("a".to_string(), 3), ("b".to_string(), 2), ("c".to_string(), 1)]
+        );
+        assert_eq!(stats.top(1), vec![("a".to_string(), 3)]);
+
+        stats.clear();
+        assert!(stats.top(10).is_empty());
+    }
+
+    #[test]
+    fn test_disabled_records_nothing() {
+        let stats = KeyStats::new(false);
+        stats.record("k");
+        assert!(!stats.enabled());
+        assert!(stats.top(10).is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+//! # Command Latency Histograms
+//!
+//! Lock-free latency tracking for `STATS`. Each histogram is a fixed array of
+//! atomic counters with power-of-two microsecond buckets, so recording a sample
+//! is a single relaxed `fetch_add` on the hot path. Percentiles are reported as
+//! the upper bound of the bucket holding the requested rank, which is accurate
+//! to within a factor of two — enough to spot a slow command type.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Number of buckets. Bucket 0 holds samples under 1µs; bucket `i` holds
+/// samples in `[2^(i-1), 2^i)` µs. The last bucket also absorbs anything slower.
+const BUCKETS: usize = 32;
+
+/// Fixed-bucket latency histogram.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for LatencyHistogram {
+    fn clone(&self) -> Self {
+        Self {
+            buckets: std::array::from_fn(|i| AtomicU64::new(self.buckets[i].load(Ordering::Relaxed))),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    /// Record one sample.
+    pub fn record(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Discard all samples.
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of samples recorded.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Latency in µs at or below which `percentile` percent of samples fall
+    /// (bucket upper bound), or `None` with no samples.
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((percentile / 100.0) * total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(1u64 << i);
+            }
+        }
+        Some(1u64 << (BUCKETS - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_histogram_has_no_percentiles() {
+        let h = LatencyHistogram::new();
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.percentile(50.0), None);
+    }
+
+    #[test]
+    fn test_percentiles_use_bucket_upper_bounds() {
+        let h = LatencyHistogram::new();
+        for _ in 0..98 {
+            h.record(Duration::from_micros(3)); // bucket [2, 4)
+        }
+        h.record(Duration::from_micros(900)); // bucket [512, 1024)
+        h.record(Duration::from_secs(3600)); // clamped into the last bucket
+
+        assert_eq!(h.count(), 100);
+        assert_eq!(h.percentile(50.0), Some(4));
+        assert_eq!(h.percentile(99.0), Some(1024));
+        assert_eq!(h.percentile(100.0), Some(1 << 31));
+        assert_eq!(h.clone().count(), 100);
+
+        h.reset();
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.percentile(50.0), None);
+    }
+}
@@ -27,10 +27,16 @@ use anyhow::Result;
 use std::path::PathBuf;
 
 // Core modules for the MerkleKV system
+mod access; // Per-key access tracking (idle time, last write)
 mod config; // Configuration management
+mod glob; // Glob pattern matching for key patterns
+mod history; // Per-key ring buffers of recent writes for HISTORY
+mod hotkeys; // Per-key GET/SET hit counts for HOTKEYS
+mod latency; // Per-command latency histograms for STATS
 mod protocol; // Command parsing and protocol handling
 mod replication; // MQTT-based replication (stub)
 mod server; // TCP server for client connections
+mod slowlog; // Ring buffer of slow commands for SLOWLOG
 mod store; // Storage engine and Merkle tree
 mod sync; // Anti-entropy synchronization (stub)
 mod change_event; // Change event schema & codecs
@@ -115,37 +121,58 @@ fn main() -> Result<()> {
     }
 
     // Create a multi-threaded async runtime for handling concurrent connections
-    let runtime = tokio::runtime::Builder::new_multi_thread()
-        .enable_all() // Enable all Tokio features (timers, I/O, etc.)
-        .build()?;
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all(); // Enable all Tokio features (timers, I/O, etc.)
+    if config.worker_threads > 0 {
+        builder.worker_threads(config.worker_threads);
+    }
+    let runtime = builder.build()?;
 
     // Start the server in the async runtime
     runtime.block_on(async {
         // Initialize the storage engine based on configuration
-        let store: Box<dyn KVEngineStoreTrait + Send + Sync> = match config.engine.as_str() {
-            "rwlock" => {
-                println!("Using thread-safe RwLockEngine");
-                Box::new(RwLockEngine::new(&config.storage_path)?)
-            }
+        match config.engine.as_str() {
+            "rwlock" => println!("Using thread-safe RwLockEngine"),
             "kv" => {
                 println!("⚠️  WARNING: Using non-thread-safe KvEngine!");
                 println!("   This engine is NOT safe for concurrent access.");
                 println!("   Only use this for single-threaded applications or testing.");
-                Box::new(KvEngine::new(&config.storage_path)?)
-            }
-            "sled" => {
-                println!("Using persistent SledEngine");
-                Box::new(SledEngine::new(&config.storage_path)?)
             }
+            "sled" => println!("Using persistent SledEngine"),
             _ => {
                 eprintln!("Error: Unknown engine type '{}'", config.engine);
                 eprintln!("Available engines: rwlock, kv, sled");
                 std::process::exit(1);
             }
-        };
+        }
+
+        // One engine per logical database; database 0 keeps the configured path
+        let mut stores: Vec<Box<dyn KVEngineStoreTrait + Send + Sync>> = Vec::new();
+        for db in 0..config.databases.max(1) {
+            let path = if db == 0 {
+                config.storage_path.clone()
+            } else {
+                format!("{}-db{}", config.storage_path, db)
+            };
+            stores.push(open_engine(&config, &path)?);
+        }
 
         // Create and start the TCP server
-        let server = server::Server::new(config.clone(), store);
+        let server = server::Server::with_databases(config.clone(), stores);
         server.run().await
     })
 }
+
+/// Open a storage engine of the configured type at `path`.
+/// Only the `rwlock` engine supports in-memory compression and integer encoding.
+fn open_engine(config: &config::Config, path: &str) -> Result<Box<dyn KVEngineStoreTrait + Send + Sync>> {
+    Ok(match config.engine.as_str() {
+        "kv" => Box::new(KvEngine::new(path)?),
+        "sled" => Box::new(SledEngine::new(path)?),
+        _ => Box::new(
+            RwLockEngine::new(path)?
+                .with_compress_threshold(config.compress_threshold_bytes)
+                .with_int_encoding(config.int_encoding),
+        ),
+    })
+}
@@ -9,26 +9,164 @@
 //!
 //! ### Basic Operations
 //! - `GET <key>` - Retrieve a value by key
+//! - `GETEX <key> [EX <seconds> | PX <millis> | PERSIST]` - Retrieve a value like `GET` and in
+//!   the same step make the key expire after the given time, or never with `PERSIST`
+//! - `GETLEN <key>` (or `BGET <key>`) - `VALUE <byte_len>`, then exactly that many raw bytes
+//!   and CRLF; safe for values written with `BSET` that contain CR, LF or NUL
 //! - `SET <key> <value>` - Store a key-value pair  
+//! - `SETRAW <key> <byte_len>` (or `BSET <key> <byte_len>`) - Followed by exactly `byte_len`
+//!   bytes and CRLF; the way to store values containing CR or LF. Such payloads are kept as
+//!   binary values: `GETLEN`/`BGET` return the exact bytes, `GET` refuses them and `EXPORT`
+//!   writes them as a `"base64"` line
 //! - `DEL <key>` or `DELETE <key>` - Delete a key
+//! - `DEL <key1> ... <keyN> VERBOSE` - Delete keys, reporting which were missing
+//! - `UNLINK <key1> ... <keyN>` - Delete keys, freeing their values in the background
+//!   (`UNLINKED <n>`)
+//!
+//! A SET/APPEND/PREPEND value starting with `"` is a double-quoted string with
+//! `\n`, `\r`, `\t`, `\\`, `\"` and `\xHH` escapes, e.g. `SET k "  spaced\t"`. A SET
+//! value containing CR, LF or NUL is stored as a binary value, read back with `BGET`.
 //!
 //! ### Numeric Operations
 //! - `INC <key> [amount]` - Increment a numeric value (default: 1)
 //! - `DEC <key> [amount]` - Decrement a numeric value (default: 1)
 //!
+//! ### Key Inspection
+//! - `EXISTS <key1> ... <keyN> [VERBOSE]` - Count existing keys, optionally with per-key flags
+//! - `OBJECT ENCODING <key>` - Report `int` for integer values, `raw` otherwise
+//! - `OBJECT IDLETIME <key>` - Seconds since the key was last read or written
+//! - `HISTORY <key> [n]` - The last `n` (default all kept) writes to a key through this node,
+//!   oldest first: `HISTORY <count>`, then `<ts> <op> [value]` lines; needs `key_history`
+//! - `HOTKEYS [n]` - The `n` (default 10) keys with the most GET/SET hits, busiest first:
+//!   `HOTKEYS <k>`, then `<key> <count>` lines; needs `track_key_stats`
+//! - `SCAN [prefix] [MATCH <pattern>]` - Keys starting with `prefix` (`KEYS <n>` followed by
+//!   one key per line); with `MATCH`, only those that also match the glob `pattern`
+//! - `SCANVALUES [prefix] [MATCH <pattern>]` - Keys matching like `SCAN` with their values,
+//!   read in one step (`PAIRS <n>` followed by `key value` lines); hash and binary values
+//!   are skipped as in `MGET`
+//! - `EXPIRETIME <key>` / `PEXPIRETIME <key>` - Absolute expiry deadline in Unix seconds /
+//!   milliseconds (`EXPIRETIME <ts>`; `-1` if the key has no expiry, `-2` if it is missing)
+//! - `DBSIZE [pattern]` - Number of keys, or of keys matching a glob (`DBSIZE <pattern> <n>`)
+//! - `MEMORY [USAGE <pattern>]` - Estimated memory use, or the key and value bytes of keys
+//!   matching a glob (`MEMORY <pattern> <bytes>`)
+//! - `MEMORY DOCTOR` - Value size report from one pass over the keyspace:
+//!   `MEMORY DOCTOR keys=<n> bytes=<n>`, one `BUCKET <=<size> <count>` line per value size bucket
+//!   (the last one `BUCKET ><size> <count>`), then `LARGEST <n>` and up to ten
+//!   `<key> <value_bytes>` lines, largest first
+//!
 //! ### String Operations
 //! - `APPEND <key> <value>` - Append value to existing string
 //! - `PREPEND <key> <value>` - Prepend value to existing string
+//! - `CAS <key> <expected> <new>` - Set `new` only if the current value equals `expected`
+//!   (`""` as `expected` matches a missing key, i.e. create-if-absent)
+//! - `GETVER <key>` - Value with its version (`VALUE <v> VERSION <n>` or `NOT_FOUND`)
+//! - `CASVER <key> <expected_version> <new>` - Set `new` only if the key is still at
+//!   `expected_version` (0 matches a missing key); `VERSION_MISMATCH` otherwise
+//!
+//! ### Logical Databases
+//! - `SELECT <index>` - Switch this connection to database `index` (default 0)
+//! - `MOVE <key> <db>` - Move a key to database `db`; fails if the key exists there
+//!
+//! ### Sampling
+//! - `RANDOMKEY` - Return a random key as `KEY <k>` (or `NOT_FOUND` when empty)
+//!
+//! ### Hashes
+//! - `HSET <key> <field> <value>` - Set a field of the hash at `key`
+//! - `HGET <key> <field>` - Get a field (`VALUE <v>` or `NOT_FOUND`)
+//! - `HGETALL <key>` - All fields sorted by name (`VALUES <n>` followed by `field value` lines)
+//! - `HDEL <key> <field1> ... <fieldN>` - Remove fields (`DELETED <n>`)
+//! - `HLEN <key>` - Number of fields (`HLEN <n>`)
+//!
+//! String commands against a hash key (and hash commands against a string key)
+//! return `ERROR WRONGTYPE ...`.
+//!
+//! ### Migration
+//! - `DUMP <key>` - Serialize a key as `DUMP <hex>` (or `NOT_FOUND`)
+//! - `RESTORE <key> <hex> [REPLACE]` - Apply a dump; refused with `ERROR busykey` when
+//!   the local value is newer, unless `REPLACE` is given
 //!
 //! ### Bulk Operations
 //! - `MGET <key1> <key2> ... <keyN>` - Get multiple keys in one command
 //! - `MSET <key1> <value1> <key2> <value2> ...` - Set multiple key-value pairs
+//! - `MSETEX <key1> <ttl1> <value1> ...` - Set multiple keys, each expiring after its own
+//!   TTL in seconds (0 for no expiry); a bad TTL rejects the whole batch
+//! - `FSYNC` - Reply `OK` once every write so far is flushed to disk (sled); a durability
+//!   barrier that is a no-op for in-memory engines
+//! - `COMPACT` - Reclaim disk space left by deleted keys (sled); a no-op for in-memory engines
 //! - `TRUNCATE` - Clear all keys/values in the store
+//! - `EXPORT` - Every key as a `{"key":...,"value":...}` JSON line, sorted by key, then `END`;
+//!   hashes carry `"hash":{...}` and binary values `"base64":...` instead of `"value"`.
+//!   Lines are streamed, so this is not a point-in-time snapshot: a key written meanwhile
+//!   is exported as it is when its line is reached
+//! - `IMPORT` - Followed by JSON lines in the `EXPORT` format and `END`; stores them all
+//!   (`IMPORTED <n>`) or, if any line is malformed, none (`ERROR bad line <n>`). At most
+//!   `max_bulk_keys` lines are accepted. Expiry deadlines are not carried over
+//!
+//! ### Administration
+//! - `CONFIG GET <param>` - Read a runtime setting as `CONFIG <param> <value>`
+//! - `CONFIG SET <param> <value>` - Change a runtime setting; supported parameters are
+//!   `max_connections`, `rate_limit_per_sec` and `read_only` (`on|off`; while on, writes
+//!   return `ERROR READONLY`)
+//! - `CONFIG GETALL` - Every effective setting, sorted: `CONFIGALL <n>`, then `<name> <value>`
+//!   lines. Runtime settings show their current value; passwords read `****`
+//!
+//! ### Synchronization
+//! - `HASH [prefix]` - Merkle root over all keys (or keys with `prefix`) as `HASH [prefix] <hex>`,
+//!   or `HASH [prefix] EMPTY` when no key matches
+//! - `HASH BUCKETS <n>` - Merkle root per bucket, keys assigned by `hash(key) % n`
+//! - `HASH RANGE <start> <end>` - Merkle root over keys in `[start, end)`, in byte order
+//! - `MERKLE DUMP` - The Merkle tree over all keys in preorder: `NODES <n>`, then one
+//!   `NODE <depth> <hex> [key]` line per node (leaves name their key)
+//! - `MERKLE BENCH` - Rebuild the Merkle tree over all keys and time it:
+//!   `MERKLE BENCH keys=<n> micros=<t> root=<hex>` (`root=EMPTY` without keys)
+//! - `VERIFY` - Rebuild the Merkle tree and check it against the root cached by the last
+//!   `VERIFY`: `VERIFY ok <hex>`, or `VERIFY repaired <hex>` when the keyspace changed
+//!   without a write being recorded and the cache was replaced (`EMPTY` without keys)
+//! - `SYNC <host> <port> [--full] [--verify] [--dry-run]` - Pull divergent keys from a peer;
+//!   replies `SYNCED pulled=<n> pushed=<n> conflicts=<n>`. With `--dry-run` nothing is
+//!   written and the reply is `DRYRUN pull=<n> push=<n>`, the keys a real run would change
+//! - `SYNC PEERS <host:port>...` - Pull from several peers at once, the most recent write
+//!   of each key winning; needs a majority of the peers reachable. Replies `SYNCED <n>`,
+//!   then one `<host:port> pulled=<n> pushed=<n> conflicts=<n>` or `<host:port> ERROR <msg>`
+//!   line per peer
+//! - `DIFF <host> <port> [prefix]` - List keys that differ from a peer, without changing anything
+//! - `WAIT <numreplicas> <timeout_ms>` - Block until `numreplicas` replicas have acknowledged
+//!   every write this node has replicated so far, or the timeout elapses; replies
+//!   `WAIT <n>` with the number of replicas that acknowledged
+//! - `WATERMARK` - Highest replication timestamp this node has applied, in nanoseconds:
+//!   `WATERMARK <ts>` (`0` before any event or without replication)
 //!
 //! ### Statistical Commands
 //! - `STATS` - Return general server statistics (connections, operations, memory usage)
+//! - `STATS RESET` - Zero the command and connection counters
+//! - `SLOWLOG GET [n]` - Up to `n` (default 10) most recent slow commands, newest first
+//! - `SLOWLOG RESET` - Empty the slow log
+//! - `DEBUG SLEEP <seconds>` - Block this connection for up to 10 seconds (testing aid).
+//!   Needs `enable_debug`
+//! - `MONITOR` - Reply `OK`, then stream every command any client runs as
+//!   `<unix_ts> [<client_id>] <command>`; the connection accepts no further commands.
+//!   Needs `enable_monitor`
 //! - `INFO` - Return detailed server information (version, uptime, config)
 //! - `PING` - Simple health check command
+//! - `PING DEEP` - Write, read back and delete a sentinel key; `PONG` only if the
+//!   storage engine completes the round trip
+//! - `HELLO [proto [CRLF|LF] [COMPRESS]]` - Handshake reporting protocol version, server version,
+//!   engine and commands; the optional line ending frames every later response on the connection,
+//!   and `COMPRESS` lets large GET values arrive as `VALUEZ zstd <len>` followed by `len`
+//!   bytes holding one standard zstd frame (RFC 8878) and CRLF; any zstd library decodes it.
+//!   The `compress:<algo>` HELLO line names the algorithm, or `none` if the server has
+//!   compression off
+//! - `ENGINE` - Storage engine backing the server, whether it persists, and its storage path
+//! - `NODE` - Replication identity: `NODE node_id=<id> topic_prefix=<p> codec=<c>
+//!   replication_enabled=<true|false>`
+//! - `AUTH <user> <password>` - Log in as a user from `users`; once any are configured, other
+//!   commands need a login (`ERROR NOAUTH`) and run only within the user's categories
+//!   (`ERROR NOPERM`)
+//! - `COMMAND` / `COMMAND DOCS` - `COMMANDS <n>`, then one `<name> <min_args> <max_args> <flags>`
+//!   line per command; `max_args` is `*` when unbounded and flags are a comma separated list
+//!   of `readonly`, `write` and `admin`
+//! - `HEALTH` - `HEALTH ok uptime=<s>`, or `HEALTH degraded <reasons> uptime=<s>` when storage
+//!   cannot be flushed (`storage`) or the replication broker is unreachable (`replication`)
 //!
 //! ## Example Usage
 //! ```
@@ -49,7 +187,249 @@
 //! - Success responses: `VALUE <data>`, `OK`
 //! - Error responses: `ERROR <message>`, `NOT_FOUND`
 
+use crate::store::{binary_value, hash_value};
 use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+/// Version of the text protocol implemented by this server.
+///
+/// Clients may request a specific version with `HELLO <proto>`; any other
+/// version is rejected so that client libraries can fall back gracefully.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Upper bound on `HASH BUCKETS <n>` to keep responses reasonably sized.
+pub const MAX_HASH_BUCKETS: usize = 65536;
+
+/// Entries returned by `SLOWLOG GET` without a count.
+pub const DEFAULT_SLOWLOG_COUNT: usize = 10;
+
+/// Keys returned by `HOTKEYS` without a count.
+pub const DEFAULT_HOTKEYS_COUNT: usize = 10;
+
+/// Longest `DEBUG SLEEP`, in seconds.
+pub const MAX_DEBUG_SLEEP_SECS: f64 = 10.0;
+
+/// Names of all commands understood by the server, as reported by `HELLO`.
+pub const SUPPORTED_COMMANDS: &[&str] = &[
+    "GET", "SET", "DEL", "DELETE", "PING", "ECHO", "EXISTS", "SCAN", "HASH", "INC", "DEC",
+    "APPEND", "PREPEND", "MGET", "MSET", "SYNC", "REPLICATE", "TRUNCATE", "STATS", "INFO",
+    "DBSIZE", "VERSION", "FLUSHDB", "SHUTDOWN", "MEMORY", "CLIENT", "HELLO", "CAS", "OBJECT",
+    "DUMP", "RESTORE", "CONFIG", "HSET", "HGET", "HGETALL", "HDEL", "HLEN",
+    "RANDOMKEY", "SELECT", "MOVE", "SETEX", "PSETEX", "INCRLIMIT",
+    "BSET", "BGET", "DIFF", "ENGINE", "SLOWLOG", "DEBUG", "UNLINK",
+    "HEALTH", "EXPORT", "IMPORT", "GETVER", "CASVER", "SCANVALUES", "MERKLE",
+    "EXPIRETIME", "PEXPIRETIME", "NODE", "GETLEN", "MSETEX", "MONITOR", "WAIT", "VERIFY",
+    "WATERMARK", "HISTORY", "GETEX", "COMMAND", "HOTKEYS", "AUTH", "COMPACT", "FSYNC",
+    "SETRAW",
+];
+
+/// How a command affects the server, as listed by `COMMAND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandFlag {
+    /// Reads keys or server state without changing the keyspace
+    Readonly,
+    /// May change the keyspace
+    Write,
+    /// Manages the server, its connections or replication
+    Admin,
+}
+
+impl CommandFlag {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CommandFlag::Readonly => "readonly",
+            CommandFlag::Write => "write",
+            CommandFlag::Admin => "admin",
+        }
+    }
+}
+
+/// Arity and flags of one command name. Arguments are the whitespace
+/// separated words after the name, subcommands included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub min_args: usize,
+    /// `None` when trailing values may contain spaces or keys repeat
+    pub max_args: Option<usize>,
+    pub flags: &'static [CommandFlag],
+}
+
+const fn spec(
+    name: &'static str,
+    min_args: usize,
+    max_args: Option<usize>,
+    flags: &'static [CommandFlag],
+) -> CommandSpec {
+    CommandSpec { name, min_args, max_args, flags }
+}
+
+const READONLY: &[CommandFlag] = &[CommandFlag::Readonly];
+const WRITE: &[CommandFlag] = &[CommandFlag::Write];
+const ADMIN: &[CommandFlag] = &[CommandFlag::Admin];
+
+/// One constant per `COMMAND_TABLE` entry, so `Command::spec` can name its
+/// entry directly.
+mod specs {
+    use super::{spec, CommandFlag, CommandSpec, ADMIN, READONLY, WRITE};
+
+    pub const GET: CommandSpec = spec("GET", 1, Some(1), READONLY);
+    pub const GETEX: CommandSpec = spec("GETEX", 1, Some(3), WRITE);
+    pub const GETLEN: CommandSpec = spec("GETLEN", 1, Some(1), READONLY);
+    pub const BGET: CommandSpec = spec("BGET", 1, Some(1), READONLY);
+    pub const BSET: CommandSpec = spec("BSET", 2, Some(2), WRITE);
+    pub const SETRAW: CommandSpec = spec("SETRAW", 2, Some(2), WRITE);
+    pub const SET: CommandSpec = spec("SET", 2, None, WRITE);
+    pub const SETEX: CommandSpec = spec("SETEX", 3, None, WRITE);
+    pub const PSETEX: CommandSpec = spec("PSETEX", 3, None, WRITE);
+    pub const DEL: CommandSpec = spec("DEL", 1, None, WRITE);
+    pub const DELETE: CommandSpec = spec("DELETE", 1, None, WRITE);
+    pub const UNLINK: CommandSpec = spec("UNLINK", 1, None, WRITE);
+    pub const INC: CommandSpec = spec("INC", 1, Some(2), WRITE);
+    pub const DEC: CommandSpec = spec("DEC", 1, Some(2), WRITE);
+    pub const INCRLIMIT: CommandSpec = spec("INCRLIMIT", 3, Some(3), WRITE);
+    pub const APPEND: CommandSpec = spec("APPEND", 2, None, WRITE);
+    pub const PREPEND: CommandSpec = spec("PREPEND", 2, None, WRITE);
+    pub const EXISTS: CommandSpec = spec("EXISTS", 1, None, READONLY);
+    pub const OBJECT: CommandSpec = spec("OBJECT", 2, Some(2), READONLY);
+    pub const HISTORY: CommandSpec = spec("HISTORY", 1, Some(2), READONLY);
+    pub const HOTKEYS: CommandSpec = spec("HOTKEYS", 0, Some(1), READONLY);
+    pub const SCAN: CommandSpec = spec("SCAN", 0, Some(3), READONLY);
+    pub const SCANVALUES: CommandSpec = spec("SCANVALUES", 0, Some(3), READONLY);
+    pub const EXPIRETIME: CommandSpec = spec("EXPIRETIME", 1, Some(1), READONLY);
+    pub const PEXPIRETIME: CommandSpec = spec("PEXPIRETIME", 1, Some(1), READONLY);
+    pub const DBSIZE: CommandSpec = spec("DBSIZE", 0, Some(1), READONLY);
+    pub const MEMORY: CommandSpec = spec("MEMORY", 0, Some(2), READONLY);
+    pub const CAS: CommandSpec = spec("CAS", 3, None, WRITE);
+    pub const GETVER: CommandSpec = spec("GETVER", 1, Some(1), READONLY);
+    pub const CASVER: CommandSpec = spec("CASVER", 3, None, WRITE);
+    pub const SELECT: CommandSpec = spec("SELECT", 1, Some(1), READONLY);
+    pub const MOVE: CommandSpec = spec("MOVE", 2, Some(2), WRITE);
+    pub const RANDOMKEY: CommandSpec = spec("RANDOMKEY", 0, Some(0), READONLY);
+    pub const HSET: CommandSpec = spec("HSET", 3, None, WRITE);
+    pub const HGET: CommandSpec = spec("HGET", 2, Some(2), READONLY);
+    pub const HGETALL: CommandSpec = spec("HGETALL", 1, Some(1), READONLY);
+    pub const HDEL: CommandSpec = spec("HDEL", 2, None, WRITE);
+    pub const HLEN: CommandSpec = spec("HLEN", 1, Some(1), READONLY);
+    pub const DUMP: CommandSpec = spec("DUMP", 1, Some(1), READONLY);
+    pub const RESTORE: CommandSpec = spec("RESTORE", 2, Some(3), WRITE);
+    pub const MGET: CommandSpec = spec("MGET", 1, None, READONLY);
+    pub const MSET: CommandSpec = spec("MSET", 2, None, WRITE);
+    pub const MSETEX: CommandSpec = spec("MSETEX", 3, None, WRITE);
+    pub const TRUNCATE: CommandSpec = spec("TRUNCATE", 0, Some(0), WRITE);
+    pub const FLUSHDB: CommandSpec = spec("FLUSHDB", 0, Some(0), WRITE);
+    pub const FSYNC: CommandSpec = spec("FSYNC", 0, Some(0), WRITE);
+    pub const EXPORT: CommandSpec = spec("EXPORT", 0, Some(0), READONLY);
+    pub const IMPORT: CommandSpec = spec("IMPORT", 0, Some(0), WRITE);
+    pub const CONFIG: CommandSpec = spec("CONFIG", 1, Some(3), ADMIN);
+    pub const HASH: CommandSpec = spec("HASH", 0, Some(3), READONLY);
+    pub const MERKLE: CommandSpec = spec("MERKLE", 1, Some(1), READONLY);
+    pub const VERIFY: CommandSpec = spec("VERIFY", 0, Some(0), READONLY);
+    pub const SYNC: CommandSpec = spec("SYNC", 1, None, &[CommandFlag::Write, CommandFlag::Admin]);
+    pub const DIFF: CommandSpec = spec("DIFF", 2, Some(3), READONLY);
+    pub const REPLICATE: CommandSpec = spec("REPLICATE", 1, Some(1), ADMIN);
+    pub const WAIT: CommandSpec = spec("WAIT", 2, Some(2), READONLY);
+    pub const WATERMARK: CommandSpec = spec("WATERMARK", 0, Some(0), READONLY);
+    pub const STATS: CommandSpec = spec("STATS", 0, Some(1), ADMIN);
+    pub const SLOWLOG: CommandSpec = spec("SLOWLOG", 1, Some(2), ADMIN);
+    pub const DEBUG: CommandSpec = spec("DEBUG", 2, Some(2), ADMIN);
+    pub const MONITOR: CommandSpec = spec("MONITOR", 0, Some(0), ADMIN);
+    pub const INFO: CommandSpec = spec("INFO", 0, Some(0), ADMIN);
+    pub const CLIENT: CommandSpec = spec("CLIENT", 1, Some(3), ADMIN);
+    pub const SHUTDOWN: CommandSpec = spec("SHUTDOWN", 0, Some(0), ADMIN);
+    pub const COMPACT: CommandSpec = spec("COMPACT", 0, Some(0), ADMIN);
+    pub const PING: CommandSpec = spec("PING", 0, None, READONLY);
+    pub const ECHO: CommandSpec = spec("ECHO", 1, None, READONLY);
+    pub const HELLO: CommandSpec = spec("HELLO", 0, Some(3), READONLY);
+    pub const AUTH: CommandSpec = spec("AUTH", 2, Some(2), READONLY);
+    pub const VERSION: CommandSpec = spec("VERSION", 0, Some(0), READONLY);
+    pub const ENGINE: CommandSpec = spec("ENGINE", 0, Some(0), READONLY);
+    pub const NODE: CommandSpec = spec("NODE", 0, Some(0), READONLY);
+    pub const HEALTH: CommandSpec = spec("HEALTH", 0, Some(0), READONLY);
+    pub const COMMAND: CommandSpec = spec("COMMAND", 0, Some(1), READONLY);
+}
+
+/// Every name in `SUPPORTED_COMMANDS` with its arity and flags (`COMMAND`).
+pub const COMMAND_TABLE: &[CommandSpec] = &[
+    specs::GET,
+    specs::GETEX,
+    specs::GETLEN,
+    specs::BGET,
+    specs::BSET,
+    specs::SETRAW,
+    specs::SET,
+    specs::SETEX,
+    specs::PSETEX,
+    specs::DEL,
+    specs::DELETE,
+    specs::UNLINK,
+    specs::INC,
+    specs::DEC,
+    specs::INCRLIMIT,
+    specs::APPEND,
+    specs::PREPEND,
+    specs::EXISTS,
+    specs::OBJECT,
+    specs::HISTORY,
+    specs::HOTKEYS,
+    specs::SCAN,
+    specs::SCANVALUES,
+    specs::EXPIRETIME,
+    specs::PEXPIRETIME,
+    specs::DBSIZE,
+    specs::MEMORY,
+    specs::CAS,
+    specs::GETVER,
+    specs::CASVER,
+    specs::SELECT,
+    specs::MOVE,
+    specs::RANDOMKEY,
+    specs::HSET,
+    specs::HGET,
+    specs::HGETALL,
+    specs::HDEL,
+    specs::HLEN,
+    specs::DUMP,
+    specs::RESTORE,
+    specs::MGET,
+    specs::MSET,
+    specs::MSETEX,
+    specs::TRUNCATE,
+    specs::FLUSHDB,
+    specs::FSYNC,
+    specs::EXPORT,
+    specs::IMPORT,
+    specs::CONFIG,
+    specs::HASH,
+    specs::MERKLE,
+    specs::VERIFY,
+    specs::SYNC,
+    specs::DIFF,
+    specs::REPLICATE,
+    specs::WAIT,
+    specs::WATERMARK,
+    specs::STATS,
+    specs::SLOWLOG,
+    specs::DEBUG,
+    specs::MONITOR,
+    specs::INFO,
+    specs::CLIENT,
+    specs::SHUTDOWN,
+    specs::COMPACT,
+    specs::PING,
+    specs::ECHO,
+    specs::HELLO,
+    specs::AUTH,
+    specs::VERSION,
+    specs::ENGINE,
+    specs::NODE,
+    specs::HEALTH,
+    specs::COMMAND,
+];
 
 /// Represents the different commands that clients can send to the server.
 ///
@@ -58,6 +438,8 @@ use anyhow::{anyhow, Result};
 pub struct SyncOptions {
     pub full: bool,
     pub verify: bool,
+    /// Only count the keys a sync would change (`--dry-run`)
+    pub dry_run: bool,
 }
 #[derive(Debug, Clone, PartialEq)]
 pub enum ReplicateAction {
@@ -65,18 +447,74 @@ pub enum ReplicateAction {
     Disable,
     Status,
 }
+/// How `GETEX` changes the expiry of the key it reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExpiryChange {
+    /// Expire this many milliseconds from now (`EX`/`PX`; always positive)
+    In(u64),
+    /// Remove the expiry (`PERSIST`)
+    Persist,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectSubcommand {
+    Encoding,
+    Idletime,
+}
+
+/// Line terminator the server uses for responses on one connection.
+///
+/// Requests may end in either; only response framing is affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Crlf,
+    Lf,
+}
+
+impl LineEnding {
+    /// Rewrite a response framed with `\r\n` for this line ending.
+    pub fn frame(self, response: &str) -> Cow<'_, str> {
+        match self {
+            LineEnding::Crlf => Cow::Borrowed(response),
+            LineEnding::Lf => Cow::Owned(response.replace("\r\n", "\n")),
+        }
+    }
+
+    /// The terminator itself.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Lf => "\n",
+        }
+    }
+}
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     /// Control replication settings
     Replicate {
         action: ReplicateAction,
     },
+    /// Wait for replicas to acknowledge the writes replicated so far
+    Wait {
+        /// Number of replicas to wait for
+        replicas: usize,
+        /// Give up after this many milliseconds
+        timeout_ms: u64,
+    },
     /// Retrieve a value by its key
     Get {
         /// The key to look up
         key: String,
     },
 
+    /// Retrieve a value and change the key's expiry in the same step (`GETEX`)
+    GetEx {
+        /// The key to look up
+        key: String,
+        /// The expiry change; without one this is a plain `GET`
+        expiry: Option<ExpiryChange>,
+    },
+
     /// Store a key-value pair
     Set {
         /// The key to store
@@ -85,16 +523,57 @@ pub enum Command {
         value: String,
     },
 
+    /// Store a key-value pair that expires after `ttl_ms` (`SETEX`/`PSETEX`)
+    SetEx {
+        /// The key to store
+        key: String,
+        /// The value to associate with the key
+        value: String,
+        /// Time to live in milliseconds (always positive)
+        ttl_ms: u64,
+    },
+
+    /// Store raw bytes: `BSET <key> <len>` followed by exactly `len` bytes and CRLF.
+    ///
+    /// The parser only sees the header line, so it yields an empty `value`;
+    /// the server reads the payload from the connection and fills it in.
+    BinarySet {
+        /// The key to store
+        key: String,
+        /// Payload length in bytes, from the header
+        len: usize,
+        /// The payload
+        value: Vec<u8>,
+    },
+
+    /// Retrieve a value as length-prefixed raw bytes
+    BinaryGet {
+        /// The key to look up
+        key: String,
+    },
+
     /// Delete a key-value pair
     Delete {
         /// The key to delete
         key: String,
     },
+    /// Delete several keys, reporting a flag per key
+    DeleteVerbose {
+        /// The keys to delete
+        keys: Vec<String>,
+    },
+    /// Delete keys now and free their values on a background task
+    Unlink {
+        /// The keys to delete
+        keys: Vec<String>,
+    },
     /// Ping the server with an optional message
     Ping {
         /// The message to include in the ping response
         message: String,
     },
+    /// Round-trip a sentinel key through the store (`PING DEEP`)
+    PingDeep,
     /// Echo the provided message back to the client
     Echo {
         /// The message to echo back
@@ -105,16 +584,50 @@ pub enum Command {
         /// The key to check for existence
         keys: Vec<String>,
     },
+    /// Check which of the given keys exist, reporting a flag per key
+    ExistsVerbose {
+        /// The keys to check for existence
+        keys: Vec<String>,
+    },
     /// Scan for keys matching a prefix
     Scan {
         /// The prefix to scan for
         prefix: String,
+        /// Glob the prefixed keys must also match (`MATCH <pattern>`)
+        pattern: Option<String>,
+    },
+    /// Scan for keys matching a prefix, returning their values too
+    ScanValues {
+        /// The prefix to scan for
+        prefix: String,
+        /// Glob the prefixed keys must also match (`MATCH <pattern>`)
+        pattern: Option<String>,
     },
     /// Hash a key (not implemented)
     Hash {
         /// The key to hash
         pattern: Option<String>
     },
+    /// Per-bucket Merkle roots over the whole keyspace
+    HashBuckets {
+        /// Number of buckets
+        n: usize,
+    },
+    /// Merkle root over keys in `[start, end)`
+    HashRange {
+        /// First key of the range (inclusive)
+        start: String,
+        /// End of the range (exclusive)
+        end: String,
+    },
+    /// Every node of the Merkle tree over all keys, in preorder (`MERKLE DUMP`)
+    MerkleDump,
+    /// Rebuild the Merkle tree over all keys and report how long it took (`MERKLE BENCH`)
+    MerkleBench,
+    /// Check the cached Merkle root against a full rebuild, replacing it (`VERIFY`)
+    Verify,
+    /// Highest replication timestamp applied on this node (`WATERMARK`)
+    Watermark,
     /// Increment a numeric value
     Increment {
         /// The key to increment
@@ -131,12 +644,24 @@ pub enum Command {
         amount: Option<i64>,
     },
 
+    /// Increment a counter and report whether it is still within `limit`
+    IncrLimit {
+        /// The counter key
+        key: String,
+        /// The amount to increment by
+        amount: i64,
+        /// The highest value that still counts as allowed
+        limit: i64,
+    },
+
     /// Append a value to an existing string
     Append {
         /// The key to append to
         key: String,
         /// The value to append
         value: String,
+        /// Reply with the resulting length only (`APPEND key value LEN`)
+        len_only: bool,
     },
 
     /// Prepend a value to an existing string
@@ -145,12 +670,126 @@ pub enum Command {
         key: String,
         /// The value to prepend
         value: String,
+        /// Reply with the resulting length only (`PREPEND key value LEN`)
+        len_only: bool,
+    },
+
+    /// Absolute expiry deadline of a key (`EXPIRETIME` / `PEXPIRETIME`)
+    ExpireTime {
+        /// The key to inspect
+        key: String,
+        /// Report milliseconds (`PEXPIRETIME`) instead of seconds
+        millis: bool,
+    },
+
+    /// Recent writes to a key, oldest first (`HISTORY`)
+    History {
+        /// The key to inspect
+        key: String,
+        /// Most events to return; all kept ones when absent
+        count: Option<usize>,
+    },
+
+    /// The most read and written keys with their hit counts (`HOTKEYS [n]`)
+    HotKeys {
+        /// Most keys to return
+        count: usize,
+    },
+
+    /// Inspect how a key's value is stored
+    Object {
+        /// The introspection to perform
+        subcmd: ObjectSubcommand,
+        /// The key to inspect
+        key: String,
+    },
+
+    /// Compare-and-swap: set `new` only if the current value equals `expected`
+    Cas {
+        /// The key to update
+        key: String,
+        /// The value the key must currently hold (empty matches a missing key)
+        expected: String,
+        /// The value to store on a match
+        new: String,
+    },
+
+    /// Get a value together with its version
+    GetVersioned {
+        /// The key to look up
+        key: String,
+    },
+
+    /// Set a value only if the key is still at the given version
+    CasVersion {
+        /// The key to update
+        key: String,
+        /// The version the key must currently have (0 matches a missing key)
+        expected: u64,
+        /// The value to store on a match
+        new: String,
+    },
+
+    /// Set a field of a hash
+    HSet {
+        /// The hash key
+        key: String,
+        /// The field to set
+        field: String,
+        /// The field value (may contain spaces)
+        value: String,
+    },
+
+    /// Get a field of a hash
+    HGet {
+        /// The hash key
+        key: String,
+        /// The field to read
+        field: String,
+    },
+
+    /// Get all fields of a hash
+    HGetAll {
+        /// The hash key
+        key: String,
+    },
+
+    /// Remove fields from a hash
+    HDel {
+        /// The hash key
+        key: String,
+        /// The fields to remove
+        fields: Vec<String>,
+    },
+
+    /// Number of fields in a hash
+    HLen {
+        /// The hash key
+        key: String,
+    },
+
+    /// Serialize a single key for migration to another server
+    DumpKey {
+        /// The key to dump
+        key: String,
+    },
+
+    /// Apply a payload produced by `DUMP`
+    RestoreKey {
+        /// The key to restore into
+        key: String,
+        /// Hex-encoded bincode `ChangeEvent`
+        hex: String,
+        /// Overwrite even if the local value is newer
+        replace: bool,
     },
 
     /// Get multiple keys in one command
     MultiGet {
         /// The keys to look up
         keys: Vec<String>,
+        /// Positional values plus a presence bitmap (`MGET ... COMPACT`)
+        compact: bool,
     },
 
     /// Set multiple key-value pairs
@@ -159,22 +798,87 @@ pub enum Command {
         pairs: Vec<(String, String)>,
     },
 
+    /// Set multiple key-value pairs, each with its own TTL
+    MultiSetEx {
+        /// `(key, ttl_ms, value)` triples; a TTL of 0 stores the key without expiry
+        entries: Vec<(String, u64, String)>,
+    },
+
+    /// Dump every key-value pair as JSON lines
+    Export,
+
+    /// Store key-value pairs sent as JSON lines after the command
+    Import {
+        /// The pairs read from the lines following `IMPORT`; filled in by the server
+        pairs: Vec<(String, String)>,
+    },
+
     Sync {
         host: String,    
         port: u16,
         options: SyncOptions,
     },
+    /// Pull from several peers, last write wins (`SYNC PEERS host:port...`)
+    SyncPeers {
+        /// `(host, port)` of each peer, in the order given
+        peers: Vec<(String, u16)>,
+    },
+    /// List keys that differ from a peer (`DIFF host port [prefix]`), read-only
+    Diff {
+        host: String,
+        port: u16,
+        /// Only compare keys starting with this prefix (empty for all keys)
+        prefix: String,
+    },
     /// Clear all keys/values in the store
     Truncate,
     
     /// Return general server statistics (connections, operations, memory usage)
     Stats,
+
+    /// Zero the statistics counters
+    StatsReset,
+
+    /// Most recent slow commands, newest first
+    SlowlogGet {
+        /// Maximum number of entries to return
+        count: usize,
+    },
+
+    /// Empty the slow log
+    SlowlogReset,
+
+    /// Sleep before replying (`DEBUG SLEEP <seconds>`), for testing
+    DebugSleep {
+        /// How long to sleep, in milliseconds
+        millis: u64,
+    },
     
     /// Return detailed server information (version, uptime, config)
     Info,
 
-    /// Return the current keystore size
-    Dbsize,
+    /// Return the current keystore size, or the number of keys matching a glob
+    Dbsize {
+        /// Only count keys matching this glob
+        pattern: Option<String>,
+    },
+
+    /// Return a random key
+    RandomKey,
+
+    /// Switch this connection to another logical database
+    Select {
+        /// Database index
+        index: usize,
+    },
+
+    /// Move a key from the selected database to another one
+    Move {
+        /// The key to move
+        key: String,
+        /// Target database index
+        target: usize,
+    },
 
     /// Return server version
     Version,
@@ -185,18 +889,242 @@ pub enum Command {
     /// Gracefully shut down the server
     Shutdown,
 
-    /// Get memory usage    
-    Memory,
+    /// Get memory usage, or the key and value bytes of keys matching a glob
+    Memory {
+        /// Only sum keys matching this glob (`MEMORY USAGE <pattern>`)
+        pattern: Option<String>,
+    },
+
+    /// Report the value size distribution and the largest keys (`MEMORY DOCTOR`)
+    MemDoctor,
 
     /// List connected clients
     Clientlist,
+
+    /// Close the connection of the client with this id (`CLIENT KILL ID <id>`)
+    ClientKill {
+        id: u64,
+    },
+
+    /// Stream every command run by any client to this connection
+    Monitor,
+
+    /// Report the storage engine backing the server
+    EngineInfo,
+
+    /// Report this node's replication identity (`NODE`)
+    NodeInfo,
+
+    /// Report whether storage and replication are working
+    Health,
+
+    /// Handshake reporting server capabilities
+    Hello {
+        /// The protocol version requested by the client
+        proto: u32,
+        /// Response line ending requested by the client, if any
+        line_ending: Option<LineEnding>,
+        /// Whether the client can decode compressed `VALUEZ` replies
+        compress: bool,
+    },
+
+    /// Log this connection in as a configured user
+    Auth {
+        user: String,
+        password: String,
+    },
+
+    /// Wait until every write so far is on disk (`FSYNC`)
+    FSync,
+
+    /// Reclaim disk space held by deleted keys (`COMPACT`)
+    Compact,
+
+    /// List every command with its arity and flags (`COMMAND [DOCS]`)
+    #[allow(clippy::enum_variant_names)]
+    CommandList,
+
+    /// Read a runtime setting
+    ConfigGet {
+        /// Parameter name (lowercase), e.g. `max_connections`
+        param: String,
+    },
+
+    /// Change a runtime setting
+    ConfigSet {
+        /// Parameter name (lowercase), e.g. `read_only`
+        param: String,
+        /// New value
+        value: String,
+    },
+
+    /// Every effective setting with secrets redacted (`CONFIG GETALL`)
+    ConfigAll,
+}
+
+impl Command {
+    /// The `COMMAND_TABLE` entry of this command; aliases report their
+    /// primary entry (`DEL`, `SETEX`, `BGET`). The match is exhaustive, so
+    /// every variant has an entry.
+    pub fn spec(&self) -> &'static CommandSpec {
+        match self {
+            Command::Replicate { .. } => &specs::REPLICATE,
+            Command::Wait { .. } => &specs::WAIT,
+            Command::Get { .. } => &specs::GET,
+            Command::GetEx { .. } => &specs::GETEX,
+            Command::Set { .. } => &specs::SET,
+            Command::SetEx { .. } => &specs::SETEX,
+            Command::BinarySet { .. } => &specs::BSET,
+            Command::BinaryGet { .. } => &specs::BGET,
+            Command::Delete { .. } | Command::DeleteVerbose { .. } => &specs::DEL,
+            Command::Unlink { .. } => &specs::UNLINK,
+            Command::Ping { .. } | Command::PingDeep => &specs::PING,
+            Command::Echo { .. } => &specs::ECHO,
+            Command::Exists { .. } | Command::ExistsVerbose { .. } => &specs::EXISTS,
+            Command::Scan { .. } => &specs::SCAN,
+            Command::ScanValues { .. } => &specs::SCANVALUES,
+            Command::Hash { .. } | Command::HashBuckets { .. } | Command::HashRange { .. } => &specs::HASH,
+            Command::MerkleDump | Command::MerkleBench => &specs::MERKLE,
+            Command::Verify => &specs::VERIFY,
+            Command::Watermark => &specs::WATERMARK,
+            Command::Increment { .. } => &specs::INC,
+            Command::Decrement { .. } => &specs::DEC,
+            Command::IncrLimit { .. } => &specs::INCRLIMIT,
+            Command::Append { .. } => &specs::APPEND,
+            Command::Prepend { .. } => &specs::PREPEND,
+            Command::ExpireTime { millis: false, .. } => &specs::EXPIRETIME,
+            Command::ExpireTime { millis: true, .. } => &specs::PEXPIRETIME,
+            Command::History { .. } => &specs::HISTORY,
+            Command::HotKeys { .. } => &specs::HOTKEYS,
+            Command::Object { .. } => &specs::OBJECT,
+            Command::Cas { .. } => &specs::CAS,
+            Command::GetVersioned { .. } => &specs::GETVER,
+            Command::CasVersion { .. } => &specs::CASVER,
+            Command::HSet { .. } => &specs::HSET,
+            Command::HGet { .. } => &specs::HGET,
+            Command::HGetAll { .. } => &specs::HGETALL,
+            Command::HDel { .. } => &specs::HDEL,
+            Command::HLen { .. } => &specs::HLEN,
+            Command::DumpKey { .. } => &specs::DUMP,
+            Command::RestoreKey { .. } => &specs::RESTORE,
+            Command::MultiGet { .. } => &specs::MGET,
+            Command::MultiSet { .. } => &specs::MSET,
+            Command::MultiSetEx { .. } => &specs::MSETEX,
+            Command::Export => &specs::EXPORT,
+            Command::Import { .. } => &specs::IMPORT,
+            Command::Sync { .. } | Command::SyncPeers { .. } => &specs::SYNC,
+            Command::Diff { .. } => &specs::DIFF,
+            Command::Truncate => &specs::TRUNCATE,
+            Command::Stats | Command::StatsReset => &specs::STATS,
+            Command::SlowlogGet { .. } | Command::SlowlogReset => &specs::SLOWLOG,
+            Command::DebugSleep { .. } => &specs::DEBUG,
+            Command::Info => &specs::INFO,
+            Command::Dbsize { .. } => &specs::DBSIZE,
+            Command::RandomKey => &specs::RANDOMKEY,
+            Command::Select { .. } => &specs::SELECT,
+            Command::Move { .. } => &specs::MOVE,
+            Command::Version => &specs::VERSION,
+            Command::Flushdb => &specs::FLUSHDB,
+            Command::Shutdown => &specs::SHUTDOWN,
+            Command::Compact => &specs::COMPACT,
+            Command::FSync => &specs::FSYNC,
+            Command::Memory { .. } | Command::MemDoctor => &specs::MEMORY,
+            Command::Clientlist | Command::ClientKill { .. } => &specs::CLIENT,
+            Command::Monitor => &specs::MONITOR,
+            Command::EngineInfo => &specs::ENGINE,
+            Command::NodeInfo => &specs::NODE,
+            Command::Health => &specs::HEALTH,
+            Command::Hello { .. } => &specs::HELLO,
+            Command::Auth { .. } => &specs::AUTH,
+            Command::ConfigGet { .. } | Command::ConfigSet { .. } | Command::ConfigAll => &specs::CONFIG,
+            Command::CommandList => &specs::COMMAND,
+        }
+    }
+
+    /// The single key a command operates on, if it has exactly one.
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            Command::Get { key }
+            | Command::Set { key, .. }
+            | Command::SetEx { key, .. }
+            | Command::BinarySet { key, .. }
+            | Command::BinaryGet { key }
+            | Command::Delete { key }
+            | Command::Increment { key, .. }
+            | Command::Decrement { key, .. }
+            | Command::IncrLimit { key, .. }
+            | Command::Append { key, .. }
+            | Command::Prepend { key, .. }
+            | Command::Object { key, .. }
+            | Command::History { key, .. }
+            | Command::ExpireTime { key, .. }
+            | Command::Cas { key, .. }
+            | Command::GetVersioned { key }
+            | Command::GetEx { key, .. }
+            | Command::CasVersion { key, .. }
+            | Command::HSet { key, .. }
+            | Command::HGet { key, .. }
+            | Command::HGetAll { key }
+            | Command::HDel { key, .. }
+            | Command::HLen { key }
+            | Command::DumpKey { key }
+            | Command::RestoreKey { key, .. }
+            | Command::Move { key, .. } => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Every key the command names, for commands that take keys.
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            Command::Exists { keys }
+            | Command::ExistsVerbose { keys }
+            | Command::DeleteVerbose { keys }
+            | Command::Unlink { keys }
+            | Command::MultiGet { keys, .. } => keys.iter().map(String::as_str).collect(),
+            Command::MultiSet { pairs } | Command::Import { pairs } => {
+                pairs.iter().map(|(key, _)| key.as_str()).collect()
+            }
+            Command::MultiSetEx { entries } => entries.iter().map(|(key, _, _)| key.as_str()).collect(),
+            _ => self.key().into_iter().collect(),
+        }
+    }
+
+    /// The value a single-key write stores, if any.
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Command::Set { value, .. }
+            | Command::SetEx { value, .. }
+            | Command::Append { value, .. }
+            | Command::Prepend { value, .. }
+            | Command::HSet { value, .. } => Some(value),
+            Command::Cas { new, .. } | Command::CasVersion { new, .. } => Some(new),
+            _ => None,
+        }
+    }
+
+    /// Every text value the command writes.
+    pub fn values(&self) -> Vec<&str> {
+        match self {
+            Command::MultiSet { pairs } => pairs.iter().map(|(_, value)| value.as_str()).collect(),
+            Command::MultiSetEx { entries } => entries.iter().map(|(_, _, value)| value.as_str()).collect(),
+            _ => self.value().into_iter().collect(),
+        }
+    }
 }
 
 /// Protocol parser that converts text commands into structured Command enums.
 ///
-/// This parser is stateless and can be safely shared across threads.
+/// This parser holds only its limits and command aliases, and can be safely
+/// shared across threads.
+pub struct Protocol {
+    /// Most keys one MGET, MSET, MSETEX or EXISTS may name; 0 means unlimited
+    max_bulk_keys: usize,
 
-pub struct Protocol;
+    /// Uppercase command name → the command it stands for; an empty target
+    /// disables the name
+    aliases: HashMap<String, String>,
+}
 
 impl Protocol {
     /// Create a new protocol parser instance.
@@ -204,7 +1132,66 @@ impl Protocol {
     /// # Returns
     /// * `Protocol` - A new parser instance
     pub fn new() -> Self {
-        Self
+        Self { max_bulk_keys: 0, aliases: HashMap::new() }
+    }
+
+    /// Accept `aliases` (name → command) and reject every name in `disabled`
+    /// as an unknown command. Names are case-insensitive and an alias with an
+    /// empty target also disables its name. Disabling applies to the name as
+    /// sent, so `FLUSHDB` can be disabled while an alias still reaches it.
+    pub fn with_command_aliases(mut self, aliases: &HashMap<String, String>, disabled: &[String]) -> Self {
+        for (name, target) in aliases {
+            self.aliases.insert(name.to_uppercase(), target.to_uppercase());
+        }
+        for name in disabled {
+            self.aliases.insert(name.to_uppercase(), String::new());
+        }
+        self
+    }
+
+    /// Replace an aliased command name with its target, failing for disabled names.
+    fn resolve_alias<'a>(&self, input: &'a str) -> Result<Cow<'a, str>> {
+        if self.aliases.is_empty() {
+            return Ok(Cow::Borrowed(input));
+        }
+        let name_len = input.find(' ').unwrap_or(input.len());
+        match self.aliases.get(&input[..name_len].to_uppercase()) {
+            None => Ok(Cow::Borrowed(input)),
+            Some(target) if target.is_empty() => Err(anyhow!("unknown command")),
+            Some(target) => Ok(Cow::Owned(format!("{}{}", target, &input[name_len..]))),
+        }
+    }
+
+    /// Reject MGET, MSET, MSETEX and EXISTS naming more than `max` keys (0 disables).
+    pub fn with_max_bulk_keys(mut self, max: usize) -> Self {
+        self.max_bulk_keys = max;
+        self
+    }
+
+    /// Split bulk arguments on whitespace, `args_per_key` per key plus up to
+    /// `flags` trailing flags, failing with `too many keys` as soon as the
+    /// count passes `max_bulk_keys` instead of collecting the whole line.
+    fn split_bulk<'a>(&self, rest: &'a str, args_per_key: usize, flags: usize) -> Result<Vec<&'a str>> {
+        let limit = match self.max_bulk_keys {
+            0 => usize::MAX,
+            max => max * args_per_key + flags,
+        };
+        let mut args = Vec::new();
+        for arg in rest.split_whitespace() {
+            if args.len() == limit {
+                return Err(anyhow!("too many keys"));
+            }
+            args.push(arg);
+        }
+        Ok(args)
+    }
+
+    /// Check a key count against `max_bulk_keys` once trailing flags are removed.
+    fn check_bulk_keys(&self, count: usize) -> Result<()> {
+        if self.max_bulk_keys != 0 && count > self.max_bulk_keys {
+            return Err(anyhow!("too many keys"));
+        }
+        Ok(())
     }
 
     /// Parse a text command into a structured Command enum.
@@ -241,6 +1228,8 @@ impl Protocol {
         if input.is_empty() {
             return Err(anyhow!("Empty command"));
         }
+        let input = self.resolve_alias(input)?;
+        let input = input.as_ref();
         
         // Split command into parts - for SET we need to split into exactly 3 parts
         // to allow spaces in values. For GET/DELETE, we can split normally.
@@ -256,7 +1245,12 @@ impl Protocol {
             }
             
             match input.to_uppercase().as_str() {
-                "GET" | "SET" | "DELETE" | "DEL" | "ECHO" | "EXISTS" | "SYNC" | "REPLICATE" => {
+                "GET" | "SET" | "DELETE" | "DEL" | "ECHO" | "EXISTS" | "SYNC" | "REPLICATE" | "CLIENT"
+                | "CAS" | "OBJECT" | "DUMP" | "RESTORE" | "CONFIG" | "HSET" | "HGET" | "HGETALL"
+                | "HDEL" | "HLEN" | "SELECT" | "MOVE" | "SETEX" | "PSETEX" | "INCRLIMIT"
+                | "BSET" | "BGET" | "DIFF" | "SLOWLOG" | "DEBUG" | "UNLINK" | "GETVER" | "CASVER"
+                | "MERKLE" | "EXPIRETIME" | "PEXPIRETIME" | "GETLEN" | "SETRAW" | "WAIT" | "HISTORY"
+                | "GETEX" | "AUTH" => {
                     return Err(anyhow!("{} command requires arguments", input.to_uppercase()));
                 }
                 "TRUNCATE" => return Ok(Command::Truncate),
@@ -264,13 +1258,29 @@ impl Protocol {
                 "INFO" => return Ok(Command::Info),
                 "VERSION" => return Ok(Command::Version),
                 "FLUSHDB" => return Ok(Command::Flushdb),
-                "MEMORY" => return Ok(Command::Memory),
-                "SCAN" => return Ok(Command::Scan { prefix: String::new() }),
+                "MEMORY" => return Ok(Command::Memory { pattern: None }),
+                "SCAN" => return Ok(Command::Scan { prefix: String::new(), pattern: None }),
+                "SCANVALUES" => return Ok(Command::ScanValues { prefix: String::new(), pattern: None }),
                 "HASH" => return Ok(Command::Hash { pattern: None }),
-                "CLIENT" => return Ok(Command::Clientlist),
                 "PING" => return Ok(Command::Ping { message: String::new() }),
                 "SHUTDOWN" => return Ok(Command::Shutdown),
-                "DBSIZE" => return Ok(Command::Dbsize),
+                "COMPACT" => return Ok(Command::Compact),
+                "FSYNC" => return Ok(Command::FSync),
+                "DBSIZE" => return Ok(Command::Dbsize { pattern: None }),
+                "HELLO" => {
+                    return Ok(Command::Hello { proto: PROTOCOL_VERSION, line_ending: None, compress: false })
+                }
+                "RANDOMKEY" => return Ok(Command::RandomKey),
+                "EXPORT" => return Ok(Command::Export),
+                "IMPORT" => return Ok(Command::Import { pairs: Vec::new() }),
+                "ENGINE" => return Ok(Command::EngineInfo),
+                "NODE" => return Ok(Command::NodeInfo),
+                "HEALTH" => return Ok(Command::Health),
+                "MONITOR" => return Ok(Command::Monitor),
+                "VERIFY" => return Ok(Command::Verify),
+                "WATERMARK" => return Ok(Command::Watermark),
+                "COMMAND" => return Ok(Command::CommandList),
+                "HOTKEYS" => return Ok(Command::HotKeys { count: DEFAULT_HOTKEYS_COUNT }),
                 _ => return Err(anyhow!("Unknown command: {}", input)),
             }
         }
@@ -332,8 +1342,76 @@ impl Protocol {
                 }
                 
                 Ok(Command::Set {
+                    key: key.to_string(),
+                    value: parse_value(value)?,
+                })
+            }
+            "BSET" | "SETRAW" => {
+                let name = command.to_uppercase();
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let [key, len] = parts.as_slice() else {
+                    return Err(anyhow!("{} command requires a key and a byte length", name));
+                };
+                let len = len
+                    .parse()
+                    .map_err(|_| anyhow!("{} length must be a non-negative integer", name))?;
+                Ok(Command::BinarySet {
+                    key: key.to_string(),
+                    len,
+                    value: Vec::new(),
+                })
+            }
+            "BGET" | "GETLEN" => {
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let [key] = parts.as_slice() else {
+                    return Err(anyhow!("{} command requires exactly one key", command.to_uppercase()));
+                };
+                Ok(Command::BinaryGet { key: key.to_string() })
+            }
+            "EXPIRETIME" | "PEXPIRETIME" => {
+                let name = command.to_uppercase();
+                if rest.contains(' ') {
+                    return Err(anyhow!("{} command accepts only one argument", name));
+                }
+                if rest.contains('\t') || rest.contains('\n') {
+                    return Err(anyhow!("Invalid character in key"));
+                }
+                Ok(Command::ExpireTime {
+                    key: rest.to_string(),
+                    millis: name == "PEXPIRETIME",
+                })
+            }
+            "SETEX" | "PSETEX" => {
+                // Syntax: SETEX <key> <seconds> <value...>; PSETEX takes milliseconds
+                let name = command.to_uppercase();
+                let mut parts = rest.splitn(3, ' ');
+                let (key, ttl, value) = match (parts.next(), parts.next(), parts.next()) {
+                    (Some(k), Some(t), Some(v)) if !k.is_empty() => (k, t, v),
+                    _ => return Err(anyhow!("{} command requires a key, expire time and value", name)),
+                };
+                if key.contains('\t') {
+                    return Err(anyhow!("Invalid character: tab character not allowed in key"));
+                }
+                if key.contains('\n') || value.contains('\n') {
+                    return Err(anyhow!("Invalid character: newline character not allowed in {} arguments", name));
+                }
+                let ttl: i64 = ttl
+                    .parse()
+                    .map_err(|_| anyhow!("invalid expire time in {}", name))?;
+                if ttl <= 0 {
+                    return Err(anyhow!("invalid expire time in {}", name));
+                }
+                let ttl_ms = if name == "SETEX" {
+                    (ttl as u64)
+                        .checked_mul(1000)
+                        .ok_or_else(|| anyhow!("invalid expire time in {}", name))?
+                } else {
+                    ttl as u64
+                };
+                Ok(Command::SetEx {
                     key: key.to_string(),
                     value: value.to_string(),
+                    ttl_ms,
                 })
             }
             // Support both "DEL" and "DELETE" for convenience
@@ -342,7 +1420,17 @@ impl Protocol {
                     return Err(anyhow!("DELETE command requires a key"));
                 }
                 if rest.contains(' ') {
-                    return Err(anyhow!("DELETE command accepts only one argument"));
+                    // Several keys are only accepted with a trailing VERBOSE flag
+                    let mut keys: Vec<String> = self.split_bulk(rest, 1, 1)?
+                        .into_iter()
+                        .map(|s| s.to_string())
+                        .collect();
+                    if !keys.last().is_some_and(|k| k.eq_ignore_ascii_case("VERBOSE")) {
+                        return Err(anyhow!("DELETE command accepts only one argument"));
+                    }
+                    keys.pop();
+                    self.check_bulk_keys(keys.len())?;
+                    return Ok(Command::DeleteVerbose { keys });
                 }
                 // Check for invalid characters in key
                 if rest.contains('\t') {
@@ -355,11 +1443,45 @@ impl Protocol {
                     key: rest.to_string(),
                 })
             }
+            "UNLINK" => {
+                let keys: Vec<String> = rest.split_whitespace().map(|s| s.to_string()).collect();
+                if keys.is_empty() {
+                    return Err(anyhow!("UNLINK command requires at least one key"));
+                }
+                Ok(Command::Unlink { keys })
+            }
             "DBSIZE" => {
-                if !rest.is_empty() {
-                    return Err(anyhow!("DBSIZE command does not accept any arguments"));
+                if rest.contains(' ') {
+                    return Err(anyhow!("DBSIZE command accepts at most one pattern"));
+                }
+                Ok(Command::Dbsize { pattern: Some(rest.to_string()) })
+            }
+            "RANDOMKEY" => Err(anyhow!("RANDOMKEY command does not accept any arguments")),
+            "EXPORT" => Err(anyhow!("EXPORT command does not accept any arguments")),
+            "IMPORT" => Err(anyhow!("IMPORT command does not accept any arguments; send JSON lines after it")),
+            "ENGINE" => Err(anyhow!("ENGINE command does not accept any arguments")),
+            "NODE" => Err(anyhow!("NODE command does not accept any arguments")),
+            "HEALTH" => Err(anyhow!("HEALTH command does not accept any arguments")),
+            "COMPACT" => Err(anyhow!("COMPACT command does not accept any arguments")),
+            "FSYNC" => Err(anyhow!("FSYNC command does not accept any arguments")),
+            "SELECT" => {
+                let index = rest
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow!("SELECT requires a numeric database index"))?;
+                Ok(Command::Select { index })
+            }
+            "MOVE" => {
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                match parts.as_slice() {
+                    [key, target] => Ok(Command::Move {
+                        key: key.to_string(),
+                        target: target
+                            .parse()
+                            .map_err(|_| anyhow!("MOVE requires a numeric database index"))?,
+                    }),
+                    _ => Err(anyhow!("MOVE command requires a key and a database index")),
                 }
-                Ok(Command::Dbsize)
             }
             "PING" => {
                 // Allow optional message after PING
@@ -369,6 +1491,9 @@ impl Protocol {
                 if rest.contains('\n') {
                     return Err(anyhow!("Invalid character: newline character not allowed in message"));
                 }
+                if rest.eq_ignore_ascii_case("DEEP") {
+                    return Ok(Command::PingDeep);
+                }
                 Ok(Command::Ping {
                     message: rest.to_string(),
                 })
@@ -394,10 +1519,19 @@ impl Protocol {
                 }
                 
                 // Extract all keys
-                let keys: Vec<String> = rest.split_whitespace()
+                let mut keys: Vec<String> = self.split_bulk(rest, 1, 1)?
+                    .into_iter()
                     .map(|s| s.to_string())
                     .collect();
 
+                // A trailing VERBOSE flag requests per-key presence flags
+                let verbose = keys.len() > 1
+                    && keys.last().is_some_and(|k| k.eq_ignore_ascii_case("VERBOSE"));
+                if verbose {
+                    keys.pop();
+                }
+                self.check_bulk_keys(keys.len())?;
+
                 if keys.is_empty() {
                     return Err(anyhow!("EXISTS command requires at least one key"));
                 }
@@ -412,22 +1546,30 @@ impl Protocol {
                     }
                 }
 
-                Ok(Command::Exists { keys })
+                if verbose {
+                    Ok(Command::ExistsVerbose { keys })
+                } else {
+                    Ok(Command::Exists { keys })
+                }
             }
             "SYNC" => {
-                // Syntax: SYNC <host> <port> [--full] [--verify]
+                // Syntax: SYNC <host> <port> [--full] [--verify] [--dry-run]
                 // Examples:
                 //   SYNC 192.168.1.10 7878
                 //   SYNC example.com 7878 --full --verify
                 //   SYNC [::1] 7878 --verify
 
                 if rest.is_empty() {
-                    return Err(anyhow!("SYNC requires arguments: <host> <port> [--full] [--verify]"));
+                    return Err(anyhow!("SYNC requires arguments: <host> <port> [--full] [--verify] [--dry-run]"));
                 }
 
                 // Split by ASCII whitespace
                 let mut it = rest.split_whitespace();
 
+                if it.clone().next().is_some_and(|t| t.eq_ignore_ascii_case("PEERS")) {
+                    return parse_sync_peers(it.skip(1));
+                }
+
                 // --- host ---
                 let host = it
                     .next()
@@ -458,9 +1600,10 @@ impl Protocol {
                     .map_err(|_| anyhow!("Invalid port: must be an integer in 0..=65535"))?;
 
                 // --- options ---
-                // Supported flags: --full, --verify (each at most once)
+                // Supported flags: --full, --verify, --dry-run (each at most once)
                 let mut opt_full = false;
                 let mut opt_verify = false;
+                let mut opt_dry_run = false;
 
                 for tok in it {
                     if tok.contains('\t') || tok.contains('\n') {
@@ -479,6 +1622,12 @@ impl Protocol {
                             }
                             opt_verify = true;
                         }
+                        "--dry-run" => {
+                            if opt_dry_run {
+                                return Err(anyhow!("Duplicate option: --dry-run"));
+                            }
+                            opt_dry_run = true;
+                        }
                         _ => {
                             // Unknown flag → hard error to surface typos early
                             return Err(anyhow!(format!("Unknown option: {}", tok)));
@@ -495,18 +1644,65 @@ impl Protocol {
                     options: SyncOptions {
                         full: opt_full,
                         verify: opt_verify,
+                        dry_run: opt_dry_run,
                     },
                 })
             }
-            "HASH" => {
-                if rest.contains(' ') {
-                    return Err(anyhow!("HASH command accepts only one argument"));
-                }
-                // Check for invalid characters in key
-                if rest.contains('\t') {
-                    return Err(anyhow!("Invalid character: tab character not allowed in key"));
-                }
-                if rest.contains('\n') {
+            "DIFF" => {
+                let mut it = rest.split_whitespace();
+                let host = it
+                    .next()
+                    .ok_or_else(|| anyhow!("DIFF requires arguments: <host> <port> [prefix]"))?
+                    .to_string();
+                let port: u16 = it
+                    .next()
+                    .ok_or_else(|| anyhow!("DIFF requires <port> as the second argument"))?
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid port: must be an integer in 0..=65535"))?;
+                let prefix = it.next().unwrap_or("").to_string();
+                if it.next().is_some() {
+                    return Err(anyhow!("DIFF accepts at most one prefix"));
+                }
+                Ok(Command::Diff { host, port, prefix })
+            }
+            "MERKLE" => {
+                match rest.to_uppercase().as_str() {
+                    "DUMP" => Ok(Command::MerkleDump),
+                    "BENCH" => Ok(Command::MerkleBench),
+                    _ => Err(anyhow!("MERKLE supports only DUMP and BENCH")),
+                }
+            }
+            "HASH" => {
+                if let Some((sub, n)) = rest.split_once(' ') {
+                    if sub.eq_ignore_ascii_case("BUCKETS") {
+                        let n: usize = n
+                            .trim()
+                            .parse()
+                            .map_err(|_| anyhow!("HASH BUCKETS requires a numeric bucket count"))?;
+                        if n == 0 || n > MAX_HASH_BUCKETS {
+                            return Err(anyhow!("HASH BUCKETS count must be between 1 and {}", MAX_HASH_BUCKETS));
+                        }
+                        return Ok(Command::HashBuckets { n });
+                    }
+                    if sub.eq_ignore_ascii_case("RANGE") {
+                        let bounds: Vec<&str> = n.split_whitespace().collect();
+                        let [start, end] = bounds[..] else {
+                            return Err(anyhow!("HASH RANGE requires <start> <end>"));
+                        };
+                        return Ok(Command::HashRange {
+                            start: start.to_string(),
+                            end: end.to_string(),
+                        });
+                    }
+                }
+                if rest.contains(' ') {
+                    return Err(anyhow!("HASH command accepts only one argument"));
+                }
+                // Check for invalid characters in key
+                if rest.contains('\t') {
+                    return Err(anyhow!("Invalid character: tab character not allowed in key"));
+                }
+                if rest.contains('\n') {
                     return Err(anyhow!("Invalid character: newline character not allowed in key"));
                 }
                 Ok(Command::Hash {
@@ -526,24 +1722,159 @@ impl Protocol {
                 };
                 Ok(Command::Replicate { action })
             }
+            "WAIT" => {
+                let mut it = rest.split_whitespace();
+                let (Some(replicas), Some(timeout_ms), None) = (it.next(), it.next(), it.next()) else {
+                    return Err(anyhow!("WAIT requires <numreplicas> <timeout_ms>"));
+                };
+                let replicas = replicas
+                    .parse::<usize>()
+                    .map_err(|_| anyhow!("WAIT numreplicas must be a non-negative integer"))?;
+                let timeout_ms = timeout_ms
+                    .parse::<u64>()
+                    .map_err(|_| anyhow!("WAIT timeout must be a non-negative integer"))?;
+                Ok(Command::Wait { replicas, timeout_ms })
+            }
             "MEMORY" => {
-                if !rest.is_empty() {
-                    return Err(anyhow!("MEMORY command does not accept any arguments"));
+                if rest.eq_ignore_ascii_case("DOCTOR") {
+                    return Ok(Command::MemDoctor);
+                }
+                match rest.split_once(' ') {
+                    Some((sub, pattern)) if sub.eq_ignore_ascii_case("USAGE") && !pattern.contains(' ') => {
+                        Ok(Command::Memory { pattern: Some(pattern.to_string()) })
+                    }
+                    _ => Err(anyhow!("MEMORY accepts no arguments, DOCTOR or USAGE <pattern>")),
                 }
-                Ok(Command::Memory)
             }
             "CLIENT" => {
                 let mut it = rest.split_whitespace();
                 let sub = it.next().unwrap_or("").to_ascii_uppercase();
                 match sub.as_str() {
                     "LIST" => Ok(Command::Clientlist),
+                    "KILL" => {
+                        let filter = it.next().unwrap_or("");
+                        if !filter.eq_ignore_ascii_case("ID") {
+                            return Err(anyhow!("CLIENT KILL requires ID <id>"));
+                        }
+                        let id = it
+                            .next()
+                            .and_then(|id| id.parse().ok())
+                            .ok_or_else(|| anyhow!("CLIENT KILL ID requires a numeric client id"))?;
+                        if it.next().is_some() {
+                            return Err(anyhow!("CLIENT KILL ID accepts only one id"));
+                        }
+                        Ok(Command::ClientKill { id })
+                    }
                     _ => Err(anyhow::anyhow!("Unknown CLIENT subcommand")),
                 }
             }
-            "SCAN" => {
-                if rest.contains(' ') {
-                    return Err(anyhow!("SCAN command accepts only one argument"));
+            "SLOWLOG" => {
+                let mut it = rest.split_whitespace();
+                let sub = it.next().unwrap_or("").to_ascii_uppercase();
+                match (sub.as_str(), it.next(), it.next()) {
+                    ("GET", None, None) => Ok(Command::SlowlogGet { count: DEFAULT_SLOWLOG_COUNT }),
+                    ("GET", Some(count), None) => Ok(Command::SlowlogGet {
+                        count: count
+                            .parse()
+                            .map_err(|_| anyhow!("SLOWLOG GET count must be a non-negative integer"))?,
+                    }),
+                    ("RESET", None, None) => Ok(Command::SlowlogReset),
+                    _ => Err(anyhow!("SLOWLOG requires GET [count] or RESET")),
+                }
+            }
+            "DEBUG" => {
+                let mut it = rest.split_whitespace();
+                let sub = it.next().unwrap_or("").to_ascii_uppercase();
+                match (sub.as_str(), it.next(), it.next()) {
+                    ("SLEEP", Some(secs), None) => {
+                        let secs: f64 = secs
+                            .parse()
+                            .ok()
+                            .filter(|s: &f64| s.is_finite() && *s >= 0.0)
+                            .ok_or_else(|| anyhow!("DEBUG SLEEP requires a non-negative number of seconds"))?;
+                        if secs > MAX_DEBUG_SLEEP_SECS {
+                            return Err(anyhow!("DEBUG SLEEP is limited to {} seconds", MAX_DEBUG_SLEEP_SECS));
+                        }
+                        Ok(Command::DebugSleep { millis: (secs * 1000.0) as u64 })
+                    }
+                    _ => Err(anyhow!("Unknown DEBUG subcommand")),
+                }
+            }
+            "CONFIG" => {
+                let mut it = rest.split_whitespace();
+                let sub = it.next().unwrap_or("").to_ascii_uppercase();
+                match sub.as_str() {
+                    "GET" => {
+                        let param = match (it.next(), it.next()) {
+                            (Some(p), None) => p,
+                            _ => return Err(anyhow!("CONFIG GET requires exactly one parameter")),
+                        };
+                        Ok(Command::ConfigGet {
+                            param: param.to_ascii_lowercase(),
+                        })
+                    }
+                    "SET" => {
+                        let (param, value) = match (it.next(), it.next(), it.next()) {
+                            (Some(p), Some(v), None) => (p, v),
+                            _ => return Err(anyhow!("CONFIG SET requires a parameter and a value")),
+                        };
+                        Ok(Command::ConfigSet {
+                            param: param.to_ascii_lowercase(),
+                            value: value.to_string(),
+                        })
+                    }
+                    "GETALL" => match it.next() {
+                        None => Ok(Command::ConfigAll),
+                        Some(_) => Err(anyhow!("CONFIG GETALL does not accept any arguments")),
+                    },
+                    _ => Err(anyhow!("Unknown CONFIG subcommand")),
+                }
+            }
+            "HISTORY" => {
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let (key, count) = match parts.as_slice() {
+                    [key] => (key, None),
+                    [key, count] => {
+                        let count = count
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|n| *n > 0)
+                            .ok_or_else(|| anyhow!("HISTORY count must be a positive integer"))?;
+                        (key, Some(count))
+                    }
+                    _ => return Err(anyhow!("HISTORY command requires a key and an optional count")),
+                };
+                Ok(Command::History { key: key.to_string(), count })
+            }
+            "HOTKEYS" => {
+                let count = rest
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| anyhow!("HOTKEYS count must be a positive integer"))?;
+                Ok(Command::HotKeys { count })
+            }
+            "OBJECT" => {
+                let mut it = rest.split_whitespace();
+                let sub = it.next().unwrap_or("").to_ascii_uppercase();
+                let subcmd = match sub.as_str() {
+                    "ENCODING" => ObjectSubcommand::Encoding,
+                    "IDLETIME" => ObjectSubcommand::Idletime,
+                    _ => return Err(anyhow!("Unknown OBJECT subcommand")),
+                };
+                let key = it
+                    .next()
+                    .ok_or_else(|| anyhow!("OBJECT {} requires a key", sub))?;
+                if it.next().is_some() {
+                    return Err(anyhow!("OBJECT {} accepts only one key", sub));
                 }
+                Ok(Command::Object {
+                    subcmd,
+                    key: key.to_string(),
+                })
+            }
+            "SCAN" | "SCANVALUES" => {
+                let name = command.to_uppercase();
                 // Check for invalid characters in prefix
                 if rest.contains('\t') {
                     return Err(anyhow!("Invalid character: tab character not allowed in prefix"));
@@ -551,8 +1882,21 @@ impl Protocol {
                 if rest.contains('\n') {
                     return Err(anyhow!("Invalid character: newline character not allowed in prefix"));
                 }
-                Ok(Command::Scan {
-                    prefix: rest.to_string(),
+                let args: Vec<&str> = rest.split(' ').collect();
+                let (prefix, pattern) = match args.as_slice() {
+                    [prefix] => (prefix.to_string(), None),
+                    [keyword, pattern] if keyword.eq_ignore_ascii_case("MATCH") => {
+                        (String::new(), Some(pattern.to_string()))
+                    }
+                    [prefix, keyword, pattern] if keyword.eq_ignore_ascii_case("MATCH") => {
+                        (prefix.to_string(), Some(pattern.to_string()))
+                    }
+                    _ => return Err(anyhow!("{} command accepts [prefix] [MATCH <pattern>]", name)),
+                };
+                Ok(if name == "SCAN" {
+                    Command::Scan { prefix, pattern }
+                } else {
+                    Command::ScanValues { prefix, pattern }
                 })
             }
             "INC" => {
@@ -591,6 +1935,24 @@ impl Protocol {
                     amount,
                 })
             }
+            "INCRLIMIT" => {
+                // Syntax: INCRLIMIT <key> <amount> <limit>
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let [key, amount, limit] = parts.as_slice() else {
+                    return Err(anyhow!("INCRLIMIT command requires a key, amount and limit"));
+                };
+                let amount = amount
+                    .parse()
+                    .map_err(|_| anyhow!("INCRLIMIT amount must be a valid number"))?;
+                let limit = limit
+                    .parse()
+                    .map_err(|_| anyhow!("INCRLIMIT limit must be a valid number"))?;
+                Ok(Command::IncrLimit {
+                    key: key.to_string(),
+                    amount,
+                    limit,
+                })
+            }
             "DEC" => {
                 if rest.is_empty() {
                     return Err(anyhow!("DEC command requires a key"));
@@ -654,9 +2016,11 @@ impl Protocol {
                 
                 // Allow empty values for APPEND
                 
+                let (value, len_only) = split_len_flag(value);
                 Ok(Command::Append {
                     key: key.to_string(),
-                    value: value.to_string(),
+                    value: parse_value(value)?,
+                    len_only,
                 })
             }
             "PREPEND" => {
@@ -686,9 +2050,161 @@ impl Protocol {
                 
                 // Allow empty values for PREPEND
                 
+                let (value, len_only) = split_len_flag(value);
                 Ok(Command::Prepend {
                     key: key.to_string(),
-                    value: value.to_string(),
+                    value: parse_value(value)?,
+                    len_only,
+                })
+            }
+            "CAS" => {
+                // Syntax: CAS <key> <expected> <new...>; the new value may contain spaces
+                let mut parts = rest.splitn(3, ' ');
+                let key = parts.next().unwrap_or("");
+                let expected = parts.next();
+                let new = parts.next();
+                let (expected, new) = match (expected, new) {
+                    (Some(e), Some(n)) if !key.is_empty() && !e.is_empty() => (e, n),
+                    _ => return Err(anyhow!("CAS command requires a key, expected value and new value")),
+                };
+
+                // Check for invalid characters in key (tabs allowed in values; newlines reserved for CRLF framing)
+                if key.contains('\t') {
+                    return Err(anyhow!("Invalid character: tab character not allowed in key"));
+                }
+                if key.contains('\n') || expected.contains('\n') || new.contains('\n') {
+                    return Err(anyhow!("Invalid character: newline character not allowed in CAS arguments"));
+                }
+
+                // `""` is the sentinel for "key must be absent"
+                let expected = if expected == "\"\"" { "" } else { expected };
+
+                Ok(Command::Cas {
+                    key: key.to_string(),
+                    expected: expected.to_string(),
+                    new: new.to_string(),
+                })
+            }
+            "GETEX" => {
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let ttl = |amount: &str, unit_ms: u64| {
+                    amount
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .and_then(|n| n.checked_mul(unit_ms))
+                        .ok_or_else(|| anyhow!("invalid expire time in GETEX"))
+                };
+                let (key, expiry) = match parts.as_slice() {
+                    [key] => (key, None),
+                    [key, option] if option.eq_ignore_ascii_case("PERSIST") => (key, Some(ExpiryChange::Persist)),
+                    [key, option, amount] if option.eq_ignore_ascii_case("EX") => {
+                        (key, Some(ExpiryChange::In(ttl(amount, 1000)?)))
+                    }
+                    [key, option, amount] if option.eq_ignore_ascii_case("PX") => {
+                        (key, Some(ExpiryChange::In(ttl(amount, 1)?)))
+                    }
+                    _ => return Err(anyhow!("GETEX syntax: GETEX <key> [EX <seconds> | PX <millis> | PERSIST]")),
+                };
+                Ok(Command::GetEx { key: key.to_string(), expiry })
+            }
+            "GETVER" => {
+                if rest.contains(' ') {
+                    return Err(anyhow!("GETVER command accepts only one argument"));
+                }
+                if rest.contains('\t') || rest.contains('\n') {
+                    return Err(anyhow!("Invalid character in key"));
+                }
+                Ok(Command::GetVersioned { key: rest.to_string() })
+            }
+            "CASVER" => {
+                // Syntax: CASVER <key> <expected_version> <new...>; the new value may contain spaces
+                let mut parts = rest.splitn(3, ' ');
+                let (key, expected, new) = match (parts.next(), parts.next(), parts.next()) {
+                    (Some(k), Some(e), Some(n)) if !k.is_empty() => (k, e, n),
+                    _ => return Err(anyhow!("CASVER command requires a key, expected version and new value")),
+                };
+                if key.contains('\t') {
+                    return Err(anyhow!("Invalid character: tab character not allowed in key"));
+                }
+                if key.contains('\n') || new.contains('\n') {
+                    return Err(anyhow!("Invalid character: newline character not allowed in CASVER arguments"));
+                }
+                let expected = expected
+                    .parse::<u64>()
+                    .map_err(|_| anyhow!("CASVER expected version must be a non-negative integer"))?;
+                Ok(Command::CasVersion {
+                    key: key.to_string(),
+                    expected,
+                    new: new.to_string(),
+                })
+            }
+            "HSET" => {
+                // Syntax: HSET <key> <field> <value...>; the value may contain spaces
+                let mut parts = rest.splitn(3, ' ');
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some(key), Some(field), Some(value)) if !key.is_empty() && !field.is_empty() => {
+                        Ok(Command::HSet {
+                            key: key.to_string(),
+                            field: field.to_string(),
+                            value: value.to_string(),
+                        })
+                    }
+                    _ => Err(anyhow!("HSET command requires a key, a field and a value")),
+                }
+            }
+            "HGET" => {
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                match parts.as_slice() {
+                    [key, field] => Ok(Command::HGet {
+                        key: key.to_string(),
+                        field: field.to_string(),
+                    }),
+                    _ => Err(anyhow!("HGET command requires a key and a field")),
+                }
+            }
+            "HGETALL" => {
+                if rest.is_empty() || rest.contains(' ') {
+                    return Err(anyhow!("HGETALL command requires exactly one key"));
+                }
+                Ok(Command::HGetAll { key: rest.to_string() })
+            }
+            "HLEN" => {
+                if rest.is_empty() || rest.contains(' ') {
+                    return Err(anyhow!("HLEN command requires exactly one key"));
+                }
+                Ok(Command::HLen { key: rest.to_string() })
+            }
+            "HDEL" => {
+                let mut parts = rest.split_whitespace();
+                let key = parts.next();
+                let fields: Vec<String> = parts.map(|f| f.to_string()).collect();
+                match key {
+                    Some(key) if !fields.is_empty() => Ok(Command::HDel {
+                        key: key.to_string(),
+                        fields,
+                    }),
+                    _ => Err(anyhow!("HDEL command requires a key and at least one field")),
+                }
+            }
+            "DUMP" => {
+                if rest.is_empty() || rest.contains(' ') {
+                    return Err(anyhow!("DUMP command requires exactly one key"));
+                }
+                Ok(Command::DumpKey { key: rest.to_string() })
+            }
+            "RESTORE" => {
+                // Syntax: RESTORE <key> <hex> [REPLACE]
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let (key, hex, replace) = match parts.as_slice() {
+                    [key, hex] => (*key, *hex, false),
+                    [key, hex, flag] if flag.eq_ignore_ascii_case("REPLACE") => (*key, *hex, true),
+                    _ => return Err(anyhow!("RESTORE command requires a key, a payload and an optional REPLACE flag")),
+                };
+                Ok(Command::RestoreKey {
+                    key: key.to_string(),
+                    hex: hex.to_string(),
+                    replace,
                 })
             }
             "MGET" => {
@@ -697,9 +2213,17 @@ impl Protocol {
                 }
                 
                 // Extract all keys
-                let keys: Vec<String> = rest.split_whitespace()
+                let mut keys: Vec<String> = self.split_bulk(rest, 1, 1)?
+                    .into_iter()
                     .map(|s| s.to_string())
                     .collect();
+
+                // A trailing COMPACT selects the positional response format
+                let compact = keys.len() > 1 && keys.last().is_some_and(|k| k.eq_ignore_ascii_case("COMPACT"));
+                if compact {
+                    keys.pop();
+                }
+                self.check_bulk_keys(keys.len())?;
                 
                 if keys.is_empty() {
                     return Err(anyhow!("MGET command requires at least one key"));
@@ -715,7 +2239,7 @@ impl Protocol {
                     }
                 }
                 
-                Ok(Command::MultiGet { keys })
+                Ok(Command::MultiGet { keys, compact })
             }
             "MSET" => {
                 if rest.is_empty() {
@@ -723,10 +2247,10 @@ impl Protocol {
                 }
                 
                 // Extract all parts
-                let args: Vec<&str> = rest.split_whitespace().collect();
+                let args = self.split_bulk(rest, 2, 0)?;
                 
                 // We need an even number of parts for key-value pairs
-                if args.len() % 2 != 0 {
+                if !args.len().is_multiple_of(2) {
                     return Err(anyhow!("MSET command requires an even number of arguments (key-value pairs)"));
                 }
                 
@@ -754,26 +2278,230 @@ impl Protocol {
                 
                 Ok(Command::MultiSet { pairs })
             }
+            "MSETEX" => {
+                // Syntax: MSETEX <key> <ttl-seconds> <value> [<key> <ttl-seconds> <value> ...]
+                let args = self.split_bulk(rest, 3, 0)?;
+                if args.is_empty() || !args.len().is_multiple_of(3) {
+                    return Err(anyhow!("MSETEX command requires key, expire time and value triples"));
+                }
+
+                let mut entries = Vec::with_capacity(args.len() / 3);
+                for entry in args.chunks(3) {
+                    let (key, ttl, value) = (entry[0], entry[1], entry[2]);
+                    if key.contains('\t') {
+                        return Err(anyhow!("Invalid character: tab character not allowed in key"));
+                    }
+                    if key.contains('\n') {
+                        return Err(anyhow!("Invalid character: newline character not allowed in key"));
+                    }
+                    let ttl_ms = ttl
+                        .parse::<u64>()
+                        .ok()
+                        .and_then(|secs| secs.checked_mul(1000))
+                        .ok_or_else(|| anyhow!("invalid expire time in MSETEX"))?;
+                    entries.push((key.to_string(), ttl_ms, value.to_string()));
+                }
+
+                Ok(Command::MultiSetEx { entries })
+            }
+            "HELLO" => {
+                let args: Vec<&str> = rest.split_whitespace().collect();
+                if args.len() > 3 {
+                    return Err(anyhow!("HELLO command accepts at most three arguments"));
+                }
+                let proto = args[0]
+                    .parse::<u32>()
+                    .map_err(|_| anyhow!("HELLO protocol version must be a valid number"))?;
+                let mut options = &args[1..];
+                let line_ending = match options.first().map(|s| s.to_uppercase()).as_deref() {
+                    Some("CRLF") => Some(LineEnding::Crlf),
+                    Some("LF") => Some(LineEnding::Lf),
+                    _ => None,
+                };
+                if line_ending.is_some() {
+                    options = &options[1..];
+                }
+                let compress = match options {
+                    [] => false,
+                    [flag] if flag.eq_ignore_ascii_case("COMPRESS") => true,
+                    _ => return Err(anyhow!("HELLO options must be CRLF or LF, then COMPRESS")),
+                };
+                Ok(Command::Hello { proto, line_ending, compress })
+            }
             "FLUSHDB" => {
                 Ok(Command::Flushdb)
             }
-            "MEMORY" => {
-                Ok(Command::Memory)
-            }
             "TRUNCATE" => {
                 Ok(Command::Truncate)
             }
             "STATS" => {
+                if rest.eq_ignore_ascii_case("RESET") {
+                    return Ok(Command::StatsReset);
+                }
                 Ok(Command::Stats)
             }
             "INFO" => {
                 Ok(Command::Info)
             }
+            "AUTH" => {
+                let parts: Vec<&str> = rest.split_whitespace().collect();
+                let [user, password] = parts.as_slice() else {
+                    return Err(anyhow!("AUTH command requires a user and a password"));
+                };
+                Ok(Command::Auth {
+                    user: user.to_string(),
+                    password: password.to_string(),
+                })
+            }
+            "COMMAND" => {
+                if !rest.eq_ignore_ascii_case("DOCS") {
+                    return Err(anyhow!("COMMAND accepts only DOCS"));
+                }
+                Ok(Command::CommandList)
+            }
             _ => Err(anyhow!("Unknown command: {}", command)),
         }
     }
 }
 
+/// Check a key against the `max_key_bytes` limit (0 disables the check).
+pub fn validate_key(key: &str, max_key_bytes: usize) -> Result<()> {
+    if max_key_bytes != 0 && key.len() > max_key_bytes {
+        return Err(anyhow!("key too long"));
+    }
+    Ok(())
+}
+
+/// Check a text value before it is written. A leading NUL is reserved for
+/// the markers of encoded hash and binary values, so text may not start with
+/// one, and CR or LF would break the line a reply carries it on.
+pub fn validate_value(value: &str) -> Result<()> {
+    if value.starts_with('\0') {
+        return Err(anyhow!("value must not start with NUL"));
+    }
+    if value.contains(['\r', '\n']) {
+        return Err(anyhow!("value must not contain CR or LF"));
+    }
+    Ok(())
+}
+
+/// Parse the `host:port` list of `SYNC PEERS`.
+fn parse_sync_peers<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Command> {
+    let mut peers: Vec<(String, u16)> = Vec::new();
+    for token in tokens {
+        let (host, port) = token
+            .rsplit_once(':')
+            .filter(|(host, _)| !host.is_empty())
+            .ok_or_else(|| anyhow!("Invalid peer address: {}", token))?;
+        let port: u16 = port
+            .parse()
+            .map_err(|_| anyhow!("Invalid port in peer address: {}", token))?;
+        if peers.iter().any(|(h, p)| h == host && *p == port) {
+            return Err(anyhow!("Duplicate peer: {}", token));
+        }
+        peers.push((host.to_string(), port));
+    }
+    if peers.is_empty() {
+        return Err(anyhow!("SYNC PEERS requires at least one <host:port>"));
+    }
+    Ok(Command::SyncPeers { peers })
+}
+
+/// Decode a SET/APPEND/PREPEND value. A value starting with `"` is a
+/// double-quoted string supporting `\n`, `\r`, `\t`, `\\`, `\"` and `\xHH`
+/// escapes, so it can keep surrounding whitespace and control characters;
+/// anything else is taken literally.
+fn parse_value(value: &str) -> Result<String> {
+    let Some(quoted) = value.strip_prefix('"') else {
+        return Ok(value.to_string());
+    };
+    let mut bytes = Vec::with_capacity(quoted.len());
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' if i + 1 == quoted.len() => {
+                return String::from_utf8(bytes).map_err(|_| anyhow!("quoted value is not valid UTF-8"));
+            }
+            '"' => return Err(anyhow!("unexpected characters after closing quote")),
+            '\\' => {
+                let byte = match chars.next().map(|(_, c)| c) {
+                    Some('n') => b'\n',
+                    Some('r') => b'\r',
+                    Some('t') => b'\t',
+                    Some('\\') => b'\\',
+                    Some('"') => b'"',
+                    Some('x') => {
+                        let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+                        if hex.len() != 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                            return Err(anyhow!("invalid \\x escape in quoted value"));
+                        }
+                        u8::from_str_radix(&hex, 16).unwrap()
+                    }
+                    _ => return Err(anyhow!("invalid escape in quoted value")),
+                };
+                bytes.push(byte);
+            }
+            c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Err(anyhow!("unterminated quoted value"))
+}
+
+/// One line of `EXPORT` output / `IMPORT` input. Exactly one of `value`
+/// (text), `hash` (the fields of an HSET hash) or `base64` (BSET bytes) is set,
+/// so encoded values travel typed rather than as their stored markers.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeyValueLine {
+    key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base64: Option<String>,
+}
+
+/// Format a stored key-value pair as an `EXPORT` line (without the line ending).
+pub fn format_export_line(key: &str, value: &str) -> String {
+    let mut line = KeyValueLine { key: key.to_string(), ..Default::default() };
+    if let Some(fields) = hash_value::decode(value) {
+        line.hash = Some(fields);
+    } else if binary_value::is_binary(value) {
+        line.base64 = Some(STANDARD.encode(binary_value::decode(value)));
+    } else {
+        line.value = Some(value.to_string());
+    }
+    serde_json::to_string(&line).expect("string pairs always serialize")
+}
+
+/// Parse one `IMPORT` line back into the key and the value to store. Text
+/// values must pass `validate_value`, so a line cannot forge an encoded value.
+pub fn parse_import_line(line: &str) -> Result<(String, String)> {
+    let line: KeyValueLine =
+        serde_json::from_str(line.trim_end()).map_err(|e| anyhow!("invalid IMPORT line: {}", e))?;
+    let value = match (line.value, line.hash, line.base64) {
+        (Some(value), None, None) => {
+            validate_value(&value)?;
+            value
+        }
+        (None, Some(fields), None) => hash_value::encode(&fields),
+        (None, None, Some(b64)) => {
+            let bytes = STANDARD.decode(b64).map_err(|e| anyhow!("invalid base64 in IMPORT line: {}", e))?;
+            binary_value::encode(&bytes)
+        }
+        _ => return Err(anyhow!("IMPORT line needs exactly one of value, hash or base64")),
+    };
+    Ok((line.key, value))
+}
+
+/// Strip a trailing ` LEN` flag from an APPEND/PREPEND value.
+fn split_len_flag(value: &str) -> (&str, bool) {
+    match value.strip_suffix(" LEN") {
+        Some(value) => (value, true),
+        None => (value, false),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -813,6 +2541,70 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_setex_psetex() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("SETEX session 10 logged in").unwrap(),
+            Command::SetEx {
+                key: "session".to_string(),
+                value: "logged in".to_string(),
+                ttl_ms: 10_000,
+            }
+        );
+        assert_eq!(
+            protocol.parse("psetex session 250 v").unwrap(),
+            Command::SetEx {
+                key: "session".to_string(),
+                value: "v".to_string(),
+                ttl_ms: 250,
+            }
+        );
+
+        assert!(protocol.parse("SETEX session 0 v").is_err());
+        assert!(protocol.parse("PSETEX session -5 v").is_err());
+        assert!(protocol.parse("SETEX session ten v").is_err());
+
+        assert_eq!(
+            protocol.parse("EXPIRETIME session").unwrap(),
+            Command::ExpireTime { key: "session".to_string(), millis: false }
+        );
+        assert_eq!(
+            protocol.parse("pexpiretime session").unwrap(),
+            Command::ExpireTime { key: "session".to_string(), millis: true }
+        );
+        assert!(protocol.parse("EXPIRETIME").is_err());
+        assert!(protocol.parse("EXPIRETIME a b").is_err());
+        assert!(protocol.parse("SETEX session 10").is_err());
+        assert!(protocol.parse("SETEX").is_err());
+    }
+
+    #[test]
+    fn test_parse_bset_bget() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("BSET blob 5").unwrap(),
+            Command::BinarySet { key: "blob".to_string(), len: 5, value: Vec::new() }
+        );
+        assert_eq!(
+            protocol.parse("BGET blob").unwrap(),
+            Command::BinaryGet { key: "blob".to_string() }
+        );
+        assert!(protocol.parse("BSET blob").is_err());
+        assert!(protocol.parse("BSET blob -1").is_err());
+        assert!(protocol.parse("BGET a b").is_err());
+        assert_eq!(
+            protocol.parse("GETLEN blob").unwrap(),
+            Command::BinaryGet { key: "blob".to_string() }
+        );
+        assert!(protocol.parse("GETLEN a b").is_err());
+        assert_eq!(
+            protocol.parse("SETRAW blob 5").unwrap(),
+            Command::BinarySet { key: "blob".to_string(), len: 5, value: Vec::new() }
+        );
+        assert!(protocol.parse("SETRAW blob").is_err());
+    }
+
     #[test]
     fn test_parse_delete() {
         let protocol = Protocol::new();
@@ -823,6 +2615,22 @@ mod tests {
                 key: "test_key".to_string()
             }
         );
+
+        let result = protocol.parse("DEL a b VERBOSE").unwrap();
+        assert_eq!(
+            result,
+            Command::DeleteVerbose {
+                keys: vec!["a".to_string(), "b".to_string()]
+            }
+        );
+        // A lone VERBOSE is a key name; extra keys still need the flag
+        assert_eq!(
+            protocol.parse("DEL verbose").unwrap(),
+            Command::Delete {
+                key: "verbose".to_string()
+            }
+        );
+        assert!(protocol.parse("DEL a b").is_err());
     }
 
     #[test]
@@ -832,13 +2640,62 @@ mod tests {
         assert_eq!(
             result,
             Command::Scan {
-                prefix: "test_prefix".to_string()
+                prefix: "test_prefix".to_string(),
+                pattern: None
+            }
+        );
+        // Test SCAN with empty prefix (returns all keys; used by anti-entropy sync)
+        assert_eq!(
+            protocol.parse("SCAN").unwrap(),
+            Command::Scan {
+                prefix: String::new(),
+                pattern: None
             }
         );
-        // Test SCAN with empty prefix
-        assert!(protocol.parse("SCAN").is_err());
         // Test SCAN with spaces in prefix
         assert!(protocol.parse("SCAN test prefix").is_err());
+
+        // Prefix narrowed further by a glob, or a glob alone
+        assert_eq!(
+            protocol.parse("SCAN user: match *admin*").unwrap(),
+            Command::Scan {
+                prefix: "user:".to_string(),
+                pattern: Some("*admin*".to_string())
+            }
+        );
+        assert_eq!(
+            protocol.parse("SCAN MATCH *admin*").unwrap(),
+            Command::Scan {
+                prefix: String::new(),
+                pattern: Some("*admin*".to_string())
+            }
+        );
+        assert!(protocol.parse("SCAN user: MATCH").is_err());
+        assert!(protocol.parse("SCAN user: MATCH a b").is_err());
+        assert!(protocol.parse("SCAN user: FILTER *admin*").is_err());
+
+        assert_eq!(
+            protocol.parse("scanvalues user:").unwrap(),
+            Command::ScanValues {
+                prefix: "user:".to_string(),
+                pattern: None
+            }
+        );
+        assert_eq!(
+            protocol.parse("SCANVALUES").unwrap(),
+            Command::ScanValues {
+                prefix: String::new(),
+                pattern: None
+            }
+        );
+        assert!(protocol.parse("SCANVALUES a b").is_err());
+        assert_eq!(
+            protocol.parse("SCANVALUES user: MATCH *:1").unwrap(),
+            Command::ScanValues {
+                prefix: "user:".to_string(),
+                pattern: Some("*:1".to_string())
+            }
+        );
     }
     #[test]
     fn test_parse_ping() {
@@ -859,6 +2716,13 @@ mod tests {
                 message: "".to_string()
             }
         );
+
+        assert_eq!(protocol.parse("PING DEEP").unwrap(), Command::PingDeep);
+        assert_eq!(protocol.parse("ping deep").unwrap(), Command::PingDeep);
+        assert_eq!(
+            protocol.parse("PING DEEP now").unwrap(),
+            Command::Ping { message: "DEEP now".to_string() }
+        );
     }
     #[test]
     fn test_parse_echo() {
@@ -878,15 +2742,169 @@ mod tests {
     fn test_parse_dbsize() {
         let protocol = Protocol::new();
         let result = protocol.parse("DBSIZE").unwrap();
-        assert_eq!(result, Command::Dbsize);
-        
+        assert_eq!(result, Command::Dbsize { pattern: None });
+        assert_eq!(
+            protocol.parse("DBSIZE user:*").unwrap(),
+            Command::Dbsize { pattern: Some("user:*".to_string()) }
+        );
+
         // Test DBSIZE with extra arguments (should error)
-        assert!(protocol.parse("DBSIZE extra_arg").is_err());
+        assert!(protocol.parse("DBSIZE user:* extra_arg").is_err());
     }
     #[test]
-    fn test_parse_exists() {
+    fn test_parse_select_move() {
         let protocol = Protocol::new();
-        
+        assert_eq!(protocol.parse("SELECT 2").unwrap(), Command::Select { index: 2 });
+        assert_eq!(
+            protocol.parse("MOVE key1 1").unwrap(),
+            Command::Move { key: "key1".to_string(), target: 1 }
+        );
+
+        assert!(protocol.parse("SELECT").is_err());
+        assert!(protocol.parse("SELECT one").is_err());
+        assert!(protocol.parse("SELECT -1").is_err());
+        assert!(protocol.parse("MOVE key1").is_err());
+        assert!(protocol.parse("MOVE key1 x").is_err());
+        assert!(protocol.parse("MOVE key1 1 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_randomkey() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("RANDOMKEY").unwrap(), Command::RandomKey);
+        assert_eq!(protocol.parse("randomkey").unwrap(), Command::RandomKey);
+        assert!(protocol.parse("RANDOMKEY extra_arg").is_err());
+    }
+    #[test]
+    fn test_parse_slowlog_and_debug_sleep() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("SLOWLOG GET").unwrap(), Command::SlowlogGet { count: DEFAULT_SLOWLOG_COUNT });
+        assert_eq!(protocol.parse("slowlog get 3").unwrap(), Command::SlowlogGet { count: 3 });
+        assert_eq!(protocol.parse("SLOWLOG RESET").unwrap(), Command::SlowlogReset);
+        assert!(protocol.parse("SLOWLOG").is_err());
+        assert!(protocol.parse("SLOWLOG GET -1").is_err());
+        assert!(protocol.parse("SLOWLOG RESET now").is_err());
+
+        assert_eq!(protocol.parse("DEBUG SLEEP 0.25").unwrap(), Command::DebugSleep { millis: 250 });
+        assert_eq!(protocol.parse("debug sleep 2").unwrap(), Command::DebugSleep { millis: 2000 });
+        assert!(protocol.parse("DEBUG SLEEP").is_err());
+        assert!(protocol.parse("DEBUG SLEEP -1").is_err());
+        assert!(protocol.parse("DEBUG SLEEP inf").is_err());
+        assert!(protocol.parse("DEBUG SLEEP 10").is_ok());
+        assert!(protocol.parse("DEBUG SLEEP 1e9").is_err());
+        assert!(protocol.parse("DEBUG SEGFAULT").is_err());
+    }
+
+    #[test]
+    fn test_parse_engine() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("ENGINE").unwrap(), Command::EngineInfo);
+        assert_eq!(protocol.parse("engine").unwrap(), Command::EngineInfo);
+        assert!(protocol.parse("ENGINE sled").is_err());
+    }
+
+    #[test]
+    fn test_parse_node() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("NODE").unwrap(), Command::NodeInfo);
+        assert_eq!(protocol.parse("node").unwrap(), Command::NodeInfo);
+        assert!(protocol.parse("NODE x").is_err());
+    }
+
+    #[test]
+    fn test_parse_health() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("HEALTH").unwrap(), Command::Health);
+        assert!(protocol.parse("HEALTH now").is_err());
+    }
+
+    #[test]
+    fn test_parse_quoted_values() {
+        let protocol = Protocol::new();
+        let set = |key: &str, value: &str| Command::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        assert_eq!(protocol.parse(r#"SET k "  spaced  ""#).unwrap(), set("k", "  spaced  "));
+        assert_eq!(protocol.parse(r#"SET k "a\x00b""#).unwrap(), set("k", "a\0b"));
+        assert_eq!(protocol.parse(r#"SET k "a\r\nb""#).unwrap(), set("k", "a\r\nb"));
+        assert_eq!(
+            protocol.parse(r#"SET k "tab\there \"q\" back\\slash \xc3\xa9""#).unwrap(),
+            set("k", "tab\there \"q\" back\\slash é")
+        );
+        assert_eq!(protocol.parse(r#"SET k """#).unwrap(), set("k", ""));
+        // Unquoted values are unchanged
+        assert_eq!(protocol.parse(r#"SET k a"b"#).unwrap(), set("k", "a\"b"));
+
+        assert_eq!(
+            protocol.parse(r#"APPEND k " x" LEN"#).unwrap(),
+            Command::Append { key: "k".to_string(), value: " x".to_string(), len_only: true }
+        );
+        assert_eq!(
+            protocol.parse(r#"PREPEND k "x ""#).unwrap(),
+            Command::Prepend { key: "k".to_string(), value: "x ".to_string(), len_only: false }
+        );
+
+        for bad in [r#"SET k "open"#, r#"SET k "a" b"#, r#"SET k "\q""#, r#"SET k "\x4""#, r#"SET k "\xff""#] {
+            assert!(protocol.parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_export_lines_round_trip() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("EXPORT").unwrap(), Command::Export);
+        assert_eq!(protocol.parse("import").unwrap(), Command::Import { pairs: vec![] });
+        assert!(protocol.parse("EXPORT all").is_err());
+
+        let line = format_export_line("k \"1\"", "tab\there é");
+        assert_eq!(
+            parse_import_line(&format!("{}\r\n", line)).unwrap(),
+            ("k \"1\"".to_string(), "tab\there é".to_string())
+        );
+        assert!(parse_import_line("{\"key\":\"k\"}").is_err());
+        assert!(parse_import_line("END").is_err());
+
+        // Hashes and bytes are exported typed and re-encoded on import
+        let mut fields = BTreeMap::new();
+        fields.insert("f".to_string(), "a\r\nb".to_string());
+        let hash = hash_value::encode(&fields);
+        let line = format_export_line("h", &hash);
+        assert_eq!(line, r#"{"key":"h","hash":{"f":"a\r\nb"}}"#);
+        assert_eq!(parse_import_line(&line).unwrap(), ("h".to_string(), hash));
+        let bytes = binary_value::encode(b"\0\xff\r\n");
+        let line = format_export_line("b", &bytes);
+        assert_eq!(line, r#"{"key":"b","base64":"AP8NCg=="}"#);
+        assert_eq!(parse_import_line(&line).unwrap(), ("b".to_string(), bytes));
+
+        // Text lines cannot carry a marker or a line break, nor mix types
+        for bad in [
+            r#"{"key":"k","value":"\u0000hash:{}"}"#,
+            r#"{"key":"k","value":"\u0000bin:AA=="}"#,
+            r#"{"key":"k","value":"a\r\nVALUE forged"}"#,
+            r#"{"key":"k","value":"v","hash":{}}"#,
+            r#"{"key":"k","base64":"!!"}"#,
+        ] {
+            assert!(parse_import_line(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_unlink() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("UNLINK a b").unwrap(),
+            Command::Unlink {
+                keys: vec!["a".to_string(), "b".to_string()]
+            }
+        );
+        assert!(protocol.parse("UNLINK").is_err());
+        assert!(protocol.parse("UNLINK   ").is_err());
+    }
+    #[test]
+    fn test_parse_exists() {
+        let protocol = Protocol::new();
+        
         // Test with a single key
         let result = protocol.parse("EXISTS key1").unwrap();
         assert_eq!(
@@ -907,13 +2925,39 @@ mod tests {
         
         // Test with no keys (should error)
         assert!(protocol.parse("EXISTS").is_err());
+
+        // Test VERBOSE flag
+        let result = protocol.parse("EXISTS key1 key2 VERBOSE").unwrap();
+        assert_eq!(
+            result,
+            Command::ExistsVerbose {
+                keys: vec!["key1".to_string(), "key2".to_string()]
+            }
+        );
+
+        // A lone VERBOSE is treated as a key name
+        let result = protocol.parse("EXISTS verbose").unwrap();
+        assert_eq!(
+            result,
+            Command::Exists {
+                keys: vec!["verbose".to_string()]
+            }
+        );
     }
     #[test]
     fn test_parse_memory() {
         let protocol = Protocol::new();
         let result = protocol.parse("MEMORY").unwrap();
-        assert_eq!(result, Command::Memory);
-        
+        assert_eq!(result, Command::Memory { pattern: None });
+        assert_eq!(
+            protocol.parse("MEMORY usage tenant:a:*").unwrap(),
+            Command::Memory { pattern: Some("tenant:a:*".to_string()) }
+        );
+        assert_eq!(protocol.parse("memory doctor").unwrap(), Command::MemDoctor);
+        assert!(protocol.parse("MEMORY DOCTOR now").is_err());
+        assert!(protocol.parse("MEMORY USAGE").is_err());
+        assert!(protocol.parse("MEMORY USAGE a b").is_err());
+
         // Test MEMORY with extra arguments (should error)
         assert!(protocol.parse("MEMORY extra_arg").is_err());
     }
@@ -929,6 +2973,364 @@ mod tests {
         // Test CLIENT with no subcommand (should error)
         assert!(protocol.parse("CLIENT").is_err());
     }
+    #[test]
+    fn test_parse_client_kill() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("CLIENT KILL ID 7").unwrap(), Command::ClientKill { id: 7 });
+        assert_eq!(protocol.parse("client kill id 12").unwrap(), Command::ClientKill { id: 12 });
+
+        assert!(protocol.parse("CLIENT KILL").is_err());
+        assert!(protocol.parse("CLIENT KILL 7").is_err());
+        assert!(protocol.parse("CLIENT KILL ID").is_err());
+        assert!(protocol.parse("CLIENT KILL ID abc").is_err());
+        assert!(protocol.parse("CLIENT KILL ID 1 2").is_err());
+    }
+    #[test]
+    fn test_parse_object() {
+        let protocol = Protocol::new();
+        let result = protocol.parse("OBJECT ENCODING key1").unwrap();
+        assert_eq!(
+            result,
+            Command::Object {
+                subcmd: ObjectSubcommand::Encoding,
+                key: "key1".to_string()
+            }
+        );
+        let result = protocol.parse("object idletime key1").unwrap();
+        assert_eq!(
+            result,
+            Command::Object {
+                subcmd: ObjectSubcommand::Idletime,
+                key: "key1".to_string()
+            }
+        );
+
+        assert!(protocol.parse("OBJECT").is_err());
+        assert!(protocol.parse("OBJECT ENCODING").is_err());
+        assert!(protocol.parse("OBJECT FREQ key1").is_err());
+        assert!(protocol.parse("OBJECT ENCODING a b").is_err());
+    }
+
+    #[test]
+    fn test_parse_dump_restore() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("DUMP key1").unwrap(),
+            Command::DumpKey { key: "key1".to_string() }
+        );
+        assert_eq!(
+            protocol.parse("RESTORE key1 abcd").unwrap(),
+            Command::RestoreKey { key: "key1".to_string(), hex: "abcd".to_string(), replace: false }
+        );
+        assert_eq!(
+            protocol.parse("restore key1 abcd replace").unwrap(),
+            Command::RestoreKey { key: "key1".to_string(), hex: "abcd".to_string(), replace: true }
+        );
+
+        assert!(protocol.parse("DUMP").is_err());
+        assert!(protocol.parse("DUMP a b").is_err());
+        assert!(protocol.parse("RESTORE").is_err());
+        assert!(protocol.parse("RESTORE key1").is_err());
+        assert!(protocol.parse("RESTORE key1 abcd FORCE").is_err());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("CONFIG SET read-only on").unwrap(),
+            Command::ConfigSet { param: "read-only".to_string(), value: "on".to_string() }
+        );
+        assert_eq!(
+            protocol.parse("config set READ-ONLY off").unwrap(),
+            Command::ConfigSet { param: "read-only".to_string(), value: "off".to_string() }
+        );
+
+        assert!(protocol.parse("CONFIG").is_err());
+        assert!(protocol.parse("CONFIG SET").is_err());
+        assert!(protocol.parse("CONFIG SET read-only").is_err());
+        assert!(protocol.parse("CONFIG SET read-only on now").is_err());
+        assert!(protocol.parse("CONFIG RESETSTAT").is_err());
+
+        assert_eq!(
+            protocol.parse("CONFIG GET Max_Connections").unwrap(),
+            Command::ConfigGet { param: "max_connections".to_string() }
+        );
+        assert!(protocol.parse("CONFIG GET").is_err());
+        assert!(protocol.parse("CONFIG GET a b").is_err());
+        assert_eq!(protocol.parse("config getall").unwrap(), Command::ConfigAll);
+        assert!(protocol.parse("CONFIG GETALL now").is_err());
+    }
+
+    #[test]
+    fn test_parse_hash_commands() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("HSET user:1 name Ada Lovelace").unwrap(),
+            Command::HSet {
+                key: "user:1".to_string(),
+                field: "name".to_string(),
+                value: "Ada Lovelace".to_string()
+            }
+        );
+        assert_eq!(
+            protocol.parse("HGET user:1 name").unwrap(),
+            Command::HGet { key: "user:1".to_string(), field: "name".to_string() }
+        );
+        assert_eq!(
+            protocol.parse("hgetall user:1").unwrap(),
+            Command::HGetAll { key: "user:1".to_string() }
+        );
+        assert_eq!(
+            protocol.parse("HDEL user:1 a b").unwrap(),
+            Command::HDel { key: "user:1".to_string(), fields: vec!["a".to_string(), "b".to_string()] }
+        );
+        assert_eq!(protocol.parse("HLEN user:1").unwrap(), Command::HLen { key: "user:1".to_string() });
+
+        assert!(protocol.parse("HSET").is_err());
+        assert!(protocol.parse("HSET k f").is_err());
+        assert!(protocol.parse("HGET k").is_err());
+        assert!(protocol.parse("HGET k f extra").is_err());
+        assert!(protocol.parse("HGETALL a b").is_err());
+        assert!(protocol.parse("HDEL k").is_err());
+        assert!(protocol.parse("HLEN").is_err());
+    }
+
+    #[test]
+    fn test_parse_hash_buckets() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("HASH BUCKETS 16").unwrap(), Command::HashBuckets { n: 16 });
+        assert_eq!(protocol.parse("hash buckets 1").unwrap(), Command::HashBuckets { n: 1 });
+
+        // A single argument is still a prefix, even if it reads "BUCKETS"
+        assert_eq!(
+            protocol.parse("HASH BUCKETS").unwrap(),
+            Command::Hash { pattern: Some("BUCKETS".to_string()) }
+        );
+
+        assert!(protocol.parse("HASH BUCKETS 0").is_err());
+        assert!(protocol.parse("HASH BUCKETS many").is_err());
+        assert!(protocol.parse("HASH BUCKETS 100000").is_err());
+        assert!(protocol.parse("HASH a b").is_err());
+    }
+
+    #[test]
+    fn test_parse_hash_range() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("HASH RANGE a m").unwrap(),
+            Command::HashRange { start: "a".to_string(), end: "m".to_string() }
+        );
+        assert_eq!(
+            protocol.parse("hash range user:0 user:5").unwrap(),
+            Command::HashRange { start: "user:0".to_string(), end: "user:5".to_string() }
+        );
+        assert!(protocol.parse("HASH RANGE a").is_err());
+        assert!(protocol.parse("HASH RANGE a b c").is_err());
+    }
+
+    #[test]
+    fn test_parse_merkle_dump() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("MERKLE DUMP").unwrap(), Command::MerkleDump);
+        assert_eq!(protocol.parse("merkle dump").unwrap(), Command::MerkleDump);
+        assert_eq!(protocol.parse("MERKLE BENCH").unwrap(), Command::MerkleBench);
+        assert_eq!(protocol.parse("merkle bench").unwrap(), Command::MerkleBench);
+        assert!(protocol.parse("MERKLE").is_err());
+        assert!(protocol.parse("MERKLE LOAD").is_err());
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("DIFF 127.0.0.1 7379").unwrap(),
+            Command::Diff { host: "127.0.0.1".to_string(), port: 7379, prefix: String::new() }
+        );
+        assert_eq!(
+            protocol.parse("diff node-b 7379 user:").unwrap(),
+            Command::Diff { host: "node-b".to_string(), port: 7379, prefix: "user:".to_string() }
+        );
+
+        assert!(protocol.parse("DIFF").is_err());
+        assert!(protocol.parse("DIFF host").is_err());
+        assert!(protocol.parse("DIFF host port").is_err());
+        assert!(protocol.parse("DIFF host 7379 a b").is_err());
+    }
+
+    #[test]
+    fn test_parse_sync_peers() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("SYNC PEERS 127.0.0.1:7001 node-c:7002 [::1]:7003").unwrap(),
+            Command::SyncPeers {
+                peers: vec![
+                    ("127.0.0.1".to_string(), 7001),
+                    ("node-c".to_string(), 7002),
+                    ("[::1]".to_string(), 7003),
+                ],
+            }
+        );
+        assert!(matches!(protocol.parse("sync peers a:1").unwrap(), Command::SyncPeers { .. }));
+        assert_eq!(protocol.parse("SYNC PEERS 7379").unwrap_err().to_string(), "Invalid peer address: 7379");
+
+        assert!(protocol.parse("SYNC PEERS").is_err());
+        assert!(protocol.parse("SYNC PEERS a").is_err());
+        assert!(protocol.parse("SYNC PEERS :7001").is_err());
+        assert!(protocol.parse("SYNC PEERS a:70000").is_err());
+        assert!(protocol.parse("SYNC PEERS a:1 a:1").is_err());
+    }
+
+    #[test]
+    fn test_parse_wait() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("WAIT 1 500").unwrap(), Command::Wait { replicas: 1, timeout_ms: 500 });
+        assert_eq!(protocol.parse("wait 0 0").unwrap(), Command::Wait { replicas: 0, timeout_ms: 0 });
+        assert!(protocol.parse("WAIT").is_err());
+        assert!(protocol.parse("WAIT 1").is_err());
+        assert!(protocol.parse("WAIT 1 500 extra").is_err());
+        assert!(protocol.parse("WAIT -1 500").is_err());
+        assert!(protocol.parse("WAIT 1 soon").is_err());
+    }
+
+    #[test]
+    fn test_parse_verify() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("VERIFY").unwrap(), Command::Verify);
+        assert_eq!(protocol.parse("verify").unwrap(), Command::Verify);
+        assert!(protocol.parse("VERIFY all").is_err());
+    }
+
+    #[test]
+    fn test_parse_history() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("HISTORY k").unwrap(),
+            Command::History { key: "k".to_string(), count: None }
+        );
+        assert_eq!(
+            protocol.parse("history k 3").unwrap(),
+            Command::History { key: "k".to_string(), count: Some(3) }
+        );
+        assert!(protocol.parse("HISTORY").is_err());
+        assert!(protocol.parse("HISTORY k 0").is_err());
+        assert!(protocol.parse("HISTORY k many").is_err());
+        assert!(protocol.parse("HISTORY k 1 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_watermark() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("WATERMARK").unwrap(), Command::Watermark);
+        assert_eq!(protocol.parse("watermark").unwrap(), Command::Watermark);
+        assert!(protocol.parse("WATERMARK now").is_err());
+    }
+
+    #[test]
+    fn test_parse_monitor() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("MONITOR").unwrap(), Command::Monitor);
+        assert_eq!(protocol.parse("monitor").unwrap(), Command::Monitor);
+        assert!(protocol.parse("MONITOR now").is_err());
+    }
+
+    #[test]
+    fn test_command_aliases() {
+        let aliases: HashMap<String, String> = [
+            ("keys".to_string(), "SCAN".to_string()),
+            ("SECRET_FLUSH".to_string(), "flushdb".to_string()),
+            ("NOPE".to_string(), String::new()),
+        ]
+        .into_iter()
+        .collect();
+        let protocol = Protocol::new().with_command_aliases(&aliases, &["FlushDB".to_string(), "SHUTDOWN".to_string()]);
+
+        assert_eq!(protocol.parse("KEYS user:").unwrap(), Command::Scan { prefix: "user:".to_string(), pattern: None });
+        assert_eq!(protocol.parse("keys").unwrap(), Command::Scan { prefix: String::new(), pattern: None });
+        assert_eq!(protocol.parse("SECRET_FLUSH").unwrap(), Command::Flushdb);
+        // Other commands are untouched
+        assert_eq!(protocol.parse("SCAN a").unwrap(), Command::Scan { prefix: "a".to_string(), pattern: None });
+
+        for disabled in ["FLUSHDB", "flushdb", "SHUTDOWN", "NOPE", "NOPE with args"] {
+            assert_eq!(protocol.parse(disabled).unwrap_err().to_string(), "unknown command", "{}", disabled);
+        }
+        // Without aliases KEYS stays unknown
+        assert!(Protocol::new().parse("KEYS").is_err());
+    }
+
+    #[test]
+    fn test_validate_keys() {
+        let protocol = Protocol::new();
+        let check = |cmd: &str, max: usize| {
+            let command = protocol.parse(cmd).unwrap();
+            command.keys().into_iter().try_for_each(|key| validate_key(key, max))
+        };
+
+        assert!(check("GET abcd", 4).is_ok());
+        assert!(check("GET abcde", 4).is_err());
+        assert!(check("GET abcde", 0).is_ok());
+        assert!(check("MGET a abcde", 4).is_err());
+        assert!(check("MSET a 1 b 12345", 4).is_ok());
+        assert_eq!(check("EXISTS a abcde", 4).unwrap_err().to_string(), "key too long");
+        assert!(check("PING", 1).is_ok());
+    }
+
+    #[test]
+    fn test_validate_values() {
+        let protocol = Protocol::new();
+        let check = |cmd: &str| protocol.parse(cmd).unwrap().values().into_iter().try_for_each(validate_value);
+
+        assert!(check("SET k a\0b").is_ok());
+        assert!(check("SET k \0hash:{}").is_err());
+        assert!(check(r#"SET k "\x00bin:AA==""#).is_err());
+        assert!(check("APPEND k \0x").is_err());
+        assert!(check("MSET a 1 b \0bin:AA==").is_err());
+        assert!(check("MSETEX a 5 \0x").is_err());
+        assert!(check("CAS k old \0x").is_err());
+        assert!(check("GET k").is_ok());
+    }
+
+    #[test]
+    fn test_max_bulk_keys() {
+        let protocol = Protocol::new().with_max_bulk_keys(3);
+        let too_many = |cmd: &str| protocol.parse(cmd).unwrap_err().to_string() == "too many keys";
+
+        assert!(protocol.parse("MGET a b c").is_ok());
+        assert!(protocol.parse("MGET a b c COMPACT").is_ok());
+        assert!(too_many("MGET a b c d"));
+        assert!(too_many("MGET a b c d COMPACT"));
+
+        assert!(protocol.parse("MSET a 1 b 2 c 3").is_ok());
+        assert!(too_many("MSET a 1 b 2 c 3 d 4"));
+        assert!(protocol.parse("MSETEX a 1 x b 2 y c 3 z").is_ok());
+        assert!(too_many("MSETEX a 1 x b 2 y c 3 z d 4 w"));
+
+        assert!(protocol.parse("EXISTS a b c VERBOSE").is_ok());
+        assert!(too_many("EXISTS a b c d"));
+
+        // 0 disables the limit
+        let many = vec!["k"; 10_000].join(" ");
+        assert!(Protocol::new().parse(&format!("MGET {}", many)).is_ok());
+        assert!(too_many(&format!("MGET {}", many)));
+    }
+
+    #[test]
+    fn test_parse_incrlimit() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("INCRLIMIT hits:alice 1 100").unwrap(),
+            Command::IncrLimit {
+                key: "hits:alice".to_string(),
+                amount: 1,
+                limit: 100,
+            }
+        );
+        assert!(protocol.parse("INCRLIMIT hits 1").is_err());
+        assert!(protocol.parse("INCRLIMIT hits one 100").is_err());
+        assert!(protocol.parse("INCRLIMIT hits 1 100 extra").is_err());
+        assert!(protocol.parse("INCRLIMIT").is_err());
+    }
+
     #[test]
     fn test_parse_increment() {
         let protocol = Protocol::new();
@@ -997,7 +3399,29 @@ mod tests {
             result,
             Command::Append {
                 key: "key_name".to_string(),
-                value: "suffix_value".to_string()
+                value: "suffix_value".to_string(),
+                len_only: false,
+            }
+        );
+
+        let result = protocol.parse("APPEND key_name more text LEN").unwrap();
+        assert_eq!(
+            result,
+            Command::Append {
+                key: "key_name".to_string(),
+                value: "more text".to_string(),
+                len_only: true,
+            }
+        );
+
+        // The flag must be a separate trailing word
+        let result = protocol.parse("APPEND key_name LEN").unwrap();
+        assert_eq!(
+            result,
+            Command::Append {
+                key: "key_name".to_string(),
+                value: "LEN".to_string(),
+                len_only: false,
             }
         );
     }
@@ -1010,9 +3434,199 @@ mod tests {
             result,
             Command::Prepend {
                 key: "key_name".to_string(),
-                value: "prefix_value".to_string()
+                value: "prefix_value".to_string(),
+                len_only: false,
+            }
+        );
+
+        let result = protocol.parse("PREPEND key_name prefix_value LEN").unwrap();
+        assert_eq!(
+            result,
+            Command::Prepend {
+                key: "key_name".to_string(),
+                value: "prefix_value".to_string(),
+                len_only: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_cas() {
+        let protocol = Protocol::new();
+        let result = protocol.parse("CAS key old new value").unwrap();
+        assert_eq!(
+            result,
+            Command::Cas {
+                key: "key".to_string(),
+                expected: "old".to_string(),
+                new: "new value".to_string()
+            }
+        );
+
+        // `""` stands for a missing key
+        let result = protocol.parse("CAS key \"\" first").unwrap();
+        assert_eq!(
+            result,
+            Command::Cas {
+                key: "key".to_string(),
+                expected: String::new(),
+                new: "first".to_string()
+            }
+        );
+
+        assert!(protocol.parse("CAS").is_err());
+        assert!(protocol.parse("CAS key").is_err());
+        assert!(protocol.parse("CAS key old").is_err());
+    }
+
+    #[test]
+    fn test_parse_getex() {
+        let protocol = Protocol::new();
+        let getex = |expiry| Command::GetEx { key: "k".to_string(), expiry };
+        assert_eq!(protocol.parse("GETEX k").unwrap(), getex(None));
+        assert_eq!(protocol.parse("GETEX k EX 10").unwrap(), getex(Some(ExpiryChange::In(10_000))));
+        assert_eq!(protocol.parse("getex k px 250").unwrap(), getex(Some(ExpiryChange::In(250))));
+        assert_eq!(protocol.parse("GETEX k persist").unwrap(), getex(Some(ExpiryChange::Persist)));
+
+        assert!(protocol.parse("GETEX").is_err());
+        assert!(protocol.parse("GETEX k EX").is_err());
+        assert!(protocol.parse("GETEX k EX 0").is_err());
+        assert!(protocol.parse("GETEX k EX -1").is_err());
+        assert!(protocol.parse(&format!("GETEX k EX {}", u64::MAX)).is_err());
+        assert!(protocol.parse("GETEX k PERSIST 5").is_err());
+        assert!(protocol.parse("GETEX k KEEPTTL").is_err());
+    }
+
+    #[test]
+    fn test_parse_hotkeys() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("HOTKEYS").unwrap(), Command::HotKeys { count: DEFAULT_HOTKEYS_COUNT });
+        assert_eq!(protocol.parse("hotkeys 3").unwrap(), Command::HotKeys { count: 3 });
+        assert!(protocol.parse("HOTKEYS 0").is_err());
+        assert!(protocol.parse("HOTKEYS many").is_err());
+        assert!(protocol.parse("HOTKEYS 3 4").is_err());
+    }
+
+    #[test]
+    fn test_parse_auth() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("AUTH reader s3cret").unwrap(),
+            Command::Auth { user: "reader".to_string(), password: "s3cret".to_string() }
+        );
+        assert!(protocol.parse("AUTH").is_err());
+        assert!(protocol.parse("AUTH reader").is_err());
+        assert!(protocol.parse("AUTH reader a b").is_err());
+    }
+
+    #[test]
+    fn test_parse_sync_options() {
+        let protocol = Protocol::new();
+        let sync = |full, verify, dry_run| Command::Sync {
+            host: "node-b".to_string(),
+            port: 7379,
+            options: SyncOptions { full, verify, dry_run },
+        };
+        assert_eq!(protocol.parse("SYNC node-b 7379").unwrap(), sync(false, false, false));
+        assert_eq!(protocol.parse("SYNC node-b 7379 --dry-run").unwrap(), sync(false, false, true));
+        assert_eq!(protocol.parse("SYNC node-b 7379 --verify --dry-run --full").unwrap(), sync(true, true, true));
+        assert_eq!(
+            protocol.parse("SYNC node-b 7379 --dry-run --dry-run").unwrap_err().to_string(),
+            "Duplicate option: --dry-run"
+        );
+        assert!(protocol.parse("SYNC node-b 7379 --dryrun").is_err());
+    }
+
+    #[test]
+    fn test_parse_compact() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("COMPACT").unwrap(), Command::Compact);
+        assert_eq!(protocol.parse("compact").unwrap(), Command::Compact);
+        assert!(protocol.parse("COMPACT now").is_err());
+    }
+
+    #[test]
+    fn test_parse_fsync() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("FSYNC").unwrap(), Command::FSync);
+        assert_eq!(protocol.parse("fsync").unwrap(), Command::FSync);
+        assert!(protocol.parse("FSYNC all").is_err());
+    }
+
+    #[test]
+    fn test_parse_command_list() {
+        let protocol = Protocol::new();
+        assert_eq!(protocol.parse("COMMAND").unwrap(), Command::CommandList);
+        assert_eq!(protocol.parse("command docs").unwrap(), Command::CommandList);
+        assert!(protocol.parse("COMMAND INFO").is_err());
+    }
+
+    #[test]
+    fn test_command_table_matches_supported_commands() {
+        let mut table: Vec<&str> = COMMAND_TABLE.iter().map(|spec| spec.name).collect();
+        let mut supported = SUPPORTED_COMMANDS.to_vec();
+        table.sort_unstable();
+        supported.sort_unstable();
+        assert_eq!(table, supported);
+        assert!(COMMAND_TABLE
+            .iter()
+            .all(|spec| !spec.flags.is_empty() && spec.max_args.is_none_or(|max| max >= spec.min_args)));
+    }
+
+    #[test]
+    fn test_command_table_covers_every_variant() {
+        // One request per `Command` variant, checked against its entry's arity
+        let samples = [
+            "REPLICATE status", "WAIT 1 100", "GET k", "GETEX k EX 5", "SET k a b", "SETEX k 5 v",
+            "BSET k 3", "BGET k", "DEL k", "DEL a b VERBOSE", "UNLINK a b", "PING hi", "PING DEEP",
+            "ECHO hi there", "EXISTS a b", "EXISTS a b VERBOSE", "SCAN p MATCH *", "SCANVALUES p",
+            "HASH p", "HASH BUCKETS 4", "HASH RANGE a z", "MERKLE DUMP", "MERKLE BENCH", "VERIFY",
+            "WATERMARK", "INC k 2", "DEC k", "INCRLIMIT k 1 10", "APPEND k v", "PREPEND k v",
+            "PEXPIRETIME k", "HISTORY k 3", "HOTKEYS 5", "OBJECT ENCODING k", "CAS k old new value", "GETVER k",
+            "CASVER k 1 v", "HSET k f v", "HGET k f", "HGETALL k", "HDEL k f g", "HLEN k", "DUMP k",
+            "RESTORE k 00 REPLACE", "MGET a b", "MSET a 1 b 2", "MSETEX a 5 1", "EXPORT", "IMPORT",
+            "SYNC host 7878 --full", "SYNC PEERS a:1 b:2", "DIFF host 7878 p", "TRUNCATE", "STATS",
+            "STATS RESET", "SLOWLOG GET 5", "SLOWLOG RESET", "DEBUG SLEEP 1", "INFO", "DBSIZE u:*",
+            "RANDOMKEY", "SELECT 1", "MOVE k 1", "VERSION", "FLUSHDB", "SHUTDOWN", "MEMORY USAGE u:*",
+            "MEMORY DOCTOR", "CLIENT LIST", "CLIENT KILL ID 3", "MONITOR", "ENGINE", "NODE", "HEALTH",
+            "HELLO 1 LF COMPRESS", "CONFIG GET read_only", "CONFIG SET read_only yes", "CONFIG GETALL", "COMMAND DOCS",
+            "AUTH user secret", "COMPACT", "FSYNC",
+        ];
+        let protocol = Protocol::new();
+        let mut variants = std::collections::HashSet::new();
+        for request in samples {
+            let command = protocol.parse(request).unwrap();
+            let spec = command.spec();
+            assert!(COMMAND_TABLE.contains(spec), "{}", request);
+            let args = request.split_whitespace().count() - 1;
+            assert!(spec.min_args <= args, "{}", request);
+            assert!(spec.max_args.is_none_or(|max| args <= max), "{}", request);
+            variants.insert(std::mem::discriminant(&command));
+        }
+        assert_eq!(variants.len(), samples.len());
+    }
+
+    #[test]
+    fn test_parse_versioned_commands() {
+        let protocol = Protocol::new();
+        assert_eq!(
+            protocol.parse("GETVER k").unwrap(),
+            Command::GetVersioned { key: "k".to_string() }
+        );
+        assert_eq!(
+            protocol.parse("CASVER k 3 new value").unwrap(),
+            Command::CasVersion {
+                key: "k".to_string(),
+                expected: 3,
+                new: "new value".to_string()
             }
         );
+
+        assert!(protocol.parse("GETVER").is_err());
+        assert!(protocol.parse("GETVER a b").is_err());
+        assert!(protocol.parse("CASVER k 3").is_err());
+        assert!(protocol.parse("CASVER k -1 v").is_err());
+        assert!(protocol.parse("CASVER k abc v").is_err());
     }
 
     #[test]
@@ -1024,7 +3638,8 @@ mod tests {
         assert_eq!(
             result,
             Command::MultiGet {
-                keys: vec!["key1".to_string()]
+                keys: vec!["key1".to_string()],
+                compact: false,
             }
         );
         
@@ -1033,7 +3648,28 @@ mod tests {
         assert_eq!(
             result,
             Command::MultiGet {
-                keys: vec!["key1".to_string(), "key2".to_string(), "key3".to_string()]
+                keys: vec!["key1".to_string(), "key2".to_string(), "key3".to_string()],
+                compact: false,
+            }
+        );
+
+        // Trailing COMPACT selects the compact format
+        let result = protocol.parse("MGET key1 key2 compact").unwrap();
+        assert_eq!(
+            result,
+            Command::MultiGet {
+                keys: vec!["key1".to_string(), "key2".to_string()],
+                compact: true,
+            }
+        );
+
+        // A lone COMPACT is a key
+        let result = protocol.parse("MGET COMPACT").unwrap();
+        assert_eq!(
+            result,
+            Command::MultiGet {
+                keys: vec!["COMPACT".to_string()],
+                compact: false,
             }
         );
     }
@@ -1065,6 +3701,31 @@ mod tests {
         );
     }
     
+    #[test]
+    fn test_parse_msetex() {
+        let protocol = Protocol::new();
+
+        assert_eq!(
+            protocol.parse("MSETEX a 10 x b 0 y").unwrap(),
+            Command::MultiSetEx {
+                entries: vec![
+                    ("a".to_string(), 10_000, "x".to_string()),
+                    ("b".to_string(), 0, "y".to_string()),
+                ]
+            }
+        );
+
+        assert!(protocol.parse("MSETEX").is_err());
+        assert!(protocol.parse("MSETEX a 10").is_err());
+        assert!(protocol.parse("MSETEX a 10 x b 5").is_err());
+        for ttl in ["-1", "soon", "1.5", "18446744073709552"] {
+            assert_eq!(
+                protocol.parse(&format!("MSETEX a 10 x b {} y", ttl)).unwrap_err().to_string(),
+                "invalid expire time in MSETEX"
+            );
+        }
+    }
+
     #[test]
     fn test_parse_truncate() {
         let protocol = Protocol::new();
@@ -1077,6 +3738,8 @@ mod tests {
         let protocol = Protocol::new();
         let result = protocol.parse("STATS").unwrap();
         assert_eq!(result, Command::Stats);
+        assert_eq!(protocol.parse("STATS RESET").unwrap(), Command::StatsReset);
+        assert_eq!(protocol.parse("stats reset").unwrap(), Command::StatsReset);
     }
     
     #[test]
@@ -1087,12 +3750,37 @@ mod tests {
     }
     
     #[test]
-    fn test_parse_ping() {
+    fn test_parse_hello() {
         let protocol = Protocol::new();
-        let result = protocol.parse("PING").unwrap();
-        assert_eq!(result, Command::Ping);
+
+        // Without arguments the current protocol version is assumed
+        let result = protocol.parse("HELLO").unwrap();
+        assert_eq!(result, Command::Hello { proto: PROTOCOL_VERSION, line_ending: None, compress: false });
+
+        // Explicit protocol version
+        let result = protocol.parse("HELLO 2").unwrap();
+        assert_eq!(result, Command::Hello { proto: 2, line_ending: None, compress: false });
+
+        // Response line ending, case-insensitive
+        let result = protocol.parse("HELLO 1 lf").unwrap();
+        assert_eq!(result, Command::Hello { proto: 1, line_ending: Some(LineEnding::Lf), compress: false });
+        let result = protocol.parse("HELLO 1 CRLF").unwrap();
+        assert_eq!(result, Command::Hello { proto: 1, line_ending: Some(LineEnding::Crlf), compress: false });
+
+        // Compressed replies, with or without a line ending
+        let result = protocol.parse("HELLO 1 compress").unwrap();
+        assert_eq!(result, Command::Hello { proto: 1, line_ending: None, compress: true });
+        let result = protocol.parse("HELLO 1 LF COMPRESS").unwrap();
+        assert_eq!(result, Command::Hello { proto: 1, line_ending: Some(LineEnding::Lf), compress: true });
+
+        // Invalid protocol version or options
+        assert!(protocol.parse("HELLO abc").is_err());
+        assert!(protocol.parse("HELLO 1 2").is_err());
+        assert!(protocol.parse("HELLO 1 LF extra").is_err());
+        assert!(protocol.parse("HELLO 1 COMPRESS LF").is_err());
+        assert!(protocol.parse("HELLO 1 LF COMPRESS extra").is_err());
     }
-    
+
     #[test]
     fn test_parse_version() {
         let protocol = Protocol::new();
@@ -13,6 +13,9 @@
 //! 3. **Remote Application**: Other nodes receive the message and apply the
 //!    same operation to their local storage
 //! 4. **Loop Prevention**: Nodes ignore messages from themselves
+//! 5. **Acknowledgement**: After applying another node's event, a node publishes
+//!    an ack carrying the event timestamp to `{topic_prefix}/acks`; the writer
+//!    tracks the newest acknowledged timestamp per peer for `WAIT`
 //! 
 //! ## Message Format
 //! 
@@ -37,18 +40,112 @@
 //! - Proper error handling and retry logic
 //! - Conflict resolution for concurrent writes
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use log::{error, warn};
-use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS, Transport};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, watch};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use base64::Engine;
+use rand::Rng;
 
-use crate::config::Config;
+use crate::config::{Config, ReplicationConfig};
 use crate::store::KVEngineStoreTrait;
 use crate::change_event::{ChangeCodec, ChangeEvent, OpKind};
 
+/// Codec used for published change events.
+pub const DEFAULT_CODEC: ChangeCodec = ChangeCodec::Cbor;
+
+/// Delay before the first reconnect attempt after the broker connection fails.
+const RECONNECT_BACKOFF_BASE: Duration = Duration::from_millis(500);
+
+/// Longest delay between reconnect attempts.
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// Delay before reconnect attempt `attempt` (0-based): doubles from
+/// `RECONNECT_BACKOFF_BASE` up to `RECONNECT_BACKOFF_MAX`.
+fn reconnect_backoff(attempt: u32) -> Duration {
+    RECONNECT_BACKOFF_BASE
+        .saturating_mul(1u32 << attempt.min(16))
+        .min(RECONNECT_BACKOFF_MAX)
+}
+
+/// `delay` scaled by a random factor in `[0.5, 1.0]`, so nodes that lost the
+/// broker together do not reconnect in lockstep.
+fn with_jitter(delay: Duration) -> Duration {
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// The MQTT QoS for a `replication.qos` level.
+fn mqtt_qos(level: u8) -> Result<QoS> {
+    match level {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        _ => Err(anyhow!("replication.qos must be 0, 1 or 2, got {}", level)),
+    }
+}
+
+/// Broker transport for `config`: plain TCP unless `mqtt_tls` is set.
+fn mqtt_transport(config: &ReplicationConfig) -> Result<Transport> {
+    if !config.mqtt_tls {
+        return Ok(Transport::tcp());
+    }
+    let read = |what: &str, path: &str| {
+        std::fs::read(path).with_context(|| format!("reading MQTT {} {}", what, path))
+    };
+    let client_auth = match (&config.client_cert_path, &config.client_key_path) {
+        (Some(cert), Some(key)) => Some((read("client certificate", cert)?, read("client key", key)?)),
+        (None, None) => None,
+        _ => return Err(anyhow!("client_cert_path and client_key_path must be set together")),
+    };
+    match &config.ca_cert_path {
+        Some(ca) => Ok(Transport::tls(read("CA certificate", ca)?, client_auth, None)),
+        None if client_auth.is_some() => Err(anyhow!("an MQTT client certificate requires ca_cert_path")),
+        None => Ok(Transport::tls_with_default_config()),
+    }
+}
+
+/// Published by a node once it has applied an event written by `src`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Ack {
+    /// The acknowledging node
+    node: String,
+    /// The node that wrote the event
+    src: String,
+    /// Timestamp of the applied event
+    ts: u64,
+}
+
+/// Newest acknowledged timestamp per peer, for events written by this node.
+type AckedTimestamps = HashMap<String, u64>;
+
+/// Record an ack payload in `acks` if it acknowledges an event from `node_id`.
+fn record_ack(acks: &watch::Sender<AckedTimestamps>, node_id: &str, payload: &[u8]) {
+    let ack: Ack = match serde_json::from_slice(payload) {
+        Ok(ack) => ack,
+        Err(e) => {
+            warn!("Failed to decode replication ack: {}", e);
+            return;
+        }
+    };
+    if ack.src != node_id || ack.node == node_id {
+        return;
+    }
+    acks.send_modify(|acks| {
+        let ts = acks.entry(ack.node).or_default();
+        *ts = (*ts).max(ack.ts);
+    });
+}
+
+/// Whether `key` falls under one of `prefixes`; an empty list matches every key.
+fn matches_prefixes(prefixes: &[String], key: &str) -> bool {
+    prefixes.is_empty() || prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))
+}
+
 /// Handles MQTT-based replication of write operations.
 /// 
 /// The Replicator connects to an MQTT broker and provides methods to
@@ -67,8 +164,29 @@ pub struct Replicator {
     /// Preferred codec for on-wire messages
     codec: ChangeCodec,
 
+    /// QoS used for publishing and subscribing (`replication.qos`)
+    qos: QoS,
+
     /// Channel carrying decoded ChangeEvents from the MQTT eventloop
     tx: broadcast::Sender<ChangeEvent>,
+
+    /// Whether the eventloop currently holds a broker connection
+    connected: Arc<AtomicBool>,
+
+    /// Timestamp of the newest event this node published
+    last_published: Arc<AtomicU64>,
+
+    /// Acks from peers for events this node published
+    acks: Arc<watch::Sender<AckedTimestamps>>,
+
+    /// Highest timestamp applied by the replication handler
+    watermark: Arc<AtomicU64>,
+
+    /// Key prefixes this node publishes (`replication.publish_prefixes`)
+    publish_prefixes: Arc<[String]>,
+
+    /// Key prefixes this node applies (`replication.subscribe_prefixes`)
+    subscribe_prefixes: Arc<[String]>,
 }
 
 impl Replicator {
@@ -117,7 +235,9 @@ impl Replicator {
             &config.replication.mqtt_broker,
             config.replication.mqtt_port,
         );
+        let qos = mqtt_qos(config.replication.qos)?;
         mqtt_options.set_keep_alive(Duration::from_secs(30));
+        mqtt_options.set_transport(mqtt_transport(&config.replication)?);
 
         // -----------------------------------------------------------------------------
         // Rationale (Compatibility)
@@ -138,16 +258,46 @@ impl Replicator {
     // Create MQTT client and event loop
     let (client, mut eventloop) = AsyncClient::new(mqtt_options, 10);
         
-        // Subscribe to the replication topic pattern
-        let topic = format!("{}/events/#", config.replication.topic_prefix);
-        client.subscribe(&topic, QoS::AtLeastOnce).await?;
+        // Subscribe to the replication topic pattern and to peer acks
+        let topics = [
+            format!("{}/events/#", config.replication.topic_prefix),
+            format!("{}/acks", config.replication.topic_prefix),
+        ];
+        for topic in &topics {
+            client.subscribe(topic, qos).await?;
+        }
 
         // Create broadcast channel and spawn the MQTT poller
         let (tx, _rx_unused) = broadcast::channel::<ChangeEvent>(1024);
         let tx_clone = tx.clone();
+        let connected = Arc::new(AtomicBool::new(false));
+        let connected_flag = Arc::clone(&connected);
+        let acks = Arc::new(watch::channel(AckedTimestamps::new()).0);
+        let acks_clone = Arc::clone(&acks);
+        let node_id = config.replication.client_id.clone();
+        let resubscribe = client.clone();
         tokio::spawn(async move {
+            let mut failed_attempts = 0;
+            let mut was_connected = false;
             loop {
                 match eventloop.poll().await {
+                    Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                        // A clean session forgets subscriptions; the first one is
+                        // already queued, later connections must subscribe again
+                        if was_connected {
+                            for topic in &topics {
+                                if let Err(e) = resubscribe.try_subscribe(topic.as_str(), qos) {
+                                    error!("MQTT resubscribe to {} failed: {}", topic, e);
+                                }
+                            }
+                        }
+                        was_connected = true;
+                        failed_attempts = 0;
+                        connected_flag.store(true, Ordering::Relaxed);
+                    }
+                    Ok(Event::Incoming(Incoming::Publish(p))) if p.topic == topics[1] => {
+                        record_ack(&acks_clone, &node_id, &p.payload);
+                    }
                     Ok(Event::Incoming(Incoming::Publish(p))) => {
                         match ChangeEvent::decode_any(&p.payload) {
                             Ok(ev) => {
@@ -158,8 +308,11 @@ impl Replicator {
                     }
                     Ok(_) => {}
                     Err(e) => {
-                        error!("MQTT eventloop error: {}", e);
-                        tokio::time::sleep(Duration::from_secs(3)).await;
+                        connected_flag.store(false, Ordering::Relaxed);
+                        let delay = with_jitter(reconnect_backoff(failed_attempts));
+                        failed_attempts = failed_attempts.saturating_add(1);
+                        error!("MQTT eventloop error: {} (reconnecting in {:?})", e, delay);
+                        tokio::time::sleep(delay).await;
                     }
                 }
             }
@@ -169,10 +322,38 @@ impl Replicator {
             client,
             topic_prefix: config.replication.topic_prefix.clone(),
             node_id: config.replication.client_id.clone(),
-            codec: ChangeCodec::Cbor,
+            codec: DEFAULT_CODEC,
+            qos,
             tx,
+            connected,
+            last_published: Arc::new(AtomicU64::new(0)),
+            acks,
+            watermark: Arc::new(AtomicU64::new(0)),
+            publish_prefixes: config.replication.publish_prefixes.clone().into(),
+            subscribe_prefixes: config.replication.subscribe_prefixes.clone().into(),
         })
     }
+
+    /// Node identity stamped on published events (`replication.client_id`).
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// Prefix of the replication topics.
+    pub fn topic_prefix(&self) -> &str {
+        &self.topic_prefix
+    }
+
+    /// Codec used for published events.
+    pub fn codec(&self) -> ChangeCodec {
+        self.codec
+    }
+
+    /// Whether the MQTT connection is up, as last reported by the eventloop.
+    /// False until the broker acknowledges the first connection.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
     
     /// Publish a SET operation to other nodes.
     /// 
@@ -200,6 +381,16 @@ impl Replicator {
         self.publish_event(ev).await
     }
     
+    /// Publish a SET that expires after `ttl_secs` (from `SETEX`/`PSETEX`).
+    ///
+    /// Receivers derive the deadline from the event timestamp, so the key
+    /// expires at about the same moment on every node.
+    pub async fn publish_set_with_ttl(&self, key: &str, value: &str, ttl_secs: u64) -> Result<()> {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        let ev = ChangeEvent::with_str_value(1, OpKind::Set, key, Some(value), ts, self.node_id.clone(), None, Some(ttl_secs));
+        self.publish_event(ev).await
+    }
+
     /// Publish a DELETE operation to other nodes.
     /// 
     /// This method should be called by the TCP server after successfully
@@ -253,29 +444,82 @@ impl Replicator {
         self.publish_event(ev).await
     }
 
-    /// Serialize and publish a change event to MQTT with QoS 1 (at-least-once).
+    /// Publish a bulk clear (TRUNCATE/FLUSHDB) so peers empty their stores too.
+    pub async fn publish_clear(&self) -> Result<()> {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        let ev = ChangeEvent::with_str_value(1, OpKind::Clear, "", None, ts, self.node_id.clone(), None, None);
+        self.publish_event(ev).await
+    }
+
+    /// Serialize and publish a change event to MQTT with the configured QoS.
+    ///
+    /// Keyed events outside `publish_prefixes` are skipped, and so are bulk
+    /// clears while any prefix is set: they would empty peers of keys this
+    /// node does not publish.
     async fn publish_event(&self, ev: ChangeEvent) -> Result<()> {
+        let published = match ev.op {
+            OpKind::Clear => self.publish_prefixes.is_empty(),
+            _ => matches_prefixes(&self.publish_prefixes, &ev.key),
+        };
+        if !published {
+            return Ok(());
+        }
         let topic = format!("{}/events", self.topic_prefix);
         let payload = self.codec.encode(&ev).map_err(|e| anyhow::anyhow!(e))?;
         self.client
-            .publish(&topic, QoS::AtLeastOnce, false, payload)
+            .publish(&topic, self.qos, false, payload)
+            .await?;
+        self.last_published.fetch_max(ev.ts, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Tell the writer of `ev` that this node has applied it.
+    async fn publish_ack(&self, ev: &ChangeEvent) -> Result<()> {
+        let topic = format!("{}/acks", self.topic_prefix);
+        let ack = Ack { node: self.node_id.clone(), src: ev.src.clone(), ts: ev.ts };
+        self.client
+            .publish(&topic, self.qos, false, serde_json::to_vec(&ack)?)
             .await?;
         Ok(())
     }
+
+    /// Wait until `replicas` peers have acknowledged the newest event this
+    /// node has published, or `timeout` elapses.
+    ///
+    /// Returns the number of peers that have acknowledged it.
+    pub async fn wait_for_replicas(&self, replicas: usize, timeout: Duration) -> usize {
+        let target = self.last_published.load(Ordering::Relaxed);
+        let caught_up = |acks: &AckedTimestamps| acks.values().filter(|ts| **ts >= target).count();
+        let mut rx = self.acks.subscribe();
+        let _ = tokio::time::timeout(timeout, rx.wait_for(|acks| caught_up(acks) >= replicas)).await;
+        let acknowledged = caught_up(&rx.borrow());
+        acknowledged
+    }
     
+    /// Highest timestamp among the remote events applied so far (`WATERMARK`),
+    /// or 0 before the first one.
+    pub fn watermark(&self) -> u64 {
+        self.watermark.load(Ordering::Relaxed)
+    }
+
     /// Start background tasks for (1) forwarding MQTT publish packets into a
     /// channel, and (2) applying them to local storage with idempotency and LWW.
     ///
     /// Teaching note: We separate transport concerns (MQTT event loop) from
     /// application concerns (idempotent LWW apply) with a channel. This models
     /// the classic “ingress queue” in replicated systems.
-    pub async fn start_replication_handler(&self, store: Arc<Mutex<Box<dyn KVEngineStoreTrait + Send + Sync>>>) {
+    ///
+    /// `writes` is bumped right before and right after each event is applied.
+    pub async fn start_replication_handler(
+        &self,
+        store: Arc<dyn KVEngineStoreTrait + Send + Sync>,
+        writes: Arc<AtomicU64>,
+    ) {
         // Subscribe to broadcasted events from the MQTT poller
         let mut rx = self.tx.subscribe();
-        let node_id = self.node_id.clone();
+        let mut state = ApplyState::new(self.node_id.clone(), self.subscribe_prefixes.to_vec());
+        let replicator = self.clone();
         tokio::spawn(async move {
-            let mut seen: HashSet<[u8; 16]> = HashSet::new();
-            let mut last_ts: HashMap<String, u64> = HashMap::new();
             loop {
                 let ev = match rx.recv().await {
                     Ok(ev) => ev,
@@ -284,31 +528,18 @@ impl Replicator {
                         continue;
                     }
                 };
-                if ev.src == node_id { continue; } // loop prevention
-                if seen.contains(&ev.op_id) { continue; } // idempotency
-                let current_ts = last_ts.get(&ev.key).cloned().unwrap_or(0);
-                if ev.ts < current_ts { continue; } // LWW
-
-                let mut guard = store.lock().await;
-                match ev.op {
-                    OpKind::Del => {
-                        guard.delete(&ev.key);
-                    }
-                    _ => {
-                        if let Some(bytes) = ev.val.clone() {
-                            // Interpret as UTF-8 if possible, otherwise store base64 string
-                            let value = String::from_utf8(bytes.clone())
-                                .unwrap_or_else(|_| base64::encode(bytes));
-                            // We apply by writing the resulting value (idempotent)
-                            if let Err(e) = guard.set(ev.key.clone(), value) {
-                                warn!("Failed to apply event to store: {}", e);
-                            }
-                        }
+                if !state.subscribes(&ev) {
+                    continue;
+                }
+                writes.fetch_add(1, Ordering::AcqRel);
+                state.apply(store.as_ref(), &ev);
+                writes.fetch_add(1, Ordering::AcqRel);
+                replicator.watermark.store(state.watermark, Ordering::Relaxed);
+                if ev.src != replicator.node_id {
+                    if let Err(e) = replicator.publish_ack(&ev).await {
+                        warn!("Failed to publish replication ack: {}", e);
                     }
                 }
-                // Update LWW state and dedupe set
-                last_ts.insert(ev.key.clone(), ev.ts);
-                seen.insert(ev.op_id);
 
                 // TODO: Update Merkle tree – in this prototype the store engines
                 // are in-memory maps without an exposed Merkle instance. The
@@ -319,6 +550,113 @@ impl Replicator {
     }
 }
 
+/// Bookkeeping for applying remote events: loop prevention, idempotency,
+/// per-key last-writer-wins and the timestamp of the latest bulk clear.
+///
+/// Writes are ordered by `(ts, op_id)`: on a timestamp tie the event with the
+/// lexicographically larger `op_id` wins, so every node converges on the same
+/// value whatever order the events arrive in.
+struct ApplyState {
+    node_id: String,
+    seen: HashSet<[u8; 16]>,
+    /// `ts` of the winning write per key
+    last_ts: HashMap<String, u64>,
+    /// `op_id` of the winning write per key, breaking `ts` ties
+    last_op_id: HashMap<String, [u8; 16]>,
+    /// Timestamp of the latest applied `Clear`; older events are dropped
+    clear_ts: u64,
+    /// Keyed events outside these prefixes are ignored; empty accepts all
+    subscribe_prefixes: Vec<String>,
+    /// Largest `ts` of any applied event
+    watermark: u64,
+}
+
+impl ApplyState {
+    fn new(node_id: String, subscribe_prefixes: Vec<String>) -> Self {
+        Self {
+            node_id,
+            seen: HashSet::new(),
+            last_ts: HashMap::new(),
+            last_op_id: HashMap::new(),
+            clear_ts: 0,
+            subscribe_prefixes,
+            watermark: 0,
+        }
+    }
+
+    /// Whether `ev` is inside `subscribe_prefixes`; bulk clears always are, and
+    /// are then limited to those prefixes when applied.
+    fn subscribes(&self, ev: &ChangeEvent) -> bool {
+        ev.op == OpKind::Clear || matches_prefixes(&self.subscribe_prefixes, &ev.key)
+    }
+
+    /// Apply one remote event to `store`, ignoring our own, duplicate and stale events.
+    fn apply(&mut self, store: &dyn KVEngineStoreTrait, ev: &ChangeEvent) {
+        if ev.src == self.node_id { return; } // loop prevention
+        if !self.subscribes(ev) { return; } // outside subscribe_prefixes
+        if self.seen.contains(&ev.op_id) { return; } // idempotency
+        if ev.ts < self.clear_ts { return; } // predates a bulk clear
+        let current_ts = self.last_ts.get(&ev.key).cloned().unwrap_or(0);
+        if ev.ts < current_ts { return; } // LWW
+        if ev.ts == current_ts && self.last_op_id.get(&ev.key).is_some_and(|id| ev.op_id < *id) {
+            return; // tie: the larger op_id wins
+        }
+
+        match ev.op {
+            OpKind::Del => {
+                store.delete(&ev.key);
+            }
+            OpKind::Clear => {
+                // Keep keys whose last replicated write is newer than the clear,
+                // and any outside subscribe_prefixes
+                self.last_ts.retain(|_, ts| *ts > ev.ts);
+                let last_ts = &self.last_ts;
+                self.last_op_id.retain(|key, _| last_ts.contains_key(key));
+                let result = if self.last_ts.is_empty() && self.subscribe_prefixes.is_empty() {
+                    store.truncate()
+                } else {
+                    for key in store.keys() {
+                        if !self.last_ts.contains_key(&key) && matches_prefixes(&self.subscribe_prefixes, &key) {
+                            store.delete(&key);
+                        }
+                    }
+                    Ok(())
+                };
+                if let Err(e) = result {
+                    warn!("Failed to apply clear to store: {}", e);
+                }
+                self.clear_ts = ev.ts;
+                self.watermark = self.watermark.max(ev.ts);
+                self.seen.insert(ev.op_id);
+                return;
+            }
+            _ => {
+                if let Some(bytes) = ev.val.clone() {
+                    // Interpret as UTF-8 if possible, otherwise store base64 string
+                    let value = String::from_utf8(bytes.clone())
+                        .unwrap_or_else(|_| base64::engine::general_purpose::STANDARD.encode(bytes));
+                    // We apply by writing the resulting value (idempotent)
+                    let applied = match ev.ttl {
+                        Some(ttl) => {
+                            let deadline_ms = ev.ts / 1_000_000 + ttl.saturating_mul(1000);
+                            store.set_with_expiry(ev.key.clone(), value, deadline_ms)
+                        }
+                        None => store.set(ev.key.clone(), value),
+                    };
+                    if let Err(e) = applied {
+                        warn!("Failed to apply event to store: {}", e);
+                    }
+                }
+            }
+        }
+        // Update LWW state and dedupe set
+        self.last_ts.insert(ev.key.clone(), ev.ts);
+        self.last_op_id.insert(ev.key.clone(), ev.op_id);
+        self.watermark = self.watermark.max(ev.ts);
+        self.seen.insert(ev.op_id);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // TODO: Implement comprehensive tests for replication logic
@@ -349,4 +687,234 @@ mod tests {
     // async fn test_mqtt_integration() {
     //     // Mock MQTT broker and test publish/subscribe flow
     // }
+
+    use super::*;
+    use crate::store::RwLockEngine;
+
+    fn event(op: OpKind, key: &str, value: Option<&str>, ts: u64) -> ChangeEvent {
+        ChangeEvent::with_str_value(1, op, key, value, ts, "nodeA", None, None)
+    }
+
+    #[test]
+    fn test_reconnect_backoff_grows_to_cap() {
+        let delays: Vec<Duration> = (0..40).map(reconnect_backoff).collect();
+        assert_eq!(delays[0], RECONNECT_BACKOFF_BASE);
+        assert_eq!(delays[1], RECONNECT_BACKOFF_BASE * 2);
+        assert!(delays.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(*delays.last().unwrap(), RECONNECT_BACKOFF_MAX);
+        assert!(delays.iter().all(|d| *d <= RECONNECT_BACKOFF_MAX));
+
+        for attempt in [0, 3, 39] {
+            let delay = reconnect_backoff(attempt);
+            for _ in 0..100 {
+                let jittered = with_jitter(delay);
+                assert!(jittered >= delay / 2 && jittered <= delay, "{:?} vs {:?}", jittered, delay);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mqtt_qos_from_config() {
+        let mut config = Config::default().replication;
+        assert_eq!(mqtt_qos(config.qos).unwrap(), QoS::AtLeastOnce);
+        config.qos = 0;
+        assert_eq!(mqtt_qos(config.qos).unwrap(), QoS::AtMostOnce);
+        config.qos = 2;
+        assert_eq!(mqtt_qos(config.qos).unwrap(), QoS::ExactlyOnce);
+        config.qos = 3;
+        assert!(mqtt_qos(config.qos).is_err());
+    }
+
+    #[test]
+    fn test_mqtt_transport_from_config() {
+        let mut config = Config::default().replication;
+        assert!(matches!(mqtt_transport(&config).unwrap(), Transport::Tcp));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            Some(path.to_string_lossy().into_owned())
+        };
+        config.mqtt_tls = true;
+        config.ca_cert_path = path("ca.pem", "ca");
+        config.client_cert_path = path("client.pem", "cert");
+        config.client_key_path = path("client.key", "key");
+
+        let mut options = MqttOptions::new("node1", "broker", 8883);
+        options.set_transport(mqtt_transport(&config).unwrap());
+        match options.transport() {
+            Transport::Tls(rumqttc::TlsConfiguration::Simple { ca, client_auth, .. }) => {
+                assert_eq!(ca, b"ca");
+                assert_eq!(client_auth, Some((b"cert".to_vec(), b"key".to_vec())));
+            }
+            _ => panic!("expected a TLS transport"),
+        }
+
+        config.client_key_path = None;
+        assert!(mqtt_transport(&config).is_err());
+        config.client_key_path = Some(dir.path().join("missing.key").to_string_lossy().into_owned());
+        assert!(mqtt_transport(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_replicas_counts_acks_for_the_latest_write() {
+        // Nothing listens on the broker port; publishes just queue up
+        let mut config = Config::default();
+        config.replication.mqtt_broker = "127.0.0.1".to_string();
+        config.replication.mqtt_port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        config.replication.client_id = "primary".to_string();
+        let primary = Replicator::new(&config).await.unwrap();
+        assert_eq!(primary.wait_for_replicas(1, Duration::from_millis(20)).await, 0);
+
+        primary.publish_set("user:1", "alice").await.unwrap();
+        let ts = primary.last_published.load(Ordering::Relaxed);
+        let ack = |node: &str, src: &str, ts: u64| {
+            serde_json::to_vec(&Ack { node: node.to_string(), src: src.to_string(), ts }).unwrap()
+        };
+
+        let waiter = tokio::spawn({
+            let primary = primary.clone();
+            async move { primary.wait_for_replicas(1, Duration::from_secs(5)).await }
+        });
+        // Older writes, other writers and our own acks do not count
+        record_ack(&primary.acks, "primary", &ack("replica", "primary", ts - 1));
+        record_ack(&primary.acks, "primary", &ack("replica", "other", ts));
+        record_ack(&primary.acks, "primary", &ack("primary", "primary", ts));
+        record_ack(&primary.acks, "primary", b"not json");
+        assert_eq!(primary.wait_for_replicas(1, Duration::from_millis(20)).await, 0);
+
+        record_ack(&primary.acks, "primary", &ack("replica", "primary", ts));
+        assert_eq!(waiter.await.unwrap(), 1);
+        assert_eq!(primary.wait_for_replicas(2, Duration::from_millis(20)).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_publish_prefixes_skip_other_keys() {
+        let mut config = Config::default();
+        config.replication.mqtt_broker = "127.0.0.1".to_string();
+        config.replication.mqtt_port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        config.replication.publish_prefixes = vec!["tenant-a:".to_string()];
+        let replicator = Replicator::new(&config).await.unwrap();
+
+        replicator.publish_set("tenant-b:1", "x").await.unwrap();
+        replicator.publish_delete("tenant-b:1").await.unwrap();
+        assert_eq!(replicator.last_published.load(Ordering::Relaxed), 0);
+
+        // A clear would also empty peers' tenant-b keys
+        replicator.publish_clear().await.unwrap();
+        assert_eq!(replicator.last_published.load(Ordering::Relaxed), 0);
+
+        replicator.publish_set("tenant-a:1", "x").await.unwrap();
+        assert_ne!(replicator.last_published.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_subscribe_prefixes_ignore_other_keys() {
+        let store = RwLockEngine::new("").unwrap();
+        let mut node_b = ApplyState::new("nodeB".to_string(), vec!["tenant-a:".to_string()]);
+        node_b.apply(&store, &event(OpKind::Set, "tenant-a:1", Some("1"), 10));
+        node_b.apply(&store, &event(OpKind::Set, "tenant-b:1", Some("2"), 10));
+        assert_eq!(store.get("tenant-a:1").as_deref(), Some("1"));
+        assert_eq!(store.get("tenant-b:1"), None);
+
+        // No prefixes applies everything
+        let mut node_c = ApplyState::new("nodeC".to_string(), Vec::new());
+        node_c.apply(&store, &event(OpKind::Set, "tenant-b:1", Some("2"), 10));
+        assert_eq!(store.get("tenant-b:1").as_deref(), Some("2"));
+
+        // A clear only reaches the subscribed keys
+        store.set("tenant-a:local".to_string(), "3".to_string()).unwrap();
+        node_b.apply(&store, &event(OpKind::Clear, "", None, 20));
+        assert_eq!(store.get("tenant-a:1"), None);
+        assert_eq!(store.get("tenant-a:local"), None);
+        assert_eq!(store.get("tenant-b:1").as_deref(), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn test_watermark_tracks_highest_applied_timestamp() {
+        let mut config = Config::default();
+        config.replication.mqtt_broker = "127.0.0.1".to_string();
+        config.replication.mqtt_port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        config.replication.client_id = "nodeB".to_string();
+        let replicator = Replicator::new(&config).await.unwrap();
+        let store: Arc<dyn KVEngineStoreTrait + Send + Sync> = Arc::new(RwLockEngine::new("").unwrap());
+        replicator.start_replication_handler(store, Arc::new(AtomicU64::new(0))).await;
+        assert_eq!(replicator.watermark(), 0);
+
+        for (key, ts) in [("a", 10), ("b", 30), ("a", 20)] {
+            replicator.tx.send(event(OpKind::Set, key, Some("v"), ts)).unwrap();
+        }
+        // Stale and own events do not move it
+        replicator.tx.send(event(OpKind::Set, "b", Some("old"), 25)).unwrap();
+        let own = ChangeEvent::with_str_value(1, OpKind::Set, "c", Some("v"), 99, "nodeB", None, None);
+        replicator.tx.send(own).unwrap();
+        replicator.tx.send(event(OpKind::Set, "c", Some("v"), 40)).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while replicator.watermark() != 40 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_clear_event_empties_peer_store() {
+        let store = RwLockEngine::new("").unwrap();
+        let mut node_b = ApplyState::new("nodeB".to_string(), Vec::new());
+        store.set("local".to_string(), "1".to_string()).unwrap();
+        node_b.apply(&store, &event(OpKind::Set, "replicated", Some("2"), 10));
+        assert_eq!(store.len(), 2);
+
+        // Node A truncated at ts 20
+        node_b.apply(&store, &event(OpKind::Clear, "", None, 20));
+        assert_eq!(store.len(), 0);
+
+        // Writes from before the clear that arrive late are dropped
+        node_b.apply(&store, &event(OpKind::Set, "late", Some("3"), 15));
+        assert_eq!(store.get("late"), None);
+        node_b.apply(&store, &event(OpKind::Set, "after", Some("4"), 25));
+        assert_eq!(store.get("after").as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn test_clear_event_keeps_newer_writes() {
+        let store = RwLockEngine::new("").unwrap();
+        let mut node_b = ApplyState::new("nodeB".to_string(), Vec::new());
+        node_b.apply(&store, &event(OpKind::Set, "old", Some("1"), 10));
+        // Delivered before the clear, but written after it
+        node_b.apply(&store, &event(OpKind::Set, "new", Some("2"), 30));
+
+        node_b.apply(&store, &event(OpKind::Clear, "", None, 20));
+        assert_eq!(store.get("old"), None);
+        assert_eq!(store.get("new").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_same_timestamp_converges_in_either_order() {
+        let mut low = event(OpKind::Set, "tie", Some("low"), 500);
+        let mut high = event(OpKind::Set, "tie", Some("high"), 500);
+        low.op_id = [1; 16];
+        high.op_id = [2; 16];
+        let deleted = {
+            let mut ev = event(OpKind::Del, "tie", None, 500);
+            ev.op_id = [3; 16];
+            ev
+        };
+
+        for order in [[&low, &high], [&high, &low]] {
+            let store = RwLockEngine::new("").unwrap();
+            let mut node = ApplyState::new("nodeB".to_string(), Vec::new());
+            for ev in order {
+                node.apply(&store, ev);
+            }
+            assert_eq!(store.get("tie").as_deref(), Some("high"));
+
+            // A tied delete with an even larger op_id wins too
+            node.apply(&store, &deleted);
+            assert_eq!(store.get("tie"), None);
+        }
+    }
 }
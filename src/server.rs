@@ -23,26 +23,42 @@
 //!
 //! ## Concurrency
 //!
-//! The storage engine is wrapped in `Arc<Mutex<>>` to allow safe concurrent access
-//! from multiple client connections. Each connection gets its own task but shares
-//! the same underlying storage.
-use hex; 
+//! Each database's storage engine is shared as an `Arc<dyn KVEngineStoreTrait>`.
+//! Each connection gets its own task and calls the engine directly; engines
+//! synchronize internally, so reads from different connections run in parallel
+//! and read-modify-write commands (INC, APPEND, CAS, ...) are atomic per call.
 use crate::sync::SyncManager;
-use crate::protocol::{SyncOptions, ReplicateAction};     // the options parsed by SYNC (full/verify)
+use crate::access::{unix_nanos, AccessTracker};
+use crate::glob::glob_match;
+use crate::latency::LatencyHistogram;
+use crate::change_event::{ChangeEvent, OpKind};
+use crate::protocol::{ExpiryChange, ObjectSubcommand, ReplicateAction};
+use crate::store::compressed_value::{self, Stored};
+use crate::store::binary_value::{self, BINARY_VALUE};
+use crate::store::expiry;
+use crate::store::hash_value::{self, WRONGTYPE};
+use crate::store::kv_trait::MemoryReport;
 use crate::store::KVEngineStoreTrait;
-use anyhow::Result;
-use log::{error, info};
+use anyhow::{anyhow, Result};
+use log::{debug, error, info, warn};
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 use std::collections::HashMap; 
-use crate::config::Config;
-use crate::protocol::{Command, Protocol};
+use crate::config::{AclCategory, Config, EvictionPolicy, RuntimeConfig, UserConfig};
+use crate::protocol::{
+    format_export_line, parse_import_line, validate_key, validate_value, Command, CommandFlag, LineEnding, Protocol,
+    COMMAND_TABLE, PROTOCOL_VERSION, SUPPORTED_COMMANDS,
+};
 use crate::replication::Replicator;
+use crate::history::KeyHistory;
+use crate::hotkeys::KeyStats;
+use crate::slowlog::SlowLog;
 
 /// Server statistics for monitoring and diagnostics.
 ///
@@ -113,11 +129,70 @@ pub struct ServerStats {
     /// Number of SYNC commands processed
     pub sync_commands: AtomicU64,
 
+    /// Keys changed by SYNC runs, summed over all runs
+    pub sync_keys_reconciled: AtomicU64,
+
+    /// Microseconds commands spent waiting for their database's gate, summed
+    pub store_lock_wait_micros: AtomicU64,
+
     /// Number of hash commands processed
     pub hash_commands: AtomicU64,
 
     /// number of replication actions performed
     pub replicate_commands: AtomicU64,
+
+    /// Number of CAS commands processed
+    pub cas_commands: AtomicU64,
+
+    /// Execution latency per command group, indexed like `LATENCY_GROUPS`
+    pub latencies: [LatencyHistogram; LATENCY_GROUPS.len()],
+}
+
+/// Command groups with their own latency histogram, named like the STATS counters.
+const LATENCY_GROUPS: [&str; 12] = [
+    "get", "set", "delete", "numeric", "string", "bulk", "scan", "exists", "hash", "sync", "cas", "other",
+];
+
+/// Index into `LATENCY_GROUPS` for a command.
+fn latency_group(command: &Command) -> usize {
+    match command {
+        Command::Get { .. }
+        | Command::GetEx { .. }
+        | Command::GetVersioned { .. }
+        | Command::BinaryGet { .. }
+        | Command::DumpKey { .. }
+        | Command::HGet { .. }
+        | Command::HGetAll { .. }
+        | Command::HLen { .. } => 0,
+        Command::Set { .. }
+        | Command::SetEx { .. }
+        | Command::BinarySet { .. }
+        | Command::RestoreKey { .. }
+        | Command::HSet { .. } => 1,
+        Command::Delete { .. }
+        | Command::DeleteVerbose { .. }
+        | Command::Unlink { .. }
+        | Command::HDel { .. } => 2,
+        Command::Increment { .. } | Command::Decrement { .. } | Command::IncrLimit { .. } => 3,
+        Command::Append { .. } | Command::Prepend { .. } => 4,
+        Command::MultiGet { .. }
+        | Command::MultiSet { .. }
+        | Command::MultiSetEx { .. }
+        | Command::Export
+        | Command::Import { .. }
+        | Command::Truncate => 5,
+        Command::Scan { .. } | Command::ScanValues { .. } => 6,
+        Command::Exists { .. } | Command::ExistsVerbose { .. } => 7,
+        Command::Hash { .. }
+        | Command::HashBuckets { .. }
+        | Command::HashRange { .. }
+        | Command::MerkleDump
+        | Command::MerkleBench
+        | Command::Verify => 8,
+        Command::Sync { .. } | Command::SyncPeers { .. } | Command::Diff { .. } => 9,
+        Command::Cas { .. } | Command::CasVersion { .. } => 10,
+        _ => 11,
+    }
 }
 
 struct ClientMeta {
@@ -125,7 +200,508 @@ struct ClientMeta {
     addr: SocketAddr,
     connected_unix: u64,                    // thời điểm connect (epoch seconds)
     last_cmd_unix: std::sync::atomic::AtomicU64, // lần cuối gửi lệnh (epoch seconds)
+    kill: tokio::sync::Notify,              // CLIENT KILL: close after the current command
+}
+/// One logical keyspace, selected per connection with `SELECT <index>`.
+struct Database {
+    /// The storage engine for this database
+    store: Arc<dyn KVEngineStoreTrait + Send + Sync>,
+
+    /// Per-key access times (OBJECT IDLETIME, RESTORE last-write-wins)
+    access: AccessTracker,
+
+    /// Queue to this database's write batcher, when `write_batching` is on
+    writer: Option<tokio::sync::mpsc::Sender<QueuedWrite>>,
+
+    /// Root of the last full Merkle rebuild, checked by `VERIFY`
+    merkle: MerkleCache,
+
+    /// Recent writes per key, for `HISTORY`
+    history: KeyHistory,
+
+    /// GET/SET hits per key, for `HOTKEYS`
+    key_stats: KeyStats,
+
+    /// Held by writes that may create keys while `max_keys` is set, so the
+    /// key count cannot change between checking and applying one
+    key_limit: tokio::sync::Mutex<()>,
+
+    /// Shared by every command on this database; held exclusively by MOVE,
+    /// RESTORE and the Merkle commands, so their read-then-write or
+    /// whole-keyspace view cannot interleave with other clients' commands
+    gate: tokio::sync::RwLock<()>,
+}
+
+/// The Merkle root of a database as of its last full rebuild.
+///
+/// Writes bump `generation` right before and right after they change the
+/// store. `VERIFY` compares a fresh rebuild with the cached root only when
+/// nothing could have changed the keyspace since the cache was filled: no
+/// write, and no key reaching its expiry deadline. A mismatch then means the
+/// cache and the store have drifted apart.
+#[derive(Default)]
+struct MerkleCache {
+    /// Bumped around every write; shared with the replication and sync tasks
+    generation: Arc<AtomicU64>,
+    /// The root of the last full rebuild
+    cached: std::sync::Mutex<Option<CachedRoot>>,
+}
+
+struct CachedRoot {
+    /// `generation` when the rebuild started
+    generation: u64,
+    /// Earliest expiry deadline (Unix ms) among the keys at the time
+    next_expiry_ms: Option<u64>,
+    /// Root hash, `None` for an empty keyspace
+    root: Option<Vec<u8>>,
+}
+
+impl MerkleCache {
+    /// Called right before and right after a write.
+    fn touch(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Cache `rebuilt`, built from the store starting at generation
+    /// `rebuilt.generation`. Returns whether the cached root should have
+    /// matched it but did not.
+    fn replace(&self, rebuilt: CachedRoot) -> bool {
+        let mut cached = self.cached.lock().unwrap();
+        // A write overlapped the rebuild; the result is not a reliable baseline
+        if self.generation() != rebuilt.generation {
+            *cached = None;
+            return false;
+        }
+        let drifted = cached.as_ref().is_some_and(|c| {
+            c.generation == rebuilt.generation
+                && c.next_expiry_ms.is_none_or(|deadline| expiry::now_millis() < deadline)
+                && c.root != rebuilt.root
+        });
+        *cached = Some(rebuilt);
+        drifted
+    }
+}
+
+/// A SET waiting for the write batcher, with the channel for its result.
+type QueuedWrite = (String, String, tokio::sync::oneshot::Sender<Result<()>>);
+
+/// Most SETs the write batcher takes off its queue at once.
+const MAX_WRITE_BATCH: usize = 256;
+
+/// Apply queued SETs to `store` from a single task, draining whatever is
+/// waiting in batches of up to `MAX_WRITE_BATCH`, each with one `set_many`.
+async fn run_write_batcher(
+    store: Arc<dyn KVEngineStoreTrait + Send + Sync>,
+    mut queue: tokio::sync::mpsc::Receiver<QueuedWrite>,
+) {
+    let mut batch = Vec::with_capacity(MAX_WRITE_BATCH);
+    while queue.recv_many(&mut batch, MAX_WRITE_BATCH).await > 0 {
+        let (pairs, replies): (Vec<(String, String)>, Vec<_>) =
+            batch.drain(..).map(|(key, value, reply)| ((key, value), reply)).unzip();
+        match store.set_many(&pairs) {
+            Ok(()) => replies.into_iter().for_each(|reply| {
+                let _ = reply.send(Ok(()));
+            }),
+            // Retry one by one so only the failing SETs see the error
+            Err(_) => {
+                for ((key, value), reply) in pairs.into_iter().zip(replies) {
+                    let _ = reply.send(store.set(key, value));
+                }
+            }
+        }
+    }
+}
+
+/// Hand a SET to the write batcher and wait until it has been applied.
+async fn queue_write(writer: &tokio::sync::mpsc::Sender<QueuedWrite>, key: String, value: String) -> Result<()> {
+    let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+    writer
+        .send((key, value, reply_tx))
+        .await
+        .map_err(|_| anyhow!("write batcher stopped"))?;
+    reply_rx.await.map_err(|_| anyhow!("write batcher stopped"))?
+}
+
+/// Longest request line accepted; longer lines are rejected with `ERROR line too long`.
+const MAX_LINE_BYTES: usize = 1024 * 1024;
+
+/// Largest payload accepted by `BSET`, matching the request line limit.
+const MAX_BINARY_VALUE_BYTES: usize = MAX_LINE_BYTES;
+
+/// Read one request line into `line`, returning the bytes consumed.
+///
+/// A line longer than `MAX_LINE_BYTES` is discarded up to its newline without
+/// being buffered; the count returned is then over the limit and `line` is empty.
+async fn read_request_line(reader: &mut BufReader<OwnedReadHalf>, line: &mut String) -> std::io::Result<usize> {
+    let mut bytes = Vec::new();
+    let mut read = (&mut *reader).take(MAX_LINE_BYTES as u64 + 1).read_until(b'\n', &mut bytes).await?;
+    if read <= MAX_LINE_BYTES {
+        line.push_str(
+            std::str::from_utf8(&bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+        );
+        return Ok(read);
+    }
+    // Skip the rest of the oversized line
+    while bytes.last() != Some(&b'\n') {
+        bytes.clear();
+        let skipped = (&mut *reader).take(64 * 1024).read_until(b'\n', &mut bytes).await?;
+        if skipped == 0 {
+            break;
+        }
+        read += skipped;
+    }
+    Ok(read)
+}
+
+/// How often keys past their `SETEX`/`PSETEX` deadline are purged.
+const EXPIRY_PURGE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long shutdown waits for open connections to finish before flushing.
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Prefix of the sentinel keys written by `PING DEEP`.
+const DEEP_PING_PREFIX: &str = "__merkle_kv_ping__:";
+
+/// Write `sentinel`, read it back and delete it, failing at the first step
+/// the engine gets wrong.
+fn deep_ping(store: &dyn KVEngineStoreTrait, sentinel: &str) -> Result<()> {
+    store
+        .set(sentinel.to_string(), sentinel.to_string())
+        .map_err(|e| anyhow!("write: {}", e))?;
+    let read = store.get(sentinel);
+    let deleted = store.delete(sentinel);
+    if read.as_deref() != Some(sentinel) {
+        return Err(anyhow!("read back {}", if read.is_some() { "a wrong value" } else { "nothing" }));
+    }
+    if !deleted {
+        return Err(anyhow!("delete"));
+    }
+    Ok(())
+}
+
+/// Apply the configured socket options to an accepted client connection.
+fn configure_socket(socket: &TcpStream, config: &Config) -> std::io::Result<()> {
+    socket.set_nodelay(config.tcp_nodelay)?;
+    if config.tcp_keepalive_secs != 0 {
+        let keepalive = socket2::TcpKeepalive::new().with_time(Duration::from_secs(config.tcp_keepalive_secs));
+        socket2::SockRef::from(socket).set_tcp_keepalive(&keepalive)?;
+    }
+    Ok(())
+}
+
+/// Resolve on SIGTERM or SIGINT (Ctrl-C on other platforms).
+///
+/// Unix handlers are installed before this returns, so a signal that arrives
+/// while the server is still starting up is not lost.
+fn shutdown_signal() -> std::io::Result<impl std::future::Future<Output = ()>> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        Ok(async move {
+            tokio::select! {
+                _ = terminate.recv() => info!("Received SIGTERM, shutting down"),
+                _ = interrupt.recv() => info!("Received SIGINT, shutting down"),
+            }
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(async {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("Received Ctrl-C, shutting down");
+        })
+    }
+}
+
+/// Periodically remove expired keys from every database.
+///
+/// Reads already hide expired keys; this makes sure keys that are never
+/// touched again also leave the store, `KEYS`/`DBSIZE` and the Merkle tree.
+/// Expiry is not replicated as a delete: peers hold the same deadline.
+async fn purge_expired_keys(databases: Arc<Vec<Database>>) {
+    let mut ticker = tokio::time::interval(EXPIRY_PURGE_INTERVAL);
+    loop {
+        ticker.tick().await;
+        for database in databases.iter() {
+            let expired = database.store.purge_expired();
+            for key in expired {
+                database.access.remove(&key);
+            }
+        }
+    }
+}
+
+/// Whether `command` may change the store; these are refused while read-only.
+fn is_write(command: &Command) -> bool {
+    // A dry run only compares against the peer
+    if let Command::Sync { options, .. } = command {
+        return !options.dry_run;
+    }
+    matches!(
+        command,
+        Command::Set { .. }
+            | Command::SetEx { .. }
+            | Command::BinarySet { .. }
+            | Command::Delete { .. }
+            | Command::DeleteVerbose { .. }
+            | Command::Unlink { .. }
+            | Command::GetEx { expiry: Some(_), .. }
+            | Command::Increment { .. }
+            | Command::Decrement { .. }
+            | Command::IncrLimit { .. }
+            | Command::Append { .. }
+            | Command::Prepend { .. }
+            | Command::Cas { .. }
+            | Command::CasVersion { .. }
+            | Command::RestoreKey { .. }
+            | Command::MultiSet { .. }
+            | Command::MultiSetEx { .. }
+            | Command::Import { .. }
+            | Command::SyncPeers { .. }
+            | Command::Truncate
+            | Command::Flushdb
+            | Command::HSet { .. }
+            | Command::HDel { .. }
+            | Command::Move { .. }
+    )
+}
+
+/// Approximate bytes a write adds to `store`, net of what it replaces, or
+/// `None` for commands that cannot grow it.
+///
+/// Overwrites are charged only for growing past the entry they replace;
+/// in-place edits (APPEND, HSET, INC, ...) are not charged again for a key
+/// that already exists.
+fn write_footprint(store: &dyn KVEngineStoreTrait, command: &Command) -> Option<usize> {
+    let incoming = match command {
+        Command::MultiSet { pairs } | Command::Import { pairs } => pairs.iter().map(|(k, v)| k.len() + v.len()).sum(),
+        Command::MultiSetEx { entries } => entries.iter().map(|(k, _, v)| k.len() + v.len()).sum(),
+        Command::BinarySet { key, value, .. } => key.len() + value.len(),
+        Command::Set { .. }
+        | Command::SetEx { .. }
+        | Command::Append { .. }
+        | Command::Prepend { .. }
+        | Command::HSet { .. }
+        | Command::Cas { .. }
+        | Command::CasVersion { .. }
+        | Command::RestoreKey { .. }
+        | Command::Increment { .. }
+        | Command::Decrement { .. }
+        | Command::IncrLimit { .. } => {
+            command.keys().iter().map(|k| k.len()).sum::<usize>() + command.value().map_or(0, str::len)
+        }
+        _ => return None,
+    };
+    let overwrites = matches!(
+        command,
+        Command::MultiSet { .. }
+            | Command::Import { .. }
+            | Command::MultiSetEx { .. }
+            | Command::BinarySet { .. }
+            | Command::Set { .. }
+            | Command::SetEx { .. }
+            | Command::Cas { .. }
+            | Command::CasVersion { .. }
+    );
+    let mut keys = command.keys();
+    keys.sort_unstable();
+    keys.dedup();
+    let replaced: usize = keys
+        .into_iter()
+        .filter_map(|key| store.get(key).map(|old| key.len() + if overwrites { old.len() } else { 0 }))
+        .sum();
+    Some(incoming.saturating_sub(replaced))
+}
+
+/// Whether writing `keys` would take `store` past `max_keys` keys; keys that
+/// already exist do not count.
+fn over_key_limit(store: &dyn KVEngineStoreTrait, keys: &[&str], max_keys: usize) -> bool {
+    let mut new_keys: Vec<&str> = keys.iter().copied().filter(|key| !store.exists(key)).collect();
+    if new_keys.is_empty() {
+        return false;
+    }
+    new_keys.sort_unstable();
+    new_keys.dedup();
+    let count = store.count_keys().unwrap_or_else(|_| store.len() as u64) as usize;
+    count + new_keys.len() > max_keys
+}
+
+/// Why the connection logged in as `user` may not run `command`, if it may
+/// not. Without configured `users` every command is allowed, and `AUTH`
+/// always is.
+fn acl_denial(users: &[UserConfig], user: Option<&str>, command: &Command) -> Option<String> {
+    if users.is_empty() || matches!(command, Command::Auth { .. }) {
+        return None;
+    }
+    let Some(user) = user.and_then(|name| users.iter().find(|u| u.name == name)) else {
+        return Some("NOAUTH authentication required".to_string());
+    };
+    let spec = command.spec();
+    (!flags_allowed(spec.flags, &user.categories))
+        .then(|| format!("NOPERM user {} may not run {}", user.name, spec.name))
+}
+
+/// Whether `categories` cover every one of a command's `flags`. A command with
+/// no flags is denied rather than waved through, so an unclassified entry in the
+/// command table fails closed.
+fn flags_allowed(flags: &[CommandFlag], categories: &[AclCategory]) -> bool {
+    !flags.is_empty()
+        && flags.iter().all(|flag| {
+            let category = match flag {
+                CommandFlag::Readonly => AclCategory::Read,
+                CommandFlag::Write => AclCategory::Write,
+                CommandFlag::Admin => AclCategory::Admin,
+            };
+            categories.contains(&category)
+        })
+}
+
+/// Compares a supplied password against the configured one without exiting at
+/// the first differing byte, so response timing does not leak a matching prefix.
+fn password_matches(expected: &str, supplied: &str) -> bool {
+    let (expected, supplied) = (expected.as_bytes(), supplied.as_bytes());
+    expected.len() == supplied.len()
+        && expected.iter().zip(supplied).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Number of keys matching the glob `pattern` and their key plus value bytes.
+fn pattern_usage(store: &dyn KVEngineStoreTrait, pattern: &str) -> (usize, usize) {
+    let (mut count, mut bytes) = (0, 0);
+    store.for_each_entry(&mut |key, value| {
+        if glob_match(pattern, key) {
+            count += 1;
+            bytes += key.len() + value.len();
+        }
+    });
+    (count, bytes)
+}
+
+/// Make room under `maxmemory_bytes` for a write adding `incoming` bytes.
+///
+/// Returns the keys evicted (least recently accessed first under `lru`), or
+/// `None` when the write must be refused.
+fn make_room(
+    store: &dyn KVEngineStoreTrait,
+    access: &AccessTracker,
+    cfg: &Config,
+    incoming: usize,
+) -> Option<Vec<String>> {
+    let mut used = store.memory_usage();
+    let mut evicted = Vec::new();
+    if used + incoming <= cfg.maxmemory_bytes {
+        return Some(evicted);
+    }
+    if cfg.eviction_policy == EvictionPolicy::NoEviction {
+        return None;
+    }
+    for key in access.least_recently_used(store.keys()) {
+        if let Some(value) = store.unlink(&key) {
+            used = used.saturating_sub(key.len() + value.len());
+            access.remove(&key);
+            evicted.push(key);
+        }
+        if used + incoming <= cfg.maxmemory_bytes {
+            return Some(evicted);
+        }
+    }
+    // Everything is gone and the write alone is over budget
+    None
+}
+
+/// Write one `EXPORT` line per key still present, then `END`. Each value is
+/// read as its line is written, so the whole dump is never held in memory
+/// and keys changed meanwhile are exported as they are when reached.
+async fn stream_export(
+    writer: &mut OwnedWriteHalf,
+    store: &dyn KVEngineStoreTrait,
+    keys: Vec<String>,
+    line_ending: LineEnding,
+) -> std::io::Result<()> {
+    for key in keys {
+        if let Some(value) = store.get(&key) {
+            let line = format!("{}\r\n", format_export_line(&key, &value));
+            writer.write_all(line_ending.frame(&line).as_bytes()).await?;
+        }
+    }
+    writer.write_all(line_ending.frame("END\r\n").as_bytes()).await
+}
+
+/// Lines a slow `MONITOR` connection may fall behind before it skips some.
+const MONITOR_BUFFER: usize = 1024;
+
+/// Format a `MONITOR` line: `<unix_ts> [<client_id>] <command>`, the
+/// timestamp in seconds with microseconds.
+fn monitor_line(client_id: u64, request: &str) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:06} [{}] {}\r\n", now.as_secs(), now.subsec_micros(), client_id, request)
+}
+
+/// Forward `feed` to a `MONITOR` connection until the client disconnects, is
+/// killed or the server shuts down. Anything the client sends is discarded.
+async fn stream_monitor(
+    reader: &mut BufReader<OwnedReadHalf>,
+    writer: &mut OwnedWriteHalf,
+    mut feed: broadcast::Receiver<String>,
+    shutdown_rx: &mut tokio::sync::watch::Receiver<bool>,
+    client_meta: &ClientMeta,
+    line_ending: LineEnding,
+) {
+    let mut ignored = String::new();
+    loop {
+        tokio::select! {
+            line = feed.recv() => match line {
+                Ok(line) => {
+                    if writer.write_all(line_ending.frame(&line).as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("MONITOR client {} fell behind, skipped {} commands", client_meta.id, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            read = reader.read_line(&mut ignored) => match read {
+                Ok(0) | Err(_) => return,
+                Ok(_) => ignored.clear(),
+            },
+            // Drop the watch guard here: it is not Send and must not live across the writes above
+            _ = async { drop(shutdown_rx.wait_for(|stopping| *stopping).await) } => return,
+            _ = client_meta.kill.notified() => return,
+        }
+    }
 }
+
+/// Format a `command_log` entry for one executed command.
+///
+/// `status` is derived from the response: `error`, `not_found` or `ok`.
+/// Values are only included when `with_values` is set.
+fn command_log_line(client_id: u64, name: &str, command: &Command, response: &str, with_values: bool) -> String {
+    let status = if response.starts_with("ERROR") {
+        "error"
+    } else if response.starts_with("NOT_FOUND") {
+        "not_found"
+    } else {
+        "ok"
+    };
+    let mut line = format!("command client_id={} cmd={}", client_id, name);
+    if let Some(key) = command.key() {
+        line.push_str(&format!(" key={:?}", key));
+    }
+    if with_values {
+        if let Some(value) = command.value() {
+            line.push_str(&format!(" value={:?}", value));
+        }
+    }
+    line.push_str(&format!(" status={}", status));
+    line
+}
+
 type ClientTable = Arc<tokio::sync::Mutex<HashMap<u64, Arc<ClientMeta>>>>;
 
 impl Clone for ServerStats {
@@ -151,9 +727,13 @@ impl Clone for ServerStats {
             bulk_commands: AtomicU64::new(self.bulk_commands.load(Ordering::Relaxed)),
             stat_commands: AtomicU64::new(self.stat_commands.load(Ordering::Relaxed)),
             sync_commands: AtomicU64::new(self.sync_commands.load(Ordering::Relaxed)),
+            sync_keys_reconciled: AtomicU64::new(self.sync_keys_reconciled.load(Ordering::Relaxed)),
+            store_lock_wait_micros: AtomicU64::new(self.store_lock_wait_micros.load(Ordering::Relaxed)),
             replicate_commands: AtomicU64::new(self.replicate_commands.load(Ordering::Relaxed)),
+            cas_commands: AtomicU64::new(self.cas_commands.load(Ordering::Relaxed)),
             management_commands: AtomicU64::new(self.management_commands.load(Ordering::Relaxed)),
             start_time: self.start_time,
+            latencies: self.latencies.clone(),
         }
     }
 }
@@ -191,7 +771,11 @@ impl ServerStats {
             management_commands: AtomicU64::new(0),
             start_time: Instant::now(),
             sync_commands: AtomicU64::new(0),
+            sync_keys_reconciled: AtomicU64::new(0),
+            store_lock_wait_micros: AtomicU64::new(0),
             replicate_commands: AtomicU64::new(0),
+            cas_commands: AtomicU64::new(0),
+            latencies: std::array::from_fn(|_| LatencyHistogram::new()),
         }
     }
     
@@ -216,63 +800,159 @@ impl ServerStats {
         self.total_commands.fetch_add(1, Ordering::Relaxed);
         
         match command {
-            Command::Get { .. } => {
+            Command::Get { .. }
+            | Command::GetEx { .. }
+            | Command::GetVersioned { .. }
+            | Command::BinaryGet { .. }
+            | Command::DumpKey { .. }
+            | Command::HGet { .. }
+            | Command::HGetAll { .. }
+            | Command::HLen { .. } => {
                 self.get_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Scan { .. } => {
+            Command::Scan { .. } | Command::ScanValues { .. } => {
                 self.scan_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Ping { .. } => {
+            Command::Ping { .. } | Command::PingDeep => {
                 self.ping_commands.fetch_add(1, Ordering::Relaxed);
             }
             Command::Echo { .. } => {
                 self.echo_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Dbsize => {
+            Command::Dbsize { .. } | Command::RandomKey => {
                 self.dbsize_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Exists { .. } => {
+            Command::Exists { .. } | Command::ExistsVerbose { .. } => {
                 self.exists_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Set { .. } => {
+            Command::Set { .. }
+            | Command::SetEx { .. }
+            | Command::BinarySet { .. }
+            | Command::RestoreKey { .. }
+            | Command::HSet { .. } => {
                 self.set_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Delete { .. } => {
+            Command::Delete { .. }
+            | Command::DeleteVerbose { .. }
+            | Command::Unlink { .. }
+            | Command::HDel { .. } => {
                 self.delete_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Increment { .. } | Command::Decrement { .. } => {
+            Command::Increment { .. } | Command::Decrement { .. } | Command::IncrLimit { .. } => {
                 self.numeric_commands.fetch_add(1, Ordering::Relaxed);
             }
             Command::Append { .. } | Command::Prepend { .. } => {
                 self.string_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::MultiGet { .. } | Command::MultiSet { .. } | Command::Truncate => {
+            Command::MultiGet { .. }
+            | Command::MultiSet { .. }
+            | Command::MultiSetEx { .. }
+            | Command::Export
+            | Command::Import { .. }
+            | Command::Truncate => {
                 self.bulk_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Stats | Command::Info => {
+            Command::Stats
+            | Command::StatsReset
+            | Command::SlowlogGet { .. }
+            | Command::SlowlogReset
+            | Command::Info
+            | Command::Hello { .. }
+            | Command::EngineInfo
+            | Command::NodeInfo
+            | Command::Health
+            | Command::CommandList
+            | Command::Object { .. }
+            | Command::History { .. }
+            | Command::HotKeys { .. }
+            | Command::ExpireTime { .. } => {
                 self.stat_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Version | Command::Flushdb | Command::Shutdown => {
+            Command::Version
+            | Command::Flushdb
+            | Command::Shutdown
+            | Command::Compact
+            | Command::FSync
+            | Command::DebugSleep { .. } => {
                 self.management_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Memory => {
+            Command::Memory { .. } | Command::MemDoctor => {
                 self.memory_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Clientlist => {
+            Command::Clientlist
+            | Command::ClientKill { .. }
+            | Command::Monitor
+            | Command::ConfigGet { .. }
+            | Command::ConfigSet { .. }
+            | Command::ConfigAll
+            | Command::Auth { .. }
+            | Command::Select { .. }
+            | Command::Move { .. } => {
                 self.management_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Sync {..} => {
+            Command::Sync {..} | Command::SyncPeers { .. } | Command::Diff { .. } => {
                 self.sync_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Hash {..} => {
+            Command::Hash {..}
+            | Command::HashBuckets { .. }
+            | Command::HashRange { .. }
+            | Command::MerkleDump
+            | Command::MerkleBench
+            | Command::Verify => {
                 self.hash_commands.fetch_add(1, Ordering::Relaxed);
             }
-            Command::Replicate {..} => {
+            Command::Replicate {..} | Command::Wait { .. } | Command::Watermark => {
                 self.replicate_commands.fetch_add(1, Ordering::Relaxed);
             }
+            Command::Cas { .. } | Command::CasVersion { .. } => {
+                self.cas_commands.fetch_add(1, Ordering::Relaxed);
+            }
         }
     }
     
+    /// Zero every counter and latency histogram (`STATS RESET`).
+    ///
+    /// `active_connections` is a gauge of open connections, not a counter, and
+    /// `start_time` is left alone so uptime keeps counting from server start.
+    pub fn reset(&self) {
+        for counter in [
+            &self.total_connections,
+            &self.total_commands,
+            &self.get_commands,
+            &self.scan_commands,
+            &self.ping_commands,
+            &self.echo_commands,
+            &self.dbsize_commands,
+            &self.exists_commands,
+            &self.flushdb_commands,
+            &self.memory_commands,
+            &self.clientlist_commands,
+            &self.set_commands,
+            &self.delete_commands,
+            &self.numeric_commands,
+            &self.string_commands,
+            &self.bulk_commands,
+            &self.stat_commands,
+            &self.management_commands,
+            &self.sync_commands,
+            &self.sync_keys_reconciled,
+            &self.store_lock_wait_micros,
+            &self.hash_commands,
+            &self.replicate_commands,
+            &self.cas_commands,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        for histogram in &self.latencies {
+            histogram.reset();
+        }
+    }
+
+    /// Record how long a command took to execute.
+    pub fn record_latency(&self, command: &Command, elapsed: Duration) {
+        self.latencies[latency_group(command)].record(elapsed);
+    }
+
     /// Format all statistics as a multi-line string for the STATS command
     pub fn format_stats(&self) -> String {
         let mut result = String::new();
@@ -298,13 +978,25 @@ impl ServerStats {
         result.push_str(&format!("bulk_commands:{}\r\n", self.bulk_commands.load(Ordering::Relaxed)));
         result.push_str(&format!("stat_commands:{}\r\n", self.stat_commands.load(Ordering::Relaxed)));
         result.push_str(&format!("sync_commands:{}\r\n", self.sync_commands.load(Ordering::Relaxed)));
+        result.push_str(&format!("sync_keys_reconciled:{}\r\n", self.sync_keys_reconciled.load(Ordering::Relaxed)));
+        result.push_str(&format!("store_lock_wait_micros:{}\r\n", self.store_lock_wait_micros.load(Ordering::Relaxed)));
         result.push_str(&format!("hash_commands:{}\r\n", self.hash_commands.load(Ordering::Relaxed)));
         result.push_str(&format!("replicate_commands:{}\r\n", self.replicate_commands.load(Ordering::Relaxed)));
+        result.push_str(&format!("cas_commands:{}\r\n", self.cas_commands.load(Ordering::Relaxed)));
         result.push_str(&format!("management_commands:{}\r\n", self.management_commands.load(Ordering::Relaxed)));
+
+        // Latency percentiles for command groups that have run
+        for (name, histogram) in LATENCY_GROUPS.iter().zip(&self.latencies) {
+            if let (Some(p50), Some(p99)) = (histogram.percentile(50.0), histogram.percentile(99.0)) {
+                result.push_str(&format!("{}_latency_count:{}\r\n", name, histogram.count()));
+                result.push_str(&format!("{}_latency_p50_us:{}\r\n", name, p50));
+                result.push_str(&format!("{}_latency_p99_us:{}\r\n", name, p99));
+            }
+        }
         
         // Add memory usage estimate (this is a very rough estimate)
         let estimated_memory_kb = std::process::Command::new("ps")
-            .args(&["-o", "rss=", "-p", &std::process::id().to_string()])
+            .args(["-o", "rss=", "-p", &std::process::id().to_string()])
             .output()
             .map(|output| {
                 String::from_utf8_lossy(&output.stdout)
@@ -328,11 +1020,45 @@ pub struct Server {
     /// Server configuration including bind address and port
     config: Config,
 
-    /// The storage engine that will be shared across all client connections
-    store: Box<dyn KVEngineStoreTrait + Send + Sync>,
+    /// One storage engine per logical database (index 0 is the default),
+    /// shared across all client connections
+    stores: Vec<Box<dyn KVEngineStoreTrait + Send + Sync>>,
     
     /// Server statistics for monitoring and diagnostics
     stats: ServerStats,
+
+    /// The storage engine backing every database, reported by `ENGINE`
+    engine: EngineDescriptor,
+}
+
+/// Which storage engine the server runs on.
+#[derive(Debug, Clone)]
+struct EngineDescriptor {
+    /// Engine name as configured: `rwlock`, `kv` or `sled`
+    name: String,
+    /// Whether data survives a restart
+    persistent: bool,
+    /// Storage path of database 0; only meaningful for persistent engines
+    path: String,
+}
+
+impl EngineDescriptor {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            name: config.engine.clone(),
+            persistent: config.engine == "sled",
+            path: config.storage_path.clone(),
+        }
+    }
+
+    /// `ENGINE` response, e.g. `ENGINE sled persistent=yes path=data`.
+    fn response(&self) -> String {
+        if self.persistent {
+            format!("ENGINE {} persistent=yes path={}\r\n", self.name, self.path)
+        } else {
+            format!("ENGINE {} persistent=no\r\n", self.name)
+        }
+    }
 }
 
 impl Server {
@@ -344,22 +1070,37 @@ impl Server {
     ///
     /// # Returns
     /// * `Server` - New server instance ready to run
+    #[allow(dead_code)]
     pub fn new(config: Config, store: Box<dyn KVEngineStoreTrait + Send + Sync>) -> Self {
-        Self { 
-            config, 
-            store,
+        Self::with_databases(config, vec![store])
+    }
+
+    /// Create a server with several logical databases, selectable per
+    /// connection with `SELECT <index>`.
+    ///
+    /// Replication and anti-entropy sync operate on database 0 only.
+    ///
+    /// # Arguments
+    /// * `config` - Server configuration (address, port, etc.)
+    /// * `stores` - One storage engine per database; must not be empty
+    pub fn with_databases(config: Config, stores: Vec<Box<dyn KVEngineStoreTrait + Send + Sync>>) -> Self {
+        assert!(!stores.is_empty(), "at least one database is required");
+        Self {
+            engine: EngineDescriptor::from_config(&config),
+            config,
+            stores,
             stats: ServerStats::new(),
         }
     }
 
     /// Start the server and begin accepting connections.
     ///
-    /// This method runs indefinitely, accepting new connections and spawning
-    /// tasks to handle them. Each connection gets its own async task but all
-    /// share the same storage engine.
+    /// This method runs until SIGTERM/SIGINT or a `SHUTDOWN` command, accepting
+    /// new connections and spawning tasks to handle them. Each connection gets
+    /// its own async task but all share the same storage engine.
     ///
     /// # Returns
-    /// * `Result<()>` - Never returns normally, only on bind errors
+    /// * `Result<()>` - `Ok` after a graceful shutdown, an error on bind failures
     ///
     /// # Errors
     /// Returns an error if:
@@ -371,26 +1112,98 @@ impl Server {
     /// let config = Config::default();
     /// let store = Box::new(RwLockEngine::new("./data")?);
     /// let server = Server::new(config, store);
-    /// server.run().await?; // Runs forever
+    /// server.run().await?; // Runs until SIGTERM, SIGINT or SHUTDOWN
     /// ```
     pub async fn run(self) -> Result<()> {
+        let signal = shutdown_signal()?;
+        self.run_until(signal).await
+    }
+
+    /// Like `run`, but shut down when `signal` resolves instead of on process
+    /// signals.
+    ///
+    /// Shutdown stops accepting, asks every connection to close after its
+    /// current command, waits up to `SHUTDOWN_DRAIN_TIMEOUT` for them, then
+    /// calls `sync` on every database.
+    pub async fn run_until(self, signal: impl std::future::Future<Output = ()>) -> Result<()> {
         let cfg = Arc::new(self.config.clone());
         let clients: ClientTable = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
         let client_id_gen = Arc::new(AtomicU64::new(0));
 
-        let addr = format!("{}:{}", self.config.host, self.config.port);
-        let listener = TcpListener::bind(&addr).await?;
-        info!("Server listening on {}", addr);
+        // Bind every address up front so a bad one fails startup, then feed
+        // all accepted connections into the single accept loop below
+        let (accepted_tx, mut accepted_rx) = tokio::sync::mpsc::channel(64);
+        let mut accept_tasks = Vec::new();
+        for addr in self.config.listen_addrs() {
+            let listener = TcpListener::bind(&addr).await?;
+            info!("Server listening on {}", addr);
+            let accepted_tx = accepted_tx.clone();
+            accept_tasks.push(tokio::spawn(async move {
+                loop {
+                    let accepted = listener.accept().await;
+                    if accepted_tx.send(accepted).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+        drop(accepted_tx);
+
+        // Set by a signal or the SHUTDOWN command; connections watch it to close
+        let (shutdown_tx, mut shutdown_rx) = tokio::sync::watch::channel(false);
+        let shutdown_tx = Arc::new(shutdown_tx);
 
-        // Wrap the storage in `Arc<Mutex<>>` for safe concurrent access
-        let store = Arc::new(Mutex::new(self.store));
-        
-        let sync_manager = Arc::new(tokio::sync::Mutex::new(
-            SyncManager::new_with_shared_store(&self.config, Arc::clone(&store))
-        ));
+        // Share each database's storage across connections; engines synchronize internally
+        let databases: Arc<Vec<Database>> = Arc::new(
+            self.stores
+                .into_iter()
+                .map(|store| {
+                    let store: Arc<dyn KVEngineStoreTrait + Send + Sync> = Arc::from(store);
+                    let writer = self.config.write_batching.then(|| {
+                        let (writer, queue) = tokio::sync::mpsc::channel(MAX_WRITE_BATCH);
+                        tokio::spawn(run_write_batcher(Arc::clone(&store), queue));
+                        writer
+                    });
+                    Database {
+                        store,
+                        access: AccessTracker::new(),
+                        writer,
+                        merkle: MerkleCache::default(),
+                        history: KeyHistory::new(self.config.key_history),
+                        key_stats: KeyStats::new(self.config.track_key_stats),
+                        key_limit: tokio::sync::Mutex::new(()),
+                        gate: tokio::sync::RwLock::new(()),
+                    }
+                })
+                .collect(),
+        );
+        let store = Arc::clone(&databases[0].store);
+
+        // Every SyncManager method takes `&self`, so syncs run concurrently
+        let sync_manager = Arc::new(SyncManager::new_with_shared_store(&self.config, Arc::clone(&store)));
+
+        // Periodic anti-entropy with configured peers
+        if self.config.sync.enabled && !self.config.sync.peers.is_empty() {
+            let interval = Duration::from_secs(self.config.sync.interval_secs.max(1));
+            info!("Background sync every {:?} with {:?}", interval, self.config.sync.peers);
+            tokio::spawn(crate::sync::run_periodic_sync(
+                Arc::clone(&sync_manager),
+                self.config.sync.peers.clone(),
+                interval,
+                Arc::clone(&databases[0].merkle.generation),
+            ));
+        }
+
+        tokio::spawn(purge_expired_keys(Arc::clone(&databases)));
 
         // Share server statistics across all connections
         let stats = Arc::new(self.stats.clone());
+        let engine = Arc::new(self.engine.clone());
+        let slowlog = Arc::new(SlowLog::new(self.config.slowlog_max_len));
+        let (monitor, _) = broadcast::channel(MONITOR_BUFFER);
+
+        // Settings adjustable with CONFIG SET
+        let runtime = Arc::new(RwLock::new(RuntimeConfig::from_config(&self.config)));
 
         let replicator: Arc<Mutex<Option<Replicator>>> = Arc::new(Mutex::new(None));
 
@@ -398,20 +1211,36 @@ impl Server {
         if self.config.replication.enabled {
             let r = Replicator::new(&self.config).await?;
             // background apply loop
-            r.start_replication_handler(Arc::clone(&store)).await;
+            r.start_replication_handler(Arc::clone(&store), Arc::clone(&databases[0].merkle.generation)).await;
             *replicator.lock().await = Some(r);
         }
 
-        // TODO: Add graceful shutdown handling
         // TODO: Add connection limits and rate limiting
 
+        tokio::pin!(signal);
         loop {
-            match listener.accept().await {
-                Ok((socket, addr)) => {
+            let accepted = tokio::select! {
+                accepted = accepted_rx.recv() => accepted,
+                _ = &mut signal => break,
+                _ = shutdown_rx.wait_for(|stopping| *stopping) => break,
+            };
+            match accepted {
+                Some(Ok((mut socket, addr))) => {
+                    let max_connections = runtime.read().unwrap().max_connections;
+                    if max_connections != 0
+                        && stats.active_connections.load(Ordering::Relaxed) >= max_connections as u64
+                    {
+                        info!("Rejecting connection from {}: max_connections ({}) reached", addr, max_connections);
+                        let _ = socket.write_all(b"ERROR max number of clients reached\r\n").await;
+                        continue;
+                    }
                     info!("Accepted connection from {}", addr);
+                    if let Err(e) = configure_socket(&socket, &cfg) {
+                        warn!("Failed to set socket options for {}: {}", addr, e);
+                    }
                     
                     // Clone the Arc for this connection
-                    let store_clone = Arc::clone(&store);
+                    let databases_clone = Arc::clone(&databases);
                     let stats_clone = Arc::clone(&stats);
                     
                     // Update connection statistics
@@ -431,6 +1260,7 @@ impl Server {
                         addr,
                         connected_unix: now_unix,
                         last_cmd_unix: AtomicU64::new(now_unix),
+                        kill: tokio::sync::Notify::new(),
                     });
                     {
                         let mut tbl = clients.lock().await;
@@ -439,20 +1269,52 @@ impl Server {
                     let clients_clone = Arc::clone(&clients);
                     let meta_clone = Arc::clone(&meta);
                     let cfg_cl = Arc::clone(&cfg);
+                    let runtime_clone = Arc::clone(&runtime);
+                    let shutdown_clone = Arc::clone(&shutdown_tx);
+                    let engine_clone = Arc::clone(&engine);
+                    let slowlog_clone = Arc::clone(&slowlog);
+                    let monitor_clone = monitor.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(socket, addr, store_clone, stats_clone.clone(), repl_clone, meta_clone, clients_clone.clone(), sync_manager_clone, cfg_cl).await {
+                        if let Err(e) = Self::handle_connection(socket, addr, databases_clone, stats_clone.clone(), repl_clone, meta_clone, clients_clone.clone(), sync_manager_clone, cfg_cl, runtime_clone, shutdown_clone, engine_clone, slowlog_clone, monitor_clone).await {
                             error!("Error handling connection from {}: {}", addr, e);
                         }
+                        clients_clone.lock().await.remove(&id);
                         
                         // Decrement active connections when the connection ends
                         stats_clone.active_connections.fetch_sub(1, Ordering::Relaxed);
                     });
                 }
-                Err(e) => {
+                Some(Err(e)) => {
                     error!("Error accepting connection: {}", e);
                 }
+                None => break,
+            }
+        }
+
+        // Stop accepting: awaiting the aborted tasks drops their listeners
+        shutdown_tx.send_replace(true);
+        for task in accept_tasks {
+            task.abort();
+            let _ = task.await;
+        }
+
+        // Drain: connections close after their current command
+        let deadline = Instant::now() + SHUTDOWN_DRAIN_TIMEOUT;
+        while stats.active_connections.load(Ordering::Relaxed) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let remaining = stats.active_connections.load(Ordering::Relaxed);
+        if remaining > 0 {
+            warn!("Shutting down with {} connections still open", remaining);
+        }
+
+        for (index, database) in databases.iter().enumerate() {
+            if let Err(e) = database.store.sync() {
+                error!("Failed to sync database {} during shutdown: {}", index, e);
             }
         }
+        info!("Server shut down");
+        Ok(())
     }
 
     /// Handle a single client connection.
@@ -464,8 +1326,9 @@ impl Server {
     /// # Arguments
     /// * `socket` - The TCP stream for this client connection
     /// * `addr` - Client's address (for logging)
-    /// * `store` - Shared reference to the storage engine
+    /// * `databases` - Shared logical databases (storage engine + access tracker each)
     /// * `stats` - Shared reference to server statistics
+    /// * `runtime` - Shared settings adjustable with `CONFIG SET`
     /// 
     /// # Returns
     /// * `Result<()>` - Success when client disconnects normally, error on failures
@@ -480,48 +1343,105 @@ impl Server {
     /// - Invalid commands result in ERROR responses
     /// - Network errors terminate the connection
     /// - Storage errors are converted to ERROR responses
+    #[allow(clippy::too_many_arguments)]
     async fn handle_connection(
         socket: TcpStream,
         addr: SocketAddr,
-        store: Arc<Mutex<Box<dyn KVEngineStoreTrait + Send + Sync>>>,
+        databases: Arc<Vec<Database>>,
         stats: Arc<ServerStats>,
         replicator: Arc<Mutex<Option<Replicator>>>,
         client_meta: Arc<ClientMeta>,
         clients: ClientTable,
-        sync_manager: Arc<tokio::sync::Mutex<SyncManager>>,
+        sync_manager: Arc<SyncManager>,
         cfg: Arc<crate::config::Config>,
+        runtime: Arc<RwLock<RuntimeConfig>>,
+        shutdown: Arc<tokio::sync::watch::Sender<bool>>,
+        engine: Arc<EngineDescriptor>,
+        slowlog: Arc<SlowLog>,
+        monitor: broadcast::Sender<String>,
     ) -> Result<()> {
+        let mut shutdown_rx = shutdown.subscribe();
         let (read_half, mut write_half) = socket.into_split();
         let mut reader = BufReader::new(read_half);
-        let protocol = Protocol::new();
+        let protocol = Protocol::new()
+            .with_max_bulk_keys(cfg.max_bulk_keys)
+            .with_command_aliases(&cfg.command_aliases, &cfg.rename_commands);
 
         // Local helper describing what to publish after the storage write.
         enum Publish {
             Set(String, String),
+            SetEx(String, String, u64),
             Delete(String),
+            Clear,
             Incr(String, i64),
             Decr(String, i64),
             Append(String, String),
             Prepend(String, String),
         }
 
+        impl Publish {
+            /// The write as a change event stamped with `src` and `ts`, for `HISTORY`.
+            fn event(&self, src: &str, ts: u64) -> ChangeEvent {
+                let (op, key, value, ttl) = match self {
+                    Publish::Set(k, v) => (OpKind::Set, k.as_str(), Some(v.clone()), None),
+                    Publish::SetEx(k, v, t) => (OpKind::Set, k.as_str(), Some(v.clone()), Some(*t)),
+                    Publish::Delete(k) => (OpKind::Del, k.as_str(), None, None),
+                    Publish::Clear => (OpKind::Clear, "", None, None),
+                    Publish::Incr(k, n) => (OpKind::Incr, k.as_str(), Some(n.to_string()), None),
+                    Publish::Decr(k, n) => (OpKind::Decr, k.as_str(), Some(n.to_string()), None),
+                    Publish::Append(k, v) => (OpKind::Append, k.as_str(), Some(v.clone()), None),
+                    Publish::Prepend(k, v) => (OpKind::Prepend, k.as_str(), Some(v.clone()), None),
+                };
+                ChangeEvent::with_str_value(1, op, key, value.as_deref(), ts, src, None, ttl)
+            }
+        }
+
+        // Database selected with SELECT (0 until changed)
+        let mut db: usize = 0;
+
+        // User logged in with AUTH, when `users` are configured
+        let mut auth_user: Option<String> = None;
+
+        // Per-connection rate limit state (fixed one-second window)
+        let mut rate_window = Instant::now();
+        let mut rate_count: u64 = 0;
+
+        // Response line ending, negotiated with HELLO
+        let mut line_ending = LineEnding::default();
+        // GET values longer than this are sent compressed; 0 until HELLO ... COMPRESS
+        let mut compress_threshold = 0;
+
         loop {
             // Read a complete line from the client (terminated by \n)
             // Defensive upper bound to prevent OOM attacks
             let mut request_line = String::new();
-            match reader.read_line(&mut request_line).await {
+            let read = tokio::select! {
+                read = read_request_line(&mut reader, &mut request_line) => read,
+                _ = shutdown_rx.wait_for(|stopping| *stopping) => {
+                    info!("Closing connection {} for shutdown", addr);
+                    break;
+                }
+                _ = client_meta.kill.notified() => {
+                    info!("Closing connection {}: killed by CLIENT KILL", addr);
+                    break;
+                }
+            };
+            match read {
                 Ok(0) => {
                     // Client closed the connection
                     info!("Client {} disconnected", addr);
                     break;
                 }
                 Ok(bytes_read) => {
-                    // Check for line length abuse (1MB limit)
-                    if bytes_read > 1024 * 1024 {
-                        let error_msg = "ERROR line too long\r\n";
-                        let _ = write_half.write_all(error_msg.as_bytes()).await;
-                        error!("Dropping connection {}: line too long ({} bytes)", addr, bytes_read);
-                        break;
+                    // The oversized line has been drained; reject it and keep the connection
+                    if bytes_read > MAX_LINE_BYTES {
+                        warn!("Rejected a {} byte request line from {}", bytes_read, addr);
+                        let error_msg = line_ending.frame("ERROR line too long\r\n");
+                        if let Err(e) = write_half.write_all(error_msg.as_bytes()).await {
+                            error!("Error writing to client {}: {}", addr, e);
+                            break;
+                        }
+                        continue;
                     }
                     // Successfully read a line
                 }
@@ -531,44 +1451,397 @@ impl Server {
                 }
             };
 
-            match protocol.parse(&request_line) {
+            let mut parsed = protocol.parse(&request_line);
+
+            // BSET/SETRAW is followed by a raw payload; consume it now, whatever happens
+            // to the command, so the next request line is framed correctly
+            if let Ok(Command::BinarySet { len, value, .. }) = &mut parsed {
+                if *len > MAX_BINARY_VALUE_BYTES {
+                    let _ = write_half.write_all(line_ending.frame("ERROR value too large\r\n").as_bytes()).await;
+                    error!("Dropping connection {}: BSET payload of {} bytes", addr, len);
+                    break;
+                }
+                value.resize(*len, 0);
+                let mut terminator = String::new();
+                let framed = match reader.read_exact(value).await {
+                    Ok(_) => reader.read_line(&mut terminator).await.is_ok(),
+                    Err(_) => false,
+                };
+                if !framed || (terminator != "\r\n" && terminator != "\n") {
+                    let _ = write_half.write_all(line_ending.frame("ERROR bad payload framing\r\n").as_bytes()).await;
+                    error!("Dropping connection {}: malformed BSET payload", addr);
+                    break;
+                }
+            }
+
+            // IMPORT is followed by JSON lines up to END; read them all, even when
+            // one is malformed or the ACL refuses the command, so the next request
+            // line is framed correctly. A refused client's lines are only drained.
+            let import_denial = match &parsed {
+                Ok(command @ Command::Import { .. }) => acl_denial(&cfg.users, auth_user.as_deref(), command),
+                _ => None,
+            };
+            if let Ok(Command::Import { pairs }) = &mut parsed {
+                let mut import_error = import_denial.map(|denial| anyhow!(denial));
+                let mut line_number = 0;
+                loop {
+                    let mut line = String::new();
+                    let read = match read_request_line(&mut reader, &mut line).await {
+                        Ok(0) | Err(_) => {
+                            info!("Client {} disconnected during IMPORT", addr);
+                            return Ok(());
+                        }
+                        Ok(read) => read,
+                    };
+                    if line.trim_end() == "END" {
+                        break;
+                    }
+                    line_number += 1;
+                    if read > MAX_LINE_BYTES {
+                        // Already skipped by read_request_line
+                        if import_error.is_none() {
+                            pairs.clear();
+                            import_error = Some(anyhow!("line too long"));
+                        }
+                        continue;
+                    }
+                    if import_error.is_none() && cfg.max_bulk_keys != 0 && line_number > cfg.max_bulk_keys {
+                        // Keep draining to END, but stop collecting pairs
+                        pairs.clear();
+                        import_error = Some(anyhow!("too many keys"));
+                    }
+                    if import_error.is_none() {
+                        match parse_import_line(&line) {
+                            Ok(pair) => pairs.push(pair),
+                            Err(e) => {
+                                debug!("IMPORT from {} line {}: {}", addr, line_number, e);
+                                import_error = Some(anyhow!("bad line {}", line_number));
+                            }
+                        }
+                    }
+                }
+                if let Some(e) = import_error {
+                    parsed = Err(e);
+                }
+            }
+
+            // Key limits are checked only once any BSET payload has been consumed
+            if let Ok(command) = &parsed {
+                if let Err(e) = command.keys().into_iter().try_for_each(|key| validate_key(key, cfg.max_key_bytes)) {
+                    parsed = Err(e);
+                } else if let Err(e) = command.values().into_iter().try_for_each(|value| match command {
+                    // SET keeps CR, LF and NUL by storing the value as binary, like BSET
+                    Command::Set { .. } if !value.starts_with('\0') => Ok(()),
+                    _ => validate_value(value),
+                }) {
+                    parsed = Err(e);
+                }
+            }
+
+            // Refused before anything runs, so a denied write cannot evict keys either
+            if let Ok(command) = &parsed {
+                if let Some(denial) = acl_denial(&cfg.users, auth_user.as_deref(), command) {
+                    parsed = Err(anyhow!(denial));
+                }
+            }
+
+            let rate_limit = runtime.read().unwrap().rate_limit_per_sec;
+            if rate_limit != 0 {
+                if rate_window.elapsed() >= Duration::from_secs(1) {
+                    rate_window = Instant::now();
+                    rate_count = 0;
+                }
+                rate_count += 1;
+                if rate_count > rate_limit {
+                    if let Err(e) = write_half.write_all(line_ending.frame("ERROR rate limit exceeded\r\n").as_bytes()).await {
+                        error!("Error writing to client {}: {}", addr, e);
+                        break;
+                    }
+                    continue;
+                }
+            }
+
+            match parsed {
                 Ok(command) => {
                     let now_unix = SystemTime::now()
                         .duration_since(UNIX_EPOCH)
                         .unwrap_or(Duration::from_secs(0))
                         .as_secs();
                     client_meta.last_cmd_unix.store(now_unix, Ordering::Relaxed);
+                    // AUTH is kept out of MONITOR and the slow log, which would show the password
+                    if monitor.receiver_count() > 0 && !matches!(command, Command::Monitor | Command::Auth { .. }) {
+                        let _ = monitor.send(monitor_line(client_meta.id, request_line.trim_end()));
+                    }
                     // Update command statistics
                     stats.increment_command_counter(&command);
                     
                     // Process the command. We avoid holding the store lock across awaits
                     // by computing an optional publish action and performing it afterward.
                     let mut publishes: Vec<Publish> = Vec::new();
+                    // Raw bytes sent after the response line (BGET)
+                    let mut binary_payload: Option<Vec<u8>> = None;
+                    // Set by MONITOR: the connection streams this feed after replying
+                    let mut monitor_feed: Option<broadcast::Receiver<String>> = None;
+                    let mut export_keys: Option<Vec<String>> = None;
+                    // Set by DEBUG SLEEP: how long to wait once the gate is released
+                    let mut debug_sleep: Option<Duration> = None;
+
+                    let started = Instant::now();
+
+                    // MOVE takes both databases, lower index first, so two MOVEs
+                    // in opposite directions cannot deadlock
+                    let exclusive_dbs = match &command {
+                        Command::Move { target, .. } if *target < databases.len() && *target != db => {
+                            vec![db.min(*target), db.max(*target)]
+                        }
+                        Command::RestoreKey { .. }
+                        | Command::Hash { .. }
+                        | Command::HashBuckets { .. }
+                        | Command::HashRange { .. }
+                        | Command::MerkleDump => vec![db],
+                        _ => vec![],
+                    };
+                    let waiting = Instant::now();
+                    let mut exclusive_gates = Vec::with_capacity(exclusive_dbs.len());
+                    for &index in &exclusive_dbs {
+                        exclusive_gates.push(databases[index].gate.write().await);
+                    }
+                    let shared_gate = if exclusive_gates.is_empty() {
+                        Some(databases[db].gate.read().await)
+                    } else {
+                        None
+                    };
+                    stats.store_lock_wait_micros.fetch_add(waiting.elapsed().as_micros() as u64, Ordering::Relaxed);
+
+                    // Every command below operates on the selected database
+                    let store = &*databases[db].store;
+                    let access = &databases[db].access;
+
+                    // String commands must not read or rewrite an encoded hash, and
+                    // text commands must not mangle bytes stored with BSET
+                    let (wrong_type, binary) = match &command {
+                        Command::Get { key }
+                        | Command::GetEx { key, .. }
+                        | Command::Increment { key, .. }
+                        | Command::Decrement { key, .. }
+                        | Command::IncrLimit { key, .. }
+                        | Command::Append { key, .. }
+                        | Command::Prepend { key, .. }
+                        | Command::GetVersioned { key }
+                        | Command::CasVersion { key, .. }
+                        | Command::BinaryGet { key } => {
+                            let value = store.get(key);
+                            let binary = !matches!(command, Command::BinaryGet { .. })
+                                && value.as_deref().is_some_and(binary_value::is_binary);
+                            (value.is_some_and(|v| hash_value::is_hash(&v)), binary)
+                        }
+                        _ => (false, false),
+                    };
+
+                    // Under a key cap, writes that may create keys are checked and
+                    // applied one at a time per database
+                    let key_target = match &command {
+                        _ if cfg.max_keys == 0 => None,
+                        Command::Move { key, target } if *target < databases.len() => Some((*target, vec![key.as_str()])),
+                        _ if write_footprint(store, &command).is_some() => Some((db, command.keys())),
+                        _ => None,
+                    };
+                    let (key_limit_guard, over_key_limit) = match key_target {
+                        Some((target, keys)) => {
+                            let guard = databases[target].key_limit.lock().await;
+                            (Some(guard), over_key_limit(&*databases[target].store, &keys, cfg.max_keys))
+                        }
+                        None => (None, false),
+                    };
+
+                    // Under a memory budget, growing writes that will otherwise
+                    // succeed first make room or are refused
+                    let refused = wrong_type || binary || over_key_limit || runtime.read().unwrap().read_only;
+                    let out_of_memory = match write_footprint(store, &command) {
+                        Some(incoming) if cfg.maxmemory_bytes != 0 && !refused => {
+                            match make_room(store, access, &cfg, incoming) {
+                                Some(evicted) => {
+                                    publishes.extend(evicted.into_iter().map(Publish::Delete));
+                                    false
+                                }
+                                None => true,
+                            }
+                        }
+                        _ => false,
+                    };
+
+                    // Writes bump the Merkle cache generation on both sides so that
+                    // VERIFY can tell whether one overlapped its rebuild
+                    let touched: Vec<&MerkleCache> = match &command {
+                        Command::Move { target, .. } if *target < databases.len() => {
+                            vec![&databases[db].merkle, &databases[*target].merkle]
+                        }
+                        Command::PingDeep => vec![&databases[db].merkle],
+                        _ if is_write(&command) => vec![&databases[db].merkle],
+                        _ => vec![],
+                    };
+                    touched.iter().for_each(|merkle| merkle.touch());
+
                     let response = match command.clone() {
+                        _ if is_write(&command) && runtime.read().unwrap().read_only => {
+                            "ERROR READONLY server is read-only\r\n".to_string()
+                        }
+                        _ if out_of_memory => "ERROR OOM command not allowed when used memory > maxmemory\r\n".to_string(),
+                        _ if over_key_limit => "ERROR key limit reached\r\n".to_string(),
+                        _ if wrong_type => format!("ERROR {}\r\n", WRONGTYPE),
+                        _ if binary => format!("ERROR {}\r\n", BINARY_VALUE),
                         Command::Get { key } => {
-                            let store = store.lock().await;
                             match store.get(&key) {
-                                Some(value) => format!("VALUE {}\r\n", value),
+                                Some(value) => {
+                                    access.touch(&key);
+                                    databases[db].key_stats.record(&key);
+                                    // Packed only when that actually saves space
+                                    match Stored::new(value, compress_threshold) {
+                                        Stored::Packed(packed) => {
+                                            let header = format!("VALUEZ {} {}\r\n", compressed_value::ALGORITHM, packed.len());
+                                            binary_payload = Some(packed);
+                                            header
+                                        }
+                                        raw => match raw.into_value() {
+                                            Ok(value) => format!("VALUE {}\r\n", value),
+                                            Err(e) => format!("ERROR {}\r\n", e),
+                                        },
+                                    }
+                                }
+                                None => "NOT_FOUND\r\n".to_string(),
+                            }
+                        }
+                        Command::GetEx { key, expiry } => {
+                            let value = match expiry {
+                                None => store.get(&key),
+                                Some(ExpiryChange::In(ttl_ms)) => {
+                                    let deadline_ms = expiry::now_millis().saturating_add(ttl_ms);
+                                    let value = store.get_and_touch_expiry(&key, Some(deadline_ms));
+                                    if let Some(value) = &value {
+                                        // Replicated TTLs are whole seconds; round up so peers never expire early
+                                        publishes.push(Publish::SetEx(key.clone(), value.clone(), ttl_ms.div_ceil(1000)));
+                                    }
+                                    value
+                                }
+                                Some(ExpiryChange::Persist) => {
+                                    let value = store.get_and_touch_expiry(&key, None);
+                                    if let Some(value) = &value {
+                                        // A plain SET makes the key persistent on peers too
+                                        publishes.push(Publish::Set(key.clone(), value.clone()));
+                                    }
+                                    value
+                                }
+                            };
+                            match value {
+                                Some(value) => {
+                                    access.touch(&key);
+                                    format!("VALUE {}\r\n", value)
+                                }
                                 None => "NOT_FOUND\r\n".to_string(),
                             }
                         }
                         Command::Ping { message } => {
-                            let store = store.lock().await;
                             let pong_response = store.ping(&message);
                             format!("{}\r\n", pong_response)
                         }
+                        Command::PingDeep => {
+                            // Bypasses replication, access tracking and write batching:
+                            // the sentinel only checks that the engine itself responds
+                            let sentinel = format!("{}{}:{}", DEEP_PING_PREFIX, client_meta.id, unix_nanos());
+                            match deep_ping(store, &sentinel) {
+                                Ok(()) => "PONG\r\n".to_string(),
+                                Err(e) => format!("ERROR deep ping failed: {}\r\n", e),
+                            }
+                        }
                         Command::Echo { message } => {
-                            let store = store.lock().await;
                             let echo_response = store.echo(&message);
                             format!("{}\r\n", echo_response)
                         }
-                        Command::Dbsize => {
-                            let store = store.lock().await;
+                        Command::Dbsize { pattern: None } => {
                             let size = store.dbsize();
                             format!("DBSIZE {}\r\n", size)
                         }
+                        Command::Dbsize { pattern: Some(pattern) } => {
+                            let (count, _) = pattern_usage(store, &pattern);
+                            format!("DBSIZE {} {}\r\n", pattern, count)
+                        }
+                        Command::Auth { .. } if cfg.users.is_empty() => {
+                            "ERROR AUTH called without any users configured\r\n".to_string()
+                        }
+                        Command::Auth { user, password } => {
+                            if cfg.users.iter().any(|u| u.name == user && password_matches(&u.password, &password)) {
+                                auth_user = Some(user);
+                                "OK\r\n".to_string()
+                            } else {
+                                "ERROR WRONGPASS invalid username or password\r\n".to_string()
+                            }
+                        }
+                        Command::Select { index } => {
+                            if index < databases.len() {
+                                db = index;
+                                "OK\r\n".to_string()
+                            } else {
+                                "ERROR DB index is out of range\r\n".to_string()
+                            }
+                        }
+                        Command::Move { target, .. } if target >= databases.len() => {
+                            "ERROR DB index is out of range\r\n".to_string()
+                        }
+                        Command::Move { target, .. } if target == db => {
+                            "ERROR source and destination databases are the same\r\n".to_string()
+                        }
+                        Command::Move { key, target } => {
+                            let (src, dst) = (&databases[db].store, &databases[target].store);
+                            // Read with the value so the key keeps its deadline in the target
+                            let deadline = src.expiry(&key);
+
+                            match src.get(&key) {
+                                None => "NOT_FOUND\r\n".to_string(),
+                                Some(_) if dst.exists(&key) => "ERROR key exists in target database\r\n".to_string(),
+                                // Create-if-absent, so a write racing into the target is never overwritten
+                                Some(value) => match dst.compare_and_swap(&key, "", &value) {
+                                    Ok(false) => "ERROR key exists in target database\r\n".to_string(),
+                                    Ok(true) => {
+                                        if let Some(deadline_ms) = deadline {
+                                            if let Err(e) = dst.set_with_expiry(key.clone(), value.clone(), deadline_ms) {
+                                                warn!("MOVE {}: failed to carry over its expiry: {}", key, e);
+                                            }
+                                        }
+                                        src.delete(&key);
+                                        access.remove(&key);
+                                        databases[target].access.touch_write(&key);
+                                        // Each side's history gets its half of the move
+                                        let ts = unix_nanos();
+                                        let node = cfg.replication.client_id.as_str();
+                                        let set = || match deadline {
+                                            Some(deadline_ms) => {
+                                                let ttl_ms = deadline_ms.saturating_sub(expiry::now_millis());
+                                                // Replicated TTLs are whole seconds; round up so peers never expire early
+                                                Publish::SetEx(key.clone(), value.clone(), ttl_ms.div_ceil(1000).max(1))
+                                            }
+                                            None => Publish::Set(key.clone(), value.clone()),
+                                        };
+                                        databases[db].history.record(Publish::Delete(key.clone()).event(node, ts));
+                                        databases[target].history.record(set().event(node, ts));
+                                        if db == 0 {
+                                            publishes.push(Publish::Delete(key.clone()));
+                                        }
+                                        if target == 0 {
+                                            publishes.push(set());
+                                        }
+                                        "OK\r\n".to_string()
+                                    }
+                                    Err(e) => format!("ERROR {}\r\n", e),
+                                },
+                            }
+                        }
+                        Command::RandomKey => {
+                            match store.random_key() {
+                                Some(key) => format!("KEY {}\r\n", key),
+                                None => "NOT_FOUND\r\n".to_string(),
+                            }
+                        }
                         Command::Exists { keys } => {
-                            let store = store.lock().await;
                             let mut count = 0;
                             for key in keys {
                                 if store.exists(&key) {
@@ -577,42 +1850,180 @@ impl Server {
                             }
                             format!("EXISTS {}\r\n", count)
                         }
-                        Command::Scan { prefix } => {
-                            let store = store.lock().await;
-                            let results = store.scan(&prefix);
+                        Command::ExistsVerbose { keys } => {
+                            let mut count = 0;
+                            let mut lines = String::new();
+                            for key in keys {
+                                let present = store.exists(&key);
+                                if present {
+                                    count += 1;
+                                }
+                                lines.push_str(&format!("{} {}\r\n", key, present as u8));
+                            }
+                            format!("EXISTS {}\r\n{}", count, lines)
+                        }
+                        Command::Scan { prefix, pattern } => {
+                            let mut results = store.scan(&prefix);
+                            if let Some(pattern) = &pattern {
+                                results.retain(|k| glob_match(pattern, k));
+                            }
                             let mut response = format!("KEYS {}\r\n", results.len());
                             for k in results {
                                 response.push_str(&format!("{}\r\n", k));
                             }
                             response
                         }
+                        Command::ScanValues { prefix, pattern } => {
+                            // As with MGET, hash and raw-byte keys are skipped, and so is any
+                            // value that could not be sent on one line
+                            let mut pairs = store.scan_values(&prefix);
+                            pairs.retain(|(k, v)| {
+                                pattern.as_ref().is_none_or(|pattern| glob_match(pattern, k))
+                                    && !hash_value::is_hash(v)
+                                    && !binary_value::is_binary(v)
+                                    && !v.contains(['\r', '\n'])
+                            });
+                            let mut response = format!("PAIRS {}\r\n", pairs.len());
+                            for (k, v) in pairs {
+                                access.touch(&k);
+                                response.push_str(&format!("{} {}\r\n", k, v));
+                            }
+                            response
+                        }
+                        Command::Set { value, .. } if cfg.value_too_large(value.len()) => {
+                            "ERROR value too large\r\n".to_string()
+                        }
                         Command::Set { key, value } => {
-                            let store = store.lock().await;
-                            match store.set(key.clone(), value.clone()) {
+                            let value = binary_value::encode(value.as_bytes());
+                            let result = match &databases[db].writer {
+                                Some(writer) => queue_write(writer, key.clone(), value.clone()).await,
+                                None => store.set(key.clone(), value.clone()),
+                            };
+                            match result {
                                 Ok(_) => {
+                                    access.touch_write(&key);
+                                    databases[db].key_stats.record(&key);
                                     publishes.push(Publish::Set(key.clone(), value.clone()));
                                     "OK\r\n".to_string()
                                 }
                                 Err(e) => format!("ERROR {}\r\n", e),
                             }
                         }
+                        Command::SetEx { value, .. } if cfg.value_too_large(value.len()) => {
+                            "ERROR value too large\r\n".to_string()
+                        }
+                        Command::SetEx { key, value, ttl_ms } => {
+                            let deadline_ms = expiry::now_millis().saturating_add(ttl_ms);
+                            match store.set_with_expiry(key.clone(), value.clone(), deadline_ms) {
+                                Ok(_) => {
+                                    access.touch_write(&key);
+                                    // Replicated TTLs are whole seconds; round up so peers never expire early
+                                    publishes.push(Publish::SetEx(key.clone(), value.clone(), ttl_ms.div_ceil(1000)));
+                                    "OK\r\n".to_string()
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::BinarySet { value, .. } if cfg.value_too_large(value.len()) => {
+                            "ERROR value too large\r\n".to_string()
+                        }
+                        Command::BinarySet { key, value, .. } => {
+                            let encoded = binary_value::encode(&value);
+                            match store.set(key.clone(), encoded.clone()) {
+                                Ok(_) => {
+                                    access.touch_write(&key);
+                                    publishes.push(Publish::Set(key.clone(), encoded));
+                                    "OK\r\n".to_string()
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::BinaryGet { key } => {
+                            match store.get(&key) {
+                                Some(value) => {
+                                    access.touch(&key);
+                                    let bytes = binary_value::decode(&value);
+                                    let header = format!("VALUE {}\r\n", bytes.len());
+                                    binary_payload = Some(bytes);
+                                    header
+                                }
+                                None => "NOT_FOUND\r\n".to_string(),
+                            }
+                        }
                         Command::Delete { key } => {
-                            let deleted = {
-                                let store = store.lock().await;
-                                store.delete(&key)
-                            };
-                            if deleted {
+                            if store.delete(&key) {
+                                access.remove(&key);
                                 publishes.push(Publish::Delete(key.clone()));
                                 "DELETED\r\n".to_string()
                             } else {
                                 "NOT_FOUND\r\n".to_string()
                             }
                         }
-                        Command::Memory => {
-                            let store = store.lock().await;
+                        Command::DeleteVerbose { keys } => {
+                            let mut count = 0;
+                            let mut lines = String::new();
+                            for key in keys {
+                                let deleted = store.delete(&key);
+                                if deleted {
+                                    count += 1;
+                                    access.remove(&key);
+                                    publishes.push(Publish::Delete(key.clone()));
+                                }
+                                lines.push_str(&format!("{} {}\r\n", key, deleted as u8));
+                            }
+                            format!("DELETED {}\r\n{}", count, lines)
+                        }
+                        Command::Unlink { keys } => {
+                            let detached: Vec<(String, String)> = keys
+                                .into_iter()
+                                .filter_map(|key| store.unlink(&key).map(|value| (key, value)))
+                                .collect();
+                            for (key, _) in &detached {
+                                access.remove(key);
+                                publishes.push(Publish::Delete(key.clone()));
+                            }
+                            let count = detached.len();
+                            // Freeing large values can take a while; keep it off this task
+                            if count > 0 {
+                                tokio::task::spawn_blocking(move || drop(detached));
+                            }
+                            format!("UNLINKED {}\r\n", count)
+                        }
+                        Command::Memory { pattern: None } => {
                             let usage = store.memory_usage();
                             format!("MEMORY {}\r\n", usage)
                         }
+                        Command::Memory { pattern: Some(pattern) } => {
+                            let (_, bytes) = pattern_usage(store, &pattern);
+                            format!("MEMORY {} {}\r\n", pattern, bytes)
+                        }
+                        Command::MemDoctor => {
+                            let mut report = MemoryReport::default();
+                            store.for_each_entry(&mut |key, value| report.record(key, value));
+                            let mut out = format!("MEMORY DOCTOR keys={} bytes={}\r\n", report.keys, report.bytes);
+                            for (lower, upper, count) in report.histogram.buckets() {
+                                match upper {
+                                    Some(upper) => out.push_str(&format!("BUCKET <={} {}\r\n", upper, count)),
+                                    None => out.push_str(&format!("BUCKET >{} {}\r\n", lower - 1, count)),
+                                }
+                            }
+                            let largest = report.largest();
+                            out.push_str(&format!("LARGEST {}\r\n", largest.len()));
+                            for (key, size) in largest {
+                                out.push_str(&format!("{} {}\r\n", key, size));
+                            }
+                            out
+                        }
+                        Command::ClientKill { id } => {
+                            let target = clients.lock().await.get(&id).cloned();
+                            match target {
+                                Some(client) => {
+                                    client.kill.notify_one();
+                                    "OK\r\n".to_string()
+                                }
+                                None => "NOT_FOUND\r\n".to_string(),
+                            }
+                        }
                         Command::Clientlist => {
 
                             let snapshot: Vec<Arc<ClientMeta>> = {
@@ -637,17 +2048,65 @@ impl Server {
                             out.push_str("END\r\n");
                             out
                         }
+                        Command::Sync { host, port, options } if options.dry_run => {
+                            match sync_manager.diff_only(&host, port).await {
+                                Ok(report) => format!("DRYRUN pull={} push={}\r\n", report.keys_pulled, report.keys_pushed),
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
                         Command::Sync { host, port, options: _ } => {
-                            let mut mgr = sync_manager.lock().await;
-                            match mgr.sync_once(&host, port).await {
-                                Ok(_)  => "OK\r\n".to_string(),
+                            match sync_manager.sync_once(&host, port).await {
+                                Ok(report) => {
+                                    stats
+                                        .sync_keys_reconciled
+                                        .fetch_add(report.keys_reconciled() as u64, Ordering::Relaxed);
+                                    format!(
+                                        "SYNCED pulled={} pushed={} conflicts={}\r\n",
+                                        report.keys_pulled, report.keys_pushed, report.conflicts_resolved
+                                    )
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::SyncPeers { peers } => {
+                            // The sync manager works on database 0
+                            match sync_manager.sync_many(&peers, &databases[0].access).await {
+                                Ok(results) => {
+                                    let mut out = format!("SYNCED {}\r\n", results.len());
+                                    for (peer, result) in results {
+                                        match result {
+                                            Ok(report) => {
+                                                stats
+                                                    .sync_keys_reconciled
+                                                    .fetch_add(report.keys_reconciled() as u64, Ordering::Relaxed);
+                                                out.push_str(&format!(
+                                                    "{} pulled={} pushed={} conflicts={}\r\n",
+                                                    peer, report.keys_pulled, report.keys_pushed, report.conflicts_resolved
+                                                ));
+                                            }
+                                            Err(e) => out.push_str(&format!("{} ERROR {}\r\n", peer, e)),
+                                        }
+                                    }
+                                    out
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::Diff { host, port, prefix } => {
+                            match sync_manager.diff_once(&host, port, &prefix).await {
+                                Ok(keys) => {
+                                    let mut out = format!("DIFF {}\r\n", keys.len());
+                                    for key in keys {
+                                        out.push_str(&format!("{}\r\n", key));
+                                    }
+                                    out
+                                }
                                 Err(e) => format!("ERROR {}\r\n", e),
                             }
                         }
                         Command::Hash { pattern } => {
                             // 1) Collect keys (all or prefix)
                             let (keys, pat_string) = {
-                                let store = store.lock().await;
                                 // convention: empty prefix returns ALL keys (you already added this for SCAN)
                                 let ks = match &pattern {
                                     None => store.scan(""),
@@ -659,19 +2118,16 @@ impl Server {
 
                             // 2) Build a Merkle tree over selected keys
                             let mut tree = crate::store::merkle::MerkleTree::new();
-                            {
-                                let store = store.lock().await;
-                                for k in keys {
-                                    if let Some(v) = store.get(&k) {
-                                        tree.insert(&k, &v); // your Merkle uses deterministic key ordering internally
-                                    }
+                            for k in keys {
+                                if let Some(v) = store.get(&k) {
+                                    tree.insert(&k, &v); // your Merkle uses deterministic key ordering internally
                                 }
                             }
 
-                            // 3) Compute root → hex (define empty = 64 zeros for determinism)
+                            // 3) Compute root → hex; no matching keys is reported as EMPTY
                             let hex_root = match tree.get_root_hash() {
                                 Some(h) => hex::encode(h),
-                                None    => "0".repeat(64), // SHA-256 length; empty set sentinel
+                                None    => "EMPTY".to_string(),
                             };
 
                             // 4) Format response
@@ -683,6 +2139,81 @@ impl Server {
 
                             out
                         }
+                        Command::HashBuckets { n } => {
+                            let mut tree = crate::store::merkle::MerkleTree::new();
+                            for k in store.scan("") {
+                                if let Some(v) = store.get(&k) {
+                                    tree.insert(&k, &v);
+                                }
+                            }
+                            let mut out = format!("BUCKETS {}\r\n", n);
+                            for (i, root) in tree.bucket_roots(n).into_iter().enumerate() {
+                                let hex_root = root.map(hex::encode).unwrap_or_else(|| "0".repeat(64));
+                                out.push_str(&format!("{} {}\r\n", i, hex_root));
+                            }
+                            out
+                        }
+                        Command::HashRange { start, end } => {
+                            let mut tree = crate::store::merkle::MerkleTree::new();
+                            let mut keys: Vec<String> = store
+                                .keys()
+                                .into_iter()
+                                .filter(|k| start.as_str() <= k.as_str() && k.as_str() < end.as_str())
+                                .collect();
+                            keys.sort();
+                            for k in keys {
+                                if let Some(v) = store.get(&k) {
+                                    tree.insert(&k, &v);
+                                }
+                            }
+                            let hex_root = match tree.get_root_hash() {
+                                Some(h) => hex::encode(h),
+                                None => "0".repeat(64),
+                            };
+                            format!("HASH {} {} {}\r\n", start, end, hex_root)
+                        }
+                        Command::MerkleDump => {
+                            let mut tree = crate::store::merkle::MerkleTree::new();
+                            store.for_each_entry(&mut |k, v| tree.insert(k, v));
+                            let nodes = tree.preorder_nodes();
+                            let mut out = format!("NODES {}\r\n", nodes.len());
+                            for (depth, node) in nodes {
+                                out.push_str(&format!("NODE {} {}", depth, hex::encode(&node.hash)));
+                                if let Some(key) = &node.key {
+                                    out.push_str(&format!(" {}", key));
+                                }
+                                out.push_str("\r\n");
+                            }
+                            out
+                        }
+                        Command::MerkleBench => {
+                            let started = Instant::now();
+                            let mut entries = Vec::new();
+                            store.for_each_entry(&mut |k, v| entries.push((k.to_string(), v.to_string())));
+                            let keys = entries.len();
+                            let mut tree = crate::store::merkle::MerkleTree::new();
+                            tree.insert_batch(entries);
+                            let micros = started.elapsed().as_micros();
+                            let root = tree.get_root_hash().map_or_else(|| "EMPTY".to_string(), hex::encode);
+                            format!("MERKLE BENCH keys={} micros={} root={}\r\n", keys, micros, root)
+                        }
+                        Command::Verify => {
+                            let merkle = &databases[db].merkle;
+                            let generation = merkle.generation();
+                            let mut entries = Vec::new();
+                            store.for_each_entry(&mut |k, v| entries.push((k.to_string(), v.to_string())));
+                            let next_expiry_ms = entries.iter().filter_map(|(k, _)| store.expiry(k)).min();
+                            let mut tree = crate::store::merkle::MerkleTree::new();
+                            tree.insert_batch(entries);
+                            let root = tree.get_root_hash().cloned();
+                            let hex = root.as_ref().map_or_else(|| "EMPTY".to_string(), hex::encode);
+                            if merkle.replace(CachedRoot { generation, next_expiry_ms, root }) {
+                                warn!("VERIFY: cached Merkle root of database {} drifted from the store", db);
+                                format!("VERIFY repaired {}\r\n", hex)
+                            } else {
+                                format!("VERIFY ok {}\r\n", hex)
+                            }
+                        }
                         Command::Replicate { action } => {
                             match action {
                                 ReplicateAction::Enable => {
@@ -693,7 +2224,11 @@ impl Server {
                                         // Khởi động replicator mới
                                         match Replicator::new(cfg.as_ref()).await {
                                             Ok(r) => {
-                                                r.start_replication_handler(Arc::clone(&store)).await;
+                                                r.start_replication_handler(
+                                                    Arc::clone(&databases[0].store),
+                                                    Arc::clone(&databases[0].merkle.generation),
+                                                )
+                                                .await;
                                                 *g = Some(r);
                                                 "OK\r\n".to_string()
                                             }
@@ -718,217 +2253,666 @@ impl Server {
                                 }
                             }
                         }
+                        Command::Wait { replicas, timeout_ms } => {
+                            // Clone the replicator so the lock is not held while waiting
+                            let r = replicator.lock().await.clone();
+                            let acknowledged = match r {
+                                Some(r) => r.wait_for_replicas(replicas, Duration::from_millis(timeout_ms)).await,
+                                None => 0,
+                            };
+                            format!("WAIT {}\r\n", acknowledged)
+                        }
+                        Command::Watermark => {
+                            let watermark = match replicator.lock().await.as_ref() {
+                                Some(r) => r.watermark(),
+                                None => 0,
+                            };
+                            format!("WATERMARK {}\r\n", watermark)
+                        }
                         Command::Increment { key, amount } => {
-                            // Check if the key already exists
-                            let exists = { let store = store.lock().await; store.get(&key).is_some() };
-                            
-                            // If the key doesn't exist, create it with value 1 or the specified amount
-                            if !exists {
-                                let value = amount.unwrap_or(1).to_string();
-                                {
-                                    let store = store.lock().await;
-                                    match store.set(key.clone(), value.clone()) {
-                                        Ok(_) => {
-                                            let nv = value.parse().unwrap_or(1);
-                                            publishes.push(Publish::Incr(key.clone(), nv));
-                                            format!("VALUE {}\r\n", value)
+                            // The engine creates a missing key from 0 in the same step
+                            match store.increment(&key, amount) {
+                                Ok(new_value) => { access.touch_write(&key); publishes.push(Publish::Incr(key.clone(), new_value)); format!("VALUE {}\r\n", new_value) },
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::Decrement { key, amount } => {
+                            match store.decrement(&key, amount) {
+                                Ok(new_value) => { access.touch_write(&key); publishes.push(Publish::Decr(key.clone(), new_value)); format!("VALUE {}\r\n", new_value) },
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::IncrLimit { key, amount, limit } => {
+                            let res = store.increment_limit(&key, amount, limit);
+                            match res {
+                                Ok((new_value, allowed)) => {
+                                    access.touch_write(&key);
+                                    publishes.push(Publish::Incr(key.clone(), new_value));
+                                    let verdict = if allowed { "OK" } else { "LIMITED" };
+                                    format!("VALUE {} {}\r\n", new_value, verdict)
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::Append { key, value, len_only } => {
+                            let current_len = store.get(&key).map_or(0, |v| v.len());
+
+                            // Reject the write if the resulting value would exceed the limit
+                            if cfg.value_too_large(current_len + value.len()) {
+                                "ERROR value too large\r\n".to_string()
+                            } else {
+                                // APPEND creates the key when it is missing (like SET)
+                                let res = store.append(&key, &value);
+                                match res {
+                                    Ok(new_value) => {
+                                        access.touch_write(&key);
+                                        publishes.push(Publish::Append(key.clone(), new_value.clone()));
+                                        if len_only {
+                                            format!("LEN {}\r\n", new_value.len())
+                                        } else {
+                                            format!("VALUE {}\r\n", new_value)
                                         }
-                                        Err(e) => format!("ERROR {}\r\n", e),
                                     }
+                                    Err(e) => format!("ERROR {}\r\n", e),
                                 }
+                            }
+                        }
+                        Command::Prepend { key, value, len_only } => {
+                            let current_len = store.get(&key).map_or(0, |v| v.len());
+
+                            // Reject the write if the resulting value would exceed the limit
+                            if cfg.value_too_large(current_len + value.len()) {
+                                "ERROR value too large\r\n".to_string()
                             } else {
-                                // Otherwise, increment the existing value
-                                let res = { let store = store.lock().await; store.increment(&key, amount) };
+                                // PREPEND creates the key when it is missing (like SET)
+                                let res = store.prepend(&key, &value);
                                 match res {
-                                    Ok(new_value) => { publishes.push(Publish::Incr(key.clone(), new_value)); format!("VALUE {}\r\n", new_value) },
+                                    Ok(new_value) => {
+                                        access.touch_write(&key);
+                                        publishes.push(Publish::Prepend(key.clone(), new_value.clone()));
+                                        if len_only {
+                                            format!("LEN {}\r\n", new_value.len())
+                                        } else {
+                                            format!("VALUE {}\r\n", new_value)
+                                        }
+                                    }
                                     Err(e) => format!("ERROR {}\r\n", e),
                                 }
                             }
                         }
-                        Command::Decrement { key, amount } => {
-                            // Check if the key already exists
-                            let exists = { let store = store.lock().await; store.get(&key).is_some() };
-                            
-                            // If the key doesn't exist, create it with value -1 or the negative of the specified amount
-                            if !exists {
-                                let value = (-(amount.unwrap_or(1))).to_string();
-                                {
-                                    let store = store.lock().await;
-                                    match store.set(key.clone(), value.clone()) {
-                                        Ok(_) => { let v: i64 = value.parse().unwrap_or(-1); publishes.push(Publish::Decr(key.clone(), v)); format!("VALUE {}\r\n", value) },
-                                        Err(e) => format!("ERROR {}\r\n", e),
+                        Command::HSet { value, .. } if cfg.value_too_large(value.len()) => {
+                            "ERROR value too large\r\n".to_string()
+                        }
+                        Command::HSet { key, field, value } => {
+                            match store.hset(&key, &field, &value) {
+                                Ok(_) => {
+                                    access.touch_write(&key);
+                                    // Replicate the whole encoded hash so peers converge with LWW
+                                    if let Some(encoded) = store.get(&key) {
+                                        publishes.push(Publish::Set(key.clone(), encoded));
                                     }
+                                    "OK\r\n".to_string()
                                 }
-                            } else {
-                                // Otherwise, decrement the existing value
-                                let res = { let store = store.lock().await; store.decrement(&key, amount) };
-                                match res {
-                                    Ok(new_value) => { publishes.push(Publish::Decr(key.clone(), new_value)); format!("VALUE {}\r\n", new_value) },
-                                    Err(e) => format!("ERROR {}\r\n", e),
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::HGet { key, field } => {
+                            match store.hget(&key, &field) {
+                                Ok(Some(value)) => {
+                                    access.touch(&key);
+                                    format!("VALUE {}\r\n", value)
                                 }
+                                Ok(None) => "NOT_FOUND\r\n".to_string(),
+                                Err(e) => format!("ERROR {}\r\n", e),
                             }
                         }
-                        Command::Append { key, value } => {
-                            // Handle empty values for APPEND
-                            if value.is_empty() {
-                                let store = store.lock().await;
-                                match store.get(&key) {
-                                    Some(current_value) => format!("VALUE {}\r\n", current_value),
-                                    None => "ERROR Key not found\r\n".to_string(),
+                        Command::HGetAll { key } => {
+                            match store.hgetall(&key) {
+                                Ok(fields) if fields.is_empty() => "NOT_FOUND\r\n".to_string(),
+                                Ok(fields) => {
+                                    access.touch(&key);
+                                    let mut out = format!("VALUES {}\r\n", fields.len());
+                                    for (field, value) in fields {
+                                        out.push_str(&format!("{} {}\r\n", field, value));
+                                    }
+                                    out
                                 }
-                            } else {
-                                // Try to get the key first
-                                let current_value = { let store = store.lock().await; store.get(&key) };
-                                
-                                // If the key doesn't exist, create it with the value
-                                if current_value.is_none() {
-                                    let res = { let store = store.lock().await; store.set(key.clone(), value.clone()) };
-                                    match res {
-                                        Ok(_) => { publishes.push(Publish::Append(key.clone(), value.clone())); format!("VALUE {}\r\n", value) },
-                                        Err(e) => format!("ERROR {}\r\n", e),
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::HDel { key, fields } => {
+                            match store.hdel(&key, &fields) {
+                                Ok(removed) => {
+                                    if removed > 0 {
+                                        match store.get(&key) {
+                                            Some(encoded) => {
+                                                access.touch_write(&key);
+                                                publishes.push(Publish::Set(key.clone(), encoded));
+                                            }
+                                            None => {
+                                                access.remove(&key);
+                                                publishes.push(Publish::Delete(key.clone()));
+                                            }
+                                        }
                                     }
-                                } else {
-                                    // Otherwise, append to the existing value
-                                    let res = { let store = store.lock().await; store.append(&key, &value) };
-                                    match res {
-                                        Ok(new_value) => { publishes.push(Publish::Append(key.clone(), new_value.clone())); format!("VALUE {}\r\n", new_value) },
+                                    format!("DELETED {}\r\n", removed)
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::HLen { key } => {
+                            match store.hlen(&key) {
+                                Ok(n) => format!("HLEN {}\r\n", n),
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::DumpKey { key } => {
+                            let value = store.get(&key);
+                            match value {
+                                Some(v) => {
+                                    // Carry the last known write time so RESTORE can apply LWW
+                                    let ts = access.last_write(&key).unwrap_or_else(unix_nanos);
+                                    let ev = ChangeEvent::with_str_value(
+                                        1, OpKind::Set, key.as_str(), Some(&v), ts,
+                                        cfg.replication.client_id.as_str(), None, None,
+                                    );
+                                    match ev.to_bincode() {
+                                        Ok(bytes) => format!("DUMP {}\r\n", hex::encode(bytes)),
                                         Err(e) => format!("ERROR {}\r\n", e),
                                     }
                                 }
+                                None => "NOT_FOUND\r\n".to_string(),
                             }
                         }
-                        Command::Prepend { key, value } => {
-                            // Handle empty values for PREPEND
-                            if value.is_empty() {
-                                let store = store.lock().await;
-                                match store.get(&key) {
-                                    Some(current_value) => format!("VALUE {}\r\n", current_value),
-                                    None => "ERROR Key not found\r\n".to_string(),
+                        Command::RestoreKey { key, hex, replace } => {
+                            let decoded = hex::decode(&hex)
+                                .ok()
+                                .and_then(|bytes| ChangeEvent::from_bincode(&bytes).ok())
+                                .filter(|ev| ev.op == OpKind::Set)
+                                .and_then(|ev| Some((String::from_utf8(ev.val?).ok()?, ev.ts)));
+                            match decoded {
+                                None => "ERROR bad payload\r\n".to_string(),
+                                Some((value, _)) if cfg.value_too_large(value.len()) => {
+                                    "ERROR value too large\r\n".to_string()
                                 }
-                            } else {
-                                // Try to get the key first
-                                let current_value = { let store = store.lock().await; store.get(&key) };
-                                
-                                // If the key doesn't exist, create it with the value
-                                if current_value.is_none() {
-                                    let res = { let store = store.lock().await; store.set(key.clone(), value.clone()) };
-                                    match res {
-                                        Ok(_) => { publishes.push(Publish::Prepend(key.clone(), value.clone())); format!("VALUE {}\r\n", value) },
-                                        Err(e) => format!("ERROR {}\r\n", e),
-                                    }
-                                } else {
-                                    // Otherwise, prepend to the existing value
-                                    let res = { let store = store.lock().await; store.prepend(&key, &value) };
-                                    match res {
-                                        Ok(new_value) => { publishes.push(Publish::Prepend(key.clone(), new_value.clone())); format!("VALUE {}\r\n", new_value) },
-                                        Err(e) => format!("ERROR {}\r\n", e),
+                                Some((value, ts)) => {
+                                    let newer_local = store.get(&key).is_some()
+                                        && access.last_write(&key).is_some_and(|local| local > ts);
+                                    if newer_local && !replace {
+                                        "ERROR busykey\r\n".to_string()
+                                    } else {
+                                        match store.set(key.clone(), value.clone()) {
+                                            Ok(_) => {
+                                                access.touch_write_at(&key, ts);
+                                                publishes.push(Publish::Set(key.clone(), value));
+                                                "OK\r\n".to_string()
+                                            }
+                                            Err(e) => format!("ERROR {}\r\n", e),
+                                        }
                                     }
                                 }
                             }
                         }
-                        Command::MultiGet { keys } => {
-                            let store = store.lock().await;
-                            let mut response = String::new();
-                            let mut found_count = 0;
-                            
-                            for key in keys {
-                                match store.get(&key) {
-                                    Some(value) => {
-                                        response.push_str(&format!("{} {}\r\n", key, value));
-                                        found_count += 1;
-                                    }
-                                    None => {
-                                        response.push_str(&format!("{} NOT_FOUND\r\n", key));
+                        Command::ExpireTime { key, millis } => {
+                            let deadline = store.exists(&key).then(|| store.expiry(&key));
+                            let name = if millis { "PEXPIRETIME" } else { "EXPIRETIME" };
+                            match deadline {
+                                None => format!("{} -2\r\n", name),
+                                Some(None) => format!("{} -1\r\n", name),
+                                Some(Some(ms)) if millis => format!("{} {}\r\n", name, ms),
+                                Some(Some(ms)) => format!("{} {}\r\n", name, ms / 1000),
+                            }
+                        }
+                        Command::History { key, count } => {
+                            let history = &databases[db].history;
+                            if !history.enabled() {
+                                "ERROR key history is disabled (set key_history)\r\n".to_string()
+                            } else {
+                                let events = history.recent(&key, count.unwrap_or(usize::MAX));
+                                let mut out = format!("HISTORY {}\r\n", events.len());
+                                for ev in events {
+                                    out.push_str(&format!("{} {}", ev.ts, ev.op.name()));
+                                    if let Some(value) = ev.val {
+                                        out.push_str(&format!(" {}", String::from_utf8_lossy(&value)));
                                     }
+                                    out.push_str("\r\n");
                                 }
+                                out
                             }
-                            
-                            if found_count > 0 {
-                                format!("VALUES {}\r\n{}", found_count, response)
+                        }
+                        Command::HotKeys { count } => {
+                            let key_stats = &databases[db].key_stats;
+                            if !key_stats.enabled() {
+                                "ERROR key stats are disabled (set track_key_stats)\r\n".to_string()
                             } else {
-                                "NOT_FOUND\r\n".to_string()
+                                let keys = key_stats.top(count);
+                                let mut out = format!("HOTKEYS {}\r\n", keys.len());
+                                for (key, hits) in keys {
+                                    out.push_str(&format!("{} {}\r\n", key, hits));
+                                }
+                                out
                             }
                         }
-                        Command::MultiSet { pairs } => {
-                            let mut result = "OK\r\n".to_string();
-                            for (key, value) in pairs {
-                                let res = { let store = store.lock().await; store.set(key.clone(), value.clone()) };
-                                if let Err(e) = res {
-                                    result = format!("ERROR {}\r\n", e);
-                                    break;
+                        Command::Object { subcmd, key } => {
+                            let value = store.get(&key);
+                            match (value, subcmd) {
+                                (None, _) => "ERROR no such key\r\n".to_string(),
+                                (Some(v), ObjectSubcommand::Encoding) => {
+                                    let encoding = if v.parse::<i64>().is_ok() { "int" } else { "raw" };
+                                    format!("ENCODING {}\r\n", encoding)
+                                }
+                                (Some(_), ObjectSubcommand::Idletime) => {
+                                    format!("IDLETIME {}\r\n", access.idle_seconds(&key))
                                 }
-                                publishes.push(Publish::Set(key.clone(), value.clone()));
                             }
-                            result
                         }
-                        Command::Truncate => {
-                            let res = { let store = store.lock().await; store.truncate() };
+                        Command::Cas { new, .. } if cfg.value_too_large(new.len()) => {
+                            "ERROR value too large\r\n".to_string()
+                        }
+                        Command::Cas { key, expected, new } => {
+                            let res = store.compare_and_swap(&key, &expected, &new);
                             match res {
-                                Ok(_) => "OK\r\n".to_string(),
+                                Ok(true) => {
+                                    access.touch_write(&key);
+                                    // Only a successful swap is replicated
+                                    publishes.push(Publish::Set(key.clone(), new.clone()));
+                                    "OK\r\n".to_string()
+                                }
+                                Ok(false) => "CAS_MISMATCH\r\n".to_string(),
                                 Err(e) => format!("ERROR {}\r\n", e),
                             }
                         }
-                        Command::Stats => {
-                            format!("STATS\r\n{}", stats.format_stats())
-                        }
-                        Command::Info => {
-                            let mut info = String::new();
-                            
-                            // Server version from Cargo.toml
-                            info.push_str(&format!("version:{}\r\n", env!("CARGO_PKG_VERSION")));
-                            
-                            // Server uptime
-                            info.push_str(&format!("uptime_seconds:{}\r\n", stats.uptime_seconds()));
-                            info.push_str(&format!("uptime:{}\r\n", stats.uptime_human()));
-                            
-                            // Current time
-                            let now = SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap_or(Duration::from_secs(0))
-                                .as_secs();
-                            info.push_str(&format!("server_time_unix:{}\r\n", now));
-                            
-                            // Key count
-                            let key_count = { let store = store.lock().await; store.count_keys().unwrap_or(0) };
-                            info.push_str(&format!("db_keys:{}\r\n", key_count));
-                            
-                            format!("INFO\r\n{}", info)
+                        Command::GetVersioned { key } => {
+                            match store.get_versioned(&key) {
+                                Some((value, version)) => {
+                                    access.touch(&key);
+                                    format!("VALUE {} VERSION {}\r\n", value, version)
+                                }
+                                None => "NOT_FOUND\r\n".to_string(),
+                            }
                         }
-                        Command::Version => {
-                            // Return the server version from Cargo.toml
-                            format!("VERSION {}\r\n", env!("CARGO_PKG_VERSION"))
+                        Command::CasVersion { new, .. } if cfg.value_too_large(new.len()) => {
+                            "ERROR value too large\r\n".to_string()
                         }
-                        Command::Flushdb => {
-                            // Force sync to disk if the storage engine supports it
-                            let res = { let store = store.lock().await; store.truncate() };
+                        Command::CasVersion { key, expected, new } => {
+                            let res = store.set_if_version(&key, expected, new.clone());
                             match res {
-                                Ok(_) => "OK\r\n".to_string(),
+                                Ok(true) => {
+                                    access.touch_write(&key);
+                                    publishes.push(Publish::Set(key.clone(), new.clone()));
+                                    "OK\r\n".to_string()
+                                }
+                                Ok(false) => "VERSION_MISMATCH\r\n".to_string(),
                                 Err(e) => format!("ERROR {}\r\n", e),
                             }
                         }
-                        Command::Shutdown => {
-                            // Send OK response before shutting down
-                            let response = "OK\r\n".to_string();
-                            if let Err(e) = write_half.write_all(response.as_bytes()).await {
-                                error!("Error writing to client {}: {}", addr, e);
-                            }
-                            
-                            // Log shutdown request
-                            info!("Shutdown requested by client {}", addr);
-                            
-                            // Exit the process gracefully
-                            // Note: In a production system, we would want to do a more graceful
-                            // shutdown, such as closing all connections, flushing data to disk, etc.
-                            std::process::exit(0);
-                        }
-                    };
-                    // Perform publishes after the store operations (lock released)
-                    let guard = replicator.lock().await;
-                    if let Some(r) = guard.as_ref() {
-                        for p in publishes {
+                        Command::MultiGet { keys, compact } => {
+                            // Hash and raw-byte keys are not plain strings; report them as missing
+                            let fetch = |key: &str| {
+                                let value = store
+                                    .get(key)
+                                    .filter(|v| !hash_value::is_hash(v) && !binary_value::is_binary(v));
+                                if value.is_some() {
+                                    access.touch(key);
+                                }
+                                value
+                            };
+                            // Values go straight into the response as they are fetched, so
+                            // only one value is held outside the buffer at a time
+                            let mut response = String::with_capacity(keys.iter().map(|k| k.len() + 16).sum());
+
+                            if compact {
+                                // One line per requested key, then which of them exist
+                                response.push_str(&format!("VALUES {}\r\n", keys.len()));
+                                let mut present = String::with_capacity(keys.len());
+                                for key in &keys {
+                                    let value = fetch(key);
+                                    response.push_str(value.as_deref().unwrap_or(""));
+                                    response.push_str("\r\n");
+                                    present.push(if value.is_some() { '1' } else { '0' });
+                                }
+                                response.push_str(&format!("PRESENT {}\r\n", present));
+                                response
+                            } else {
+                                let mut found_count = 0;
+                                for key in &keys {
+                                    response.push_str(key);
+                                    match fetch(key) {
+                                        Some(value) => {
+                                            response.push(' ');
+                                            response.push_str(&value);
+                                            found_count += 1;
+                                        }
+                                        None => response.push_str(" NOT_FOUND"),
+                                    }
+                                    response.push_str("\r\n");
+                                }
+
+                                if found_count > 0 {
+                                    // The count is only known now; prepend it in place
+                                    response.insert_str(0, &format!("VALUES {}\r\n", found_count));
+                                    response
+                                } else {
+                                    "NOT_FOUND\r\n".to_string()
+                                }
+                            }
+                        }
+                        Command::MultiSet { pairs } if pairs.iter().any(|(_, v)| cfg.value_too_large(v.len())) => {
+                            "ERROR value too large\r\n".to_string()
+                        }
+                        Command::MultiSet { pairs } => {
+                            // All-or-nothing: events are published only after the whole batch is stored
+                            let res = store.set_many(&pairs);
+                            match res {
+                                Ok(()) => {
+                                    for (key, value) in pairs {
+                                        access.touch_write(&key);
+                                        publishes.push(Publish::Set(key, value));
+                                    }
+                                    "OK\r\n".to_string()
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::MultiSetEx { entries } if entries.iter().any(|(_, _, v)| cfg.value_too_large(v.len())) => {
+                            "ERROR value too large\r\n".to_string()
+                        }
+                        Command::MultiSetEx { entries } => {
+                            // Every deadline is taken from the same instant so the batch lands as one
+                            let now = expiry::now_millis();
+                            let batch: Vec<(String, String, Option<u64>)> = entries
+                                .iter()
+                                .map(|(key, ttl_ms, value)| {
+                                    (key.clone(), value.clone(), (*ttl_ms > 0).then(|| now.saturating_add(*ttl_ms)))
+                                })
+                                .collect();
+                            match store.set_many_with_expiry(&batch) {
+                                Ok(()) => {
+                                    for (key, ttl_ms, value) in entries {
+                                        access.touch_write(&key);
+                                        publishes.push(match ttl_ms {
+                                            0 => Publish::Set(key, value),
+                                            ttl_ms => Publish::SetEx(key, value, ttl_ms / 1000),
+                                        });
+                                    }
+                                    "OK\r\n".to_string()
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::Export => {
+                            // Lines are streamed once the (empty) reply is written
+                            let mut keys = store.keys();
+                            keys.sort();
+                            export_keys = Some(keys);
+                            String::new()
+                        }
+                        Command::Import { pairs } if pairs.iter().any(|(_, v)| cfg.value_too_large(v.len())) => {
+                            "ERROR value too large\r\n".to_string()
+                        }
+                        Command::Import { pairs } => {
+                            // All-or-nothing, like MSET
+                            let res = store.set_many(&pairs);
+                            match res {
+                                Ok(()) => {
+                                    let count = pairs.len();
+                                    for (key, value) in pairs {
+                                        access.touch_write(&key);
+                                        publishes.push(Publish::Set(key, value));
+                                    }
+                                    format!("IMPORTED {}\r\n", count)
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::Truncate => {
+                            let res = store.truncate();
+                            match res {
+                                Ok(_) => {
+                                    access.clear();
+                                    databases[db].key_stats.clear();
+                                    publishes.push(Publish::Clear);
+                                    "OK\r\n".to_string()
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::Stats => {
+                            format!("STATS\r\n{}", stats.format_stats())
+                        }
+                        Command::StatsReset => {
+                            stats.reset();
+                            "OK\r\n".to_string()
+                        }
+                        Command::SlowlogGet { count } => {
+                            let entries = slowlog.get(count);
+                            let mut out = format!("SLOWLOG {}\r\n", entries.len());
+                            for e in entries {
+                                out.push_str(&format!(
+                                    "id={} time={} client_id={} duration_us={} cmd={}\r\n",
+                                    e.id, e.unix_time, e.client_id, e.duration_us, e.command
+                                ));
+                            }
+                            out
+                        }
+                        Command::SlowlogReset => {
+                            slowlog.reset();
+                            "OK\r\n".to_string()
+                        }
+                        Command::DebugSleep { .. } if !cfg.enable_debug => "ERROR DEBUG is disabled\r\n".to_string(),
+                        Command::DebugSleep { millis } => {
+                            debug_sleep = Some(Duration::from_millis(millis));
+                            "OK\r\n".to_string()
+                        }
+                        Command::Info => {
+                            let mut info = String::new();
+                            
+                            // Server version from Cargo.toml
+                            info.push_str(&format!("version:{}\r\n", env!("CARGO_PKG_VERSION")));
+                            
+                            // Server uptime
+                            info.push_str(&format!("uptime_seconds:{}\r\n", stats.uptime_seconds()));
+                            info.push_str(&format!("uptime:{}\r\n", stats.uptime_human()));
+                            
+                            // Current time
+                            let now = SystemTime::now()
+                                .duration_since(UNIX_EPOCH)
+                                .unwrap_or(Duration::from_secs(0))
+                                .as_secs();
+                            info.push_str(&format!("server_time_unix:{}\r\n", now));
+                            
+                            // Keyspace: counts and sizes from one pass over the store
+                            let keyspace = store.keyspace_stats();
+                            info.push_str(&format!("db_keys:{}\r\n", keyspace.keys));
+                            info.push_str(&format!("db_memory_bytes:{}\r\n", keyspace.memory_bytes()));
+                            info.push_str(&format!("avg_value_bytes:{}\r\n", keyspace.avg_value_bytes()));
+                            let largest = keyspace.largest.as_ref().map(|(k, _)| k.as_str()).unwrap_or_default();
+                            info.push_str(&format!("largest_key:{}\r\n", largest));
+                            info.push_str(&format!("sizes:{}\r\n", keyspace.size_histogram_line()));
+                            
+                            format!("INFO\r\n{}", info)
+                        }
+                        Command::ConfigGet { param } => match runtime.read().unwrap().get(&param) {
+                            Ok(value) => format!("CONFIG {} {}\r\n", param, value),
+                            Err(e) => format!("ERROR {}\r\n", e),
+                        },
+                        Command::ConfigSet { param, value } => match runtime.write().unwrap().set(&param, &value) {
+                            Ok(()) => "OK\r\n".to_string(),
+                            Err(e) => format!("ERROR {}\r\n", e),
+                        },
+                        Command::ConfigAll => {
+                            let runtime = runtime.read().unwrap();
+                            let settings = cfg.settings();
+                            let mut reply = format!("CONFIGALL {}\r\n", settings.len());
+                            for (name, value) in settings {
+                                // Report what CONFIG SET changed, not the startup value
+                                let value = if RuntimeConfig::PARAMS.contains(&name.as_str()) {
+                                    runtime.get(&name).unwrap_or(value)
+                                } else {
+                                    value
+                                };
+                                reply.push_str(&format!("{} {}\r\n", name, value));
+                            }
+                            reply
+                        }
+                        Command::EngineInfo => engine.response(),
+                        Command::NodeInfo => {
+                            // A running replicator knows what it publishes with;
+                            // otherwise report what enabling it would use
+                            let (node_id, topic_prefix, codec, enabled) = match replicator.lock().await.as_ref() {
+                                Some(r) => (r.node_id().to_string(), r.topic_prefix().to_string(), r.codec(), true),
+                                None => (
+                                    cfg.replication.client_id.clone(),
+                                    cfg.replication.topic_prefix.clone(),
+                                    crate::replication::DEFAULT_CODEC,
+                                    false,
+                                ),
+                            };
+                            format!(
+                                "NODE node_id={} topic_prefix={} codec={} replication_enabled={}\r\n",
+                                node_id,
+                                topic_prefix,
+                                codec.name(),
+                                enabled
+                            )
+                        }
+                        Command::Health => {
+                            let mut degraded = Vec::new();
+                            let mut storage_ok = true;
+                            for database in databases.iter() {
+                                if let Err(e) = database.store.sync() {
+                                    warn!("HEALTH: storage flush failed: {}", e);
+                                    storage_ok = false;
+                                }
+                            }
+                            if !storage_ok {
+                                degraded.push("storage");
+                            }
+                            if replicator.lock().await.as_ref().is_some_and(|r| !r.is_connected()) {
+                                degraded.push("replication");
+                            }
+                            if degraded.is_empty() {
+                                format!("HEALTH ok uptime={}\r\n", stats.uptime_seconds())
+                            } else {
+                                format!("HEALTH degraded {} uptime={}\r\n", degraded.join(","), stats.uptime_seconds())
+                            }
+                        }
+                        Command::Hello { proto, line_ending: requested, compress } => {
+                            if proto != PROTOCOL_VERSION {
+                                "ERROR unsupported protocol\r\n".to_string()
+                            } else {
+                                // The reply itself already uses the requested line ending
+                                if let Some(requested) = requested {
+                                    line_ending = requested;
+                                }
+                                if compress {
+                                    compress_threshold = cfg.response_compress_threshold_bytes;
+                                }
+                                let mut hello = String::new();
+                                hello.push_str(&format!("proto:{}\r\n", proto));
+                                hello.push_str(&format!("version:{}\r\n", env!("CARGO_PKG_VERSION")));
+                                hello.push_str(&format!("engine:{}\r\n", cfg.engine));
+                                hello.push_str(&format!("commands:{}\r\n", SUPPORTED_COMMANDS.join(",")));
+                                if compress {
+                                    let algorithm = if compress_threshold == 0 { "none" } else { compressed_value::ALGORITHM };
+                                    hello.push_str(&format!("compress:{}\r\n", algorithm));
+                                }
+                                format!("HELLO\r\n{}", hello)
+                            }
+                        }
+                        Command::CommandList => {
+                            let mut reply = format!("COMMANDS {}\r\n", COMMAND_TABLE.len());
+                            for spec in COMMAND_TABLE {
+                                let max_args = spec.max_args.map_or_else(|| "*".to_string(), |n| n.to_string());
+                                let flags: Vec<&str> = spec.flags.iter().map(|f| f.as_str()).collect();
+                                reply.push_str(&format!(
+                                    "{} {} {} {}\r\n",
+                                    spec.name,
+                                    spec.min_args,
+                                    max_args,
+                                    flags.join(",")
+                                ));
+                            }
+                            reply
+                        }
+                        Command::Version => {
+                            // Return the server version from Cargo.toml
+                            format!("VERSION {}\r\n", env!("CARGO_PKG_VERSION"))
+                        }
+                        Command::FSync => match store.sync() {
+                            Ok(()) => "OK\r\n".to_string(),
+                            Err(e) => format!("ERROR {}\r\n", e),
+                        },
+                        Command::Compact => match store.compact() {
+                            Ok(()) => "OK\r\n".to_string(),
+                            Err(e) => format!("ERROR {}\r\n", e),
+                        },
+                        Command::Flushdb => {
+                            // Force sync to disk if the storage engine supports it
+                            let res = store.truncate();
+                            match res {
+                                Ok(_) => {
+                                    access.clear();
+                                    databases[db].key_stats.clear();
+                                    publishes.push(Publish::Clear);
+                                    "OK\r\n".to_string()
+                                }
+                                Err(e) => format!("ERROR {}\r\n", e),
+                            }
+                        }
+                        Command::Shutdown => {
+                            // Same path as SIGTERM; this connection closes on its next read
+                            info!("Shutdown requested by client {}", addr);
+                            shutdown.send_replace(true);
+                            "OK\r\n".to_string()
+                        }
+                        Command::Monitor if !cfg.enable_monitor => "ERROR MONITOR is disabled\r\n".to_string(),
+                        Command::Monitor => {
+                            // Subscribe before replying so no command after the OK is missed
+                            monitor_feed = Some(monitor.subscribe());
+                            info!("Client {} started MONITOR", addr);
+                            "OK\r\n".to_string()
+                        }
+                    };
+                    touched.iter().for_each(|merkle| merkle.touch());
+                    drop(key_limit_guard);
+                    drop((shared_gate, exclusive_gates));
+                    // Outside the gate, so a queued MOVE or HASH cannot stall the database behind it
+                    if let Some(pause) = debug_sleep {
+                        tokio::time::sleep(pause).await;
+                    }
+
+                    let elapsed = started.elapsed();
+                    stats.record_latency(&command, elapsed);
+                    if cfg.slowlog_threshold_ms != 0
+                        && elapsed >= Duration::from_millis(cfg.slowlog_threshold_ms)
+                        && !matches!(command, Command::Auth { .. })
+                    {
+                        slowlog.record(client_meta.id, request_line.trim_end(), elapsed);
+                    }
+
+                    let history = &databases[db].history;
+                    if history.enabled() && !matches!(command, Command::Move { .. }) {
+                        let ts = unix_nanos();
+                        for p in &publishes {
+                            history.record(p.event(&cfg.replication.client_id, ts));
+                        }
+                    }
+
+                    // Replication covers database 0 only; MOVE queues just the events that touch it
+                    if db != 0 && !matches!(command, Command::Move { .. }) {
+                        publishes.clear();
+                    }
+
+                    // Perform publishes after the store operations (lock released)
+                    let guard = replicator.lock().await;
+                    if let Some(r) = guard.as_ref() {
+                        for p in publishes {
                             match p {
                                 Publish::Set(k, v)      => { let _ = r.publish_set(&k, &v).await; }
+                                Publish::SetEx(k, v, t)  => { let _ = r.publish_set_with_ttl(&k, &v, t).await; }
                                 Publish::Delete(k)       => { let _ = r.publish_delete(&k).await; }
+                                Publish::Clear           => { let _ = r.publish_clear().await; }
                                 Publish::Incr(k, nv)     => { let _ = r.publish_incr(&k, nv).await; }
                                 Publish::Decr(k, nv)     => { let _ = r.publish_decr(&k, nv).await; }
                                 Publish::Append(k, nv)   => { let _ = r.publish_append(&k, &nv).await; }
@@ -936,17 +2920,44 @@ impl Server {
                             }
                         }
                     }
-                    
+                    drop(guard);
+
+                    if cfg.command_log {
+                        let name = request_line.split_whitespace().next().unwrap_or("").to_uppercase();
+                        info!(
+                            "{}",
+                            command_log_line(client_meta.id, &name, &command, &response, cfg.command_log_values)
+                        );
+                    }
+
                     // Send response back to client
-                    if let Err(e) = write_half.write_all(response.as_bytes()).await {
+                    if let Err(e) = write_half.write_all(line_ending.frame(&response).as_bytes()).await {
                         error!("Error writing to client {}: {}", addr, e);
                         break;
                     }
+                    if let Some(bytes) = binary_payload {
+                        let mut framed = bytes;
+                        framed.extend_from_slice(line_ending.as_str().as_bytes());
+                        if let Err(e) = write_half.write_all(&framed).await {
+                            error!("Error writing to client {}: {}", addr, e);
+                            break;
+                        }
+                    }
+                    if let Some(keys) = export_keys {
+                        if let Err(e) = stream_export(&mut write_half, store, keys, line_ending).await {
+                            error!("Error writing to client {}: {}", addr, e);
+                            break;
+                        }
+                    }
+                    if let Some(feed) = monitor_feed {
+                        stream_monitor(&mut reader, &mut write_half, feed, &mut shutdown_rx, &client_meta, line_ending).await;
+                        break;
+                    }
                 }
                 Err(e) => {
                     // Send error response for invalid commands
                     let error_msg = format!("ERROR {}\r\n", e);
-                    if let Err(e) = write_half.write_all(error_msg.as_bytes()).await {
+                    if let Err(e) = write_half.write_all(line_ending.frame(&error_msg).as_bytes()).await {
                         error!("Error writing to client {}: {}", addr, e);
                         break;
                     }
@@ -957,3 +2968,2836 @@ impl Server {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::kv_trait::VALUE_SIZE_BUCKETS;
+    use crate::store::{KvEngine, RwLockEngine, SledEngine};
+    use tempfile::tempdir;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::tcp::OwnedReadHalf;
+    use tokio::net::tcp::OwnedWriteHalf;
+
+    /// A minimal line-oriented client used to drive a real server over TCP.
+    struct TestClient {
+        reader: BufReader<OwnedReadHalf>,
+        writer: OwnedWriteHalf,
+    }
+
+    impl TestClient {
+        async fn connect(port: u16) -> Self {
+            let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            let (read_half, writer) = stream.into_split();
+            Self { reader: BufReader::new(read_half), writer }
+        }
+
+        /// Send a raw command line (CRLF is appended).
+        async fn send(&mut self, cmd: &str) {
+            self.writer.write_all(format!("{}\r\n", cmd).as_bytes()).await.unwrap();
+        }
+
+        /// Read one response line without the trailing CRLF.
+        async fn read_line(&mut self) -> String {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).await.unwrap();
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+
+        /// Send a command and return the first response line.
+        async fn request(&mut self, cmd: &str) -> String {
+            self.send(cmd).await;
+            self.read_line().await
+        }
+    }
+
+    fn test_config(engine: &str, storage_path: &str) -> Config {
+        let mut config = Config::default();
+        config.host = "127.0.0.1".to_string();
+        config.port = free_port();
+        config.engine = engine.to_string();
+        config.storage_path = storage_path.to_string();
+        config
+    }
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    /// Spawn a server in the background and wait until it accepts connections.
+    async fn start_server(config: Config, store: Box<dyn KVEngineStoreTrait + Send + Sync>) -> u16 {
+        let port = config.port;
+        tokio::spawn(Server::new(config, store).run_until(std::future::pending()));
+        wait_until_listening(port).await;
+        port
+    }
+
+    async fn wait_until_listening(port: u16) {
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("server did not start on port {}", port);
+    }
+
+    async fn start_rwlock_server(config: Config) -> u16 {
+        start_server(config, Box::new(RwLockEngine::new("").unwrap())).await
+    }
+
+    #[tokio::test]
+    async fn test_hello_reports_configured_engine() {
+        let dir = tempdir().unwrap();
+        let sled_path = dir.path().join("sled");
+        let sled_path = sled_path.to_str().unwrap();
+
+        let cases: Vec<(&str, Box<dyn KVEngineStoreTrait + Send + Sync>)> = vec![
+            ("rwlock", Box::new(RwLockEngine::new("").unwrap())),
+            ("kv", Box::new(KvEngine::new("").unwrap())),
+            ("sled", Box::new(SledEngine::new(sled_path).unwrap())),
+        ];
+
+        for (engine, store) in cases {
+            let port = start_server(test_config(engine, sled_path), store).await;
+            let mut client = TestClient::connect(port).await;
+
+            assert_eq!(client.request("HELLO").await, "HELLO");
+            assert_eq!(client.read_line().await, format!("proto:{}", PROTOCOL_VERSION));
+            assert_eq!(client.read_line().await, format!("version:{}", env!("CARGO_PKG_VERSION")));
+            assert_eq!(client.read_line().await, format!("engine:{}", engine));
+            let commands = client.read_line().await;
+            assert!(commands.starts_with("commands:"));
+            assert!(commands.split(',').any(|c| c == "HELLO"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_engine_reports_backend() {
+        let dir = tempdir().unwrap();
+        let sled_path = dir.path().join("sled");
+        let sled_path = sled_path.to_str().unwrap();
+
+        let cases: Vec<(&str, Box<dyn KVEngineStoreTrait + Send + Sync>, String)> = vec![
+            ("rwlock", Box::new(RwLockEngine::new("").unwrap()), "ENGINE rwlock persistent=no".to_string()),
+            ("kv", Box::new(KvEngine::new("").unwrap()), "ENGINE kv persistent=no".to_string()),
+            (
+                "sled",
+                Box::new(SledEngine::new(sled_path).unwrap()),
+                format!("ENGINE sled persistent=yes path={}", sled_path),
+            ),
+        ];
+
+        for (engine, store, expected) in cases {
+            let port = start_server(test_config(engine, sled_path), store).await;
+            let mut client = TestClient::connect(port).await;
+            assert_eq!(client.request("ENGINE").await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_lists_arity_and_flags() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        for request in ["COMMAND", "COMMAND DOCS"] {
+            assert_eq!(client.request(request).await, format!("COMMANDS {}", COMMAND_TABLE.len()));
+            let mut lines = Vec::new();
+            for _ in 0..COMMAND_TABLE.len() {
+                lines.push(client.read_line().await);
+            }
+            assert!(lines.contains(&"GET 1 1 readonly".to_string()));
+            assert!(lines.contains(&"SET 2 * write".to_string()));
+            assert!(lines.contains(&"SYNC 1 * write,admin".to_string()));
+            assert!(lines.contains(&"CONFIG 1 3 admin".to_string()));
+        }
+        assert_eq!(client.request("PING after").await, "PONG after");
+    }
+
+    #[tokio::test]
+    async fn test_hello_compress_sends_large_values_compressed() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        let large = "merkle-tree;".repeat(500);
+        assert_eq!(client.request(&format!("SET big {}", large)).await, "OK");
+        assert_eq!(client.request("SET small tiny").await, "OK");
+
+        // Clients that did not negotiate compression get plain values
+        assert_eq!(client.request("GET big").await, format!("VALUE {}", large));
+
+        assert_eq!(client.request(&format!("HELLO {} COMPRESS", PROTOCOL_VERSION)).await, "HELLO");
+        for _ in 0..4 {
+            client.read_line().await;
+        }
+        assert_eq!(client.read_line().await, "compress:zstd");
+
+        let header = client.request("GET big").await;
+        let len: usize = header.strip_prefix("VALUEZ zstd ").unwrap().parse().unwrap();
+        assert!(len < large.len());
+        let mut body = vec![0u8; len + 2];
+        client.reader.read_exact(&mut body).await.unwrap();
+        assert_eq!(&body[len..], b"\r\n");
+        // A standard zstd frame, not something only this server can read
+        assert_eq!(zstd::stream::decode_all(&body[..len]).unwrap(), large.as_bytes());
+
+        // Values under the threshold stay plain
+        assert_eq!(client.request("GET small").await, "VALUE tiny");
+    }
+
+    #[tokio::test]
+    async fn test_hello_unsupported_protocol() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("HELLO 99").await, "ERROR unsupported protocol");
+        // The connection stays usable after the rejection
+        assert_eq!(client.request(&format!("HELLO {}", PROTOCOL_VERSION)).await, "HELLO");
+    }
+
+    #[tokio::test]
+    async fn test_wait_without_replicas_times_out() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("WAIT 1 1000").await, "WAIT 0");
+
+        // Replication on, but no replica ever acknowledges
+        let mut config = test_config("rwlock", "");
+        config.replication.enabled = true;
+        config.replication.mqtt_broker = "127.0.0.1".to_string();
+        config.replication.mqtt_port = free_port();
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET user:1 alice").await, "OK");
+        let started = Instant::now();
+        assert_eq!(client.request("WAIT 1 200").await, "WAIT 0");
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert_eq!(client.request("WAIT 0 0").await, "WAIT 0");
+    }
+
+    #[tokio::test]
+    async fn test_hello_negotiates_lf_line_ending() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        async fn raw_line(client: &mut TestClient) -> String {
+            let mut line = String::new();
+            client.reader.read_line(&mut line).await.unwrap();
+            line
+        }
+
+        client.send("GET missing").await;
+        assert_eq!(raw_line(&mut client).await, "NOT_FOUND\r\n");
+
+        // Requests keep working with either terminator
+        client.writer.write_all(format!("HELLO {} LF\n", PROTOCOL_VERSION).as_bytes()).await.unwrap();
+        for _ in 0..5 {
+            assert!(!raw_line(&mut client).await.contains('\r'));
+        }
+        client.send("SET user:1 alice").await;
+        assert_eq!(raw_line(&mut client).await, "OK\n");
+        client.writer.write_all(b"GET user:1\n").await.unwrap();
+        assert_eq!(raw_line(&mut client).await, "VALUE alice\n");
+        client.send("BOGUS").await;
+        let error = raw_line(&mut client).await;
+        assert!(error.starts_with("ERROR") && !error.contains('\r'));
+
+        // Switching back restores CRLF
+        client.send("HELLO 1 CRLF").await;
+        assert_eq!(raw_line(&mut client).await, "HELLO\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_max_value_bytes_rejects_oversized_values() {
+        let mut config = test_config("rwlock", "");
+        config.max_value_bytes = 8;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        // Just under and exactly at the limit are accepted
+        assert_eq!(client.request("SET k 1234567").await, "OK");
+        assert_eq!(client.request("SET k 12345678").await, "OK");
+
+        // Just over the limit is rejected, but the connection stays open
+        assert_eq!(client.request("SET k 123456789").await, "ERROR value too large");
+        assert_eq!(client.request("GET k").await, "VALUE 12345678");
+
+        // APPEND/PREPEND are checked against the resulting value
+        assert_eq!(client.request("APPEND k 9").await, "ERROR value too large");
+        assert_eq!(client.request("PREPEND k 0").await, "ERROR value too large");
+        assert_eq!(client.request("MSET a 1 b 123456789").await, "ERROR value too large");
+        assert_eq!(client.request("GET a").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_line_keeps_the_connection() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET k before").await, "OK");
+
+        let big = format!("SET k {}", "x".repeat(2 * MAX_LINE_BYTES));
+        assert_eq!(client.request(&big).await, "ERROR line too long");
+        // The rest of the long line is not mistaken for a command
+        assert_eq!(client.request("GET k").await, "VALUE before");
+        assert_eq!(client.request("SET k after").await, "OK");
+        assert_eq!(client.request("GET k").await, "VALUE after");
+    }
+
+    #[tokio::test]
+    async fn test_max_key_bytes_rejects_long_keys() {
+        let mut config = test_config("rwlock", "");
+        config.max_key_bytes = 4;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET abcd v").await, "OK");
+        assert_eq!(client.request("SET abcde v").await, "ERROR key too long");
+        assert_eq!(client.request("GET abcd").await, "VALUE v");
+        assert_eq!(client.request("GET abcde").await, "ERROR key too long");
+        assert_eq!(client.request("INC abc").await, "VALUE 1");
+        assert_eq!(client.request("INC abcde").await, "ERROR key too long");
+        assert_eq!(client.request("APPEND abcde x").await, "ERROR key too long");
+        assert_eq!(client.request("MSET a 1 abcde 2").await, "ERROR key too long");
+        assert_eq!(client.request("GET a").await, "NOT_FOUND");
+
+        // A rejected BSET still consumes its payload
+        client.send("BSET abcde 3").await;
+        client.send("xyz").await;
+        assert_eq!(client.read_line().await, "ERROR key too long");
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 2");
+    }
+
+    #[tokio::test]
+    async fn test_append_prepend_create_missing_keys() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("APPEND a tail").await, "VALUE tail");
+        assert_eq!(client.request("PREPEND p head").await, "VALUE head");
+        assert_eq!(client.request("APPEND a !").await, "VALUE tail!");
+        assert_eq!(client.request("GET p").await, "VALUE head");
+    }
+
+    #[tokio::test]
+    async fn test_inc_dec_on_missing_keys_match_existing_zero() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET zero_inc 0").await, "OK");
+        assert_eq!(client.request("SET zero_dec 0").await, "OK");
+
+        for (missing, existing) in [("new_inc", "zero_inc"), ("new_dec", "zero_dec")] {
+            let op = if missing == "new_inc" { "INC" } else { "DEC" };
+            for amount in ["", " 5", " -3"] {
+                let created = client.request(&format!("{} {}{}", op, missing, amount)).await;
+                let updated = client.request(&format!("{} {}{}", op, existing, amount)).await;
+                assert_eq!(created, updated, "{}{}", op, amount);
+            }
+            assert_eq!(client.request(&format!("GET {}", missing)).await, client.request(&format!("GET {}", existing)).await);
+        }
+        assert_eq!(client.request("INC fresh 5").await, "VALUE 5");
+        assert_eq!(client.request("DEC other 5").await, "VALUE -5");
+    }
+
+    #[tokio::test]
+    async fn test_exists_verbose_reports_per_key_flags() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET a 1").await, "OK");
+        assert_eq!(client.request("SET c 3").await, "OK");
+
+        // Plain EXISTS keeps returning only the count
+        assert_eq!(client.request("EXISTS a b c").await, "EXISTS 2");
+
+        assert_eq!(client.request("EXISTS a b c d VERBOSE").await, "EXISTS 2");
+        assert_eq!(client.read_line().await, "a 1");
+        assert_eq!(client.read_line().await, "b 0");
+        assert_eq!(client.read_line().await, "c 1");
+        assert_eq!(client.read_line().await, "d 0");
+    }
+
+    #[tokio::test]
+    async fn test_delete_verbose_reports_missing_keys() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET a 1").await, "OK");
+        assert_eq!(client.request("SET c 3").await, "OK");
+
+        assert_eq!(client.request("DEL a b c d VERBOSE").await, "DELETED 2");
+        assert_eq!(client.read_line().await, "a 1");
+        assert_eq!(client.read_line().await, "b 0");
+        assert_eq!(client.read_line().await, "c 1");
+        assert_eq!(client.read_line().await, "d 0");
+        assert_eq!(client.request("EXISTS a c").await, "EXISTS 0");
+
+        // Single-key DEL keeps its original replies
+        assert_eq!(client.request("DEL a").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_cas_match_and_mismatch() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("CAS k \"\" 1").await, "OK");
+        assert_eq!(client.request("CAS k \"\" 1").await, "CAS_MISMATCH");
+        assert_eq!(client.request("CAS k 1 2").await, "OK");
+        assert_eq!(client.request("CAS k 1 3").await, "CAS_MISMATCH");
+        assert_eq!(client.request("GET k").await, "VALUE 2");
+    }
+
+    #[tokio::test]
+    async fn test_object_encoding_and_idletime() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET num 100").await, "OK");
+        assert_eq!(client.request("SET text hello").await, "OK");
+        assert_eq!(client.request("OBJECT ENCODING num").await, "ENCODING int");
+        assert_eq!(client.request("OBJECT ENCODING text").await, "ENCODING raw");
+        assert_eq!(client.request("OBJECT ENCODING missing").await, "ERROR no such key");
+        assert_eq!(client.request("OBJECT IDLETIME missing").await, "ERROR no such key");
+
+        assert_eq!(client.request("OBJECT IDLETIME num").await, "IDLETIME 0");
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let idle = client.request("OBJECT IDLETIME num").await;
+        let secs: u64 = idle.strip_prefix("IDLETIME ").unwrap().parse().unwrap();
+        assert!(secs >= 1, "{}", idle);
+
+        // Reading the key resets its idle time; OBJECT itself does not
+        assert_eq!(client.request("GET num").await, "VALUE 100");
+        assert_eq!(client.request("OBJECT IDLETIME num").await, "IDLETIME 0");
+    }
+
+    #[tokio::test]
+    async fn test_background_sync_converges_without_client_sync() {
+        let port_a = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut a = TestClient::connect(port_a).await;
+        assert_eq!(a.request("SET k1 v1").await, "OK");
+        assert_eq!(a.request("SET k2 v2").await, "OK");
+
+        let mut config_b = test_config("rwlock", "");
+        config_b.sync.enabled = true;
+        config_b.sync.interval_secs = 1;
+        config_b.sync.peers = vec![format!("127.0.0.1:{}", port_a)];
+        let port_b = start_rwlock_server(config_b).await;
+        let mut b = TestClient::connect(port_b).await;
+        assert_eq!(b.request("SET stale x").await, "OK");
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            if b.request("GET k1").await == "VALUE v1"
+                && b.request("GET k2").await == "VALUE v2"
+                && b.request("GET stale").await == "NOT_FOUND"
+            {
+                break;
+            }
+            assert!(tokio::time::Instant::now() < deadline, "node B did not converge");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hash_buckets() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        async fn buckets(client: &mut TestClient, n: usize) -> Vec<String> {
+            assert_eq!(client.request(&format!("HASH BUCKETS {}", n)).await, format!("BUCKETS {}", n));
+            let mut lines = Vec::new();
+            for i in 0..n {
+                let line = client.read_line().await;
+                let (idx, hex) = line.split_once(' ').unwrap();
+                assert_eq!(idx, i.to_string());
+                assert_eq!(hex.len(), 64);
+                lines.push(hex.to_string());
+            }
+            lines
+        }
+
+        let empty = buckets(&mut client, 4).await;
+        assert!(empty.iter().all(|h| h == &"0".repeat(64)));
+
+        for i in 0..20 {
+            assert_eq!(client.request(&format!("SET k{} v{}", i, i)).await, "OK");
+        }
+        let before = buckets(&mut client, 4).await;
+        assert_eq!(client.request("SET k7 changed").await, "OK");
+        let after = buckets(&mut client, 4).await;
+        let flipped = (0..4).filter(|&i| before[i] != after[i]).count();
+        assert_eq!(flipped, 1);
+
+        // A single bucket matches the plain root hash
+        let all = buckets(&mut client, 1).await;
+        assert_eq!(client.request("HASH").await, format!("HASH {}", all[0]));
+    }
+
+    #[tokio::test]
+    async fn test_quoted_values_round_trip() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request(r#"SET k "  spaced  ""#).await, "OK");
+        assert_eq!(client.request("GET k").await, "VALUE   spaced  ");
+        assert_eq!(client.request(r#"SET nul "a\x00b""#).await, "OK");
+        assert_eq!(client.request("BGET nul").await, "VALUE 3");
+        assert_eq!(client.read_line().await, "a\0b");
+
+        // Line breaks are stored as a binary value so GET cannot split its reply
+        assert_eq!(client.request(r#"SET lines "a\r\nVALUE forged""#).await, "OK");
+        assert_eq!(client.request("GET lines").await, "ERROR binary value, use BGET");
+        client.send("BGET lines").await;
+        assert_eq!(client.read_line().await, "VALUE 15");
+        assert_eq!(client.read_line().await, "a");
+        assert_eq!(client.read_line().await, "VALUE forged");
+        assert_eq!(client.request(r#"APPEND k "! ""#).await, "VALUE   spaced  ! ");
+    }
+
+    #[tokio::test]
+    async fn test_casver_rejects_stale_versions() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("GETVER k").await, "NOT_FOUND");
+        assert_eq!(client.request("CASVER k 0 first").await, "OK");
+
+        let reply = client.request("GETVER k").await;
+        let (value, version) = reply.strip_prefix("VALUE ").unwrap().split_once(" VERSION ").unwrap();
+        assert_eq!(value, "first");
+        let version: u64 = version.parse().unwrap();
+
+        // Another writer moves the key on; the old version no longer matches
+        assert_eq!(client.request("SET k other").await, "OK");
+        assert_eq!(client.request(&format!("CASVER k {} lost", version)).await, "VERSION_MISMATCH");
+        assert_eq!(client.request("GET k").await, "VALUE other");
+
+        let reply = client.request("GETVER k").await;
+        let current = reply.rsplit(' ').next().unwrap();
+        assert_eq!(client.request(&format!("CASVER k {} won", current)).await, "OK");
+        assert_eq!(client.request("GET k").await, "VALUE won");
+
+        // Hashes and binary values are not readable or replaceable as text
+        assert_eq!(client.request("HSET h f v").await, "OK");
+        client.writer.write_all(b"BSET b 2\r\n\r\n\r\n").await.unwrap();
+        assert_eq!(client.read_line().await, "OK");
+        let wrong_type = format!("ERROR {}", WRONGTYPE);
+        let binary = format!("ERROR {}", BINARY_VALUE);
+        assert_eq!(client.request("GETVER h").await, wrong_type);
+        assert_eq!(client.request("CASVER h 1 text").await, wrong_type);
+        assert_eq!(client.request("GETVER b").await, binary);
+        assert_eq!(client.request("CASVER b 1 text").await, binary);
+        assert_eq!(client.request("HGET h f").await, "VALUE v");
+    }
+
+    #[tokio::test]
+    async fn test_scanvalues_matches_individual_gets() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        for (key, value) in [("user:1", "alice"), ("user:2", "bob smith"), ("user:3", "42"), ("order:1", "x")] {
+            assert_eq!(client.request(&format!("SET {} {}", key, value)).await, "OK");
+        }
+
+        assert_eq!(client.request("SCANVALUES user:").await, "PAIRS 3");
+        let mut pairs = Vec::new();
+        for _ in 0..3 {
+            let line = client.read_line().await;
+            let (key, value) = line.split_once(' ').unwrap();
+            pairs.push((key.to_string(), value.to_string()));
+        }
+        pairs.sort();
+        assert_eq!(pairs[1], ("user:2".to_string(), "bob smith".to_string()));
+        for (key, value) in pairs {
+            assert_eq!(client.request(&format!("GET {}", key)).await, format!("VALUE {}", value));
+        }
+
+        assert_eq!(client.request("SCANVALUES none:").await, "PAIRS 0");
+    }
+
+    #[tokio::test]
+    async fn test_scanvalues_skips_values_that_are_not_line_text() {
+        // A line break can only arrive from outside the protocol, e.g. older data
+        let engine = RwLockEngine::new("").unwrap();
+        engine.set("k:legacy".to_string(), "a\r\nPAIRS 9".to_string()).unwrap();
+        let port = start_server(test_config("rwlock", ""), Box::new(engine)).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET k:text plain").await, "OK");
+        assert_eq!(client.request("HSET k:hash f v").await, "OK");
+        client.writer.write_all(b"BSET k:bin 1\r\n\0\r\n").await.unwrap();
+        assert_eq!(client.read_line().await, "OK");
+
+        assert_eq!(client.request("SCANVALUES k:").await, "PAIRS 1");
+        assert_eq!(client.read_line().await, "k:text plain");
+        assert_eq!(client.request("PING after").await, "PONG after");
+    }
+
+    #[tokio::test]
+    async fn test_scan_match_filters_prefixed_keys() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        for key in ["user:1", "user:admin", "user:superadmin", "group:admin"] {
+            assert_eq!(client.request(&format!("SET {} v", key)).await, "OK");
+        }
+
+        assert_eq!(client.request("SCAN user: MATCH *admin*").await, "KEYS 2");
+        let mut keys = vec![client.read_line().await, client.read_line().await];
+        keys.sort();
+        assert_eq!(keys, ["user:admin", "user:superadmin"]);
+
+        // Without a prefix the glob sees every key
+        assert_eq!(client.request("SCAN MATCH *:admin").await, "KEYS 2");
+        client.read_line().await;
+        client.read_line().await;
+
+        assert_eq!(client.request("SCAN user: MATCH *nobody*").await, "KEYS 0");
+        assert_eq!(client.request("SCANVALUES user: MATCH user:?").await, "PAIRS 1");
+        assert_eq!(client.read_line().await, "user:1 v");
+
+        // Plain SCAN is unchanged
+        assert_eq!(client.request("SCAN user:").await, "KEYS 3");
+    }
+
+    #[tokio::test]
+    async fn test_memory_doctor_reports_sizes_and_largest_keys() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("MEMORY DOCTOR").await, "MEMORY DOCTOR keys=0 bytes=0");
+        for _ in 0..=VALUE_SIZE_BUCKETS.len() {
+            assert!(client.read_line().await.ends_with(" 0"));
+        }
+        assert_eq!(client.read_line().await, "LARGEST 0");
+
+        let sizes = [1, 2, 3, 16, 17, 64, 65, 300, 1025, 5000, 20000, 20000];
+        for (i, size) in sizes.iter().enumerate() {
+            assert_eq!(client.request(&format!("SET k{:02} {}", i, "x".repeat(*size))).await, "OK");
+        }
+
+        let bytes: usize = sizes.iter().sum::<usize>() + 3 * sizes.len();
+        assert_eq!(client.request("MEMORY DOCTOR").await, format!("MEMORY DOCTOR keys=12 bytes={}", bytes));
+        let mut histogram = Vec::new();
+        for _ in 0..=VALUE_SIZE_BUCKETS.len() {
+            histogram.push(client.read_line().await);
+        }
+        assert_eq!(
+            histogram,
+            [
+                "BUCKET <=16 4",
+                "BUCKET <=64 2",
+                "BUCKET <=256 1",
+                "BUCKET <=1024 1",
+                "BUCKET <=4096 1",
+                "BUCKET <=16384 1",
+                "BUCKET >16384 2",
+            ]
+        );
+
+        // Top ten, largest first and ties broken by key
+        assert_eq!(client.read_line().await, "LARGEST 10");
+        let mut largest = Vec::new();
+        for _ in 0..10 {
+            largest.push(client.read_line().await);
+        }
+        assert_eq!(
+            largest,
+            [
+                "k10 20000", "k11 20000", "k09 5000", "k08 1025", "k07 300", "k06 65", "k05 64", "k04 17",
+                "k03 16", "k02 3",
+            ]
+        );
+        assert_eq!(client.request("PING alive").await, "PONG alive");
+    }
+
+    #[tokio::test]
+    async fn test_dbsize_and_memory_by_pattern() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        for i in 0..3 {
+            assert_eq!(client.request(&format!("SET tenant:a:{} {}", i, "x".repeat(100))).await, "OK");
+        }
+        assert_eq!(client.request("SET tenant:b:0 small").await, "OK");
+
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 4");
+        assert_eq!(client.request("DBSIZE tenant:a:*").await, "DBSIZE tenant:a:* 3");
+        assert_eq!(client.request("DBSIZE tenant:b:*").await, "DBSIZE tenant:b:* 1");
+        assert_eq!(client.request("DBSIZE tenant:?:0").await, "DBSIZE tenant:?:0 2");
+        assert_eq!(client.request("DBSIZE none:*").await, "DBSIZE none:* 0");
+
+        // Key plus value bytes of the matching keys only
+        assert_eq!(client.request("MEMORY USAGE tenant:a:*").await, "MEMORY tenant:a:* 330");
+        assert_eq!(client.request("MEMORY USAGE tenant:b:*").await, "MEMORY tenant:b:* 15");
+        assert!(client.request("MEMORY").await.starts_with("MEMORY "));
+    }
+
+    #[tokio::test]
+    async fn test_merkle_dump_lists_every_node() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("MERKLE DUMP").await, "NODES 0");
+
+        let mut tree = crate::store::merkle::MerkleTree::new();
+        for i in 0..5 {
+            assert_eq!(client.request(&format!("SET k{} v{}", i, i)).await, "OK");
+            tree.insert(&format!("k{}", i), &format!("v{}", i));
+        }
+
+        assert_eq!(client.request("MERKLE DUMP").await, format!("NODES {}", tree.node_count()));
+        let mut lines = Vec::new();
+        for _ in 0..tree.node_count() {
+            lines.push(client.read_line().await);
+        }
+        let root = client.request("HASH").await.replace("HASH ", "");
+        assert_eq!(lines[0], format!("NODE 0 {}", root));
+        let leaves: Vec<&str> = lines.iter().filter_map(|l| l.split(' ').nth(3)).collect();
+        assert_eq!(leaves, vec!["k0", "k1", "k2", "k3", "k4"]);
+    }
+
+    #[tokio::test]
+    async fn test_command_aliases_and_disabled_commands() {
+        let mut config = test_config("rwlock", "");
+        config.command_aliases.insert("KEYS".to_string(), "SCAN".to_string());
+        config.rename_commands = vec!["FLUSHDB".to_string(), "SHUTDOWN".to_string()];
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET user:1 alice").await, "OK");
+        assert_eq!(client.request("KEYS user:").await, "KEYS 1");
+        assert_eq!(client.read_line().await, "user:1");
+        assert_eq!(client.request("FLUSHDB").await, "ERROR unknown command");
+        assert_eq!(client.request("shutdown").await, "ERROR unknown command");
+        assert_eq!(client.request("GET user:1").await, "VALUE alice");
+    }
+
+    #[tokio::test]
+    async fn test_verify_repairs_a_drifted_merkle_cache() {
+        // Reading `tamper` changes a value behind the server's back, so the
+        // cached root silently goes stale
+        let engine = HookedEngine::new(|inner, key| {
+            if key == "tamper" {
+                inner.set("a".to_string(), "tampered".to_string()).unwrap();
+                return None;
+            }
+            inner.get(key)
+        });
+        let port = start_server(test_config("rwlock", ""), Box::new(engine)).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("VERIFY").await, "VERIFY ok EMPTY");
+
+        assert_eq!(client.request("SET a 1").await, "OK");
+        let verified = client.request("VERIFY").await;
+        assert!(verified.starts_with("VERIFY ok "), "{}", verified);
+        assert_eq!(client.request("VERIFY").await, verified);
+
+        // Ordinary writes and expiring keys are not drift
+        assert_eq!(client.request("SET b 2").await, "OK");
+        assert!(client.request("VERIFY").await.starts_with("VERIFY ok "));
+        assert_eq!(client.request("PSETEX e 50 gone").await, "OK");
+        assert!(client.request("VERIFY").await.starts_with("VERIFY ok "));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let hash = client.request("HASH").await;
+        assert_eq!(client.request("VERIFY").await, format!("VERIFY ok {}", &hash["HASH ".len()..]));
+
+        assert_eq!(client.request("GET tamper").await, "NOT_FOUND");
+        let repaired = client.request("VERIFY").await;
+        assert!(repaired.starts_with("VERIFY repaired "), "{}", repaired);
+        let ok = client.request("VERIFY").await;
+        assert_eq!(ok, repaired.replace("repaired", "ok"));
+        assert_eq!(client.request("HASH").await, format!("HASH {}", &ok["VERIFY ok ".len()..]));
+    }
+
+    #[tokio::test]
+    async fn test_merkle_bench_matches_dbsize_and_hash() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        let bench = client.request("MERKLE BENCH").await;
+        assert!(bench.starts_with("MERKLE BENCH keys=0 micros="), "{}", bench);
+        assert!(bench.ends_with(" root=EMPTY"), "{}", bench);
+
+        for i in 0..50 {
+            assert_eq!(client.request(&format!("SET key:{} value-{}", i, i)).await, "OK");
+        }
+        let bench = client.request("MERKLE BENCH").await;
+        let fields: HashMap<&str, &str> = bench
+            .strip_prefix("MERKLE BENCH ")
+            .unwrap()
+            .split(' ')
+            .filter_map(|field| field.split_once('='))
+            .collect();
+        let dbsize = client.request("DBSIZE").await;
+        assert_eq!(format!("DBSIZE {}", fields["keys"]), dbsize);
+        assert!(fields["micros"].parse::<u64>().is_ok());
+        assert_eq!(format!("HASH {}", fields["root"]), client.request("HASH").await);
+    }
+
+    #[tokio::test]
+    async fn test_hash_reports_empty_key_sets() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("HASH").await, "HASH EMPTY");
+        assert_eq!(client.request("HASH user:").await, "HASH user: EMPTY");
+
+        assert_eq!(client.request("SET user:1 alice").await, "OK");
+        let root = client.request("HASH").await;
+        let hex = root.strip_prefix("HASH ").unwrap();
+        assert_eq!(hex.len(), 64);
+        assert!(hex.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(client.request("HASH user:").await, format!("HASH user: {}", hex));
+        assert_eq!(client.request("HASH order:").await, "HASH order: EMPTY");
+    }
+
+    #[tokio::test]
+    async fn test_hash_range() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        let empty = format!("HASH b d {}", "0".repeat(64));
+        assert_eq!(client.request("HASH RANGE b d").await, empty);
+
+        for key in ["a", "b", "c", "d"] {
+            assert_eq!(client.request(&format!("SET {} v", key)).await, "OK");
+        }
+        let before = client.request("HASH RANGE b d").await;
+        assert_ne!(before, empty);
+
+        // Keys outside [b, d) do not affect the root; the end bound is exclusive
+        assert_eq!(client.request("SET a changed").await, "OK");
+        assert_eq!(client.request("SET d changed").await, "OK");
+        assert_eq!(client.request("SET e new").await, "OK");
+        assert_eq!(client.request("HASH RANGE b d").await, before);
+
+        assert_eq!(client.request("SET c changed").await, "OK");
+        assert_ne!(client.request("HASH RANGE b d").await, before);
+
+        // A range covering every key matches the plain root hash
+        let root = client.request("HASH").await;
+        let full = client.request("HASH RANGE a z").await;
+        assert_eq!(full.rsplit(' ').next(), root.rsplit(' ').next());
+    }
+
+    #[tokio::test]
+    async fn test_dump_restore_between_engines() {
+        let port_a = start_rwlock_server(test_config("rwlock", "")).await;
+        let dir = tempdir().unwrap();
+        let path = dir.path().to_str().unwrap().to_string();
+        let sled = Box::new(SledEngine::new(&path).unwrap());
+        let port_b = start_server(test_config("sled", &path), sled).await;
+        let mut a = TestClient::connect(port_a).await;
+        let mut b = TestClient::connect(port_b).await;
+
+        assert_eq!(a.request("SET user:1 hello world").await, "OK");
+        assert_eq!(a.request("DUMP missing").await, "NOT_FOUND");
+        let dump = a.request("DUMP user:1").await;
+        let payload = dump.strip_prefix("DUMP ").unwrap().to_string();
+
+        assert_eq!(b.request(&format!("RESTORE user:1 {}", payload)).await, "OK");
+        assert_eq!(b.request("GET user:1").await, "VALUE hello world");
+
+        // A newer local write wins unless REPLACE is given
+        assert_eq!(b.request("SET user:1 newer").await, "OK");
+        assert_eq!(b.request(&format!("RESTORE user:1 {}", payload)).await, "ERROR busykey");
+        assert_eq!(b.request("GET user:1").await, "VALUE newer");
+        assert_eq!(b.request(&format!("RESTORE user:1 {} REPLACE", payload)).await, "OK");
+        assert_eq!(b.request("GET user:1").await, "VALUE hello world");
+
+        assert_eq!(b.request("RESTORE user:2 zz").await, "ERROR bad payload");
+        assert_eq!(b.request("RESTORE user:2 abcd").await, "ERROR bad payload");
+    }
+
+    #[tokio::test]
+    async fn test_read_only_mode_rejects_writes() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET k v").await, "OK");
+
+        assert_eq!(client.request("CONFIG SET read-only on").await, "OK");
+        assert_eq!(client.request("SET k other").await, "ERROR READONLY server is read-only");
+        assert_eq!(client.request("DEL k").await, "ERROR READONLY server is read-only");
+        assert_eq!(client.request("INC n").await, "ERROR READONLY server is read-only");
+        assert_eq!(client.request("TRUNCATE").await, "ERROR READONLY server is read-only");
+        assert_eq!(client.request("GET k").await, "VALUE v");
+        assert_eq!(client.request("EXISTS k").await, "EXISTS 1");
+
+        assert_eq!(client.request("CONFIG SET read-only maybe").await, "ERROR read-only must be on or off");
+        assert_eq!(client.request("CONFIG SET maxmemory 10").await, "ERROR Unsupported CONFIG parameter: maxmemory");
+
+        assert_eq!(client.request("CONFIG SET read-only off").await, "OK");
+        assert_eq!(client.request("SET k other").await, "OK");
+        assert_eq!(client.request("GET k").await, "VALUE other");
+    }
+
+    #[tokio::test]
+    async fn test_read_only_from_config() {
+        let mut config = test_config("rwlock", "");
+        config.read_only = true;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET k v").await, "ERROR READONLY server is read-only");
+        assert_eq!(client.request("GET k").await, "NOT_FOUND");
+        assert_eq!(client.request("SCAN").await, "KEYS 0");
+    }
+
+    #[tokio::test]
+    async fn test_config_get_set() {
+        let mut config = test_config("rwlock", "");
+        config.max_connections = 100;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("CONFIG GET max_connections").await, "CONFIG max_connections 100");
+        assert_eq!(client.request("CONFIG GET rate_limit_per_sec").await, "CONFIG rate_limit_per_sec 0");
+        assert_eq!(client.request("CONFIG GET read_only").await, "CONFIG read_only off");
+
+        assert_eq!(client.request("CONFIG SET read_only on").await, "OK");
+        assert_eq!(client.request("CONFIG GET read_only").await, "CONFIG read_only on");
+        assert_eq!(client.request("CONFIG SET read_only off").await, "OK");
+
+        assert_eq!(client.request("CONFIG GET maxmemory").await, "ERROR Unsupported CONFIG parameter: maxmemory");
+        assert_eq!(
+            client.request("CONFIG SET max_connections lots").await,
+            "ERROR max_connections must be a non-negative integer"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_getall_reports_effective_settings() {
+        let mut config = test_config("rwlock", "");
+        config.replication.client_password = Some("mqtt-secret".to_string());
+        config.users.push(UserConfig {
+            name: "ops".to_string(),
+            password: "hunter2".to_string(),
+            categories: vec![AclCategory::Admin],
+        });
+        let expected_port = config.port.to_string();
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("AUTH ops hunter2").await, "OK");
+        assert_eq!(client.request("CONFIG SET read_only on").await, "OK");
+
+        let header = client.request("CONFIG GETALL").await;
+        let count: usize = header.strip_prefix("CONFIGALL ").unwrap().parse().unwrap();
+        let mut settings = HashMap::new();
+        for _ in 0..count {
+            let line = client.read_line().await;
+            let (name, value) = line.split_once(' ').unwrap();
+            settings.insert(name.to_string(), value.to_string());
+        }
+        assert_eq!(settings["engine"], "rwlock");
+        assert_eq!(settings["host"], "127.0.0.1");
+        assert_eq!(settings["port"], expected_port);
+        assert_eq!(settings["read_only"], "on");
+        assert_eq!(settings["replication.client_password"], "****");
+        assert_eq!(settings["users.0.password"], "****");
+        assert!(settings.values().all(|v| !v.contains("mqtt-secret") && !v.contains("hunter2")));
+    }
+
+    #[tokio::test]
+    async fn test_config_set_max_connections_applies_to_new_connections() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut first = TestClient::connect(port).await;
+        assert_eq!(first.request("CONFIG SET max_connections 1").await, "OK");
+
+        // The existing connection keeps working; a new one is turned away
+        let mut second = TestClient::connect(port).await;
+        assert_eq!(second.read_line().await, "ERROR max number of clients reached");
+        assert_eq!(first.request("GET k").await, "NOT_FOUND");
+
+        assert_eq!(first.request("CONFIG SET max_connections 0").await, "OK");
+        let mut third = TestClient::connect(port).await;
+        assert_eq!(third.request("GET k").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_config_set_rate_limit() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("CONFIG SET rate_limit_per_sec 3").await, "OK");
+
+        let mut replies = Vec::new();
+        for _ in 0..5 {
+            replies.push(client.request("GET k").await);
+        }
+        assert!(replies.contains(&"ERROR rate limit exceeded".to_string()), "{:?}", replies);
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(client.request("CONFIG SET rate_limit_per_sec 0").await, "OK");
+        for _ in 0..10 {
+            assert_eq!(client.request("GET k").await, "NOT_FOUND");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mset_is_all_or_nothing() {
+        let mut config = test_config("rwlock", "");
+        config.max_value_bytes = 4;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        // The oversized value in the middle rejects the whole batch
+        assert_eq!(client.request("MSET a 1 b toolarge c 3").await, "ERROR value too large");
+        assert_eq!(client.request("EXISTS a b c").await, "EXISTS 0");
+
+        assert_eq!(client.request("MSET a 1 b 2 c 3").await, "OK");
+        assert_eq!(client.request("EXISTS a b c").await, "EXISTS 3");
+    }
+
+    #[tokio::test]
+    async fn test_hash_commands() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("HSET user name Ada").await, "OK");
+        assert_eq!(client.request("HSET user name Ada Lovelace").await, "OK");
+        assert_eq!(client.request("HSET user born 1815").await, "OK");
+        assert_eq!(client.request("HGET user name").await, "VALUE Ada Lovelace");
+        assert_eq!(client.request("HGET user missing").await, "NOT_FOUND");
+        assert_eq!(client.request("HLEN user").await, "HLEN 2");
+
+        assert_eq!(client.request("HGETALL user").await, "VALUES 2");
+        assert_eq!(client.read_line().await, "born 1815");
+        assert_eq!(client.read_line().await, "name Ada Lovelace");
+        assert_eq!(client.request("HGETALL nokey").await, "NOT_FOUND");
+
+        assert_eq!(client.request("HDEL user born missing").await, "DELETED 1");
+        assert_eq!(client.request("HDEL user name").await, "DELETED 1");
+        assert_eq!(client.request("EXISTS user").await, "EXISTS 0");
+    }
+
+    #[tokio::test]
+    async fn test_hash_wrongtype() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        let wrongtype = format!("ERROR {}", WRONGTYPE);
+
+        assert_eq!(client.request("SET s plain").await, "OK");
+        assert_eq!(client.request("HSET s f v").await, wrongtype);
+        assert_eq!(client.request("HGET s f").await, wrongtype);
+
+        assert_eq!(client.request("HSET h f v").await, "OK");
+        assert_eq!(client.request("GET h").await, wrongtype);
+        assert_eq!(client.request("APPEND h x").await, wrongtype);
+        assert_eq!(client.request("INC h").await, wrongtype);
+        assert_eq!(client.request("HGET h f").await, "VALUE v");
+
+        // SET replaces a hash with a string, as in Redis
+        assert_eq!(client.request("SET h now-a-string").await, "OK");
+        assert_eq!(client.request("GET h").await, "VALUE now-a-string");
+
+        // Text writes cannot forge the hash marker
+        let error = "ERROR value must not start with NUL";
+        assert_eq!(client.request("SET forged \0hash:{\"f\":\"v\"}").await, error);
+        assert_eq!(client.request(r#"SET forged "\x00hash:{}""#).await, error);
+        assert_eq!(client.request("PREPEND h \0hash:").await, error);
+        assert_eq!(client.request("HGET forged f").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_sync_copies_hashes() {
+        let port_a = start_rwlock_server(test_config("rwlock", "")).await;
+        let port_b = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut a = TestClient::connect(port_a).await;
+        let mut b = TestClient::connect(port_b).await;
+
+        assert_eq!(a.request("HSET h f1 one").await, "OK");
+        assert_eq!(a.request("HSET h f2 two").await, "OK");
+        assert_eq!(
+            b.request(&format!("SYNC 127.0.0.1 {}", port_a)).await,
+            "SYNCED pulled=1 pushed=0 conflicts=0"
+        );
+        assert_eq!(b.request("HGET h f2").await, "VALUE two");
+        assert_eq!(b.request("HLEN h").await, "HLEN 2");
+    }
+
+    #[tokio::test]
+    async fn test_sync_copies_binary_values() {
+        let port_a = start_rwlock_server(test_config("rwlock", "")).await;
+        let port_b = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut a = TestClient::connect(port_a).await;
+        let mut b = TestClient::connect(port_b).await;
+
+        // The peer's newer write is a binary value that GET refuses
+        assert_eq!(b.request("SET bin old").await, "OK");
+        a.writer.write_all(b"BSET bin 3\r\na\r\n\r\n").await.unwrap();
+        assert_eq!(a.read_line().await, "OK");
+        assert_eq!(b.request(&format!("DIFF 127.0.0.1 {}", port_a)).await, "DIFF 1");
+        assert_eq!(b.read_line().await, "bin");
+        assert_eq!(b.request(&format!("SYNC 127.0.0.1 {} --dry-run", port_a)).await, "DRYRUN pull=1 push=0");
+        assert_eq!(
+            b.request(&format!("SYNC 127.0.0.1 {}", port_a)).await,
+            "SYNCED pulled=1 pushed=0 conflicts=1"
+        );
+        b.send("BGET bin").await;
+        assert_eq!(b.read_line().await, "VALUE 3");
+        let mut body = [0u8; 5];
+        b.reader.read_exact(&mut body).await.unwrap();
+        assert_eq!(&body, b"a\r\n\r\n");
+        assert_eq!(a.request("HASH").await, b.request("HASH").await);
+    }
+
+    #[tokio::test]
+    async fn test_sync_peers_converges_three_nodes() {
+        let ports = [
+            start_rwlock_server(test_config("rwlock", "")).await,
+            start_rwlock_server(test_config("rwlock", "")).await,
+            start_rwlock_server(test_config("rwlock", "")).await,
+        ];
+        let mut a = TestClient::connect(ports[0]).await;
+        let mut b = TestClient::connect(ports[1]).await;
+        let mut c = TestClient::connect(ports[2]).await;
+
+        // Writes in timestamp order: the last write of each key should win
+        assert_eq!(a.request("SET shared from-a").await, "OK");
+        assert_eq!(b.request("SET fresh from-b").await, "OK");
+        assert_eq!(b.request("SET shared from-b").await, "OK");
+        assert_eq!(c.request("SET shared from-c").await, "OK");
+        assert_eq!(a.request("SET fresh from-a").await, "OK");
+        assert_eq!(a.request("SET only-a 1").await, "OK");
+        assert_eq!(b.request("SET only-b 2").await, "OK");
+        assert_eq!(c.request("SET only-c 3").await, "OK");
+
+        // A peer that is down is reported, but two of three still make a quorum
+        let down = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        a.send(&format!("SYNC PEERS 127.0.0.1:{} 127.0.0.1:{} 127.0.0.1:{}", ports[1], ports[2], down))
+            .await;
+        assert_eq!(a.read_line().await, "SYNCED 3");
+        assert_eq!(a.read_line().await, format!("127.0.0.1:{} pulled=1 pushed=0 conflicts=0", ports[1]));
+        assert_eq!(a.read_line().await, format!("127.0.0.1:{} pulled=2 pushed=0 conflicts=1", ports[2]));
+        assert!(a.read_line().await.starts_with(&format!("127.0.0.1:{} ERROR", down)));
+
+        assert_eq!(a.request("GET shared").await, "VALUE from-c");
+        assert_eq!(a.request("GET fresh").await, "VALUE from-a");
+        assert_eq!(a.request("GET only-a").await, "VALUE 1");
+        assert_eq!(a.request("GET only-b").await, "VALUE 2");
+        assert_eq!(a.request("GET only-c").await, "VALUE 3");
+
+        // The merged state spreads to the other nodes with a plain SYNC from A
+        let sync_a = format!("SYNC 127.0.0.1 {}", ports[0]);
+        b.request(&sync_a).await;
+        c.request(&sync_a).await;
+        let root = a.request("HASH").await;
+        assert_eq!(b.request("HASH").await, root);
+        assert_eq!(c.request("HASH").await, root);
+
+        // Without a majority nothing is pulled
+        assert_eq!(c.request("SET late 1").await, "OK");
+        assert_eq!(
+            a.request(&format!("SYNC PEERS 127.0.0.1:{} 127.0.0.1:{}", ports[2], down)).await,
+            "ERROR quorum not reached: 1 of 2 peers reachable"
+        );
+        assert_eq!(a.request("GET late").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_sync_reports_reconciled_keys() {
+        let port_a = start_rwlock_server(test_config("rwlock", "")).await;
+        let port_b = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut a = TestClient::connect(port_a).await;
+        let mut b = TestClient::connect(port_b).await;
+
+        for client in [&mut a, &mut b] {
+            assert_eq!(client.request("SET same v").await, "OK");
+        }
+        // Two keys differ, one is missing locally and one exists only locally
+        assert_eq!(a.request("SET changed:1 new").await, "OK");
+        assert_eq!(b.request("SET changed:1 old").await, "OK");
+        assert_eq!(a.request("SET changed:2 new").await, "OK");
+        assert_eq!(b.request("SET changed:2 old").await, "OK");
+        assert_eq!(a.request("SET remote-only v").await, "OK");
+        assert_eq!(b.request("SET local-only v").await, "OK");
+
+        let sync = format!("SYNC 127.0.0.1 {}", port_a);
+        assert_eq!(b.request(&sync).await, "SYNCED pulled=4 pushed=0 conflicts=2");
+        assert_eq!(b.request("GET changed:2").await, "VALUE new");
+        assert_eq!(b.request("GET local-only").await, "NOT_FOUND");
+        assert_eq!(b.request(&sync).await, "SYNCED pulled=0 pushed=0 conflicts=0");
+
+        b.send("STATS").await;
+        loop {
+            let line = b.read_line().await;
+            if let Some(count) = line.strip_prefix("sync_keys_reconciled:") {
+                assert_eq!(count, "4");
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_dry_run_reports_divergence_without_writing() {
+        let port_a = start_rwlock_server(test_config("rwlock", "")).await;
+        let port_b = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut a = TestClient::connect(port_a).await;
+        let mut b = TestClient::connect(port_b).await;
+
+        for client in [&mut a, &mut b] {
+            assert_eq!(client.request("SET same v").await, "OK");
+        }
+        assert_eq!(a.request("SET changed new").await, "OK");
+        assert_eq!(b.request("SET changed old").await, "OK");
+        assert_eq!(a.request("SET remote-only v").await, "OK");
+        assert_eq!(b.request("SET local-only v").await, "OK");
+        let hash_a = a.request("HASH").await;
+        let hash_b = b.request("HASH").await;
+
+        let dry_run = format!("SYNC 127.0.0.1 {} --dry-run", port_a);
+        assert_eq!(b.request(&dry_run).await, "DRYRUN pull=3 push=0");
+        assert_eq!(b.request(&dry_run).await, "DRYRUN pull=3 push=0");
+
+        // Neither side changed
+        assert_eq!(a.request("HASH").await, hash_a);
+        assert_eq!(b.request("HASH").await, hash_b);
+        assert_eq!(b.request("GET changed").await, "VALUE old");
+        assert_eq!(b.request("GET local-only").await, "VALUE v");
+        assert_eq!(b.request("GET remote-only").await, "NOT_FOUND");
+
+        // The real run changes exactly what the dry run counted
+        let sync = format!("SYNC 127.0.0.1 {}", port_a);
+        assert_eq!(b.request(&sync).await, "SYNCED pulled=3 pushed=0 conflicts=1");
+        assert_eq!(b.request(&dry_run).await, "DRYRUN pull=0 push=0");
+    }
+
+    #[tokio::test]
+    async fn test_sync_times_out_on_unresponsive_peer() {
+        // A peer that accepts connections but never answers
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent_port = silent.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = silent.accept().await {
+                held.push(socket);
+            }
+        });
+        let healthy = start_rwlock_server(test_config("rwlock", "")).await;
+
+        let mut config = test_config("rwlock", "");
+        config.sync.timeout_ms = 1000;
+        let port = start_rwlock_server(config).await;
+        let mut stuck = TestClient::connect(port).await;
+        let mut other = TestClient::connect(port).await;
+
+        let started = Instant::now();
+        stuck.send(&format!("SYNC 127.0.0.1 {}", silent_port)).await;
+        // The stalled sync does not hold up syncs from other connections
+        let sync = format!("SYNC 127.0.0.1 {}", healthy);
+        assert_eq!(other.request(&sync).await, "SYNCED pulled=0 pushed=0 conflicts=0");
+        assert!(started.elapsed() < Duration::from_millis(1000));
+
+        assert_eq!(stuck.read_line().await, "ERROR sync timeout");
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // An unroutable address fails promptly too, whether by timeout or by
+        // an immediate network error
+        let started = Instant::now();
+        assert!(other.request("SYNC 10.255.255.1 7878").await.starts_with("ERROR"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_diff_lists_divergent_keys_without_writing() {
+        let port_a = start_rwlock_server(test_config("rwlock", "")).await;
+        let port_b = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut a = TestClient::connect(port_a).await;
+        let mut b = TestClient::connect(port_b).await;
+
+        for client in [&mut a, &mut b] {
+            assert_eq!(client.request("SET user:same v").await, "OK");
+        }
+        assert_eq!(a.request("SET user:changed old").await, "OK");
+        assert_eq!(b.request("SET user:changed new").await, "OK");
+        assert_eq!(a.request("SET user:only_a v").await, "OK");
+        assert_eq!(b.request("SET user:only_b v").await, "OK");
+        assert_eq!(b.request("SET other v").await, "OK");
+
+        assert_eq!(a.request(&format!("DIFF 127.0.0.1 {}", port_b)).await, "DIFF 4");
+        let mut keys = Vec::new();
+        for _ in 0..4 {
+            keys.push(a.read_line().await);
+        }
+        assert_eq!(keys, ["other", "user:changed", "user:only_a", "user:only_b"]);
+
+        assert_eq!(a.request(&format!("DIFF 127.0.0.1 {} user:only", port_b)).await, "DIFF 2");
+        assert_eq!(a.read_line().await, "user:only_a");
+        assert_eq!(a.read_line().await, "user:only_b");
+
+        // Read-only: nothing was copied across
+        assert_eq!(a.request("GET user:changed").await, "VALUE old");
+        assert_eq!(a.request("GET other").await, "NOT_FOUND");
+        assert_eq!(b.request(&format!("DIFF 127.0.0.1 {} user:same", port_a)).await, "DIFF 0");
+    }
+
+    #[tokio::test]
+    async fn test_randomkey() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("RANDOMKEY").await, "NOT_FOUND");
+        assert_eq!(client.request("SET only v").await, "OK");
+        assert_eq!(client.request("RANDOMKEY").await, "KEY only");
+    }
+
+    async fn start_multi_db_server(databases: usize) -> u16 {
+        let mut config = test_config("rwlock", "");
+        config.databases = databases;
+        let stores: Vec<Box<dyn KVEngineStoreTrait + Send + Sync>> = (0..databases)
+            .map(|_| Box::new(RwLockEngine::new("").unwrap()) as Box<dyn KVEngineStoreTrait + Send + Sync>)
+            .collect();
+        let port = config.port;
+        tokio::spawn(Server::with_databases(config, stores).run_until(std::future::pending()));
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+                return port;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("server did not start on port {}", port);
+    }
+
+    #[tokio::test]
+    async fn test_select_isolates_databases() {
+        let port = start_multi_db_server(2).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET k zero").await, "OK");
+        assert_eq!(client.request("SELECT 1").await, "OK");
+        assert_eq!(client.request("GET k").await, "NOT_FOUND");
+        assert_eq!(client.request("SET k one").await, "OK");
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 1");
+        assert_eq!(client.request("SELECT 0").await, "OK");
+        assert_eq!(client.request("GET k").await, "VALUE zero");
+
+        // Selection is per connection
+        let mut other = TestClient::connect(port).await;
+        assert_eq!(client.request("SELECT 1").await, "OK");
+        assert_eq!(other.request("GET k").await, "VALUE zero");
+
+        assert_eq!(client.request("SELECT 2").await, "ERROR DB index is out of range");
+        assert_eq!(client.request("GET k").await, "VALUE one");
+    }
+
+    #[tokio::test]
+    async fn test_move_between_databases() {
+        let port = start_multi_db_server(2).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET a 1").await, "OK");
+        assert_eq!(client.request("MOVE a 1").await, "OK");
+        assert_eq!(client.request("GET a").await, "NOT_FOUND");
+        assert_eq!(client.request("MOVE a 1").await, "NOT_FOUND");
+
+        // Fails without changes when the target already has the key
+        assert_eq!(client.request("SET a 2").await, "OK");
+        assert_eq!(client.request("MOVE a 1").await, "ERROR key exists in target database");
+        assert_eq!(client.request("GET a").await, "VALUE 2");
+
+        assert_eq!(client.request("MOVE a 0").await, "ERROR source and destination databases are the same");
+        assert_eq!(client.request("MOVE a 5").await, "ERROR DB index is out of range");
+
+        assert_eq!(client.request("SELECT 1").await, "OK");
+        assert_eq!(client.request("GET a").await, "VALUE 1");
+        assert_eq!(client.request("MOVE a 0").await, "ERROR key exists in target database");
+    }
+
+    #[tokio::test]
+    async fn test_move_keeps_ttl() {
+        let port = start_multi_db_server(2).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("PSETEX temp 300 short lived").await, "OK");
+        assert_eq!(client.request("SET forever v").await, "OK");
+        let deadline = client.request("PEXPIRETIME temp").await;
+        assert_eq!(client.request("MOVE temp 1").await, "OK");
+        assert_eq!(client.request("MOVE forever 1").await, "OK");
+
+        assert_eq!(client.request("SELECT 1").await, "OK");
+        assert_eq!(client.request("PEXPIRETIME temp").await, deadline);
+        assert_eq!(client.request("PEXPIRETIME forever").await, "PEXPIRETIME -1");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(client.request("GET temp").await, "NOT_FOUND");
+        assert_eq!(client.request("GET forever").await, "VALUE v");
+    }
+
+    #[tokio::test]
+    async fn test_setex_psetex_expire() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("PSETEX temp 200 short lived").await, "OK");
+        assert_eq!(client.request("SETEX session 60 long lived").await, "OK");
+        assert_eq!(client.request("GET temp").await, "VALUE short lived");
+        assert_eq!(client.request("EXISTS temp").await, "EXISTS 1");
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(client.request("GET temp").await, "NOT_FOUND");
+        assert_eq!(client.request("GET session").await, "VALUE long lived");
+        // The background purge removed it from the keyspace too
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 1");
+
+        assert!(client.request("SETEX k 0 v").await.starts_with("ERROR"));
+        assert!(client.request("PSETEX k -1 v").await.starts_with("ERROR"));
+    }
+
+    #[tokio::test]
+    async fn test_expiretime_reports_absolute_deadline() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        let before = expiry::now_millis();
+        assert_eq!(client.request("PSETEX session 60000 v").await, "OK");
+        let after = expiry::now_millis();
+
+        let reply = client.request("PEXPIRETIME session").await;
+        let deadline: u64 = reply.strip_prefix("PEXPIRETIME ").unwrap().parse().unwrap();
+        assert!((before + 60_000..=after + 60_000).contains(&deadline));
+        assert_eq!(client.request("EXPIRETIME session").await, format!("EXPIRETIME {}", deadline / 1000));
+
+        assert_eq!(client.request("SET plain v").await, "OK");
+        assert_eq!(client.request("EXPIRETIME plain").await, "EXPIRETIME -1");
+        assert_eq!(client.request("PEXPIRETIME missing").await, "PEXPIRETIME -2");
+    }
+
+    #[tokio::test]
+    async fn test_msetex_sets_independent_ttls_atomically() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        let before = expiry::now_millis();
+        assert_eq!(client.request("MSETEX short 1 a long 100 b plain 0 c").await, "OK");
+        let after = expiry::now_millis();
+
+        let deadline = |reply: String| reply.strip_prefix("PEXPIRETIME ").unwrap().parse::<u64>().unwrap();
+        let short = deadline(client.request("PEXPIRETIME short").await);
+        let long = deadline(client.request("PEXPIRETIME long").await);
+        assert!((before + 1_000..=after + 1_000).contains(&short));
+        assert!((before + 100_000..=after + 100_000).contains(&long));
+        assert_eq!(client.request("PEXPIRETIME plain").await, "PEXPIRETIME -1");
+
+        tokio::time::sleep(Duration::from_millis(1_100)).await;
+        assert_eq!(client.request("GET short").await, "NOT_FOUND");
+        assert_eq!(client.request("GET long").await, "VALUE b");
+        assert_eq!(client.request("GET plain").await, "VALUE c");
+
+        // A bad TTL anywhere rejects the whole batch
+        assert_eq!(
+            client.request("MSETEX fresh 10 x long -5 y").await,
+            "ERROR invalid expire time in MSETEX"
+        );
+        assert_eq!(client.request("GET fresh").await, "NOT_FOUND");
+        assert_eq!(client.request("GET long").await, "VALUE b");
+    }
+
+    /// Logger that records formatted messages so tests can assert on them.
+    struct CaptureLogger {
+        lines: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Info
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.lines.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: CaptureLogger = CaptureLogger {
+        lines: std::sync::Mutex::new(Vec::new()),
+    };
+
+    #[tokio::test]
+    async fn test_command_log_entries() {
+        // Only this test installs a logger; the binary's env_logger is not used in tests
+        let _ = log::set_logger(&CAPTURE);
+        log::set_max_level(log::LevelFilter::Info);
+
+        let mut config = test_config("rwlock", "");
+        config.command_log = true;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET logged secret value").await, "OK");
+        assert_eq!(client.request("get logged").await, "VALUE secret value");
+        assert_eq!(client.request("GET missing_logged").await, "NOT_FOUND");
+
+        let lines = CAPTURE.lines.lock().unwrap().clone();
+        let id = lines
+            .iter()
+            .find(|l| l.contains("cmd=GET key=\"logged\""))
+            .and_then(|l| l.split_whitespace().nth(1))
+            .and_then(|f| f.strip_prefix("client_id="))
+            .expect("GET entry logged")
+            .to_string();
+        let entries: Vec<&String> = lines
+            .iter()
+            .filter(|l| l.starts_with(&format!("command client_id={} ", id)))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                &format!("command client_id={} cmd=SET key=\"logged\" status=ok", id),
+                &format!("command client_id={} cmd=GET key=\"logged\" status=ok", id),
+                &format!("command client_id={} cmd=GET key=\"missing_logged\" status=not_found", id),
+            ]
+        );
+        assert!(!lines.iter().any(|l| l.contains("secret")));
+    }
+
+    #[test]
+    fn test_command_log_line_values_opt_in() {
+        let command = Command::Set { key: "k".to_string(), value: "v 1".to_string() };
+        assert_eq!(
+            command_log_line(7, "SET", &command, "OK\r\n", false),
+            "command client_id=7 cmd=SET key=\"k\" status=ok"
+        );
+        assert_eq!(
+            command_log_line(7, "SET", &command, "ERROR value too large\r\n", true),
+            "command client_id=7 cmd=SET key=\"k\" value=\"v 1\" status=error"
+        );
+        assert_eq!(
+            command_log_line(7, "DBSIZE", &Command::Dbsize { pattern: None }, "DBSIZE 0\r\n", true),
+            "command client_id=7 cmd=DBSIZE status=ok"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_info_keyspace_fields() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET small 1234").await, "OK");
+        assert_eq!(client.request("SET big 0123456789abcdef").await, "OK");
+
+        assert_eq!(client.request("INFO").await, "INFO");
+        let mut fields = HashMap::new();
+        loop {
+            let line = client.read_line().await;
+            let (name, value) = line.split_once(':').unwrap();
+            fields.insert(name.to_string(), value.to_string());
+            if name == "largest_key" {
+                break;
+            }
+        }
+        assert!(fields.contains_key("version"));
+        assert!(fields.contains_key("uptime_seconds"));
+        assert_eq!(fields["db_keys"], "2");
+        // keys "small" + "big" = 8 bytes, values 4 + 16 = 20 bytes
+        assert_eq!(fields["db_memory_bytes"], "28");
+        assert_eq!(fields["avg_value_bytes"], "10");
+        assert_eq!(fields["largest_key"], "big");
+    }
+
+    #[tokio::test]
+    async fn test_getex_extends_and_clears_expiry() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        let expiretime = |reply: String| reply.strip_prefix("EXPIRETIME ").unwrap().parse::<i64>().unwrap();
+        let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+
+        assert_eq!(client.request("SETEX session 10 alice").await, "OK");
+        assert_eq!(client.request("GETEX session EX 1000").await, "VALUE alice");
+        let deadline = expiretime(client.request("EXPIRETIME session").await);
+        assert!((now() + 998..=now() + 1000).contains(&deadline), "{}", deadline);
+
+        // Without an option GETEX leaves the deadline alone
+        assert_eq!(client.request("GETEX session").await, "VALUE alice");
+        assert_eq!(expiretime(client.request("EXPIRETIME session").await), deadline);
+
+        assert_eq!(client.request("GETEX session PERSIST").await, "VALUE alice");
+        assert_eq!(client.request("EXPIRETIME session").await, "EXPIRETIME -1");
+        assert_eq!(client.request("GET session").await, "VALUE alice");
+
+        assert_eq!(client.request("GETEX missing EX 10").await, "NOT_FOUND");
+        assert_eq!(client.request("EXPIRETIME missing").await, "EXPIRETIME -2");
+    }
+
+    #[tokio::test]
+    async fn test_max_keys_rejects_new_keys_but_allows_updates() {
+        let mut config = test_config("rwlock", "");
+        config.max_keys = 2;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET a 1").await, "OK");
+        assert_eq!(client.request("SET b 2").await, "OK");
+        assert_eq!(client.request("SET c 3").await, "ERROR key limit reached");
+        assert_eq!(client.request("INC c").await, "ERROR key limit reached");
+        assert_eq!(client.request("MSET a 1 c 3").await, "ERROR key limit reached");
+        assert_eq!(client.request("EXISTS c").await, "EXISTS 0");
+
+        // Existing keys can still be written
+        assert_eq!(client.request("SET a 10").await, "OK");
+        assert_eq!(client.request("MSET a 11 b 12").await, "OK");
+        assert_eq!(client.request("INC b").await, "VALUE 13");
+        assert_eq!(client.request("GET a").await, "VALUE 11");
+
+        // Deleting makes room again
+        assert_eq!(client.request("DEL a").await, "DELETED");
+        assert_eq!(client.request("SET c 3").await, "OK");
+    }
+
+    #[tokio::test]
+    async fn test_acl_limits_commands_to_user_categories() {
+        let user = |name: &str, categories: Vec<AclCategory>| UserConfig {
+            name: name.to_string(),
+            password: format!("{}-pass", name),
+            categories,
+        };
+        let mut config = test_config("rwlock", "");
+        config.users = vec![
+            user("reader", vec![AclCategory::Read]),
+            user("ops", vec![AclCategory::Read, AclCategory::Write, AclCategory::Admin]),
+        ];
+        let port = start_rwlock_server(config).await;
+
+        let mut reader = TestClient::connect(port).await;
+        assert_eq!(reader.request("GET k").await, "ERROR NOAUTH authentication required");
+        assert_eq!(reader.request("AUTH reader wrong").await, "ERROR WRONGPASS invalid username or password");
+        assert_eq!(reader.request("AUTH reader reader-pass").await, "OK");
+        assert_eq!(reader.request("GET k").await, "NOT_FOUND");
+        assert_eq!(reader.request("SET k v").await, "ERROR NOPERM user reader may not run SET");
+        assert_eq!(reader.request("FLUSHDB").await, "ERROR NOPERM user reader may not run FLUSHDB");
+
+        let mut ops = TestClient::connect(port).await;
+        assert_eq!(ops.request("AUTH ops ops-pass").await, "OK");
+        assert_eq!(ops.request("SET k v").await, "OK");
+        assert_eq!(reader.request("GET k").await, "VALUE v");
+        assert_eq!(ops.request("FLUSHDB").await, "OK");
+        assert_eq!(reader.request("GET k").await, "NOT_FOUND");
+
+        // Without users every command stays open and AUTH is refused
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET k v").await, "OK");
+        assert_eq!(client.request("AUTH ops ops-pass").await, "ERROR AUTH called without any users configured");
+    }
+
+    #[test]
+    fn test_acl_denies_commands_without_flags() {
+        let all = [AclCategory::Read, AclCategory::Write, AclCategory::Admin];
+        assert!(!flags_allowed(&[], &all));
+        assert!(flags_allowed(&[CommandFlag::Write], &all));
+        assert!(!flags_allowed(&[CommandFlag::Admin], &[AclCategory::Read]));
+        for spec in COMMAND_TABLE {
+            assert!(!spec.flags.is_empty(), "{} has no ACL flags", spec.name);
+        }
+    }
+
+    #[test]
+    fn test_password_matches() {
+        assert!(password_matches("hunter2", "hunter2"));
+        assert!(!password_matches("hunter2", "hunter3"));
+        assert!(!password_matches("hunter2", "hunter"));
+        assert!(!password_matches("", "x"));
+    }
+
+    #[tokio::test]
+    async fn test_fsync_makes_sled_writes_survive_a_crash() {
+        fn copy_dir(from: &std::path::Path, to: &std::path::Path) {
+            std::fs::create_dir_all(to).unwrap();
+            for entry in std::fs::read_dir(from).unwrap() {
+                let entry = entry.unwrap();
+                let target = to.join(entry.file_name());
+                if entry.file_type().unwrap().is_dir() {
+                    copy_dir(&entry.path(), &target);
+                } else {
+                    std::fs::copy(entry.path(), target).unwrap();
+                }
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sled");
+        let path_str = path.to_str().unwrap();
+        let port = start_server(test_config("sled", path_str), Box::new(SledEngine::new(path_str).unwrap())).await;
+        let mut client = TestClient::connect(port).await;
+        for i in 0..50 {
+            assert_eq!(client.request(&format!("SET durable:{} v{}", i, i)).await, "OK");
+        }
+        assert_eq!(client.request("FSYNC").await, "OK");
+
+        // Snapshot the files while the server still runs, as a crash would leave them
+        let crashed = dir.path().join("crashed");
+        copy_dir(&path, &crashed);
+        let reopened = SledEngine::new(crashed.to_str().unwrap()).unwrap();
+        for i in 0..50 {
+            assert_eq!(reopened.get(&format!("durable:{}", i)), Some(format!("v{}", i)));
+        }
+
+        // In-memory engines accept it as a no-op
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("FSYNC").await, "OK");
+    }
+
+    #[tokio::test]
+    async fn test_compact_keeps_remaining_sled_keys() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sled");
+        let path = path.to_str().unwrap();
+        let port = start_server(test_config("sled", path), Box::new(SledEngine::new(path).unwrap())).await;
+        let mut client = TestClient::connect(port).await;
+
+        for i in 0..500 {
+            assert_eq!(client.request(&format!("SET key:{} {}", i, "v".repeat(256))).await, "OK");
+        }
+        for i in (0..500).filter(|i| i % 5 != 0) {
+            assert_eq!(client.request(&format!("DEL key:{}", i)).await, "DELETED");
+        }
+
+        assert_eq!(client.request("COMPACT").await, "OK");
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 100");
+        for i in (0..500).step_by(5) {
+            assert_eq!(client.request(&format!("GET key:{}", i)).await, format!("VALUE {}", "v".repeat(256)));
+        }
+        assert_eq!(client.request("GET key:1").await, "NOT_FOUND");
+
+        // In-memory engines accept it as a no-op
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("COMPACT").await, "OK");
+    }
+
+    #[tokio::test]
+    async fn test_hotkeys_ranks_most_accessed_keys() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("HOTKEYS").await, "ERROR key stats are disabled (set track_key_stats)");
+
+        let mut config = test_config("rwlock", "");
+        config.track_key_stats = true;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        for (key, reads) in [("cold", 1), ("hot", 9), ("warm", 4)] {
+            assert_eq!(client.request(&format!("SET {} v", key)).await, "OK");
+            for _ in 0..reads {
+                assert_eq!(client.request(&format!("GET {}", key)).await, "VALUE v");
+            }
+        }
+        // Misses are not counted
+        assert_eq!(client.request("GET missing").await, "NOT_FOUND");
+
+        assert_eq!(client.request("HOTKEYS 2").await, "HOTKEYS 2");
+        assert_eq!(client.read_line().await, "hot 10");
+        assert_eq!(client.read_line().await, "warm 5");
+
+        assert_eq!(client.request("HOTKEYS").await, "HOTKEYS 3");
+        for expected in ["hot 10", "warm 5", "cold 2"] {
+            assert_eq!(client.read_line().await, expected);
+        }
+
+        assert_eq!(client.request("FLUSHDB").await, "OK");
+        assert_eq!(client.request("HOTKEYS").await, "HOTKEYS 0");
+    }
+
+    #[tokio::test]
+    async fn test_history_lists_writes_in_order() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("HISTORY k").await, "ERROR key history is disabled (set key_history)");
+
+        let mut config = test_config("rwlock", "");
+        config.key_history = 4;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        for request in ["SET k 1", "INC k 4", "APPEND k x", "DEL k", "SET k hello world"] {
+            client.request(request).await;
+        }
+        assert_eq!(client.request("SET other 1").await, "OK");
+
+        // The oldest write fell out of the four kept
+        assert_eq!(client.request("HISTORY k").await, "HISTORY 4");
+        let mut entries = Vec::new();
+        for _ in 0..4 {
+            let line = client.read_line().await;
+            let (ts, rest) = line.split_once(' ').unwrap();
+            entries.push((ts.parse::<u64>().unwrap(), rest.to_string()));
+        }
+        let ops: Vec<&str> = entries.iter().map(|(_, rest)| rest.as_str()).collect();
+        assert_eq!(ops, ["incr 5", "append 5x", "del", "set hello world"]);
+        assert!(entries.windows(2).all(|w| w[0].0 <= w[1].0));
+
+        assert_eq!(client.request("HISTORY k 1").await, "HISTORY 1");
+        assert_eq!(client.read_line().await.split_once(' ').unwrap().1, "set hello world");
+        assert_eq!(client.request("HISTORY missing").await, "HISTORY 0");
+    }
+
+    #[tokio::test]
+    async fn test_info_sizes_histogram() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        for (key, len) in [("a", 0), ("b", 8), ("c", 9), ("d", 64), ("e", 500), ("f", 5000), ("g", 300_000)] {
+            let value = if len == 0 { "\"\"".to_string() } else { "x".repeat(len) };
+            assert_eq!(client.request(&format!("SET {} {}", key, value)).await, "OK");
+        }
+
+        assert_eq!(client.request("INFO").await, "INFO");
+        let sizes = loop {
+            let line = client.read_line().await;
+            if let Some(sizes) = line.strip_prefix("sizes:") {
+                break sizes.to_string();
+            }
+        };
+        assert_eq!(
+            sizes,
+            "0-16=3,17-64=1,65-256=0,257-1024=1,1025-4096=0,4097-16384=1,16385+=1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_numeric_overflow_and_type_errors() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request(&format!("SET n {}", i64::MAX)).await, "OK");
+        assert_eq!(client.request("INC n").await, "ERROR increment or decrement would overflow");
+        assert_eq!(client.request("GET n").await, format!("VALUE {}", i64::MAX));
+        assert_eq!(client.request(&format!("DEC fresh {}", i64::MIN)).await, "ERROR increment or decrement would overflow");
+        assert_eq!(client.request("EXISTS fresh").await, "EXISTS 0");
+
+        assert_eq!(client.request(&format!("SET m {}", i64::MIN)).await, "OK");
+        assert_eq!(client.request("DEC m").await, "ERROR increment or decrement would overflow");
+        assert_eq!(client.request("GET m").await, format!("VALUE {}", i64::MIN));
+
+        assert_eq!(client.request("SET s abc").await, "OK");
+        assert_eq!(client.request("INC s").await, "ERROR not an integer or out of range");
+        assert_eq!(client.request("DEC s").await, "ERROR not an integer or out of range");
+    }
+
+    #[tokio::test]
+    async fn test_incrlimit_concurrent_clients() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let limit = 5;
+
+        let mut tasks = Vec::new();
+        for _ in 0..4 {
+            tasks.push(tokio::spawn(async move {
+                let mut client = TestClient::connect(port).await;
+                let mut allowed = 0;
+                for _ in 0..5 {
+                    let response = client.request(&format!("INCRLIMIT hits 1 {}", limit)).await;
+                    if response.ends_with(" OK") {
+                        allowed += 1;
+                    } else {
+                        assert!(response.ends_with(" LIMITED"), "{}", response);
+                    }
+                }
+                allowed
+            }));
+        }
+        let mut allowed = 0;
+        for task in tasks {
+            allowed += task.await.unwrap();
+        }
+        assert_eq!(allowed, limit);
+
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("GET hits").await, "VALUE 20");
+        assert_eq!(client.request("INCRLIMIT hits -15 5").await, "VALUE 5 OK");
+        assert_eq!(client.request("SET word abc").await, "OK");
+        assert!(client.request("INCRLIMIT word 1 5").await.starts_with("ERROR"));
+    }
+
+    #[tokio::test]
+    async fn test_bset_bget_round_trip_raw_bytes() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        let payload: &[u8] = b"nul\0 \xff\xfe high \r\n crlf";
+
+        let mut request = format!("BSET blob {}\r\n", payload.len()).into_bytes();
+        request.extend_from_slice(payload);
+        request.extend_from_slice(b"\r\n");
+        client.writer.write_all(&request).await.unwrap();
+        assert_eq!(client.read_line().await, "OK");
+
+        client.send("BGET blob").await;
+        assert_eq!(client.read_line().await, format!("VALUE {}", payload.len()));
+        let mut body = vec![0u8; payload.len() + 2];
+        client.reader.read_exact(&mut body).await.unwrap();
+        assert_eq!(&body[..payload.len()], payload);
+        assert_eq!(&body[payload.len()..], b"\r\n");
+
+        // Framing is intact for the next command; text commands refuse raw bytes
+        assert_eq!(client.request("GET blob").await, "ERROR binary value, use BGET");
+        assert_eq!(client.request("APPEND blob x").await, "ERROR binary value, use BGET");
+
+        // Plain text written with BSET is an ordinary string
+        client.writer.write_all(b"BSET text 5\r\nhello\r\n").await.unwrap();
+        assert_eq!(client.read_line().await, "OK");
+        assert_eq!(client.request("GET text").await, "VALUE hello");
+        assert_eq!(client.request("BGET missing").await, "NOT_FOUND");
+
+        // Text writes cannot forge the binary marker
+        let error = "ERROR value must not start with NUL";
+        assert_eq!(client.request("SET forged \0bin:AAAA").await, error);
+        assert_eq!(client.request(r#"SET forged "\x00bin:AAAA""#).await, error);
+        assert_eq!(client.request("MSET forged \0bin:AAAA").await, error);
+        assert_eq!(client.request("BGET forged").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_getlen_reads_exact_byte_count() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        let payload: &[u8] = b"line one\r\nVALUE 3\r\n\0end";
+
+        let mut request = format!("BSET blob {}\r\n", payload.len()).into_bytes();
+        request.extend_from_slice(payload);
+        request.extend_from_slice(b"\r\n");
+        client.writer.write_all(&request).await.unwrap();
+        assert_eq!(client.read_line().await, "OK");
+
+        client.send("GETLEN blob").await;
+        assert_eq!(client.read_line().await, format!("VALUE {}", payload.len()));
+        let mut body = vec![0u8; payload.len() + 2];
+        client.reader.read_exact(&mut body).await.unwrap();
+        assert_eq!(&body[..payload.len()], payload);
+        assert_eq!(&body[payload.len()..], b"\r\n");
+
+        // Plain strings are returned the same way, counted in bytes
+        assert_eq!(client.request("SET word héllo").await, "OK");
+        assert_eq!(client.request("GETLEN word").await, "VALUE 6");
+        assert_eq!(client.read_line().await, "héllo");
+        assert_eq!(client.request("GETLEN missing").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_setraw_stores_multi_line_json_verbatim() {
+        use base64::Engine;
+
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        let json = "{\r\n  \"name\": \"node1\",\n  \"peers\": [\"a\", \"b\"]\r\n}\n";
+
+        client.send(&format!("SETRAW doc {}\r\n{}", json.len(), json)).await;
+        assert_eq!(client.read_line().await, "OK");
+
+        client.send("GETLEN doc").await;
+        assert_eq!(client.read_line().await, format!("VALUE {}", json.len()));
+        let mut body = vec![0u8; json.len() + 2];
+        client.reader.read_exact(&mut body).await.unwrap();
+        assert_eq!(&body[..json.len()], json.as_bytes());
+        assert_eq!(&body[json.len()..], b"\r\n");
+
+        // The next command is framed normally
+        assert_eq!(client.request("EXISTS doc").await, "EXISTS 1");
+
+        // Line breaks make it a binary value, not text
+        assert_eq!(client.request("GET doc").await, "ERROR binary value, use BGET");
+        client.send("EXPORT").await;
+        assert_eq!(
+            client.read_line().await,
+            format!(r#"{{"key":"doc","base64":"{}"}}"#, base64::engine::general_purpose::STANDARD.encode(json))
+        );
+        assert_eq!(client.read_line().await, "END");
+    }
+
+    /// The `get` of a `HookedEngine`, given the wrapped engine and the key.
+    type GetHook = Box<dyn Fn(&RwLockEngine, &str) -> Option<String> + Send + Sync>;
+
+    /// RwLockEngine whose `get` is replaced by a test hook and which counts
+    /// `set_many` and `sync` calls; everything else goes straight to the
+    /// wrapped engine.
+    struct HookedEngine {
+        inner: RwLockEngine,
+        get: GetHook,
+        batches: Arc<AtomicU64>,
+        syncs: Arc<AtomicU64>,
+    }
+
+    impl HookedEngine {
+        fn new(get: impl Fn(&RwLockEngine, &str) -> Option<String> + Send + Sync + 'static) -> Self {
+            Self {
+                inner: RwLockEngine::new("").unwrap(),
+                get: Box::new(get),
+                batches: Arc::new(AtomicU64::new(0)),
+                syncs: Arc::new(AtomicU64::new(0)),
+            }
+        }
+    }
+
+    impl KVEngineStoreTrait for HookedEngine {
+        fn get(&self, key: &str) -> Option<String> { (self.get)(&self.inner, key) }
+        fn set(&self, key: String, value: String) -> Result<()> { self.inner.set(key, value) }
+        fn delete(&self, key: &str) -> bool { self.inner.delete(key) }
+        fn unlink(&self, key: &str) -> Option<String> { self.inner.unlink(key) }
+        fn keys(&self) -> Vec<String> { self.inner.keys() }
+        fn random_key(&self) -> Option<String> { self.inner.random_key() }
+        fn scan(&self, prefix: &str) -> Vec<String> { self.inner.scan(prefix) }
+        fn ping(&self, message: &str) -> String { self.inner.ping(message) }
+        fn echo(&self, message: &str) -> String { self.inner.echo(message) }
+        fn exists(&self, key: &str) -> bool { self.inner.exists(key) }
+        fn memory_usage(&self) -> usize { self.inner.memory_usage() }
+        fn len(&self) -> usize { self.inner.len() }
+        fn dbsize(&self) -> usize { self.inner.dbsize() }
+        fn is_empty(&self) -> bool { self.inner.is_empty() }
+        fn increment(&self, key: &str, amount: Option<i64>) -> Result<i64> { self.inner.increment(key, amount) }
+        fn decrement(&self, key: &str, amount: Option<i64>) -> Result<i64> { self.inner.decrement(key, amount) }
+        fn increment_limit(&self, key: &str, amount: i64, limit: i64) -> Result<(i64, bool)> {
+            self.inner.increment_limit(key, amount, limit)
+        }
+        fn append(&self, key: &str, value: &str) -> Result<String> { self.inner.append(key, value) }
+        fn prepend(&self, key: &str, value: &str) -> Result<String> { self.inner.prepend(key, value) }
+        fn compare_and_swap(&self, key: &str, expected: &str, new: &str) -> Result<bool> {
+            self.inner.compare_and_swap(key, expected, new)
+        }
+        fn set_many(&self, pairs: &[(String, String)]) -> Result<()> {
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            self.inner.set_many(pairs)
+        }
+        fn set_with_expiry(&self, key: String, value: String, deadline_ms: u64) -> Result<()> {
+            self.inner.set_with_expiry(key, value, deadline_ms)
+        }
+        fn set_many_with_expiry(&self, entries: &[(String, String, Option<u64>)]) -> Result<()> {
+            self.inner.set_many_with_expiry(entries)
+        }
+        fn expiry(&self, key: &str) -> Option<u64> { self.inner.expiry(key) }
+        fn get_and_touch_expiry(&self, key: &str, deadline_ms: Option<u64>) -> Option<String> {
+            self.inner.get_and_touch_expiry(key, deadline_ms)
+        }
+        fn purge_expired(&self) -> Vec<String> { self.inner.purge_expired() }
+        fn keyspace_stats(&self) -> crate::store::kv_trait::KeyspaceStats { self.inner.keyspace_stats() }
+        fn truncate(&self) -> Result<()> { self.inner.truncate() }
+        fn count_keys(&self) -> Result<u64> { self.inner.count_keys() }
+        fn sync(&self) -> Result<()> { self.syncs.fetch_add(1, Ordering::SeqCst); self.inner.sync() }
+        fn compact(&self) -> Result<()> { self.inner.compact() }
+    }
+
+    #[tokio::test]
+    async fn test_monitor_streams_other_clients_commands() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("MONITOR").await, "ERROR MONITOR is disabled");
+
+        let mut config = test_config("rwlock", "");
+        config.enable_monitor = true;
+        let port = start_rwlock_server(config).await;
+        let mut monitor = TestClient::connect(port).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(monitor.request("MONITOR").await, "OK");
+
+        // The monitoring connection no longer executes commands
+        monitor.send("SET ignored 1").await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(client.request("SET user:1 alice").await, "OK");
+        let line = monitor.read_line().await;
+        let (ts, rest) = line.split_once(' ').unwrap();
+        let (secs, micros) = ts.split_once('.').unwrap();
+        assert!(secs.parse::<u64>().unwrap() > 0);
+        assert_eq!(micros.len(), 6);
+        let (id, command) = rest.split_once(' ').unwrap();
+        assert!(id.starts_with('[') && id.ends_with(']') && id[1..id.len() - 1].parse::<u64>().is_ok());
+        assert_eq!(command, "SET user:1 alice");
+
+        assert_eq!(client.request("GET ignored").await, "NOT_FOUND");
+        assert!(monitor.read_line().await.ends_with("] GET ignored"));
+    }
+
+    #[tokio::test]
+    async fn test_ping_deep_catches_a_broken_store() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("PING DEEP").await, "PONG");
+        // The sentinel is cleaned up
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 0");
+
+        // Reads come back empty although writes are accepted
+        let engine = HookedEngine::new(|_, _| None);
+        let port = start_server(test_config("rwlock", ""), Box::new(engine)).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("PING alive").await, "PONG alive");
+        assert_eq!(client.request("PING DEEP").await, "ERROR deep ping failed: read back nothing");
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 0");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_gets_do_not_block_each_other() {
+        // Each GET parks inside the engine until all of them are there, which
+        // only happens if the server lets them into the store at the same time.
+        // After a few seconds a parked GET gives up and reports a miss.
+        const READERS: usize = 4;
+        let arrived = std::sync::Mutex::new(0);
+        let all_arrived = std::sync::Condvar::new();
+        let engine = HookedEngine::new(move |inner, key| {
+            if key == "gate" {
+                let mut arrived = arrived.lock().unwrap();
+                *arrived += 1;
+                all_arrived.notify_all();
+                let (arrived, _) = all_arrived
+                    .wait_timeout_while(arrived, Duration::from_secs(5), |n| *n < READERS)
+                    .unwrap();
+                if *arrived < READERS {
+                    return None;
+                }
+            }
+            inner.get(key)
+        });
+        engine.set("gate".to_string(), "open".to_string()).unwrap();
+        let port = start_server(test_config("rwlock", ""), Box::new(engine)).await;
+
+        let started = Instant::now();
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                tokio::spawn(async move {
+                    let mut client = TestClient::connect(port).await;
+                    client.request("GET gate").await
+                })
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.await.unwrap(), "VALUE open");
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_move_does_not_lose_a_concurrent_set() {
+        // MOVE's read of the key parks in the engine while another client
+        // overwrites it; that SET must wait rather than be deleted by the MOVE
+        let slow = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let parked = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let release = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let engine = HookedEngine::new({
+            let (slow, parked, release) = (Arc::clone(&slow), Arc::clone(&parked), Arc::clone(&release));
+            move |inner, key| {
+                if key == "k" && slow.swap(false, Ordering::SeqCst) {
+                    parked.store(true, Ordering::SeqCst);
+                    // Hand this worker's queued tasks to another thread while parked
+                    tokio::task::block_in_place(|| {
+                        let deadline = Instant::now() + Duration::from_secs(5);
+                        while !release.load(Ordering::SeqCst) && Instant::now() < deadline {
+                            std::thread::sleep(Duration::from_millis(5));
+                        }
+                    });
+                }
+                inner.get(key)
+            }
+        });
+        let mut config = test_config("rwlock", "");
+        config.databases = 2;
+        config.enable_monitor = true;
+        let port = config.port;
+        let stores: Vec<Box<dyn KVEngineStoreTrait + Send + Sync>> =
+            vec![Box::new(engine), Box::new(RwLockEngine::new("").unwrap())];
+        tokio::spawn(Server::with_databases(config, stores).run_until(std::future::pending()));
+        wait_until_listening(port).await;
+
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET k old").await, "OK");
+        let mut monitor = TestClient::connect(port).await;
+        assert_eq!(monitor.request("MONITOR").await, "OK");
+        slow.store(true, Ordering::SeqCst);
+        let mover = tokio::spawn(async move {
+            let mut client = TestClient::connect(port).await;
+            client.request("MOVE k 1").await
+        });
+        while !parked.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        // MONITOR echoes the SET just before it queues for the gate
+        client.send("SET k new").await;
+        while !monitor.read_line().await.ends_with("SET k new") {}
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        release.store(true, Ordering::SeqCst);
+        assert_eq!(client.read_line().await, "OK");
+        assert_eq!(mover.await.unwrap(), "OK");
+
+        assert_eq!(client.request("GET k").await, "VALUE new");
+        assert_eq!(client.request("SELECT 1").await, "OK");
+        assert_eq!(client.request("GET k").await, "VALUE old");
+
+        // The SET's wait for the MOVE shows up as gate contention
+        client.send("STATS").await;
+        loop {
+            let line = client.read_line().await;
+            if let Some(micros) = line.strip_prefix("store_lock_wait_micros:") {
+                assert!(micros.parse::<u64>().unwrap() >= 100_000, "{}", micros);
+                break;
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_interleaved_writes_from_many_clients() {
+        let dir = tempdir().unwrap();
+        let sled_path = dir.path().join("sled");
+        let sled_path = sled_path.to_str().unwrap();
+        let cases: Vec<(&str, Box<dyn KVEngineStoreTrait + Send + Sync>)> = vec![
+            ("rwlock", Box::new(RwLockEngine::new("").unwrap())),
+            ("kv", Box::new(KvEngine::new("").unwrap())),
+            ("sled", Box::new(SledEngine::new(sled_path).unwrap())),
+        ];
+
+        for (engine, store) in cases {
+            let port = start_server(test_config(engine, sled_path), store).await;
+            let writers: Vec<_> = (0..8)
+                .map(|id| {
+                    tokio::spawn(async move {
+                        let mut client = TestClient::connect(port).await;
+                        for i in 0..25 {
+                            assert!(client.request("INC counter").await.starts_with("VALUE "));
+                            assert!(client.request("APPEND log x").await.starts_with("VALUE "));
+                            // Writes to a client's own keys read back unchanged
+                            let key = format!("own:{}:{}", id, i);
+                            assert_eq!(client.request(&format!("SET {} {}", key, i)).await, "OK");
+                            assert_eq!(client.request(&format!("GET {}", key)).await, format!("VALUE {}", i));
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.await.unwrap();
+            }
+
+            let mut client = TestClient::connect(port).await;
+            assert_eq!(client.request("GET counter").await, "VALUE 200", "{}", engine);
+            assert_eq!(client.request("GET log").await, format!("VALUE {}", "x".repeat(200)), "{}", engine);
+            assert_eq!(client.request("DBSIZE own:*").await, "DBSIZE own:* 200", "{}", engine);
+        }
+    }
+
+    #[test]
+    fn test_record_latency_by_command_group() {
+        let stats = ServerStats::new();
+        let get = Command::Get { key: "k".to_string() };
+        stats.record_latency(&get, Duration::from_micros(10));
+        stats.record_latency(&get, Duration::from_micros(10));
+        stats.record_latency(&Command::Dbsize { pattern: None }, Duration::from_micros(1));
+
+        assert_eq!(stats.latencies[latency_group(&get)].count(), 2);
+        let formatted = stats.format_stats();
+        assert!(formatted.contains("get_latency_count:2\r\n"));
+        assert!(formatted.contains("get_latency_p50_us:16\r\n"));
+        assert!(formatted.contains("other_latency_count:1\r\n"));
+        assert!(!formatted.contains("set_latency"));
+    }
+
+    #[tokio::test]
+    async fn test_stats_reports_command_latency() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET k v").await, "OK");
+
+        assert_eq!(client.request("STATS").await, "STATS");
+        let mut fields = HashMap::new();
+        while fields.len() < 64 {
+            let line = client.read_line().await;
+            let Some((name, value)) = line.split_once(':') else { break };
+            fields.insert(name.to_string(), value.to_string());
+            if name == "set_latency_p99_us" {
+                break;
+            }
+        }
+        assert_eq!(fields["set_latency_count"], "1");
+        assert!(fields["set_latency_p50_us"].parse::<u64>().unwrap() >= 1);
+        assert!(fields.contains_key("set_latency_p99_us"));
+    }
+
+    #[tokio::test]
+    async fn test_stats_reset_zeroes_counters() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        for _ in 0..5 {
+            assert_eq!(client.request("SET k v").await, "OK");
+        }
+
+        assert_eq!(client.request("STATS RESET").await, "OK");
+        assert_eq!(client.request("GET k").await, "VALUE v");
+        assert_eq!(client.request("GET k").await, "VALUE v");
+
+        // Two GETs plus this STATS; the rest of the reply is left unread
+        assert_eq!(client.request("STATS").await, "STATS");
+        let mut fields = HashMap::new();
+        while !fields.contains_key("total_commands") {
+            let line = client.read_line().await;
+            let (name, value) = line.split_once(':').unwrap();
+            fields.insert(name.to_string(), value.to_string());
+        }
+        assert_eq!(fields["total_commands"], "3");
+        assert_eq!(fields["total_connections"], "0");
+        assert_eq!(fields["active_connections"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_debug_sleep_is_opt_in_and_releases_the_gate() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("DEBUG SLEEP 0").await, "ERROR DEBUG is disabled");
+
+        let mut config = test_config("rwlock", "");
+        config.enable_debug = true;
+        let port = start_rwlock_server(config).await;
+        let mut sleeper = TestClient::connect(port).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET k v").await, "OK");
+
+        // HASH needs the database to itself; it must not queue behind the sleep
+        sleeper.send("DEBUG SLEEP 2").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let started = Instant::now();
+        assert!(client.request("HASH").await.starts_with("HASH "));
+        assert_eq!(client.request("GET k").await, "VALUE v");
+        assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+        assert_eq!(sleeper.read_line().await, "OK");
+    }
+
+    #[tokio::test]
+    async fn test_slowlog_records_slow_commands() {
+        let mut config = test_config("rwlock", "");
+        config.slowlog_threshold_ms = 50;
+        config.enable_debug = true;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        assert_eq!(client.request("SET fast v").await, "OK");
+        assert_eq!(client.request("DEBUG SLEEP 0.1").await, "OK");
+
+        assert_eq!(client.request("SLOWLOG GET").await, "SLOWLOG 1");
+        let entry = client.read_line().await;
+        assert!(entry.starts_with("id=0 time="), "{}", entry);
+        assert!(entry.ends_with(" cmd=DEBUG SLEEP 0.1"), "{}", entry);
+        let duration_us: u64 = entry
+            .split(' ')
+            .find_map(|field| field.strip_prefix("duration_us="))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(duration_us >= 100_000);
+
+        assert_eq!(client.request("SLOWLOG RESET").await, "OK");
+        assert_eq!(client.request("SLOWLOG GET 5").await, "SLOWLOG 0");
+    }
+
+    #[tokio::test]
+    async fn test_listen_on_multiple_addresses() {
+        let mut config = test_config("rwlock", "");
+        let second = free_port();
+        config.listen = vec![
+            format!("127.0.0.1:{}", config.port),
+            format!("127.0.0.1:{}", second),
+        ];
+        let first = start_rwlock_server(config).await;
+
+        let mut a = TestClient::connect(first).await;
+        let mut b = TestClient::connect(second).await;
+        assert_eq!(a.request("SET shared 1").await, "OK");
+        assert_eq!(b.request("GET shared").await, "VALUE 1");
+    }
+
+    #[tokio::test]
+    async fn test_mget_compact_matches_verbose() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET a 1").await, "OK");
+        assert_eq!(client.request("SET c three words").await, "OK");
+
+        assert_eq!(client.request("MGET a b c d").await, "VALUES 2");
+        let verbose: Vec<String> = vec![
+            client.read_line().await,
+            client.read_line().await,
+            client.read_line().await,
+            client.read_line().await,
+        ];
+        assert_eq!(verbose, ["a 1", "b NOT_FOUND", "c three words", "d NOT_FOUND"]);
+
+        assert_eq!(client.request("MGET a b c d COMPACT").await, "VALUES 4");
+        let mut compact = Vec::new();
+        for _ in 0..5 {
+            compact.push(client.read_line().await);
+        }
+        assert_eq!(compact, ["1", "", "three words", "", "PRESENT 1010"]);
+
+        // Same information in both formats
+        for (line, (value, present)) in verbose.iter().zip(compact.iter().zip("1010".chars())) {
+            if present == '1' {
+                assert!(line.ends_with(&format!(" {}", value)));
+            } else {
+                assert!(line.ends_with(" NOT_FOUND"));
+            }
+        }
+
+        // All misses still get a full positional reply
+        assert_eq!(client.request("MGET x y compact").await, "VALUES 2");
+        assert_eq!(client.read_line().await, "");
+        assert_eq!(client.read_line().await, "");
+        assert_eq!(client.read_line().await, "PRESENT 00");
+        assert_eq!(client.request("GET a").await, "VALUE 1");
+    }
+
+    #[tokio::test]
+    async fn test_mget_ten_thousand_keys() {
+        const KEYS: usize = 10_000;
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        let pairs: Vec<String> = (0..KEYS).filter(|i| i % 2 == 0).map(|i| format!("k{} v{}", i, i)).collect();
+        assert_eq!(client.request(&format!("MSET {}", pairs.join(" "))).await, "OK");
+
+        let keys: Vec<String> = (0..KEYS).map(|i| format!("k{}", i)).collect();
+        assert_eq!(client.request(&format!("MGET {}", keys.join(" "))).await, format!("VALUES {}", KEYS / 2));
+        for i in 0..KEYS {
+            let expected = if i % 2 == 0 { format!("k{} v{}", i, i) } else { format!("k{} NOT_FOUND", i) };
+            assert_eq!(client.read_line().await, expected);
+        }
+        assert_eq!(client.request("GET k0").await, "VALUE v0");
+    }
+
+    #[tokio::test]
+    async fn test_append_prepend_len_flag() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        // Lengths are in bytes: "héllo" is 6 bytes, "wörld" 6 more
+        assert_eq!(client.request("APPEND buf héllo LEN").await, "LEN 6");
+        assert_eq!(client.request("APPEND buf wörld LEN").await, "LEN 12");
+        assert_eq!(client.request("APPEND buf two words LEN").await, "LEN 21");
+        assert_eq!(client.request("PREPEND buf >> LEN").await, "LEN 23");
+        assert_eq!(client.request("GET buf").await, "VALUE >>héllowörldtwo words");
+
+        // Default replies are unchanged
+        assert_eq!(client.request("APPEND buf !").await, "VALUE >>héllowörldtwo words!");
+    }
+
+    /// Wait for `server` to return `Ok` and check `port` no longer accepts.
+    async fn wait_for_shutdown(server: tokio::task::JoinHandle<Result<()>>, port: u16) {
+        let result = tokio::time::timeout(Duration::from_secs(10), server)
+            .await
+            .expect("server did not shut down")
+            .unwrap();
+        assert!(result.is_ok());
+        assert!(TcpStream::connect(("127.0.0.1", port)).await.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sigterm_shuts_down_gracefully() {
+        let config = test_config("rwlock", "");
+        let port = config.port;
+        let engine = HookedEngine::new(|inner, key| inner.get(key));
+        let syncs = Arc::clone(&engine.syncs);
+        let server = tokio::spawn(Server::new(config, Box::new(engine)).run());
+        wait_until_listening(port).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET k v").await, "OK");
+        assert_eq!(syncs.load(Ordering::SeqCst), 0);
+
+        let status = std::process::Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        wait_for_shutdown(server, port).await;
+        // The idle connection was closed during the drain, then the store flushed
+        assert_eq!(client.read_line().await, "");
+        assert_eq!(syncs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_command_stops_server() {
+        let config = test_config("rwlock", "");
+        let port = config.port;
+        let server = tokio::spawn(
+            Server::new(config, Box::new(RwLockEngine::new("").unwrap())).run_until(std::future::pending()),
+        );
+        wait_until_listening(port).await;
+        let mut client = TestClient::connect(port).await;
+        let mut other = TestClient::connect(port).await;
+        assert_eq!(other.request("GET missing").await, "NOT_FOUND");
+
+        assert_eq!(client.request("SHUTDOWN").await, "OK");
+        wait_for_shutdown(server, port).await;
+        assert_eq!(other.read_line().await, "");
+    }
+
+    #[tokio::test]
+    async fn test_configure_socket_applies_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let _client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+
+        let mut config = test_config("rwlock", "");
+        config.tcp_keepalive_secs = 30;
+        configure_socket(&socket, &config).unwrap();
+        assert!(socket.nodelay().unwrap());
+        assert!(socket2::SockRef::from(&socket).keepalive().unwrap());
+
+        config.tcp_nodelay = false;
+        configure_socket(&socket, &config).unwrap();
+        assert!(!socket.nodelay().unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_sets_with_and_without_write_batching() {
+        const CLIENTS: usize = 8;
+        const WRITES: usize = 200;
+
+        for batching in [false, true] {
+            let mut config = test_config("rwlock", "");
+            config.write_batching = batching;
+            let engine = HookedEngine::new(|inner, key| inner.get(key));
+            let batches = Arc::clone(&engine.batches);
+            let port = start_server(config, Box::new(engine)).await;
+
+            let writers: Vec<_> = (0..CLIENTS)
+                .map(|c| {
+                    tokio::spawn(async move {
+                        let mut client = TestClient::connect(port).await;
+                        for i in 0..WRITES {
+                            assert_eq!(client.request(&format!("SET c{}:{} v{}", c, i, i)).await, "OK");
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.await.unwrap();
+            }
+
+            let mut client = TestClient::connect(port).await;
+            assert_eq!(client.request("DBSIZE").await, format!("DBSIZE {}", CLIENTS * WRITES));
+            assert_eq!(client.request("GET c3:150").await, "VALUE v150");
+            // Batched SETs all went through set_many, never more than one call per write
+            let batches = batches.load(Ordering::SeqCst) as usize;
+            if batching {
+                assert!((1..=CLIENTS * WRITES).contains(&batches), "{}", batches);
+            } else {
+                assert_eq!(batches, 0);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_batcher_applies_waiting_sets_in_one_store_call() {
+        let engine = HookedEngine::new(|inner, key| inner.get(key));
+        let batches = Arc::clone(&engine.batches);
+        let store: Arc<dyn KVEngineStoreTrait + Send + Sync> = Arc::new(engine);
+        let (queue, queued) = tokio::sync::mpsc::channel(MAX_WRITE_BATCH);
+
+        // Everything is waiting before the batcher starts, so one batch takes it all
+        let mut replies = Vec::new();
+        for i in 0..10 {
+            let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+            queue.send((format!("k{}", i), i.to_string(), reply_tx)).await.unwrap();
+            replies.push(reply_rx);
+        }
+        tokio::spawn(run_write_batcher(Arc::clone(&store), queued));
+        for reply in replies {
+            reply.await.unwrap().unwrap();
+        }
+
+        assert_eq!(batches.load(Ordering::SeqCst), 1);
+        assert_eq!(store.len(), 10);
+        assert_eq!(store.get("k7"), Some("7".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_client_kill_closes_only_that_connection() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut victim = TestClient::connect(port).await;
+        let mut admin = TestClient::connect(port).await;
+        assert_eq!(victim.request("SET k v").await, "OK");
+
+        let victim_addr = victim.writer.local_addr().unwrap().to_string();
+        assert_eq!(admin.request("CLIENT LIST").await, "CLIENT LIST");
+        let mut victim_id = None;
+        loop {
+            let line = admin.read_line().await;
+            if line == "END" {
+                break;
+            }
+            if line.contains(&format!("addr={} ", victim_addr)) {
+                victim_id = line.strip_prefix("id=").and_then(|rest| rest.split(' ').next()).map(str::to_string);
+            }
+        }
+        let victim_id = victim_id.expect("victim missing from CLIENT LIST");
+
+        assert_eq!(admin.request(&format!("CLIENT KILL ID {}", victim_id)).await, "OK");
+        assert_eq!(victim.read_line().await, "");
+        assert_eq!(admin.request("GET k").await, "VALUE v");
+
+        // The closed connection leaves the client table
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(admin.request(&format!("CLIENT KILL ID {}", victim_id)).await, "NOT_FOUND");
+        assert_eq!(admin.request("CLIENT KILL ID 999999").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_unlink_removes_keys_immediately() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        let large = "x".repeat(100_000);
+        assert_eq!(client.request(&format!("SET big {}", large)).await, "OK");
+        assert_eq!(client.request("SET small v").await, "OK");
+
+        assert_eq!(client.request("UNLINK big small missing").await, "UNLINKED 2");
+        assert_eq!(client.request("GET big").await, "NOT_FOUND");
+        assert_eq!(client.request("EXISTS big small").await, "EXISTS 0");
+        assert_eq!(client.request("UNLINK big").await, "UNLINKED 0");
+    }
+
+    #[tokio::test]
+    async fn test_maxmemory_lru_evicts_least_recently_used() {
+        let mut config = test_config("rwlock", "");
+        config.maxmemory_bytes = 3500;
+        config.eviction_policy = EvictionPolicy::Lru;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        let value = "v".repeat(1000);
+
+        for key in ["k1", "k2", "k3"] {
+            assert_eq!(client.request(&format!("SET {} {}", key, value)).await, "OK");
+        }
+        assert!(client.request("GET k1").await.starts_with("VALUE "));
+
+        // k2 is now the least recently used key and makes room for k4
+        assert_eq!(client.request(&format!("SET k4 {}", value)).await, "OK");
+        assert_eq!(client.request("EXISTS k1 k2 k3 k4").await, "EXISTS 3");
+        assert_eq!(client.request("GET k2").await, "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_maxmemory_noeviction_rejects_writes() {
+        let mut config = test_config("rwlock", "");
+        config.maxmemory_bytes = 1500;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        let value = "v".repeat(1000);
+
+        assert_eq!(client.request(&format!("SET a {}", value)).await, "OK");
+        assert_eq!(
+            client.request(&format!("SET b {}", value)).await,
+            "ERROR OOM command not allowed when used memory > maxmemory"
+        );
+        assert_eq!(client.request("EXISTS a b").await, "EXISTS 1");
+
+        // Deletes still go through and free the budget
+        assert_eq!(client.request("DEL a").await, "DELETED");
+        assert_eq!(client.request(&format!("SET b {}", value)).await, "OK");
+    }
+
+    #[tokio::test]
+    async fn test_maxmemory_charges_only_growth_and_refused_writes_do_not_evict() {
+        let mut config = test_config("rwlock", "");
+        config.maxmemory_bytes = 2500;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        let value = "v".repeat(1000);
+
+        // Overwriting a key with a value of the same size adds nothing
+        assert_eq!(client.request(&format!("SET a {}", value)).await, "OK");
+        assert_eq!(client.request(&format!("SET b {}", value)).await, "OK");
+        assert_eq!(client.request(&format!("SET a {}", "w".repeat(1000))).await, "OK");
+
+        let mut config = test_config("rwlock", "");
+        config.maxmemory_bytes = 2500;
+        config.eviction_policy = EvictionPolicy::Lru;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request(&format!("SET a {}", value)).await, "OK");
+        assert_eq!(client.request("HSET h f v").await, "OK");
+        let large = "v".repeat(1500);
+
+        // A write that fails WRONGTYPE must not evict anything first
+        assert_eq!(
+            client.request(&format!("APPEND h {}", large)).await,
+            format!("ERROR {}", WRONGTYPE)
+        );
+        assert_eq!(client.request("EXISTS a h").await, "EXISTS 2");
+
+        // Nor may one refused in read-only mode
+        assert_eq!(client.request("CONFIG SET read_only on").await, "OK");
+        assert!(client.request(&format!("SET c {}", large)).await.starts_with("ERROR READONLY"));
+        assert_eq!(client.request("EXISTS a h").await, "EXISTS 2");
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_unreachable_broker() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert!(client.request("HEALTH").await.starts_with("HEALTH ok uptime="));
+
+        // Nothing listens on the broker port, so the connection never comes up
+        let mut config = test_config("rwlock", "");
+        config.replication.enabled = true;
+        config.replication.mqtt_broker = "127.0.0.1".to_string();
+        config.replication.mqtt_port = free_port();
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        assert!(client.request("HEALTH").await.starts_with("HEALTH degraded replication uptime="));
+
+        assert_eq!(client.request("REPLICATE DISABLE").await, "OK");
+        assert!(client.request("HEALTH").await.starts_with("HEALTH ok "));
+    }
+
+    #[tokio::test]
+    async fn test_node_reports_replication_identity() {
+        let mut config = test_config("rwlock", "");
+        config.replication.client_id = "node-a".to_string();
+        config.replication.topic_prefix = "cluster7".to_string();
+        let port = start_rwlock_server(config.clone()).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(
+            client.request("NODE").await,
+            "NODE node_id=node-a topic_prefix=cluster7 codec=cbor replication_enabled=false"
+        );
+
+        config.port = free_port();
+        config.replication.enabled = true;
+        config.replication.mqtt_broker = "127.0.0.1".to_string();
+        config.replication.mqtt_port = free_port();
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(
+            client.request("NODE").await,
+            "NODE node_id=node-a topic_prefix=cluster7 codec=cbor replication_enabled=true"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_lines_are_json() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+        assert_eq!(client.request("SET b two words").await, "OK");
+        assert_eq!(client.request(r#"SET a "tab\there \"q\" é""#).await, "OK");
+
+        // Sorted by key, with values escaped by serde_json
+        client.send("EXPORT").await;
+        for (key, value) in [("a", "tab\there \"q\" é"), ("b", "two words")] {
+            let entry: serde_json::Value = serde_json::from_str(&client.read_line().await).unwrap();
+            assert_eq!(entry["key"], key);
+            assert_eq!(entry["value"], value);
+        }
+        assert_eq!(client.read_line().await, "END");
+    }
+
+    #[tokio::test]
+    async fn test_import_unicode_lines() {
+        let port = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut client = TestClient::connect(port).await;
+
+        client.send("IMPORT").await;
+        client.send(r#"{"key":"greeting","value":"xin chào"}"#).await;
+        client.send(r#"{"key":"emoji","value":"🦀 \u00e9"}"#).await;
+        client.send(r#"{"value":"spaced  ","key":"ключ"}"#).await;
+        assert_eq!(client.request("END").await, "IMPORTED 3");
+
+        assert_eq!(client.request("GET greeting").await, "VALUE xin chào");
+        assert_eq!(client.request("GET emoji").await, "VALUE 🦀 é");
+        assert_eq!(client.request("GET ключ").await, "VALUE spaced  ");
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 3");
+    }
+
+    #[tokio::test]
+    async fn test_import_caps_lines_at_max_bulk_keys() {
+        let mut config = test_config("rwlock", "");
+        config.max_bulk_keys = 2;
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        client.send("IMPORT").await;
+        for key in ["a", "b", "c"] {
+            client.send(&format!(r#"{{"key":"{}","value":"v"}}"#, key)).await;
+        }
+        assert_eq!(client.request("END").await, "ERROR too many keys");
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 0");
+
+        client.send("IMPORT").await;
+        client.send(r#"{"key":"a","value":"v"}"#).await;
+        client.send(r#"{"key":"b","value":"v"}"#).await;
+        assert_eq!(client.request("END").await, "IMPORTED 2");
+    }
+
+    #[tokio::test]
+    async fn test_import_lines_are_bounded_and_checked_against_the_acl() {
+        let mut config = test_config("rwlock", "");
+        config.users = vec![UserConfig {
+            name: "ops".to_string(),
+            password: "ops-pass".to_string(),
+            categories: vec![AclCategory::Read, AclCategory::Write, AclCategory::Admin],
+        }];
+        let port = start_rwlock_server(config).await;
+        let mut client = TestClient::connect(port).await;
+
+        // Refused before any pair is kept, and the lines are still drained
+        client.send("IMPORT").await;
+        client.send(r#"{"key":"a","value":"v"}"#).await;
+        assert_eq!(client.request("END").await, "ERROR NOAUTH authentication required");
+        assert_eq!(client.request("AUTH ops ops-pass").await, "OK");
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 0");
+
+        // An oversized line is skipped without dropping the connection
+        client.send("IMPORT").await;
+        client.send(&format!(r#"{{"key":"big","value":"{}"}}"#, "x".repeat(MAX_LINE_BYTES))).await;
+        client.send(r#"{"key":"a","value":"v"}"#).await;
+        assert_eq!(client.request("END").await, "ERROR line too long");
+        assert_eq!(client.request("DBSIZE").await, "DBSIZE 0");
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        async fn export(client: &mut TestClient) -> Vec<String> {
+            client.send("EXPORT").await;
+            let mut lines = Vec::new();
+            loop {
+                let line = client.read_line().await;
+                if line == "END" {
+                    return lines;
+                }
+                lines.push(line);
+            }
+        }
+
+        let port_a = start_rwlock_server(test_config("rwlock", "")).await;
+        let port_b = start_rwlock_server(test_config("rwlock", "")).await;
+        let mut a = TestClient::connect(port_a).await;
+        let mut b = TestClient::connect(port_b).await;
+        assert!(export(&mut a).await.is_empty());
+
+        assert_eq!(a.request("SET plain value with spaces").await, "OK");
+        assert_eq!(a.request(r#"SET quoted "tab\there \"q\" \\ é""#).await, "OK");
+        assert_eq!(a.request("HSET h f v").await, "OK");
+        a.writer.write_all(b"BSET bin 3\r\na\r\n\r\n").await.unwrap();
+        assert_eq!(a.read_line().await, "OK");
+
+        let lines = export(&mut a).await;
+        assert_eq!(
+            lines,
+            [
+                r#"{"key":"bin","base64":"YQ0K"}"#,
+                r#"{"key":"h","hash":{"f":"v"}}"#,
+                r#"{"key":"plain","value":"value with spaces"}"#,
+                r#"{"key":"quoted","value":"tab\there \"q\" \\ é"}"#,
+            ]
+        );
+
+        b.send("IMPORT").await;
+        for line in &lines {
+            b.send(line).await;
+        }
+        assert_eq!(b.request("END").await, "IMPORTED 4");
+        assert_eq!(export(&mut b).await, lines);
+        assert_eq!(b.request("HGET h f").await, "VALUE v");
+        b.send("BGET bin").await;
+        assert_eq!(b.read_line().await, "VALUE 3");
+        let mut body = [0u8; 5];
+        b.reader.read_exact(&mut body).await.unwrap();
+        assert_eq!(&body, b"a\r\n\r\n");
+
+        // Text lines cannot forge an encoded value or smuggle a line break
+        for forged in [r#"{"key":"f","value":"\u0000hash:{}"}"#, r#"{"key":"f","value":"x\r\nVALUE y"}"#] {
+            b.send("IMPORT").await;
+            b.send(forged).await;
+            assert_eq!(b.request("END").await, "ERROR bad line 1");
+        }
+        assert_eq!(b.request("EXISTS f").await, "EXISTS 0");
+
+        // A bad line rejects the whole import, and the connection stays in sync
+        b.send("IMPORT").await;
+        b.send(r#"{"key":"new","value":"x"}"#).await;
+        b.send("not json").await;
+        assert_eq!(b.request("END").await, "ERROR bad line 2");
+        assert_eq!(b.request("GET new").await, "NOT_FOUND");
+    }
+}
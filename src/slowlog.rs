@@ -0,0 +1,128 @@
+//! # Slow Command Log
+//!
+//! Keeps the most recent commands whose execution took longer than
+//! `slowlog_threshold_ms`, in the spirit of Redis's `SLOWLOG`. The log is a
+//! bounded ring buffer: once `slowlog_max_len` entries are held, recording a
+//! new one evicts the oldest. Only slow commands take the lock, so the normal
+//! path costs a single comparison.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Longest command text kept per entry; longer commands are cut here so a
+/// slow `SET` with a large value does not pin the value in memory.
+const MAX_COMMAND_CHARS: usize = 128;
+
+/// One slow command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowLogEntry {
+    /// Increasing id, unique for the life of the server
+    pub id: u64,
+    /// When the command finished (Unix seconds)
+    pub unix_time: u64,
+    /// Id of the client that sent it, as in `CLIENT LIST`
+    pub client_id: u64,
+    /// Execution time in microseconds
+    pub duration_us: u64,
+    /// The request line, possibly truncated
+    pub command: String,
+}
+
+/// Bounded log of the most recent slow commands.
+#[derive(Debug)]
+pub struct SlowLog {
+    capacity: usize,
+    inner: Mutex<SlowLogInner>,
+}
+
+#[derive(Debug, Default)]
+struct SlowLogInner {
+    next_id: u64,
+    entries: VecDeque<SlowLogEntry>,
+}
+
+impl SlowLog {
+    /// Create a log holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(SlowLogInner::default()),
+        }
+    }
+
+    /// Record a command that took `elapsed`.
+    pub fn record(&self, client_id: u64, command: &str, elapsed: Duration) {
+        if self.capacity == 0 {
+            return;
+        }
+        let command = match command.char_indices().nth(MAX_COMMAND_CHARS) {
+            Some((cut, _)) => format!("{}...", &command[..cut]),
+            None => command.to_string(),
+        };
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        if inner.entries.len() == self.capacity {
+            inner.entries.pop_back();
+        }
+        inner.entries.push_front(SlowLogEntry {
+            id,
+            unix_time,
+            client_id,
+            duration_us: elapsed.as_micros().min(u64::MAX as u128) as u64,
+            command,
+        });
+    }
+
+    /// Up to `count` entries, newest first.
+    pub fn get(&self, count: usize) -> Vec<SlowLogEntry> {
+        let inner = self.inner.lock().unwrap();
+        inner.entries.iter().take(count).cloned().collect()
+    }
+
+    /// Drop every entry. Ids keep increasing.
+    pub fn reset(&self) {
+        self.inner.lock().unwrap().entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_newest_entries_up_to_capacity() {
+        let log = SlowLog::new(2);
+        for i in 0..3 {
+            log.record(7, &format!("GET k{}", i), Duration::from_millis(20));
+        }
+
+        let entries = log.get(10);
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].id, entries[0].command.as_str()), (2, "GET k2"));
+        assert_eq!((entries[1].id, entries[1].command.as_str()), (1, "GET k1"));
+        assert_eq!(entries[0].client_id, 7);
+        assert_eq!(entries[0].duration_us, 20_000);
+        assert_eq!(log.get(1).len(), 1);
+
+        log.reset();
+        assert!(log.get(10).is_empty());
+        log.record(7, "GET k", Duration::ZERO);
+        assert_eq!(log.get(1)[0].id, 3);
+    }
+
+    #[test]
+    fn test_long_commands_are_truncated() {
+        let log = SlowLog::new(1);
+        log.record(1, &format!("SET k {}", "é".repeat(500)), Duration::ZERO);
+        let command = &log.get(1)[0].command;
+        assert_eq!(command.chars().count(), MAX_COMMAND_CHARS + 3);
+        assert!(command.ends_with("..."));
+    }
+}
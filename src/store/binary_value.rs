@@ -0,0 +1,61 @@
+//! # Binary Values
+//!
+//! Values written with `BSET` may contain any bytes, but engines store
+//! `String`s. Payloads that are already plain protocol text are stored as-is;
+//! anything else (non-UTF-8, NUL, CR/LF) is stored as a type marker followed
+//! by base64. Encoded values stay ordinary strings, so persistence,
+//! replication, `DUMP`/`RESTORE` and the Merkle tree need no changes.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// Prefix marking a stored value as base64-encoded bytes. Like the hash
+/// marker it starts with NUL; text writes starting with NUL are refused
+/// (`validate_value`), so only `BSET` produces it.
+const BINARY_MARKER: &str = "\u{0}bin:";
+
+/// Error returned when a text command reads or edits an encoded binary value.
+pub const BINARY_VALUE: &str = "binary value, use BGET";
+
+/// Whether a stored value holds encoded bytes.
+pub fn is_binary(value: &str) -> bool {
+    value.starts_with(BINARY_MARKER)
+}
+
+/// Encode raw bytes into a stored value.
+pub fn encode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.contains(['\0', '\r', '\n']) => text.to_string(),
+        _ => format!("{}{}", BINARY_MARKER, STANDARD.encode(bytes)),
+    }
+}
+
+/// Decode a stored value back into the bytes it represents.
+pub fn decode(value: &str) -> Vec<u8> {
+    match value.strip_prefix(BINARY_MARKER) {
+        Some(b64) => STANDARD.decode(b64).unwrap_or_else(|_| value.as_bytes().to_vec()),
+        None => value.as_bytes().to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_is_stored_verbatim() {
+        assert_eq!(encode(b"hello world"), "hello world");
+        assert!(!is_binary("hello world"));
+        assert_eq!(decode("hello world"), b"hello world");
+    }
+
+    #[test]
+    fn test_raw_bytes_round_trip() {
+        for bytes in [&b"a\0b"[..], &[0xff, 0xfe, 0x00, 0x80], b"line\r\nbreak", b"\0hash:{}"] {
+            let stored = encode(bytes);
+            assert!(is_binary(&stored), "{:?}", bytes);
+            assert!(!stored.contains(['\r', '\n']));
+            assert_eq!(decode(&stored), bytes);
+        }
+    }
+}
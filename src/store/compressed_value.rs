@@ -0,0 +1,161 @@
+//! # Compressed Values
+//!
+//! `RwLockEngine` can hold large values compressed in memory. A value longer
+//! than the configured threshold is packed with zstd and kept packed only if
+//! that actually saves space. The engine unpacks on every
+//! read, so GET, replication, `DUMP` and Merkle hashing all see the original
+//! string and nodes with different thresholds still agree on hashes.
+//!
+//! With integer encoding on, a value that is the canonical decimal form of an
+//! `i64` (no sign or leading zeros beyond what `i64::to_string` prints) is
+//! held as the number itself and printed back on read, so reads, `DUMP` and
+//! Merkle hashes see exactly the string that was written.
+//!
+//! The same codec compresses large GET replies (`VALUEZ zstd <len>`) for
+//! clients that opt in with `HELLO <proto> COMPRESS`; the payload is a
+//! standard zstd frame.
+
+use anyhow::{anyhow, Result};
+
+/// Name of this codec in `VALUEZ` replies.
+pub const ALGORITHM: &str = "zstd";
+
+/// zstd level used for packing: fast, with most of the gain on repetitive text.
+const LEVEL: i32 = 3;
+
+/// A value as held in memory by the engine.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stored {
+    /// Stored as written
+    Raw(String),
+    /// zstd-packed UTF-8 bytes
+    Packed(Vec<u8>),
+    /// A canonical integer, stringified on read
+    Int(i64),
+}
+
+impl Stored {
+    /// Wrap `value`, packing it if it is longer than `threshold` bytes and
+    /// packing makes it smaller. A threshold of 0 disables compression.
+    pub fn new(value: String, threshold: usize) -> Self {
+        if threshold != 0 && value.len() > threshold {
+            if let Ok(packed) = compress(value.as_bytes()) {
+                if packed.len() < value.len() {
+                    return Stored::Packed(packed);
+                }
+            }
+        }
+        Stored::Raw(value)
+    }
+
+    /// Like `new`, but a canonical integer is kept as an `Int`.
+    pub fn with_int_encoding(value: String, threshold: usize) -> Self {
+        match value.parse::<i64>() {
+            Ok(n) if is_canonical_int(&value) => Stored::Int(n),
+            _ => Self::new(value, threshold),
+        }
+    }
+
+    /// The original value, or an error if a packed value no longer unpacks.
+    pub fn value(&self) -> Result<String> {
+        match self {
+            Stored::Raw(value) => Ok(value.clone()),
+            Stored::Int(n) => Ok(n.to_string()),
+            Stored::Packed(packed) => {
+                String::from_utf8(decompress(packed)?).map_err(|_| anyhow!("corrupt packed value: not UTF-8"))
+            }
+        }
+    }
+
+    /// The original value, consuming `self` so a raw value is not copied.
+    pub fn into_value(self) -> Result<String> {
+        match self {
+            Stored::Raw(value) => Ok(value),
+            packed => packed.value(),
+        }
+    }
+
+    /// Bytes this value occupies in memory.
+    pub fn stored_len(&self) -> usize {
+        match self {
+            Stored::Raw(value) => value.len(),
+            Stored::Packed(packed) => packed.len(),
+            // Held inline in the enum, with nothing on the heap
+            Stored::Int(_) => 0,
+        }
+    }
+}
+
+/// Whether `value` is exactly how `i64::to_string` prints some number, so
+/// storing the number loses nothing. Call only on strings that parse as `i64`.
+fn is_canonical_int(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    !value.starts_with('+') && (!digits.starts_with('0') || value == "0")
+}
+
+/// Pack `input` as a zstd frame.
+fn compress(input: &[u8]) -> std::io::Result<Vec<u8>> {
+    zstd::bulk::compress(input, LEVEL)
+}
+
+/// Unpack a zstd frame produced by `compress`.
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>> {
+    zstd::stream::decode_all(input).map_err(|e| anyhow!("corrupt packed value: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let inputs: Vec<Vec<u8>> = vec![
+            Vec::new(),
+            b"abc".to_vec(),
+            b"abcabcabcabcabcabc".to_vec(),
+            "héllo wörld ".repeat(50).into_bytes(),
+            (0..5000u32).map(|i| (i.wrapping_mul(7919) % 251) as u8).collect(),
+        ];
+        for input in inputs {
+            assert_eq!(decompress(&compress(&input).unwrap()).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_only_large_compressible_values_are_packed() {
+        let large = "x".repeat(10_000);
+        let stored = Stored::new(large.clone(), 1024);
+        assert!(matches!(stored, Stored::Packed(_)));
+        assert!(stored.stored_len() < 100);
+        assert_eq!(stored.value().unwrap(), large);
+
+        assert_eq!(Stored::new(large.clone(), 0), Stored::Raw(large.clone()));
+        assert_eq!(Stored::new("short".to_string(), 4), Stored::Raw("short".to_string()));
+    }
+
+    #[test]
+    fn test_only_canonical_integers_are_int_encoded() {
+        for value in ["0", "42", "-17", "9223372036854775807", "-9223372036854775808"] {
+            let stored = Stored::with_int_encoding(value.to_string(), 0);
+            assert!(matches!(stored, Stored::Int(_)), "{}", value);
+            assert_eq!(stored.value().unwrap(), value);
+        }
+        // These parse as i64 but would not print back the same
+        for value in ["007", "+5", "-0", "00", "9223372036854775808", "1.5", "12 ", "abc"] {
+            let stored = Stored::with_int_encoding(value.to_string(), 0);
+            assert_eq!(stored, Stored::Raw(value.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_corrupt_packed_values_are_errors() {
+        assert!(decompress(b"not a zstd frame").is_err());
+        let mut packed = compress("abc".repeat(100).as_bytes()).unwrap();
+        packed.truncate(packed.len() / 2);
+        assert!(decompress(&packed).is_err());
+
+        assert!(Stored::Packed(packed).value().is_err());
+        let not_utf8 = compress(&[0xff; 64]).unwrap();
+        assert!(Stored::Packed(not_utf8).into_value().is_err());
+    }
+}
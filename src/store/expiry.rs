@@ -0,0 +1,127 @@
+//! # Key Expiry
+//!
+//! Deadlines for keys written with `SETEX`/`PSETEX`, kept beside the data of the
+//! in-memory engines. Deadlines are absolute Unix times in milliseconds.
+//!
+//! Expiry is enforced in two ways:
+//! - **Lazily**: engines hide an expired key from reads and drop it before any
+//!   write that would otherwise build on its stale value.
+//! - **Actively**: the server periodically calls `purge_expired` so keys that
+//!   are never touched again still leave the store (and the Merkle tree).
+//!
+//! Callers hold the engine's data lock while updating the map, so a value and
+//! its deadline always change together. Lock order is data, then expiry.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current Unix time in milliseconds.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Per-key expiry deadlines for an in-memory engine.
+#[derive(Default)]
+pub struct ExpiryMap {
+    deadlines: Mutex<HashMap<String, u64>>,
+}
+
+impl ExpiryMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the deadline of `key`.
+    pub fn set(&self, key: &str, deadline_ms: u64) {
+        self.deadlines.lock().unwrap().insert(key.to_string(), deadline_ms);
+    }
+
+    /// Forget the deadline of `key`, making it persistent.
+    pub fn clear(&self, key: &str) {
+        self.deadlines.lock().unwrap().remove(key);
+    }
+
+    /// Forget every deadline.
+    pub fn clear_all(&self) {
+        self.deadlines.lock().unwrap().clear();
+    }
+
+    /// Deadline of `key`, if it has one.
+    pub fn deadline(&self, key: &str) -> Option<u64> {
+        self.deadlines.lock().unwrap().get(key).copied()
+    }
+
+    /// Whether `key` has a deadline that has passed.
+    pub fn is_expired(&self, key: &str) -> bool {
+        self.deadline(key).is_some_and(|d| d <= now_millis())
+    }
+
+    /// Drop `key` from `data` if it has expired. Returns true if it was dropped.
+    ///
+    /// Called with the engine's write lock held at the start of every
+    /// read-modify-write operation.
+    pub fn expire_if_due<V>(&self, data: &mut HashMap<String, V>, key: &str) -> bool {
+        if !self.is_expired(key) {
+            return false;
+        }
+        self.clear(key);
+        data.remove(key);
+        true
+    }
+
+    /// Drop every expired key from `data`, returning the keys removed.
+    pub fn purge<V>(&self, data: &mut HashMap<String, V>) -> Vec<String> {
+        let now = now_millis();
+        let mut deadlines = self.deadlines.lock().unwrap();
+        let expired: Vec<String> = deadlines
+            .iter()
+            .filter(|(_, d)| **d <= now)
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in &expired {
+            deadlines.remove(key);
+            data.remove(key);
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expire_if_due_only_drops_past_deadlines() {
+        let map = ExpiryMap::new();
+        let mut data = HashMap::new();
+        data.insert("old".to_string(), "1".to_string());
+        data.insert("new".to_string(), "2".to_string());
+        map.set("old", now_millis() - 1);
+        map.set("new", now_millis() + 60_000);
+
+        assert!(map.expire_if_due(&mut data, "old"));
+        assert!(!map.expire_if_due(&mut data, "new"));
+        assert!(!data.contains_key("old"));
+        assert_eq!(map.deadline("old"), None);
+        assert!(data.contains_key("new"));
+    }
+
+    #[test]
+    fn test_purge_returns_expired_keys() {
+        let map = ExpiryMap::new();
+        let mut data = HashMap::new();
+        for key in ["a", "b", "c"] {
+            data.insert(key.to_string(), "v".to_string());
+        }
+        map.set("a", 0);
+        map.set("b", now_millis() + 60_000);
+
+        assert_eq!(map.purge(&mut data), vec!["a".to_string()]);
+        assert_eq!(data.len(), 2);
+        assert!(map.purge(&mut data).is_empty());
+    }
+}
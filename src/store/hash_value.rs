@@ -0,0 +1,66 @@
+//! # Hash Values
+//!
+//! Hashes (`HSET`/`HGET`/...) are stored as ordinary string values carrying a
+//! type marker followed by a JSON object of their fields. Keeping them inside
+//! the existing string keyspace means every engine, persistence, replication,
+//! `DUMP`/`RESTORE` and the Merkle tree handle hashes without changes.
+//!
+//! Fields are kept in a `BTreeMap`, so the encoding (and therefore the Merkle
+//! leaf hash) is identical for equal hashes regardless of insertion order.
+
+use std::collections::BTreeMap;
+
+/// Prefix marking a stored value as an encoded hash. The server refuses text
+/// writes starting with NUL (`validate_value`), so only HSET produces it.
+const HASH_MARKER: &str = "\u{0}hash:";
+
+/// Error returned when a command is used against a key of the other type.
+pub const WRONGTYPE: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+/// Whether a stored value is an encoded hash.
+pub fn is_hash(value: &str) -> bool {
+    value.starts_with(HASH_MARKER)
+}
+
+/// Decode a stored value into its fields, or `None` if it is a plain string.
+pub fn decode(value: &str) -> Option<BTreeMap<String, String>> {
+    let json = value.strip_prefix(HASH_MARKER)?;
+    serde_json::from_str(json).ok()
+}
+
+/// Encode fields into a stored value.
+pub fn encode(fields: &BTreeMap<String, String>) -> String {
+    // Serializing a map of strings cannot fail
+    let json = serde_json::to_string(fields).unwrap_or_default();
+    format!("{}{}", HASH_MARKER, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let mut fields = BTreeMap::new();
+        fields.insert("name".to_string(), "ada lovelace".to_string());
+        fields.insert("line".to_string(), "a\r\nb".to_string());
+        let encoded = encode(&fields);
+
+        assert!(is_hash(&encoded));
+        assert!(!encoded.contains('\n'));
+        assert_eq!(decode(&encoded), Some(fields));
+        assert_eq!(decode("plain"), None);
+        assert!(!is_hash("plain"));
+    }
+
+    #[test]
+    fn test_encoding_independent_of_insertion_order() {
+        let mut a = BTreeMap::new();
+        a.insert("x".to_string(), "1".to_string());
+        a.insert("y".to_string(), "2".to_string());
+        let mut b = BTreeMap::new();
+        b.insert("y".to_string(), "2".to_string());
+        b.insert("x".to_string(), "1".to_string());
+        assert_eq!(encode(&a), encode(&b));
+    }
+}
@@ -24,10 +24,13 @@
 //! - Implement proper error handling for I/O operations
 
 use anyhow::Result;
+use rand::seq::IteratorRandom;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
-use super::kv_trait::KVEngineStoreTrait;
+use super::expiry::ExpiryMap;
+use super::storage_path::validate_storage_dir;
+use super::kv_trait::{validate_batch, validate_batch_keys, KVEngineStoreTrait, KeyspaceStats, NOT_AN_INTEGER, OVERFLOW};
 
 /// In-memory key-value storage engine.
 ///
@@ -42,27 +45,33 @@ pub struct KvEngine {
     /// Shared reference to the key-value data with thread-safe interior mutability
     /// Using Arc<RwLock<HashMap>> provides safe shared mutability across threads
     data: Arc<RwLock<HashMap<String, String>>>,
+    /// Expiry deadlines for keys written with `set_with_expiry`
+    expiries: Arc<ExpiryMap>,
     // TODO: Add persistent storage implementation
     // In a real implementation, this would use a persistent storage engine like Sled:
     // storage_path: PathBuf,
     // sled_db: sled::Db,
 }
 
+#[allow(dead_code)]
 impl KvEngine {
     /// Create a new storage engine instance.
     ///
     /// # Arguments
-    /// * `_storage_path` - Path where data should be stored (currently unused)
+    /// * `storage_path` - Path where data should be stored; nothing is written yet,
+    ///   but an existing path must be a directory
     ///
     /// # Returns
     /// * `Result<KvEngine>` - New storage engine instance or error
     ///
     /// # Current Behavior
-    /// Creates an empty in-memory HashMap. The storage_path is ignored.
+    /// Creates an empty in-memory HashMap. Nothing is stored under `storage_path`.
     ///
     /// # Future Implementation
     /// Should initialize or open a persistent storage engine at the given path.
-    pub fn new(_storage_path: &str) -> Result<Self> {
+    pub fn new(storage_path: &str) -> Result<Self> {
+        validate_storage_dir(storage_path)?;
+
         // TODO: Initialize persistent storage engine here
         // For example, with Sled:
         // let db = sled::open(storage_path)?;
@@ -70,6 +79,7 @@ impl KvEngine {
 
         Ok(Self {
             data: Arc::new(RwLock::new(HashMap::new())),
+            expiries: Arc::new(ExpiryMap::new()),
         })
     }
 
@@ -211,8 +221,8 @@ impl KvEngine {
             Some(value) => {
                 // Try to parse the existing value as a number
                 match value.parse::<i64>() {
-                    Ok(num) => num + increment_by,
-                    Err(_) => return Err(format!("Value for key '{}' is not a valid number", key)),
+                    Ok(num) => num.checked_add(increment_by).ok_or_else(|| OVERFLOW.to_string())?,
+                    Err(_) => return Err(NOT_AN_INTEGER.to_string()),
                 }
             }
             None => increment_by, // Key doesn't exist, start with the increment amount
@@ -246,7 +256,8 @@ impl KvEngine {
     pub fn decrement(&mut self, key: &str, amount: Option<i64>) -> Result<i64, String> {
         let decrement_by = amount.unwrap_or(1);
         // Decrement is just a negative increment
-        self.increment(key, Some(-decrement_by))
+        let negated = decrement_by.checked_neg().ok_or_else(|| OVERFLOW.to_string())?;
+        self.increment(key, Some(negated))
     }
 
     /// Append a value to an existing string.
@@ -339,7 +350,11 @@ impl KVEngineStoreTrait for KvEngine {
     /// # Returns
     /// * `Option<String>` - The value if found, None otherwise
     fn get(&self, key: &str) -> Option<String> {
-        self.data.read().unwrap().get(key).cloned()
+        let data = self.data.read().unwrap();
+        if self.expiries.is_expired(key) {
+            return None;
+        }
+        data.get(key).cloned()
     }
 
     /// Store a key-value pair.
@@ -367,7 +382,9 @@ impl KVEngineStoreTrait for KvEngine {
         // Solution: Use RwLock for thread-safe interior mutability following Copilot's
         // recommendation. Maintains existing API and LWW semantics while eliminating
         // unsafe code that could cause data corruption or memory safety violations.
-        self.data.write().unwrap().insert(key, value);
+        let mut data = self.data.write().unwrap();
+        self.expiries.clear(&key);
+        data.insert(key, value);
         Ok(())
     }
 
@@ -389,7 +406,19 @@ impl KVEngineStoreTrait for KvEngine {
         // Memory-safe deletion: use RwLock for thread-safe interior mutability,
         // eliminating unsafe raw pointer operations while preserving LWW
         // semantics and existing API behavior for anti-entropy protocols.
-        self.data.write().unwrap().remove(key).is_some()
+        let mut data = self.data.write().unwrap();
+        let expired = self.expiries.expire_if_due(&mut data, key);
+        self.expiries.clear(key);
+        data.remove(key).is_some() && !expired
+    }
+
+    fn unlink(&self, key: &str) -> Option<String> {
+        let mut data = self.data.write().unwrap();
+        if self.expiries.expire_if_due(&mut data, key) {
+            return None;
+        }
+        self.expiries.clear(key);
+        data.remove(key)
     }
 
     /// Get all keys currently stored in the engine.
@@ -400,6 +429,11 @@ impl KVEngineStoreTrait for KvEngine {
         self.data.read().unwrap().keys().cloned().collect()
     }
 
+    fn random_key(&self) -> Option<String> {
+        let data = self.data.read().unwrap();
+        data.keys().choose(&mut rand::thread_rng()).cloned()
+    }
+
     fn scan(&self, prefix: &str) -> Vec<String> {
         if prefix.is_empty() {
             return self.keys();
@@ -432,7 +466,8 @@ impl KVEngineStoreTrait for KvEngine {
     }
     
     fn exists(&self, key: &str) -> bool {
-        self.data.read().unwrap().contains_key(key)
+        let data = self.data.read().unwrap();
+        data.contains_key(key) && !self.expiries.is_expired(key)
     }
 
     fn memory_usage(&self) -> usize {
@@ -460,25 +495,26 @@ impl KVEngineStoreTrait for KvEngine {
     /// * `amount` - The amount to increment by (default: 1)
     ///
     /// # Returns
-    /// * `Result<i64>` - The new value after incrementing, or error if not a valid number
+    /// * `Result<i64>` - The new value after incrementing, or error if not an integer or on overflow
     fn increment(&self, key: &str, amount: Option<i64>) -> Result<i64> {
         // Default increment amount is 1
         let increment_by = amount.unwrap_or(1);
         
         // Use write lock to ensure exclusive access for atomic read-modify-write
         let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
         let current_value = match data.get(key) {
             Some(value) => {
                 // Try to parse the current value as a number
-                value.parse::<i64>().map_err(|_| {
-                    anyhow::anyhow!("Value for key '{}' is not a valid number", key)
-                })?
+                value.parse::<i64>().map_err(|_| anyhow::anyhow!(NOT_AN_INTEGER))?
             }
             None => 0, // Key doesn't exist, start from 0
         };
         
         // Calculate and store the new value
-        let new_value = current_value + increment_by;
+        let new_value = current_value
+            .checked_add(increment_by)
+            .ok_or_else(|| anyhow::anyhow!(OVERFLOW))?;
         data.insert(key.to_string(), new_value.to_string());
         
         Ok(new_value)
@@ -491,15 +527,33 @@ impl KVEngineStoreTrait for KvEngine {
     /// * `amount` - The amount to decrement by (default: 1)
     ///
     /// # Returns
-    /// * `Result<i64>` - The new value after decrementing, or error if not a valid number
+    /// * `Result<i64>` - The new value after decrementing, or error if not an integer or on overflow
     fn decrement(&self, key: &str, amount: Option<i64>) -> Result<i64> {
         // Decrement is just a negative increment
         let decrement_by = amount.unwrap_or(1);
-        self.increment(key, Some(-decrement_by))
+        let negated = decrement_by.checked_neg().ok_or_else(|| anyhow::anyhow!(OVERFLOW))?;
+        self.increment(key, Some(negated))
     }
     
+    fn increment_limit(&self, key: &str, amount: i64, limit: i64) -> Result<(i64, bool)> {
+        // Read, add and compare under one write lock so concurrent callers see distinct counts
+        let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
+        let current = match data.get(key) {
+            Some(value) => value.parse::<i64>().map_err(|_| anyhow::anyhow!(NOT_AN_INTEGER))?,
+            None => 0,
+        };
+        let new_value = current
+            .checked_add(amount)
+            .ok_or_else(|| anyhow::anyhow!(OVERFLOW))?;
+        data.insert(key.to_string(), new_value.to_string());
+        Ok((new_value, new_value <= limit))
+    }
+
     /// Append a value to an existing string.
     ///
+    /// If the key doesn't exist, it will be created with the value.
+    ///
     /// # Arguments
     /// * `key` - The key to append to
     /// * `value` - The value to append
@@ -509,19 +563,23 @@ impl KVEngineStoreTrait for KvEngine {
     fn append(&self, key: &str, value: &str) -> Result<String> {
         // Use write lock exclusively to avoid read/write lock deadlock
         let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
         if let Some(current_value) = data.get(key) {
             // Append the new value
             let new_value = format!("{}{}", current_value, value);
             data.insert(key.to_string(), new_value.clone());
             Ok(new_value)
         } else {
-            // Key doesn't exist, return error as per trait documentation
-            Err(anyhow::anyhow!("Key '{}' does not exist", key))
+            // Key doesn't exist, create it with the value as per trait documentation
+            data.insert(key.to_string(), value.to_string());
+            Ok(value.to_string())
         }
     }
     
     /// Prepend a value to an existing string.
     ///
+    /// If the key doesn't exist, it will be created with the value.
+    ///
     /// # Arguments
     /// * `key` - The key to prepend to
     /// * `value` - The value to prepend
@@ -535,17 +593,117 @@ impl KVEngineStoreTrait for KvEngine {
         // window where the observed value may change. We hold the write lock from the
         // start for correctness and LWW consistency—not for deadlock avoidance.  
         let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
         if let Some(current_value) = data.get(key) {
             // Prepend the new value
             let new_value = format!("{}{}", value, current_value);
             data.insert(key.to_string(), new_value.clone());
             Ok(new_value)
         } else {
-            // Key doesn't exist, return error as per trait documentation
-            Err(anyhow::anyhow!("Key '{}' does not exist", key))
+            // Key doesn't exist, create it with the value as per trait documentation
+            data.insert(key.to_string(), value.to_string());
+            Ok(value.to_string())
         }
     }
     
+    /// Atomically replace a value if it currently equals `expected`.
+    ///
+    /// A missing key matches an empty `expected` (create-if-absent).
+    ///
+    /// # Arguments
+    /// * `key` - The key to update
+    /// * `expected` - The value the key must currently hold
+    /// * `new` - The value to store on a match
+    ///
+    /// # Returns
+    /// * `Result<bool>` - True if the swap happened, false on mismatch
+    fn compare_and_swap(&self, key: &str, expected: &str, new: &str) -> Result<bool> {
+        // Hold the write lock across the comparison and the write
+        let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
+        let matches = match data.get(key) {
+            Some(current) => current == expected,
+            None => expected.is_empty(),
+        };
+        if matches {
+            data.insert(key.to_string(), new.to_string());
+        }
+        Ok(matches)
+    }
+
+    /// Store several key-value pairs as one all-or-nothing write.
+    ///
+    /// The batch is validated up front and then inserted under a single write lock.
+    fn set_many(&self, pairs: &[(String, String)]) -> Result<()> {
+        validate_batch(pairs)?;
+        let mut data = self.data.write().unwrap();
+        for (key, value) in pairs {
+            self.expiries.clear(key);
+            data.insert(key.clone(), value.clone());
+        }
+        Ok(())
+    }
+
+    fn set_with_expiry(&self, key: String, value: String, deadline_ms: u64) -> Result<()> {
+        // Hold the write lock across both updates so the deadline lands with the value
+        let mut data = self.data.write().unwrap();
+        self.expiries.set(&key, deadline_ms);
+        data.insert(key, value);
+        Ok(())
+    }
+
+    fn set_many_with_expiry(&self, entries: &[(String, String, Option<u64>)]) -> Result<()> {
+        validate_batch_keys(entries.iter().map(|(k, _, _)| k.as_str()))?;
+        let mut data = self.data.write().unwrap();
+        for (key, value, deadline_ms) in entries {
+            match deadline_ms {
+                Some(deadline_ms) => self.expiries.set(key, *deadline_ms),
+                None => self.expiries.clear(key),
+            }
+            data.insert(key.clone(), value.clone());
+        }
+        Ok(())
+    }
+
+    fn expiry(&self, key: &str) -> Option<u64> {
+        self.expiries.deadline(key)
+    }
+
+    fn get_and_touch_expiry(&self, key: &str, deadline_ms: Option<u64>) -> Option<String> {
+        // Hold the write lock so the deadline changes with the value that was read
+        let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
+        let value = data.get(key).cloned()?;
+        match deadline_ms {
+            Some(deadline_ms) => self.expiries.set(key, deadline_ms),
+            None => self.expiries.clear(key),
+        }
+        Some(value)
+    }
+
+    fn purge_expired(&self) -> Vec<String> {
+        let mut data = self.data.write().unwrap();
+        self.expiries.purge(&mut data)
+    }
+
+    fn keyspace_stats(&self) -> KeyspaceStats {
+        let data = self.data.read().unwrap();
+        let mut stats = KeyspaceStats::default();
+        for (key, value) in data.iter() {
+            stats.record(key, value);
+        }
+        stats
+    }
+
+    fn for_each_entry(&self, visit: &mut dyn FnMut(&str, &str)) {
+        let data = self.data.read().unwrap();
+        for (key, value) in data.iter() {
+            if !self.expiries.is_expired(key) {
+                visit(key, value);
+            }
+        }
+    }
+
     /// Clear all keys/values in the store.
     ///
     /// # Returns
@@ -553,7 +711,9 @@ impl KVEngineStoreTrait for KvEngine {
     fn truncate(&self) -> Result<()> {
         // Memory-safe truncation using RwLock: clears all data while preserving
         // anti-entropy reconciliation semantics without unsafe pointer operations.
-        self.data.write().unwrap().clear();
+        let mut data = self.data.write().unwrap();
+        data.clear();
+        self.expiries.clear_all();
         Ok(())
     }
     
@@ -575,6 +735,16 @@ impl KVEngineStoreTrait for KvEngine {
         // In a persistent storage engine, this would flush data to disk
         Ok(())
     }
+
+    /// Reclaim storage after heavy churn.
+    /// For this in-memory engine, this is a no-op: deleted values are freed
+    /// as soon as they are removed.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Always returns Ok(())
+    fn compact(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -588,14 +758,14 @@ mod tests {
         let temp_dir = tempdir().unwrap();
         let storage_path = temp_dir.path().to_str().unwrap();
 
-        let mut engine = KvEngine::new(storage_path).unwrap();
+        let engine = KvEngine::new(storage_path).unwrap();
 
         // Test basic set and get operations
-        engine.set("key1".to_string(), "value1".to_string());
+        engine.set("key1".to_string(), "value1".to_string()).unwrap();
         assert_eq!(engine.get("key1"), Some("value1".to_string()));
 
         // Test overwriting an existing key
-        engine.set("key1".to_string(), "new_value".to_string());
+        engine.set("key1".to_string(), "new_value".to_string()).unwrap();
         assert_eq!(engine.get("key1"), Some("new_value".to_string()));
 
         // Test delete operation
@@ -603,8 +773,8 @@ mod tests {
         assert_eq!(engine.get("key1"), None);
 
         // Test keys() method with multiple entries
-        engine.set("key2".to_string(), "value2".to_string());
-        engine.set("key3".to_string(), "value3".to_string());
+        engine.set("key2".to_string(), "value2".to_string()).unwrap();
+        engine.set("key3".to_string(), "value3".to_string()).unwrap();
 
         let keys = engine.keys();
         assert_eq!(keys.len(), 2);
@@ -616,7 +786,7 @@ mod tests {
     fn test_increment_operations() {
         let temp_dir = tempdir().unwrap();
         let storage_path = temp_dir.path().to_str().unwrap();
-        let mut engine = KvEngine::new(storage_path).unwrap();
+        let engine = KvEngine::new(storage_path).unwrap();
         
         // Test incrementing a non-existent key (should create with value 1)
         let result = engine.increment("counter1", None).unwrap();
@@ -634,7 +804,7 @@ mod tests {
         assert_eq!(engine.get("counter1"), Some("4".to_string()));
         
         // Test incrementing a key with non-numeric value
-        engine.set("text".to_string(), "hello".to_string());
+        engine.set("text".to_string(), "hello".to_string()).unwrap();
         let result = engine.increment("text", None);
         assert!(result.is_err());
     }
@@ -643,7 +813,7 @@ mod tests {
     fn test_decrement_operations() {
         let temp_dir = tempdir().unwrap();
         let storage_path = temp_dir.path().to_str().unwrap();
-        let mut engine = KvEngine::new(storage_path).unwrap();
+        let engine = KvEngine::new(storage_path).unwrap();
         
         // Test decrementing a non-existent key (should create with value -1)
         let result = engine.decrement("counter1", None).unwrap();
@@ -656,7 +826,7 @@ mod tests {
         assert_eq!(engine.get("counter1"), Some("-4".to_string()));
         
         // Test decrementing a key with non-numeric value
-        engine.set("text".to_string(), "hello".to_string());
+        engine.set("text".to_string(), "hello".to_string()).unwrap();
         let result = engine.decrement("text", None);
         assert!(result.is_err());
     }
@@ -665,10 +835,10 @@ mod tests {
     fn test_string_operations() {
         let temp_dir = tempdir().unwrap();
         let storage_path = temp_dir.path().to_str().unwrap();
-        let mut engine = KvEngine::new(storage_path).unwrap();
+        let engine = KvEngine::new(storage_path).unwrap();
         
         // Set up a key for testing
-        engine.set("greeting".to_string(), "World!".to_string());
+        engine.set("greeting".to_string(), "World!".to_string()).unwrap();
         
         // Test append to existing key
         let result = engine.append("greeting", " Hello!").unwrap();
@@ -680,16 +850,18 @@ mod tests {
         assert_eq!(result, "Hey! World! Hello!");
         assert_eq!(engine.get("greeting"), Some("Hey! World! Hello!".to_string()));
         
-        // Test append to non-existent key (should error)
-        let result = engine.append("nonexistent", "value");
-        assert!(result.is_err());
+        // Test append to non-existent key (should create it)
+        let result = engine.append("nonexistent", "value").unwrap();
+        assert_eq!(result, "value");
+        assert_eq!(engine.get("nonexistent"), Some("value".to_string()));
         
-        // Test prepend to non-existent key (should error)
-        let result = engine.prepend("another_nonexistent", "value");
-        assert!(result.is_err());
+        // Test prepend to non-existent key (should create it)
+        let result = engine.prepend("another_nonexistent", "value").unwrap();
+        assert_eq!(result, "value");
+        assert_eq!(engine.get("another_nonexistent"), Some("value".to_string()));
         
         // Set up a new key for testing
-        engine.set("new_key".to_string(), "Start: ".to_string());
+        engine.set("new_key".to_string(), "Start: ".to_string()).unwrap();
         assert_eq!(engine.get("new_key"), Some("Start: ".to_string()));
     }
     
@@ -697,12 +869,12 @@ mod tests {
     fn test_truncate_operation() {
         let temp_dir = tempdir().unwrap();
         let storage_path = temp_dir.path().to_str().unwrap();
-        let mut engine = KvEngine::new(storage_path).unwrap();
+        let engine = KvEngine::new(storage_path).unwrap();
         
         // Add some data
-        engine.set("key1".to_string(), "value1".to_string());
-        engine.set("key2".to_string(), "value2".to_string());
-        engine.set("key3".to_string(), "value3".to_string());
+        engine.set("key1".to_string(), "value1".to_string()).unwrap();
+        engine.set("key2".to_string(), "value2".to_string()).unwrap();
+        engine.set("key3".to_string(), "value3".to_string()).unwrap();
         
         // Verify data exists
         assert_eq!(engine.keys().len(), 3);
@@ -711,7 +883,7 @@ mod tests {
         assert_eq!(engine.get("key3"), Some("value3".to_string()));
         
         // Truncate the store
-        engine.truncate();
+        engine.truncate().unwrap();
         
         // Verify all data is gone
         assert_eq!(engine.keys().len(), 0);
@@ -720,7 +892,7 @@ mod tests {
         assert_eq!(engine.get("key3"), None);
         
         // Verify we can add new data after truncate
-        engine.set("new_key".to_string(), "new_value".to_string());
+        engine.set("new_key".to_string(), "new_value".to_string()).unwrap();
         assert_eq!(engine.keys().len(), 1);
         assert_eq!(engine.get("new_key"), Some("new_value".to_string()));
     }
@@ -10,7 +10,17 @@
 //! - `KvEngine`: Non-thread-safe in-memory storage using Arc<HashMap>
 //! - Future: Persistent storage engines (RocksDB, Sled, etc.)
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use super::hash_value::{self, WRONGTYPE};
+
+/// Error for numeric operations on a value that is not an `i64` (as in Redis).
+pub const NOT_AN_INTEGER: &str = "not an integer or out of range";
+
+/// Error for numeric operations whose result does not fit in an `i64`.
+pub const OVERFLOW: &str = "increment or decrement would overflow";
 
 /// Common interface for all key-value storage engines.
 ///
@@ -49,18 +59,49 @@ pub trait KVEngineStoreTrait: Send + Sync {
     /// * `bool` - True if the key existed and was deleted, false otherwise
     fn delete(&self, key: &str) -> bool;
 
+    /// Remove a key and hand back its value instead of dropping it.
+    ///
+    /// Used by `UNLINK` so large values can be freed off the request path.
+    ///
+    /// # Returns
+    /// * `Option<String>` - The detached value, or None if the key was absent
+    ///   or already expired
+    fn unlink(&self, key: &str) -> Option<String>;
+
     /// Get all keys currently stored in the engine.
     ///
     /// # Returns
     /// * `Vec<String>` - Vector of all keys in the store
     fn keys(&self) -> Vec<String>;
 
+    /// Pick a key uniformly at random.
+    ///
+    /// Implementations sample while iterating instead of collecting every key.
+    ///
+    /// # Returns
+    /// * `Option<String>` - A random key, or None if the store is empty
+    fn random_key(&self) -> Option<String>;
+
     /// Scan for keys matching a prefix.
     ///
     /// # Returns
     /// * `Vec<String>` - Vector of keys matching the prefix
     fn scan(&self, prefix: &str) -> Vec<String>;
 
+    /// Scan for keys matching a prefix, together with their values.
+    ///
+    /// The default pairs `scan` with `get`, dropping keys removed in between;
+    /// engines that can should read both in one step.
+    ///
+    /// # Returns
+    /// * `Vec<(String, String)>` - Matching keys and their values
+    fn scan_values(&self, prefix: &str) -> Vec<(String, String)> {
+        self.scan(prefix)
+            .into_iter()
+            .filter_map(|key| self.get(&key).map(|value| (key, value)))
+            .collect()
+    }
+
     //Ping method
     ///
     /// # Returns
@@ -88,6 +129,7 @@ pub trait KVEngineStoreTrait: Send + Sync {
     ///
     /// # Returns
     /// * `usize` - Number of key-value pairs
+    #[allow(dead_code)]
     fn len(&self) -> usize;
 
     /// Get the size of the database (number of key-value pairs).
@@ -99,6 +141,7 @@ pub trait KVEngineStoreTrait: Send + Sync {
     ///
     /// # Returns
     /// * `bool` - True if the store is empty, false otherwise
+    #[allow(dead_code)]
     fn is_empty(&self) -> bool;
     
     /// Increment a numeric value.
@@ -108,7 +151,8 @@ pub trait KVEngineStoreTrait: Send + Sync {
     /// * `amount` - The amount to increment by (default: 1)
     ///
     /// # Returns
-    /// * `Result<i64>` - The new value after incrementing, or error if not a valid number
+    /// * `Result<i64>` - The new value after incrementing; `NOT_AN_INTEGER` if the value
+    ///   is not a number, `OVERFLOW` if the result does not fit in an `i64`
     fn increment(&self, key: &str, amount: Option<i64>) -> Result<i64>;
     
     /// Decrement a numeric value.
@@ -118,29 +162,222 @@ pub trait KVEngineStoreTrait: Send + Sync {
     /// * `amount` - The amount to decrement by (default: 1)
     ///
     /// # Returns
-    /// * `Result<i64>` - The new value after decrementing, or error if not a valid number
+    /// * `Result<i64>` - The new value after decrementing; `NOT_AN_INTEGER` if the value
+    ///   is not a number, `OVERFLOW` if the result does not fit in an `i64`
     fn decrement(&self, key: &str, amount: Option<i64>) -> Result<i64>;
     
+    /// Increment the number at `key` by `amount` and compare the result to `limit`,
+    /// as one atomic step. A missing key starts from 0. The increment is applied
+    /// even when the limit is exceeded, so the counter reflects every attempt.
+    ///
+    /// # Returns
+    /// * `Result<(i64, bool)>` - The new value and whether it is `<= limit`;
+    ///   error if the value is not a number or the result would overflow
+    fn increment_limit(&self, key: &str, amount: i64, limit: i64) -> Result<(i64, bool)>;
+
     /// Append a value to an existing string.
     ///
+    /// If the key doesn't exist, it is created with `value` (same as SET).
+    ///
     /// # Arguments
     /// * `key` - The key to append to
     /// * `value` - The value to append
     ///
     /// # Returns
-    /// * `Result<String>` - The new value after appending, or error if key doesn't exist
+    /// * `Result<String>` - The new value after appending, or error on storage failure
     fn append(&self, key: &str, value: &str) -> Result<String>;
     
     /// Prepend a value to an existing string.
     ///
+    /// If the key doesn't exist, it is created with `value` (same as SET).
+    ///
     /// # Arguments
     /// * `key` - The key to prepend to
     /// * `value` - The value to prepend
     ///
     /// # Returns
-    /// * `Result<String>` - The new value after prepending, or error if key doesn't exist
+    /// * `Result<String>` - The new value after prepending, or error on storage failure
     fn prepend(&self, key: &str, value: &str) -> Result<String>;
     
+    /// Atomically replace the value of `key` with `new` if it currently equals `expected`.
+    ///
+    /// A missing key is treated as holding the empty string, so an empty `expected`
+    /// acts as create-if-absent (it also matches a key whose value is empty).
+    ///
+    /// # Arguments
+    /// * `key` - The key to update
+    /// * `expected` - The value the key must currently hold
+    /// * `new` - The value to store on a match
+    ///
+    /// # Returns
+    /// * `Result<bool>` - True if the swap happened, false on mismatch
+    fn compare_and_swap(&self, key: &str, expected: &str, new: &str) -> Result<bool>;
+
+    /// Get a value together with its version.
+    ///
+    /// Every write to a key gives it a higher version than it had before,
+    /// including after a delete and re-create. Engines that do not track
+    /// versions report 0.
+    ///
+    /// # Returns
+    /// * `Option<(String, u64)>` - The value and its version, or None if absent
+    fn get_versioned(&self, key: &str) -> Option<(String, u64)> {
+        self.get(key).map(|value| (value, 0))
+    }
+
+    /// Store `value` only if the key's version equals `expected`.
+    ///
+    /// A missing key has version 0, so `expected = 0` means "create only".
+    ///
+    /// # Returns
+    /// * `Result<bool>` - True if the write happened, false on version mismatch;
+    ///   error if the engine does not track versions
+    fn set_if_version(&self, _key: &str, _expected: u64, _value: String) -> Result<bool> {
+        Err(anyhow!("key versions are not supported by this engine"))
+    }
+
+    /// Store several key-value pairs as one all-or-nothing write.
+    ///
+    /// The whole batch is validated before anything is written (see
+    /// [`validate_batch`]), then applied under a single write lock or engine
+    /// transaction, so readers never observe a partially applied batch.
+    ///
+    /// # Arguments
+    /// * `pairs` - The key-value pairs to store; later duplicates win
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success, or error with no pair stored
+    fn set_many(&self, pairs: &[(String, String)]) -> Result<()>;
+
+    /// Store `value` at `key` with an expiry deadline, atomically.
+    ///
+    /// The value and deadline are written under the same lock (or engine
+    /// transaction), so the key is never visible without its deadline. Any
+    /// later plain write (`set`, `set_many`, ...) makes the key persistent again.
+    ///
+    /// # Arguments
+    /// * `key` - The key to store
+    /// * `value` - The value to store
+    /// * `deadline_ms` - Absolute Unix time in milliseconds at which the key expires
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error
+    fn set_with_expiry(&self, key: String, value: String, deadline_ms: u64) -> Result<()>;
+
+    /// Store several values, each with its own expiry deadline, atomically.
+    ///
+    /// Validated and applied like [`set_many`](Self::set_many); an entry with a
+    /// deadline is stored as by `set_with_expiry`, one without as by `set`.
+    ///
+    /// # Arguments
+    /// * `entries` - `(key, value, deadline_ms)` triples; a `None` deadline stores
+    ///   the key without expiry
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success, or error with no entry stored
+    fn set_many_with_expiry(&self, entries: &[(String, String, Option<u64>)]) -> Result<()>;
+
+    /// Expiry deadline of `key` in Unix milliseconds, if it has one.
+    fn expiry(&self, key: &str) -> Option<u64>;
+
+    /// Read `key` and, if it exists, change its expiry in the same step
+    /// (`GETEX`): expire at `deadline_ms`, or never for `None`. The value is
+    /// left untouched.
+    ///
+    /// # Returns
+    /// * `Option<String>` - The value, or `None` (and no change) if the key is missing
+    fn get_and_touch_expiry(&self, key: &str, deadline_ms: Option<u64>) -> Option<String>;
+
+    /// Remove every key whose deadline has passed.
+    ///
+    /// # Returns
+    /// * `Vec<String>` - The keys that were removed
+    fn purge_expired(&self) -> Vec<String>;
+
+    /// Set a field of the hash stored at `key`, creating the hash if needed.
+    ///
+    /// Hashes are stored as encoded string values (see `hash_value`), so this
+    /// default works for every engine; the update is applied with
+    /// `compare_and_swap` and retried if the value changed concurrently.
+    ///
+    /// # Returns
+    /// * `Result<bool>` - True if the field is new, false if it was overwritten;
+    ///   error if `key` holds a plain string
+    fn hset(&self, key: &str, field: &str, value: &str) -> Result<bool> {
+        loop {
+            let current = self.get(key);
+            let mut fields = decode_or_empty(current.as_deref())?;
+            let created = fields.insert(field.to_string(), value.to_string()).is_none();
+            let expected = current.as_deref().unwrap_or("");
+            if self.compare_and_swap(key, expected, &hash_value::encode(&fields))? {
+                return Ok(created);
+            }
+        }
+    }
+
+    /// Get a field of the hash stored at `key`.
+    ///
+    /// # Returns
+    /// * `Result<Option<String>>` - The field value if present; error if `key` holds a plain string
+    fn hget(&self, key: &str, field: &str) -> Result<Option<String>> {
+        Ok(decode_or_empty(self.get(key).as_deref())?.remove(field))
+    }
+
+    /// Get all fields of the hash stored at `key`, sorted by field name.
+    ///
+    /// # Returns
+    /// * `Result<Vec<(String, String)>>` - Field/value pairs (empty for a missing key)
+    fn hgetall(&self, key: &str) -> Result<Vec<(String, String)>> {
+        Ok(decode_or_empty(self.get(key).as_deref())?.into_iter().collect())
+    }
+
+    /// Remove fields from the hash stored at `key`. The key itself is deleted
+    /// once its last field is removed.
+    ///
+    /// # Returns
+    /// * `Result<usize>` - Number of fields that existed and were removed
+    fn hdel(&self, key: &str, fields: &[String]) -> Result<usize> {
+        loop {
+            let Some(current) = self.get(key) else {
+                return Ok(0);
+            };
+            let mut map = decode_or_empty(Some(&current))?;
+            let removed = fields.iter().filter(|f| map.remove(f.as_str()).is_some()).count();
+            if removed == 0 {
+                return Ok(0);
+            }
+            if map.is_empty() {
+                self.delete(key);
+                return Ok(removed);
+            }
+            if self.compare_and_swap(key, &current, &hash_value::encode(&map))? {
+                return Ok(removed);
+            }
+        }
+    }
+
+    /// Number of fields in the hash stored at `key` (0 for a missing key).
+    fn hlen(&self, key: &str) -> Result<usize> {
+        Ok(decode_or_empty(self.get(key).as_deref())?.len())
+    }
+
+    /// Key count, byte totals and largest value, gathered in a single pass
+    /// under one read lock so the figures are consistent with each other.
+    fn keyspace_stats(&self) -> KeyspaceStats;
+
+    /// Call `visit` with every key and value, one entry at a time, so callers
+    /// can aggregate over the keyspace without collecting it first.
+    ///
+    /// The default walks `keys` and `get`; engines with a single map visit it
+    /// under one read lock, so `visit` must not call back into the engine.
+    fn for_each_entry(&self, visit: &mut dyn FnMut(&str, &str)) {
+        for key in self.keys() {
+            if let Some(value) = self.get(&key) {
+                visit(&key, &value);
+            }
+        }
+    }
+
     /// Clear all keys/values in the store.
     ///
     /// # Returns
@@ -151,6 +388,7 @@ pub trait KVEngineStoreTrait: Send + Sync {
     ///
     /// # Returns
     /// * `Result<u64>` - Number of key-value pairs or error
+    #[allow(dead_code)]
     fn count_keys(&self) -> Result<u64>;
     
     /// Force synchronization of pending changes to persistent storage.
@@ -159,4 +397,558 @@ pub trait KVEngineStoreTrait: Send + Sync {
     /// # Returns
     /// * `Result<()>` - Success or error
     fn sync(&self) -> Result<()>;
+
+    /// Reclaim storage left behind by deleted and overwritten keys.
+    /// For in-memory engines, this is a no-op.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error
+    fn compact(&self) -> Result<()>;
+}
+
+/// Inclusive upper bounds of the value size buckets of `INFO`'s `sizes` line
+/// and `MEMORY DOCTOR`; larger values fall into one more, open-ended bucket.
+pub const VALUE_SIZE_BUCKETS: [u64; 6] = [16, 64, 256, 1024, 4096, 16384];
+
+/// Number of values per bucket of `VALUE_SIZE_BUCKETS`, then of larger values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SizeHistogram(pub [u64; VALUE_SIZE_BUCKETS.len() + 1]);
+
+impl SizeHistogram {
+    /// Count one value of `size` bytes.
+    pub fn record(&mut self, size: u64) {
+        let bucket = VALUE_SIZE_BUCKETS.iter().position(|bound| size <= *bound).unwrap_or(VALUE_SIZE_BUCKETS.len());
+        self.0[bucket] += 1;
+    }
+
+    /// Each bucket's inclusive lower and upper bound (`None` for the open
+    /// one) with its count.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, Option<u64>, u64)> + '_ {
+        let lowers = std::iter::once(0).chain(VALUE_SIZE_BUCKETS.iter().map(|bound| bound + 1));
+        let uppers = VALUE_SIZE_BUCKETS.iter().copied().map(Some).chain(std::iter::once(None));
+        lowers.zip(uppers).zip(&self.0).map(|((lower, upper), count)| (lower, upper, *count))
+    }
+}
+
+/// Keyspace totals reported by `INFO` (see [`KVEngineStoreTrait::keyspace_stats`]).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyspaceStats {
+    /// Number of keys
+    pub keys: u64,
+    /// Total bytes of all keys
+    pub key_bytes: u64,
+    /// Total bytes of all values
+    pub value_bytes: u64,
+    /// Key holding the largest value and that value's size; ties go to the
+    /// smallest key so the result does not depend on iteration order
+    pub largest: Option<(String, u64)>,
+    /// Keys per value size bucket
+    pub size_histogram: SizeHistogram,
+}
+
+impl KeyspaceStats {
+    /// Account for one stored pair.
+    pub fn record(&mut self, key: &str, value: &str) {
+        let size = value.len() as u64;
+        self.keys += 1;
+        self.key_bytes += key.len() as u64;
+        self.value_bytes += size;
+        self.size_histogram.record(size);
+        let larger = match &self.largest {
+            None => true,
+            Some((k, s)) => size > *s || (size == *s && key < k.as_str()),
+        };
+        if larger {
+            self.largest = Some((key.to_string(), size));
+        }
+    }
+
+    /// Bytes used by keys and values together.
+    pub fn memory_bytes(&self) -> u64 {
+        self.key_bytes + self.value_bytes
+    }
+
+    /// Mean value size in bytes (0 for an empty store).
+    pub fn avg_value_bytes(&self) -> u64 {
+        self.value_bytes.checked_div(self.keys).unwrap_or(0)
+    }
+
+    /// `size_histogram` labelled by byte range, e.g. `0-16=2,17-64=0,...,16385+=1`.
+    pub fn size_histogram_line(&self) -> String {
+        let parts: Vec<String> = self
+            .size_histogram
+            .buckets()
+            .map(|(lower, upper, count)| match upper {
+                Some(upper) => format!("{}-{}={}", lower, upper, count),
+                None => format!("{}+={}", lower, count),
+            })
+            .collect();
+        parts.join(",")
+    }
+}
+
+/// Number of largest keys listed by `MEMORY DOCTOR`.
+pub const MEMORY_DOCTOR_TOP_KEYS: usize = 10;
+
+/// Value size distribution reported by `MEMORY DOCTOR`, built with
+/// [`KVEngineStoreTrait::for_each_entry`].
+#[derive(Debug, Default)]
+pub struct MemoryReport {
+    /// Number of keys
+    pub keys: u64,
+    /// Total bytes of all keys and values
+    pub bytes: u64,
+    /// Keys per value size bucket
+    pub histogram: SizeHistogram,
+    /// The largest values seen so far; the heap top is the one to evict next
+    largest: BinaryHeap<(Reverse<u64>, String)>,
+}
+
+impl MemoryReport {
+    /// Account for one stored pair.
+    pub fn record(&mut self, key: &str, value: &str) {
+        let size = value.len() as u64;
+        self.keys += 1;
+        self.bytes += (key.len() + value.len()) as u64;
+        self.histogram.record(size);
+
+        if self.largest.len() == MEMORY_DOCTOR_TOP_KEYS {
+            let evict = self.largest.peek().is_some_and(|(Reverse(s), k)| size > *s || (size == *s && key < k.as_str()));
+            if !evict {
+                return;
+            }
+            self.largest.pop();
+        }
+        self.largest.push((Reverse(size), key.to_string()));
+    }
+
+    /// Keys with the largest values and their value sizes, largest first; ties
+    /// go to the smallest key so the result does not depend on iteration order.
+    pub fn largest(&self) -> Vec<(String, u64)> {
+        let mut largest: Vec<_> = self.largest.iter().map(|(Reverse(size), key)| (key.clone(), *size)).collect();
+        largest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        largest
+    }
+}
+
+/// Decode a stored value as a hash: a missing key is an empty hash, a plain
+/// string is a `WRONGTYPE` error.
+fn decode_or_empty(value: Option<&str>) -> Result<BTreeMap<String, String>> {
+    match value {
+        None => Ok(BTreeMap::new()),
+        Some(v) => hash_value::decode(v).ok_or_else(|| anyhow!(WRONGTYPE)),
+    }
+}
+
+/// Check a `set_many` batch up front so engines can apply it without partial failure.
+pub fn validate_batch(pairs: &[(String, String)]) -> Result<()> {
+    validate_batch_keys(pairs.iter().map(|(k, _)| k.as_str()))
+}
+
+/// [`validate_batch`] for batches that carry more than a value per key.
+pub fn validate_batch_keys<'a>(keys: impl Iterator<Item = &'a str>) -> Result<()> {
+    let mut keys = keys.peekable();
+    if keys.peek().is_none() {
+        return Err(anyhow!("empty batch"));
+    }
+    if keys.any(str::is_empty) {
+        return Err(anyhow!("empty key in batch"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{KvEngine, RwLockEngine, SledEngine};
+    use std::collections::HashSet;
+    use tempfile::tempdir;
+
+    /// Run `check` against every engine so that their semantics stay identical.
+    fn for_each_engine(check: impl Fn(&str, &dyn KVEngineStoreTrait)) {
+        let temp_dir = tempdir().unwrap();
+        let sled_path = temp_dir.path().to_str().unwrap();
+        let engines: Vec<(&str, Box<dyn KVEngineStoreTrait>)> = vec![
+            ("rwlock", Box::new(RwLockEngine::new(sled_path).unwrap())),
+            ("kv", Box::new(KvEngine::new(sled_path).unwrap())),
+            ("sled", Box::new(SledEngine::new(sled_path).unwrap())),
+        ];
+        for (name, engine) in &engines {
+            check(name, engine.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_append_prepend_create_missing_keys() {
+        for_each_engine(|name, engine| {
+            assert_eq!(engine.append("a", "tail").unwrap(), "tail", "{}", name);
+            assert_eq!(engine.get("a"), Some("tail".to_string()), "{}", name);
+            assert_eq!(engine.prepend("p", "head").unwrap(), "head", "{}", name);
+            assert_eq!(engine.get("p"), Some("head".to_string()), "{}", name);
+
+            // Empty values on missing keys create an empty value
+            assert_eq!(engine.append("empty_a", "").unwrap(), "", "{}", name);
+            assert_eq!(engine.get("empty_a"), Some(String::new()), "{}", name);
+            assert_eq!(engine.prepend("empty_p", "").unwrap(), "", "{}", name);
+            assert_eq!(engine.get("empty_p"), Some(String::new()), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_increment_decrement_missing_keys_start_from_zero() {
+        for_each_engine(|name, engine| {
+            for amount in [None, Some(5)] {
+                engine.set("zero".to_string(), "0".to_string()).unwrap();
+                engine.delete("missing");
+                let created = engine.increment("missing", amount).unwrap();
+                assert_eq!(created, engine.increment("zero", amount).unwrap(), "{}", name);
+                assert_eq!(engine.get("missing"), engine.get("zero"), "{}", name);
+
+                engine.set("zero".to_string(), "0".to_string()).unwrap();
+                engine.delete("missing");
+                let created = engine.decrement("missing", amount).unwrap();
+                assert_eq!(created, engine.decrement("zero", amount).unwrap(), "{}", name);
+                assert_eq!(engine.get("missing"), engine.get("zero"), "{}", name);
+            }
+            assert_eq!(engine.get("missing"), Some("-5".to_string()), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_append_prepend_existing_keys() {
+        for_each_engine(|name, engine| {
+            engine.set("s".to_string(), "core".to_string()).unwrap();
+            assert_eq!(engine.append("s", "+tail").unwrap(), "core+tail", "{}", name);
+            assert_eq!(engine.prepend("s", "head+").unwrap(), "head+core+tail", "{}", name);
+            assert_eq!(engine.append("s", "").unwrap(), "head+core+tail", "{}", name);
+            assert_eq!(engine.get("s"), Some("head+core+tail".to_string()), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_compare_and_swap() {
+        for_each_engine(|name, engine| {
+            // Missing key with empty expected creates the key
+            assert!(engine.compare_and_swap("c", "", "1").unwrap(), "{}", name);
+            assert_eq!(engine.get("c"), Some("1".to_string()), "{}", name);
+
+            // Match swaps the value
+            assert!(engine.compare_and_swap("c", "1", "2").unwrap(), "{}", name);
+            assert_eq!(engine.get("c"), Some("2".to_string()), "{}", name);
+
+            // Mismatch leaves the value untouched
+            assert!(!engine.compare_and_swap("c", "1", "3").unwrap(), "{}", name);
+            assert!(!engine.compare_and_swap("c", "", "3").unwrap(), "{}", name);
+            assert!(!engine.compare_and_swap("missing", "x", "y").unwrap(), "{}", name);
+            assert_eq!(engine.get("c"), Some("2".to_string()), "{}", name);
+            assert_eq!(engine.get("missing"), None, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_concurrent_compare_and_swap_serializes() {
+        for_each_engine(|name, engine| {
+            engine.set("counter".to_string(), "0".to_string()).unwrap();
+            std::thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        for _ in 0..50 {
+                            // Retry until our read-modify-write wins
+                            loop {
+                                let current = engine.get("counter").unwrap();
+                                let next = (current.parse::<i64>().unwrap() + 1).to_string();
+                                if engine.compare_and_swap("counter", &current, &next).unwrap() {
+                                    break;
+                                }
+                            }
+                        }
+                    });
+                }
+            });
+            assert_eq!(engine.get("counter"), Some("200".to_string()), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_concurrent_read_modify_writes_are_not_lost() {
+        for_each_engine(|name, engine| {
+            std::thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        for _ in 0..50 {
+                            engine.increment("counter", None).unwrap();
+                            engine.decrement("gauge", Some(2)).unwrap();
+                            engine.append("log", "a").unwrap();
+                            engine.prepend("log", "p").unwrap();
+                        }
+                    });
+                }
+            });
+            assert_eq!(engine.get("counter"), Some("200".to_string()), "{}", name);
+            assert_eq!(engine.get("gauge"), Some("-400".to_string()), "{}", name);
+            let log = engine.get("log").unwrap();
+            assert_eq!(log.matches('a').count(), 200, "{}", name);
+            assert_eq!(log.matches('p').count(), 200, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_set_many_applies_all_pairs() {
+        for_each_engine(|name, engine| {
+            engine.set("a".to_string(), "old".to_string()).unwrap();
+            let pairs = vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "2".to_string()),
+                ("c".to_string(), "3".to_string()),
+            ];
+            engine.set_many(&pairs).unwrap();
+            for (k, v) in &pairs {
+                assert_eq!(engine.get(k).as_deref(), Some(v.as_str()), "{}", name);
+            }
+        });
+    }
+
+    #[test]
+    fn test_set_many_invalid_batch_stores_nothing() {
+        for_each_engine(|name, engine| {
+            let pairs = vec![
+                ("a".to_string(), "1".to_string()),
+                (String::new(), "2".to_string()),
+                ("c".to_string(), "3".to_string()),
+            ];
+            assert!(engine.set_many(&pairs).is_err(), "{}", name);
+            assert!(engine.set_many(&[]).is_err(), "{}", name);
+            assert_eq!(engine.get("a"), None, "{}", name);
+            assert_eq!(engine.get("c"), None, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_hash_field_overwrite() {
+        for_each_engine(|name, engine| {
+            assert!(engine.hset("user", "name", "ada").unwrap(), "{}", name);
+            assert!(!engine.hset("user", "name", "grace").unwrap(), "{}", name);
+            assert_eq!(engine.hget("user", "name").unwrap().as_deref(), Some("grace"), "{}", name);
+            assert_eq!(engine.hget("user", "missing").unwrap(), None, "{}", name);
+            assert_eq!(engine.hget("nokey", "name").unwrap(), None, "{}", name);
+            assert_eq!(engine.hlen("user").unwrap(), 1, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_hgetall_sorted_by_field() {
+        for_each_engine(|name, engine| {
+            engine.hset("h", "zeta", "3").unwrap();
+            engine.hset("h", "alpha", "1").unwrap();
+            engine.hset("h", "mid", "2").unwrap();
+            let all = engine.hgetall("h").unwrap();
+            let fields: Vec<&str> = all.iter().map(|(f, _)| f.as_str()).collect();
+            assert_eq!(fields, vec!["alpha", "mid", "zeta"], "{}", name);
+            assert!(engine.hgetall("nokey").unwrap().is_empty(), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_hdel_counts_and_removes_empty_hash() {
+        for_each_engine(|name, engine| {
+            engine.hset("h", "a", "1").unwrap();
+            engine.hset("h", "b", "2").unwrap();
+            let fields = vec!["a".to_string(), "missing".to_string()];
+            assert_eq!(engine.hdel("h", &fields).unwrap(), 1, "{}", name);
+            assert_eq!(engine.hdel("h", &fields).unwrap(), 0, "{}", name);
+            assert_eq!(engine.hlen("h").unwrap(), 1, "{}", name);
+
+            assert_eq!(engine.hdel("h", &["b".to_string()]).unwrap(), 1, "{}", name);
+            assert!(!engine.exists("h"), "{}", name);
+            assert_eq!(engine.hdel("h", &["b".to_string()]).unwrap(), 0, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_hash_ops_on_string_key_are_wrongtype() {
+        for_each_engine(|name, engine| {
+            engine.set("s".to_string(), "plain".to_string()).unwrap();
+            for err in [
+                engine.hset("s", "f", "v").unwrap_err(),
+                engine.hget("s", "f").unwrap_err(),
+                engine.hdel("s", &["f".to_string()]).unwrap_err(),
+            ] {
+                assert!(err.to_string().starts_with("WRONGTYPE"), "{}: {}", name, err);
+            }
+            assert_eq!(engine.get("s").as_deref(), Some("plain"), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_random_key_eventually_returns_every_key() {
+        for_each_engine(|name, engine| {
+            assert_eq!(engine.random_key(), None, "{}", name);
+
+            let keys: HashSet<String> = (0..10).map(|i| format!("k{}", i)).collect();
+            for k in &keys {
+                engine.set(k.clone(), "v".to_string()).unwrap();
+            }
+            // P(missing a given key in 1000 draws) = 0.9^1000, effectively zero
+            let mut seen = HashSet::new();
+            for _ in 0..1000 {
+                let k = engine.random_key().unwrap();
+                assert!(keys.contains(&k), "{}: unexpected key {}", name, k);
+                seen.insert(k);
+            }
+            assert_eq!(seen, keys, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_set_with_expiry_hides_and_purges_expired_keys() {
+        use crate::store::expiry::now_millis;
+
+        for_each_engine(|name, engine| {
+            let later = now_millis() + 60_000;
+            engine.set_with_expiry("live".to_string(), "1".to_string(), later).unwrap();
+            engine.set_with_expiry("dead".to_string(), "5".to_string(), now_millis() - 1).unwrap();
+            engine.set("plain".to_string(), "v".to_string()).unwrap();
+
+            assert_eq!(engine.get("live").as_deref(), Some("1"), "{}", name);
+            assert_eq!(engine.expiry("live"), Some(later), "{}", name);
+            assert_eq!(engine.get("dead"), None, "{}", name);
+            assert!(!engine.exists("dead"), "{}", name);
+            assert_eq!(engine.expiry("plain"), None, "{}", name);
+
+            // Writes build on an expired key as if it were missing
+            assert_eq!(engine.increment("dead", Some(2)).unwrap(), 2, "{}", name);
+            assert_eq!(engine.expiry("dead"), None, "{}", name);
+
+            engine.set_with_expiry("gone".to_string(), "x".to_string(), now_millis() - 1).unwrap();
+            assert_eq!(engine.purge_expired(), vec!["gone".to_string()], "{}", name);
+            assert_eq!(engine.len(), 3, "{}", name);
+
+            // A plain SET makes the key persistent again
+            engine.set("live".to_string(), "2".to_string()).unwrap();
+            assert_eq!(engine.expiry("live"), None, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_get_and_touch_expiry() {
+        use crate::store::expiry::now_millis;
+
+        for_each_engine(|name, engine| {
+            let later = now_millis() + 60_000;
+            engine.set("k".to_string(), "v".to_string()).unwrap();
+            assert_eq!(engine.get_and_touch_expiry("k", Some(later)).as_deref(), Some("v"), "{}", name);
+            assert_eq!(engine.expiry("k"), Some(later), "{}", name);
+            assert_eq!(engine.get_and_touch_expiry("k", None).as_deref(), Some("v"), "{}", name);
+            assert_eq!(engine.expiry("k"), None, "{}", name);
+
+            // Missing and expired keys are left alone
+            assert_eq!(engine.get_and_touch_expiry("missing", Some(later)), None, "{}", name);
+            assert_eq!(engine.expiry("missing"), None, "{}", name);
+            engine.set_with_expiry("dead".to_string(), "x".to_string(), now_millis() - 1).unwrap();
+            assert_eq!(engine.get_and_touch_expiry("dead", None), None, "{}", name);
+            assert!(!engine.exists("dead"), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_set_many_with_expiry_sets_each_deadline() {
+        use crate::store::expiry::now_millis;
+
+        for_each_engine(|name, engine| {
+            let later = now_millis() + 60_000;
+            engine.set_with_expiry("persist".to_string(), "old".to_string(), later).unwrap();
+            let entries = vec![
+                ("dead".to_string(), "1".to_string(), Some(now_millis() - 1)),
+                ("live".to_string(), "2".to_string(), Some(later)),
+                ("persist".to_string(), "3".to_string(), None),
+            ];
+            engine.set_many_with_expiry(&entries).unwrap();
+
+            assert_eq!(engine.get("dead"), None, "{}", name);
+            assert_eq!(engine.get("live").as_deref(), Some("2"), "{}", name);
+            assert_eq!(engine.expiry("live"), Some(later), "{}", name);
+            // An entry without a deadline clears the one the key had
+            assert_eq!(engine.get("persist").as_deref(), Some("3"), "{}", name);
+            assert_eq!(engine.expiry("persist"), None, "{}", name);
+
+            // An invalid batch stores nothing
+            let invalid = vec![
+                ("fresh".to_string(), "v".to_string(), Some(later)),
+                (String::new(), "v".to_string(), None),
+            ];
+            assert!(engine.set_many_with_expiry(&invalid).is_err(), "{}", name);
+            assert!(engine.set_many_with_expiry(&[]).is_err(), "{}", name);
+            assert_eq!(engine.get("fresh"), None, "{}", name);
+            assert_eq!(engine.expiry("fresh"), None, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_keyspace_stats() {
+        for_each_engine(|name, engine| {
+            assert_eq!(engine.keyspace_stats(), KeyspaceStats::default(), "{}", name);
+
+            engine.set("a".to_string(), "x".repeat(10)).unwrap();
+            engine.set("bb".to_string(), "x".repeat(30)).unwrap();
+            engine.set("c".to_string(), "x".repeat(30)).unwrap();
+            engine.set("d".to_string(), "x".repeat(2)).unwrap();
+
+            let stats = engine.keyspace_stats();
+            assert_eq!(stats.keys, 4, "{}", name);
+            assert_eq!(stats.key_bytes, 5, "{}", name);
+            assert_eq!(stats.value_bytes, 72, "{}", name);
+            assert_eq!(stats.memory_bytes(), 77, "{}", name);
+            assert_eq!(stats.avg_value_bytes(), 18, "{}", name);
+            assert_eq!(stats.largest, Some(("bb".to_string(), 30)), "{}", name);
+            assert_eq!(stats.size_histogram, SizeHistogram([2, 2, 0, 0, 0, 0, 0]), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_increment_limit() {
+        for_each_engine(|name, engine| {
+            assert_eq!(engine.increment_limit("c", 2, 3).unwrap(), (2, true), "{}", name);
+            assert_eq!(engine.increment_limit("c", 1, 3).unwrap(), (3, true), "{}", name);
+            assert_eq!(engine.increment_limit("c", 1, 3).unwrap(), (4, false), "{}", name);
+            assert_eq!(engine.get("c").as_deref(), Some("4"), "{}", name);
+
+            engine.set("s".to_string(), "text".to_string()).unwrap();
+            assert!(engine.increment_limit("s", 1, 3).is_err(), "{}", name);
+            engine.set("max".to_string(), i64::MAX.to_string()).unwrap();
+            assert!(engine.increment_limit("max", 1, 3).is_err(), "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_numeric_errors() {
+        for_each_engine(|name, engine| {
+            engine.set("max".to_string(), i64::MAX.to_string()).unwrap();
+            assert_eq!(engine.increment("max", None).unwrap_err().to_string(), OVERFLOW, "{}", name);
+            engine.set("min".to_string(), i64::MIN.to_string()).unwrap();
+            assert_eq!(engine.decrement("min", None).unwrap_err().to_string(), OVERFLOW, "{}", name);
+            assert_eq!(engine.get("max").as_deref(), Some(i64::MAX.to_string().as_str()), "{}", name);
+            assert_eq!(engine.get("min").as_deref(), Some(i64::MIN.to_string().as_str()), "{}", name);
+            assert_eq!(engine.increment("max", Some(-1)).unwrap(), i64::MAX - 1, "{}", name);
+
+            engine.set("s".to_string(), "text".to_string()).unwrap();
+            assert_eq!(engine.increment("s", None).unwrap_err().to_string(), NOT_AN_INTEGER, "{}", name);
+            assert_eq!(engine.decrement("s", None).unwrap_err().to_string(), NOT_AN_INTEGER, "{}", name);
+        });
+    }
+
+    #[test]
+    fn test_concurrent_increment_limit_allows_exactly_limit() {
+        let engine = std::sync::Arc::new(RwLockEngine::new("").unwrap());
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let engine = engine.clone();
+                std::thread::spawn(move || {
+                    (0..25).filter(|_| engine.increment_limit("c", 1, 50).unwrap().1).count()
+                })
+            })
+            .collect();
+        let allowed: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(allowed, 50);
+        assert_eq!(engine.get("c").as_deref(), Some("200"));
+    }
 }
@@ -31,6 +31,7 @@ pub struct MerkleTree {
     leaf_map: HashMap<String, Vec<u8>>,
 }
 
+#[allow(dead_code)]
 impl MerkleTree {
     /// Create an empty Merkle tree.
     pub fn new() -> Self {
@@ -55,6 +56,15 @@ impl MerkleTree {
         self.rebuild();
     }
 
+    /// Insert or update many (key, value) pairs, rebuilding the tree once at the end.
+    pub fn insert_batch<K: AsRef<str>, V: AsRef<str>>(&mut self, entries: impl IntoIterator<Item = (K, V)>) {
+        for (key, value) in entries {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            self.leaf_map.insert(key.to_string(), Self::compute_leaf_hash(key, value));
+        }
+        self.rebuild();
+    }
+
     /// Remove a key (if it exists) and rebuild the tree.
     pub fn remove(&mut self, key: &str) {
         self.leaf_map.remove(key);
@@ -140,14 +150,20 @@ impl MerkleTree {
     /// Preorder traversal returning node hashes from the current materialized tree.
     /// (Root → Left-subtree → Right-subtree)
     pub fn preorder_hashes(&self) -> Vec<Vec<u8>> {
-        fn go(n: &MerkleNode, acc: &mut Vec<Vec<u8>>) {
-            acc.push(n.hash.clone());
-            if let Some(l) = &n.left { go(l, acc); }
-            if let Some(r) = &n.right { go(r, acc); }
+        self.preorder_nodes().into_iter().map(|(_, n)| n.hash.clone()).collect()
+    }
+
+    /// Preorder traversal returning each node with its depth (the root is at 0).
+    /// Leaves carry their key, so this is enough to draw the tree (`MERKLE DUMP`).
+    pub fn preorder_nodes(&self) -> Vec<(usize, &MerkleNode)> {
+        fn go<'a>(n: &'a MerkleNode, depth: usize, acc: &mut Vec<(usize, &'a MerkleNode)>) {
+            acc.push((depth, n));
+            if let Some(l) = &n.left { go(l, depth + 1, acc); }
+            if let Some(r) = &n.right { go(r, depth + 1, acc); }
         }
         let mut out = Vec::new();
         if let Some(r) = &self.root {
-            go(r, &mut out);
+            go(r, 0, &mut out);
         }
         out
     }
@@ -162,6 +178,48 @@ impl MerkleTree {
         self.root.as_ref().map(cnt).unwrap_or(0)
     }
 
+    // ===================== BUCKETS =====================
+
+    /// Bucket a key is assigned to: the first 8 bytes of SHA-256(key), mod `n`.
+    /// A cryptographic hash keeps the assignment identical on every node.
+    pub fn bucket_of(key: &str, n: usize) -> usize {
+        let digest = Sha256::digest(key.as_bytes());
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(&digest[..8]);
+        (u64::from_be_bytes(prefix) % n as u64) as usize
+    }
+
+    /// Merkle root of each of `n` buckets (None for an empty bucket).
+    /// Each root equals the root of a tree holding only that bucket's keys,
+    /// so peers can compare bucket-by-bucket and drill into mismatches.
+    pub fn bucket_roots(&self, n: usize) -> Vec<Option<Vec<u8>>> {
+        let mut buckets: Vec<Vec<Vec<u8>>> = vec![Vec::new(); n];
+        for (k, h) in self.leaves() {
+            buckets[Self::bucket_of(&k, n)].push(h);
+        }
+        buckets.into_iter().map(Self::root_of).collect()
+    }
+
+    /// Reduce sorted leaf hashes to a root using the same pairing rules as `rebuild`.
+    fn root_of(mut level: Vec<Vec<u8>>) -> Option<Vec<u8>> {
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|chunk| {
+                    if chunk.len() == 2 {
+                        let mut hasher = Sha256::new();
+                        hasher.update(&chunk[0]);
+                        hasher.update(&chunk[1]);
+                        hasher.finalize().to_vec()
+                    } else {
+                        chunk[0].clone()
+                    }
+                })
+                .collect();
+        }
+        level.into_iter().next()
+    }
+
     // ===================== DIFF SUPPORT (find the “wrong” keys) =====================
 
     /// Return the exact set of differing keys between `self` and `other`.
@@ -212,7 +270,6 @@ mod tests {
     use std::collections::HashSet;
     use rand::rngs::StdRng;
     use rand::{SeedableRng, Rng};
-    use rand::seq::SliceRandom;
 
     fn set<T: Eq + std::hash::Hash + Clone>(xs: &[T]) -> HashSet<T> {
         xs.iter().cloned().collect()
@@ -383,12 +440,29 @@ mod tests {
         assert_eq!(r1, r2, "🌐 Rebuilding with identical data must keep the root");
     }
 
+    #[test]
+    fn test_insert_batch_matches_single_inserts() {
+        let data = [("b", "2"), ("a", "1"), ("c", "3"), ("a", "updated")];
+        let mut single = MerkleTree::new();
+        for (k, v) in &data {
+            single.insert(k, v);
+        }
+        let mut batched = MerkleTree::new();
+        batched.insert_batch(data);
+        assert_eq!(batched.get_root_hash(), single.get_root_hash());
+        assert_eq!(batched.inorder_keys(), vec!["a", "b", "c"]);
+
+        let mut empty = MerkleTree::new();
+        empty.insert_batch(Vec::<(String, String)>::new());
+        assert!(empty.get_root_hash().is_none());
+    }
+
     // ───────────────────────── Hard/edge tests ─────────────────────────
 
     /// 🧭 Determinism (even count): same set, different insertion orders → same root.
     #[test]
     fn hard_determinism_even_count_different_insert_orders() {
-        let pairs = vec![("k1","v1"), ("k2","v2"), ("k3","v3"), ("k4","v4")];
+        let pairs = [("k1","v1"), ("k2","v2"), ("k3","v3"), ("k4","v4")];
 
         // Order A
         let mut t1 = MerkleTree::new();
@@ -406,7 +480,7 @@ mod tests {
     /// 🧭 Determinism (odd count): same set, different insertion orders → same root.
     #[test]
     fn hard_determinism_odd_count_different_insert_orders() {
-        let pairs = vec![("a","1"), ("b","2"), ("c","3")];
+        let pairs = [("a","1"), ("b","2"), ("c","3")];
 
         let mut t1 = MerkleTree::new();
         for (k,v) in pairs.iter() { t1.insert(k, v); }
@@ -446,7 +520,7 @@ mod tests {
     /// 🧪 Two independent trees over the same dataset (different orders) → same root.
     #[test]
     fn hard_two_independent_trees_same_set_same_root() {
-        let set1 = vec![("u","1"), ("v","2"), ("w","3"), ("z","4"), ("q","5")];
+        let set1 = [("u","1"), ("v","2"), ("w","3"), ("z","4"), ("q","5")];
 
         // Tree 1: order 1
         let mut t1 = MerkleTree::new();
@@ -647,7 +721,7 @@ mod tests {
 
         // Sort by key and compute manually.
         let mut sorted = items.clone();
-        sorted.sort_by(|a,b| a.0.cmp(&b.0));
+        sorted.sort_by(|a,b| a.0.cmp(b.0));
 
         let h: Vec<Vec<u8>> = sorted.iter().map(|(k,v)| leaf_hash(k,v)).collect();
 
@@ -742,7 +816,7 @@ mod tests {
     #[test]
     fn diff_empty_vs_nonempty_returns_all_keys() {
         let mut a = MerkleTree::new();
-        let mut b = MerkleTree::new();
+        let b = MerkleTree::new();
 
         for (k,v) in &[("x","1"), ("y","2"), ("z","3")] { a.insert(k,v); }
         // b stays empty
@@ -1012,7 +1086,7 @@ mod tests {
         let got = t.get_root_hash().unwrap().clone();
 
         let mut sorted = items.clone();
-        sorted.sort_by(|a,b| a.0.cmp(&b.0));
+        sorted.sort_by(|a,b| a.0.cmp(b.0));
         let hs: Vec<Vec<u8>> = sorted.iter().map(|(k,v)| leaf_hash(k,v)).collect();
 
         let mut h12 = Sha256::new(); h12.update(&hs[0]); h12.update(&hs[1]); let h12 = h12.finalize();
@@ -1181,4 +1255,52 @@ mod tests {
         assert_eq!(pre.len(), t.node_count());
     }
     
+    // 23) Buckets: one changed key flips exactly one bucket root
+    #[test]
+    fn t23_bucket_roots_change_one_bucket() {
+        let mut t = MerkleTree::new();
+        for i in 0..100 { t.insert(&format!("k{i}"), &format!("v{i}")); }
+        let before = t.bucket_roots(8);
+        assert_eq!(before.len(), 8);
+
+        t.insert("k42", "changed");
+        let after = t.bucket_roots(8);
+        let flipped: Vec<usize> = (0..8).filter(|&i| before[i] != after[i]).collect();
+        assert_eq!(flipped, vec![MerkleTree::bucket_of("k42", 8)]);
+    }
+
+    // 24) Buckets: each bucket root equals the root of a tree of just its keys
+    #[test]
+    fn t24_bucket_root_matches_subtree_root() {
+        let mut t = MerkleTree::new();
+        for i in 0..20 { t.insert(&format!("k{i}"), &format!("v{i}")); }
+        let roots = t.bucket_roots(4);
+        for (b, root) in roots.iter().enumerate() {
+            let mut sub = MerkleTree::new();
+            for i in 0..20 {
+                let k = format!("k{i}");
+                if MerkleTree::bucket_of(&k, 4) == b { sub.insert(&k, &format!("v{i}")); }
+            }
+            assert_eq!(root.as_ref(), sub.get_root_hash());
+        }
+
+        // A single bucket is the whole tree
+        assert_eq!(t.bucket_roots(1)[0].as_ref(), t.get_root_hash());
+        assert_eq!(MerkleTree::new().bucket_roots(3), vec![None, None, None]);
+    }
+
+    // 25) Preorder nodes: root first at depth 0, leaves keyed in key order
+    #[test]
+    fn t25_preorder_nodes_depths_and_keys() {
+        let mut t = MerkleTree::new();
+        for k in ["a", "b", "c"] { t.insert(k, "v"); }
+        let nodes = t.preorder_nodes();
+        assert_eq!(nodes.len(), t.node_count());
+        let shape: Vec<(usize, Option<&str>)> =
+            nodes.iter().map(|(d, n)| (*d, n.key.as_deref())).collect();
+        // ((a b) c): the odd leaf is promoted to the root's right child
+        assert_eq!(shape, vec![(0, None), (1, None), (2, Some("a")), (2, Some("b")), (1, Some("c"))]);
+        assert_eq!(&nodes[0].1.hash, t.get_root_hash().unwrap());
+        assert!(MerkleTree::new().preorder_nodes().is_empty());
+    }
 }
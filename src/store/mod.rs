@@ -6,6 +6,11 @@
 //! - **`rwlock_engine`**: Thread-safe in-memory storage using RwLock<HashMap>
 //! - **`kv_engine`**: Non-thread-safe in-memory storage using Arc<HashMap>
 //! - **`merkle`**: Merkle tree implementation for efficient synchronization
+//! - **`hash_value`**: Encoding of hash (`HSET`/`HGET`) values inside string values
+//! - **`binary_value`**: Encoding of raw-byte (`BSET`) values inside string values
+//! - **`expiry`**: Per-key expiry deadlines for `SETEX`/`PSETEX`
+//! - **`compressed_value`**: In-memory compression of large `RwLockEngine` values
+//! - **`storage_path`**: Checks that an engine's `storage_path` is usable
 //!
 //! ## Design Philosophy
 //!
@@ -21,11 +26,16 @@
 //! - Add support for range queries and iteration
 //! - Optimize Merkle tree for incremental updates
 
+pub mod binary_value;
+pub mod compressed_value;
+pub mod expiry;
+pub mod hash_value;
 pub mod kv_engine;
 pub mod kv_trait;
 pub mod merkle;
 pub mod rwlock_engine;
 pub mod sled_engine;
+pub mod storage_path;
 
 // Re-export the trait and engines for convenience
 pub use kv_engine::KvEngine;
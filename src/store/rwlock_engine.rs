@@ -11,20 +11,30 @@
 //! - **No race conditions**: All operations are properly synchronized
 //! - **Efficient**: Readers don't block each other, only writers block
 //!
+//! Values longer than the compression threshold (off by default) are held
+//! packed; see `compressed_value`. Every method still takes and returns the
+//! original strings.
+//!
 //! ## Future Implementation Plans
 //!
 //! This is a production-ready in-memory implementation. Future versions could:
 //! - Add persistent storage (e.g., RocksDB, Sled)
 //! - Support transactions and atomic operations
 //! - Implement Write-Ahead Logging (WAL)
-//! - Add compression and efficient serialization
+//! - Add efficient serialization
 //! - Support range queries and iteration
 
 use anyhow::Result;
+use log::error;
+use rand::seq::IteratorRandom;
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
-use super::kv_trait::KVEngineStoreTrait;
+use super::compressed_value::Stored;
+use super::expiry::ExpiryMap;
+use super::storage_path::validate_storage_dir;
+use super::kv_trait::{validate_batch, validate_batch_keys, KVEngineStoreTrait, KeyspaceStats, NOT_AN_INTEGER, OVERFLOW};
 
 /// Thread-safe in-memory key-value storage engine.
 ///
@@ -39,7 +49,18 @@ use super::kv_trait::KVEngineStoreTrait;
 pub struct RwLockEngine {
     /// Thread-safe shared reference to the key-value data
     /// Using RwLock allows multiple readers or a single writer
-    data: Arc<RwLock<HashMap<String, String>>>,
+    data: Arc<RwLock<HashMap<String, Stored>>>,
+    /// Expiry deadlines for keys written with `set_with_expiry`
+    expiries: Arc<ExpiryMap>,
+    /// Values longer than this many bytes are stored compressed; 0 disables
+    compress_threshold: usize,
+    /// Hold canonical integer values as `i64` instead of strings
+    int_encoding: bool,
+    /// Version of each key's last write (GETVER / CASVER)
+    versions: Arc<Mutex<HashMap<String, u64>>>,
+    /// Last version handed out; shared by all keys so a re-created key never
+    /// reuses a version it had before
+    next_version: Arc<AtomicU64>,
     // TODO: Add persistent storage implementation
     // In a real implementation, this would use a persistent storage engine like Sled:
     // storage_path: PathBuf,
@@ -50,14 +71,17 @@ impl RwLockEngine {
     /// Create a new storage engine instance.
     ///
     /// # Arguments
-    /// * `_storage_path` - Path where data should be stored (currently unused)
+    /// * `storage_path` - Path where data should be stored; nothing is written yet,
+    ///   but an existing path must be a directory
     ///
     /// # Returns
     /// * `Result<RwLockEngine>` - New storage engine instance or error
     ///
     /// # Thread Safety
     /// The returned engine is safe to share across multiple threads.
-    pub fn new(_storage_path: &str) -> Result<Self> {
+    pub fn new(storage_path: &str) -> Result<Self> {
+        validate_storage_dir(storage_path)?;
+
         // TODO: Initialize persistent storage engine here
         // For example, with Sled:
         // let db = sled::open(storage_path)?;
@@ -65,8 +89,85 @@ impl RwLockEngine {
 
         Ok(Self {
             data: Arc::new(RwLock::new(HashMap::new())),
+            expiries: Arc::new(ExpiryMap::new()),
+            compress_threshold: 0,
+            int_encoding: false,
+            versions: Arc::new(Mutex::new(HashMap::new())),
+            next_version: Arc::new(AtomicU64::new(0)),
         })
     }
+
+    /// Store values longer than `bytes` compressed (0 disables compression).
+    pub fn with_compress_threshold(mut self, bytes: usize) -> Self {
+        self.compress_threshold = bytes;
+        self
+    }
+
+    /// Hold integer values as numbers, so counters are updated in place
+    /// without formatting or parsing strings.
+    pub fn with_int_encoding(mut self, enabled: bool) -> Self {
+        self.int_encoding = enabled;
+        self
+    }
+
+    fn pack(&self, value: String) -> Stored {
+        if self.int_encoding {
+            Stored::with_int_encoding(value, self.compress_threshold)
+        } else {
+            Stored::new(value, self.compress_threshold)
+        }
+    }
+
+    /// Unpack `key`'s value for a read. A packed value that no longer
+    /// unpacks is logged and read as missing rather than failing the caller.
+    fn unpack(key: &str, stored: &Stored) -> Option<String> {
+        stored.value().map_err(|e| error!("Unreadable value for key {}: {}", key, e)).ok()
+    }
+
+    /// Replace the integer at `key` (0 if absent) with `update(current)`,
+    /// under the caller's write lock. An int-encoded value is read and
+    /// rewritten in place.
+    fn update_int(
+        &self,
+        data: &mut HashMap<String, Stored>,
+        key: &str,
+        update: impl FnOnce(i64) -> Option<i64>,
+    ) -> Result<i64> {
+        let current = match data.get(key) {
+            Some(Stored::Int(n)) => *n,
+            Some(stored) => stored.value()?.parse::<i64>().map_err(|_| anyhow::anyhow!(NOT_AN_INTEGER))?,
+            None => 0,
+        };
+        let new_value = update(current).ok_or_else(|| anyhow::anyhow!(OVERFLOW))?;
+        let stored = if self.int_encoding {
+            Stored::Int(new_value)
+        } else {
+            self.pack(new_value.to_string())
+        };
+        match data.get_mut(key) {
+            Some(slot) => *slot = stored,
+            None => {
+                data.insert(key.to_string(), stored);
+            }
+        }
+        self.bump_version(key);
+        Ok(new_value)
+    }
+
+    /// Give `key` a new version. Called with the data write lock held, so
+    /// versions follow the order writes land in.
+    fn bump_version(&self, key: &str) {
+        let version = self.next_version.fetch_add(1, Ordering::Relaxed) + 1;
+        self.versions.lock().unwrap().insert(key.to_string(), version);
+    }
+
+    /// Current version of `key`, 0 if it is absent from `data`.
+    fn version_of(&self, data: &HashMap<String, Stored>, key: &str) -> u64 {
+        if !data.contains_key(key) {
+            return 0;
+        }
+        self.versions.lock().unwrap().get(key).copied().unwrap_or(0)
+    }
 }
 
 impl KVEngineStoreTrait for RwLockEngine {
@@ -94,7 +195,10 @@ impl KVEngineStoreTrait for RwLockEngine {
     fn get(&self, key: &str) -> Option<String> {
         // Acquire shared read lock - multiple readers can proceed simultaneously
         let data = self.data.read().unwrap();
-        data.get(key).cloned()
+        if self.expiries.is_expired(key) {
+            return None;
+        }
+        data.get(key).and_then(|stored| Self::unpack(key, stored))
     }
 
     /// Store a key-value pair.
@@ -118,7 +222,9 @@ impl KVEngineStoreTrait for RwLockEngine {
     fn set(&self, key: String, value: String) -> Result<()> {
         // Acquire exclusive write lock - only one writer at a time
         let mut data = self.data.write().unwrap();
-        data.insert(key, value);
+        self.expiries.clear(&key);
+        self.bump_version(&key);
+        data.insert(key, self.pack(value));
         Ok(())
     }
 
@@ -147,7 +253,24 @@ impl KVEngineStoreTrait for RwLockEngine {
     fn delete(&self, key: &str) -> bool {
         // Acquire exclusive write lock - only one writer at a time
         let mut data = self.data.write().unwrap();
-        data.remove(key).is_some()
+        let expired = self.expiries.expire_if_due(&mut data, key);
+        self.expiries.clear(key);
+        self.versions.lock().unwrap().remove(key);
+        data.remove(key).is_some() && !expired
+    }
+
+    /// Remove a key under the write lock and return its value, so the caller
+    /// can drop it after the lock is released.
+    fn unlink(&self, key: &str) -> Option<String> {
+        let mut data = self.data.write().unwrap();
+        if self.expiries.expire_if_due(&mut data, key) {
+            return None;
+        }
+        self.expiries.clear(key);
+        self.versions.lock().unwrap().remove(key);
+        let stored = data.remove(key);
+        drop(data);
+        stored.and_then(|stored| Self::unpack(key, &stored))
     }
 
     /// Get all keys currently stored in the engine.
@@ -169,6 +292,14 @@ impl KVEngineStoreTrait for RwLockEngine {
         data.keys().cloned().collect()
     }
 
+    /// Pick a random key under the read lock.
+    ///
+    /// Samples while iterating the map, so only the chosen key is cloned.
+    fn random_key(&self) -> Option<String> {
+        let data = self.data.read().unwrap();
+        data.keys().choose(&mut rand::thread_rng()).cloned()
+    }
+
     /// Get the number of key-value pairs in the store.
     ///
     /// # Returns
@@ -193,6 +324,15 @@ impl KVEngineStoreTrait for RwLockEngine {
             .cloned()
             .collect()
     }
+    /// Matching keys and values are collected under one read lock, so the
+    /// result is a consistent snapshot of the prefix.
+    fn scan_values(&self, prefix: &str) -> Vec<(String, String)> {
+        let data = self.data.read().unwrap();
+        data.iter()
+            .filter(|(k, _)| k.starts_with(prefix) && !self.expiries.is_expired(k))
+            .filter_map(|(k, v)| Some((k.clone(), Self::unpack(k, v)?)))
+            .collect()
+    }
     fn ping(&self, message: &str) -> String {
         format!("PONG {}", message)
     }
@@ -208,7 +348,7 @@ impl KVEngineStoreTrait for RwLockEngine {
     
     fn exists(&self, key: &str) -> bool {
         let data = self.data.read().unwrap();
-        data.contains_key(key)
+        data.contains_key(key) && !self.expiries.is_expired(key)
     }
 
     fn memory_usage(&self) -> usize {
@@ -217,7 +357,7 @@ impl KVEngineStoreTrait for RwLockEngine {
         let mut size = std::mem::size_of_val(&*map);
         for (k, v) in map.iter() {
             size += std::mem::size_of_val(k) + k.len();
-            size += std::mem::size_of_val(v) + v.len();
+            size += std::mem::size_of_val(v) + v.stored_len();
         }
         size
     }
@@ -244,7 +384,7 @@ impl KVEngineStoreTrait for RwLockEngine {
     /// * `amount` - The amount to increment by (default: 1)
     ///
     /// # Returns
-    /// * `Result<i64>` - The new value after incrementing, or error if not a valid number
+    /// * `Result<i64>` - The new value after incrementing, or error if not an integer or on overflow
     ///
     /// # Thread Safety
     /// Only one thread can increment at a time. Other threads will wait for the
@@ -252,28 +392,13 @@ impl KVEngineStoreTrait for RwLockEngine {
     fn increment(&self, key: &str, amount: Option<i64>) -> Result<i64> {
         // Acquire exclusive write lock
         let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
         
         // Default increment amount is 1
         let increment_by = amount.unwrap_or(1);
         
-        // Get the current value or initialize to 0
-        let current_value = match data.get(key) {
-            Some(value) => {
-                // Try to parse the current value as a number
-                value.parse::<i64>().map_err(|_| {
-                    anyhow::anyhow!("Value for key '{}' is not a valid number", key)
-                })?
-            }
-            None => 0, // Key doesn't exist, start from 0
-        };
-        
-        // Calculate the new value
-        let new_value = current_value + increment_by;
-        
-        // Store the new value
-        data.insert(key.to_string(), new_value.to_string());
-        
-        Ok(new_value)
+        // A missing key starts from 0
+        self.update_int(&mut data, key, |current| current.checked_add(increment_by))
     }
     
     /// Decrement a numeric value.
@@ -287,7 +412,7 @@ impl KVEngineStoreTrait for RwLockEngine {
     /// * `amount` - The amount to decrement by (default: 1)
     ///
     /// # Returns
-    /// * `Result<i64>` - The new value after decrementing, or error if not a valid number
+    /// * `Result<i64>` - The new value after decrementing, or error if not an integer or on overflow
     ///
     /// # Thread Safety
     /// Only one thread can decrement at a time. Other threads will wait for the
@@ -295,30 +420,23 @@ impl KVEngineStoreTrait for RwLockEngine {
     fn decrement(&self, key: &str, amount: Option<i64>) -> Result<i64> {
         // Acquire exclusive write lock
         let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
         
         // Default decrement amount is 1
         let decrement_by = amount.unwrap_or(1);
         
-        // Get the current value or initialize to 0
-        let current_value = match data.get(key) {
-            Some(value) => {
-                // Try to parse the current value as a number
-                value.parse::<i64>().map_err(|_| {
-                    anyhow::anyhow!("Value for key '{}' is not a valid number", key)
-                })?
-            }
-            None => 0, // Key doesn't exist, start from 0
-        };
-        
-        // Calculate the new value
-        let new_value = current_value - decrement_by;
-        
-        // Store the new value
-        data.insert(key.to_string(), new_value.to_string());
-        
-        Ok(new_value)
+        // A missing key starts from 0
+        self.update_int(&mut data, key, |current| current.checked_sub(decrement_by))
     }
     
+    fn increment_limit(&self, key: &str, amount: i64, limit: i64) -> Result<(i64, bool)> {
+        // Read, add and compare under one write lock so concurrent callers see distinct counts
+        let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
+        let new_value = self.update_int(&mut data, key, |current| current.checked_add(amount))?;
+        Ok((new_value, new_value <= limit))
+    }
+
     /// Append a value to an existing string.
     ///
     /// This method acquires an **exclusive write lock** to ensure thread safety.
@@ -337,19 +455,22 @@ impl KVEngineStoreTrait for RwLockEngine {
     fn append(&self, key: &str, value: &str) -> Result<String> {
         // Acquire exclusive write lock
         let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
         
         // Check if the key exists
-        if let Some(current_value) = data.get(key) {
+        if let Some(current_value) = data.get(key).map(Stored::value).transpose()? {
             // Append the new value
             let new_value = format!("{}{}", current_value, value);
             
             // Store the new value
-            data.insert(key.to_string(), new_value.clone());
+            data.insert(key.to_string(), self.pack(new_value.clone()));
+            self.bump_version(key);
             
             Ok(new_value)
         } else {
             // Key doesn't exist, create it with the value
-            data.insert(key.to_string(), value.to_string());
+            data.insert(key.to_string(), self.pack(value.to_string()));
+            self.bump_version(key);
             Ok(value.to_string())
         }
     }
@@ -372,23 +493,169 @@ impl KVEngineStoreTrait for RwLockEngine {
     fn prepend(&self, key: &str, value: &str) -> Result<String> {
         // Acquire exclusive write lock
         let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
         
         // Check if the key exists
-        if let Some(current_value) = data.get(key) {
+        if let Some(current_value) = data.get(key).map(Stored::value).transpose()? {
             // Prepend the new value
             let new_value = format!("{}{}", value, current_value);
             
             // Store the new value
-            data.insert(key.to_string(), new_value.clone());
+            data.insert(key.to_string(), self.pack(new_value.clone()));
+            self.bump_version(key);
             
             Ok(new_value)
         } else {
             // Key doesn't exist, create it with the value
-            data.insert(key.to_string(), value.to_string());
+            data.insert(key.to_string(), self.pack(value.to_string()));
+            self.bump_version(key);
             Ok(value.to_string())
         }
     }
     
+    /// Atomically replace a value if it currently equals `expected`.
+    ///
+    /// This method acquires an **exclusive write lock** so the comparison and the
+    /// write happen as a single step. A missing key matches an empty `expected`.
+    ///
+    /// # Arguments
+    /// * `key` - The key to update
+    /// * `expected` - The value the key must currently hold
+    /// * `new` - The value to store on a match
+    ///
+    /// # Returns
+    /// * `Result<bool>` - True if the swap happened, false on mismatch
+    ///
+    /// # Thread Safety
+    /// Concurrent CAS attempts on the same key are serialized by the write lock.
+    fn compare_and_swap(&self, key: &str, expected: &str, new: &str) -> Result<bool> {
+        // Acquire exclusive write lock
+        let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
+
+        let matches = match data.get(key).map(Stored::value).transpose()? {
+            Some(current) => current == expected,
+            None => expected.is_empty(),
+        };
+        if matches {
+            data.insert(key.to_string(), self.pack(new.to_string()));
+            self.bump_version(key);
+        }
+        Ok(matches)
+    }
+
+    fn get_versioned(&self, key: &str) -> Option<(String, u64)> {
+        let data = self.data.read().unwrap();
+        if self.expiries.is_expired(key) {
+            return None;
+        }
+        let value = data.get(key).and_then(|stored| Self::unpack(key, stored))?;
+        Some((value, self.version_of(&data, key)))
+    }
+
+    /// Store `value` only if the key's version still equals `expected`.
+    ///
+    /// The check and the write happen under one write lock, like
+    /// `compare_and_swap`. A missing key has version 0.
+    fn set_if_version(&self, key: &str, expected: u64, value: String) -> Result<bool> {
+        let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
+        if self.version_of(&data, key) != expected {
+            return Ok(false);
+        }
+        self.expiries.clear(key);
+        data.insert(key.to_string(), self.pack(value));
+        self.bump_version(key);
+        Ok(true)
+    }
+
+    /// Store several key-value pairs as one all-or-nothing write.
+    ///
+    /// The batch is validated before the write lock is taken; once it is held,
+    /// inserting into the map cannot fail, so every pair lands together.
+    ///
+    /// # Thread Safety
+    /// Readers see either none or all of the batch.
+    fn set_many(&self, pairs: &[(String, String)]) -> Result<()> {
+        validate_batch(pairs)?;
+        let mut data = self.data.write().unwrap();
+        for (key, value) in pairs {
+            self.expiries.clear(key);
+            self.bump_version(key);
+            data.insert(key.clone(), self.pack(value.clone()));
+        }
+        Ok(())
+    }
+
+    fn set_with_expiry(&self, key: String, value: String, deadline_ms: u64) -> Result<()> {
+        // Hold the write lock across both updates so the deadline lands with the value
+        let mut data = self.data.write().unwrap();
+        self.expiries.set(&key, deadline_ms);
+        self.bump_version(&key);
+        data.insert(key, self.pack(value));
+        Ok(())
+    }
+
+    fn set_many_with_expiry(&self, entries: &[(String, String, Option<u64>)]) -> Result<()> {
+        validate_batch_keys(entries.iter().map(|(k, _, _)| k.as_str()))?;
+        let mut data = self.data.write().unwrap();
+        for (key, value, deadline_ms) in entries {
+            match deadline_ms {
+                Some(deadline_ms) => self.expiries.set(key, *deadline_ms),
+                None => self.expiries.clear(key),
+            }
+            self.bump_version(key);
+            data.insert(key.clone(), self.pack(value.clone()));
+        }
+        Ok(())
+    }
+
+    fn expiry(&self, key: &str) -> Option<u64> {
+        self.expiries.deadline(key)
+    }
+
+    fn get_and_touch_expiry(&self, key: &str, deadline_ms: Option<u64>) -> Option<String> {
+        // Hold the write lock so the deadline changes with the value that was read
+        let mut data = self.data.write().unwrap();
+        self.expiries.expire_if_due(&mut data, key);
+        let value = data.get(key).and_then(|stored| Self::unpack(key, stored))?;
+        match deadline_ms {
+            Some(deadline_ms) => self.expiries.set(key, deadline_ms),
+            None => self.expiries.clear(key),
+        }
+        Some(value)
+    }
+
+    fn purge_expired(&self) -> Vec<String> {
+        let mut data = self.data.write().unwrap();
+        let purged = self.expiries.purge(&mut data);
+        let mut versions = self.versions.lock().unwrap();
+        for key in &purged {
+            versions.remove(key);
+        }
+        purged
+    }
+
+    fn keyspace_stats(&self) -> KeyspaceStats {
+        let data = self.data.read().unwrap();
+        let mut stats = KeyspaceStats::default();
+        for (key, value) in data.iter() {
+            if let Some(value) = Self::unpack(key, value) {
+                stats.record(key, &value);
+            }
+        }
+        stats
+    }
+
+    fn for_each_entry(&self, visit: &mut dyn FnMut(&str, &str)) {
+        let data = self.data.read().unwrap();
+        for (key, value) in data.iter() {
+            if let Some(value) = Self::unpack(key, value).filter(|_| !self.expiries.is_expired(key)) {
+                visit(key, &value);
+            }
+        }
+    }
+
     /// Clear all keys/values in the store.
     ///
     /// This method acquires an **exclusive write lock** to ensure thread safety.
@@ -405,6 +672,8 @@ impl KVEngineStoreTrait for RwLockEngine {
         
         // Clear all entries
         data.clear();
+        self.expiries.clear_all();
+        self.versions.lock().unwrap().clear();
         
         Ok(())
     }
@@ -434,6 +703,16 @@ impl KVEngineStoreTrait for RwLockEngine {
         // In a persistent storage engine, this would flush data to disk
         Ok(())
     }
+
+    /// Reclaim storage after heavy churn.
+    /// For this in-memory engine, this is a no-op: deleted values are freed
+    /// as soon as they are removed.
+    ///
+    /// # Returns
+    /// * `Result<()>` - Always returns Ok(())
+    fn compact(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -484,6 +763,104 @@ mod tests {
         assert!(!engine.is_empty());
     }
 
+    #[test]
+    fn test_large_values_are_stored_compressed() {
+        let plain = RwLockEngine::new("").unwrap();
+        let packed = RwLockEngine::new("").unwrap().with_compress_threshold(1024);
+        let large = "merkle ".repeat(10_000);
+        for engine in [&plain, &packed] {
+            engine.set("big".to_string(), large.clone()).unwrap();
+            engine.set("small".to_string(), "v".to_string()).unwrap();
+        }
+
+        assert_eq!(packed.get("big"), Some(large.clone()));
+        assert!(packed.memory_usage() + large.len() / 2 < plain.memory_usage());
+
+        // Read-modify-write operations see the original value
+        assert_eq!(packed.append("big", "!").unwrap().len(), large.len() + 1);
+        assert!(packed.compare_and_swap("big", &format!("{}!", large), "done").unwrap());
+        assert_eq!(packed.get("big"), Some("done".to_string()));
+        assert_eq!(packed.keyspace_stats().value_bytes, 5);
+    }
+
+    #[test]
+    fn test_int_encoding_reads_and_hashes_like_strings() {
+        use crate::store::merkle::MerkleTree;
+
+        let plain = RwLockEngine::new("").unwrap();
+        let encoded = RwLockEngine::new("").unwrap().with_int_encoding(true);
+        for engine in [&plain, &encoded] {
+            engine.set("padded".to_string(), "007".to_string()).unwrap();
+            engine.set("text".to_string(), "12 apples".to_string()).unwrap();
+            engine.set("max".to_string(), i64::MAX.to_string()).unwrap();
+            for i in 0..1000 {
+                let key = format!("counter:{}", i % 50);
+                if i % 3 == 0 {
+                    engine.decrement(&key, Some(i)).unwrap();
+                } else {
+                    engine.increment(&key, None).unwrap();
+                }
+            }
+            assert_eq!(engine.increment_limit("limited", 5, 3).unwrap(), (5, false));
+            assert!(engine.increment("max", None).is_err());
+            assert!(engine.increment("text", None).is_err());
+            engine.increment("padded", Some(1)).unwrap();
+            engine.append("counter:0", "1").unwrap();
+        }
+
+        let mut keys = plain.keys();
+        keys.sort();
+        assert_eq!(keys.len(), 54);
+        let root = |engine: &RwLockEngine| {
+            let mut tree = MerkleTree::new();
+            for key in &keys {
+                tree.insert(key, &engine.get(key).unwrap());
+            }
+            tree.get_root_hash().cloned()
+        };
+        for key in &keys {
+            assert_eq!(encoded.get(key), plain.get(key), "{}", key);
+        }
+        assert_eq!(root(&encoded), root(&plain));
+        assert_eq!(encoded.get("padded"), Some("8".to_string()));
+        assert!(matches!(encoded.data.read().unwrap().get("counter:1"), Some(Stored::Int(_))));
+        assert!(encoded.memory_usage() < plain.memory_usage());
+    }
+
+    #[test]
+    fn test_versions_increase_with_every_write() {
+        let engine = RwLockEngine::new("").unwrap();
+        assert_eq!(engine.get_versioned("k"), None);
+
+        engine.set("k".to_string(), "a".to_string()).unwrap();
+        let (_, v1) = engine.get_versioned("k").unwrap();
+        engine.append("k", "b").unwrap();
+        let (value, v2) = engine.get_versioned("k").unwrap();
+        assert_eq!(value, "ab");
+        assert!(v2 > v1);
+
+        // Reads do not change the version; a re-created key never goes back
+        assert_eq!(engine.get_versioned("k").unwrap().1, v2);
+        assert!(engine.delete("k"));
+        assert_eq!(engine.get_versioned("k"), None);
+        engine.set("k".to_string(), "c".to_string()).unwrap();
+        assert!(engine.get_versioned("k").unwrap().1 > v2);
+    }
+
+    #[test]
+    fn test_set_if_version() {
+        let engine = RwLockEngine::new("").unwrap();
+        // Version 0 means the key must be absent
+        assert!(engine.set_if_version("k", 0, "first".to_string()).unwrap());
+        assert!(!engine.set_if_version("k", 0, "again".to_string()).unwrap());
+
+        let (_, version) = engine.get_versioned("k").unwrap();
+        assert!(!engine.set_if_version("k", version + 1, "x".to_string()).unwrap());
+        assert!(engine.set_if_version("k", version, "second".to_string()).unwrap());
+        assert!(!engine.set_if_version("k", version, "stale".to_string()).unwrap());
+        assert_eq!(engine.get("k"), Some("second".to_string()));
+    }
+
     #[test]
     fn test_concurrent_reads() {
         let engine = Arc::new(RwLockEngine::new("./test_data").unwrap());
@@ -498,7 +875,7 @@ mod tests {
 
         // Spawn multiple reader threads
         let mut handles = vec![];
-        for i in 0..10 {
+        for _ in 0..10 {
             let engine_clone = engine.clone();
             let handle = thread::spawn(move || {
                 for _ in 0..100 {
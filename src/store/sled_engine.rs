@@ -1,27 +1,91 @@
 // src/store/sled_engine.rs
 use anyhow::{Result, anyhow};
-use sled::{Db, Tree, IVec};
-use super::kv_trait::KVEngineStoreTrait;
+use rand::seq::IteratorRandom;
+use sled::transaction::{ConflictableTransactionError, TransactionalTree};
+use sled::{Db, Tree, IVec, Transactional};
+use super::expiry::now_millis;
+use super::storage_path::prepare_storage_dir;
+use super::kv_trait::{validate_batch, validate_batch_keys, KVEngineStoreTrait, KeyspaceStats, NOT_AN_INTEGER, OVERFLOW};
 
 pub struct SledEngine {
     db: Db,
     tree: Tree,
+    /// Expiry deadlines (big-endian Unix millis) for keys in `tree`
+    expiries: Tree,
 }
 
 impl SledEngine {
+    /// Open (or create) the database in `storage_path`, creating the
+    /// directory if needed. Fails if the directory cannot be written.
     pub fn new(storage_path: &str) -> Result<Self> {
+        prepare_storage_dir(storage_path)?;
         let db = sled::open(storage_path)?;
         let tree = db.open_tree(b"merkle_kv")?;
-        Ok(Self { db, tree })
+        let expiries = db.open_tree(b"merkle_kv_expiry")?;
+        Ok(Self { db, tree, expiries })
     }
 
     fn to_string_opt(v: Option<IVec>) -> Option<String> {
         v.map(|ivec| String::from_utf8_lossy(&ivec).to_string())
     }
+
+    fn decode_deadline(v: &[u8]) -> Option<u64> {
+        Some(u64::from_be_bytes(v.try_into().ok()?))
+    }
+
+    fn is_expired(&self, key: &str) -> bool {
+        matches!(self.expiry(key), Some(d) if d <= now_millis())
+    }
+
+    /// Run `f` in a transaction over the data and expiry trees.
+    fn transact<R>(
+        &self,
+        f: impl Fn(&TransactionalTree, &TransactionalTree) -> std::result::Result<R, ConflictableTransactionError<()>>,
+    ) -> Result<R> {
+        (&self.tree, &self.expiries)
+            .transaction(|(t, e)| f(t, e))
+            .map_err(|e| anyhow!("sled transaction failed: {:?}", e))
+    }
+
+    /// Remove `key` if its deadline has passed, checked inside the transaction
+    /// so a concurrent rewrite of the key is never lost. Returns true if removed.
+    fn expire_if_due(&self, key: &str) -> Result<bool> {
+        if !self.is_expired(key) {
+            return Ok(false);
+        }
+        self.transact(|t, e| {
+            let due = e
+                .get(key)?
+                .and_then(|v| Self::decode_deadline(&v))
+                .is_some_and(|d| d <= now_millis());
+            if due {
+                e.remove(key)?;
+                t.remove(key)?;
+            }
+            Ok(due)
+        })
+    }
+
+    /// Replace the value of `key` with `update(current)`, retrying the
+    /// compare-and-swap until no other writer got in between. Returns the
+    /// value written.
+    fn read_modify_write(&self, key: &str, update: impl Fn(Option<&str>) -> Result<String>) -> Result<String> {
+        self.expire_if_due(key)?;
+        loop {
+            let old = self.tree.get(key)?;
+            let new = update(old.as_ref().map(|v| String::from_utf8_lossy(v)).as_deref())?;
+            if self.tree.compare_and_swap(key, old, Some(new.as_bytes()))?.is_ok() {
+                return Ok(new);
+            }
+        }
+    }
 }
 
 impl KVEngineStoreTrait for SledEngine {
     fn get(&self, key: &str) -> Option<String> {
+        if self.is_expired(key) {
+            return None;
+        }
         match self.tree.get(key) {
             Ok(opt) => Self::to_string_opt(opt),
             Err(_) => None,
@@ -29,15 +93,32 @@ impl KVEngineStoreTrait for SledEngine {
     }
 
     fn set(&self, key: String, value: String) -> Result<()> {
-        self.tree.insert(key.as_bytes(), value.as_bytes())?;
-        Ok(())
+        // A plain write makes the key persistent again
+        self.transact(|t, e| {
+            t.insert(key.as_bytes(), value.as_bytes())?;
+            e.remove(key.as_bytes())?;
+            Ok(())
+        })
     }
 
     fn delete(&self, key: &str) -> bool {
-        match self.tree.remove(key) {
-            Ok(opt) => opt.is_some(),
-            Err(_) => false,
+        let expired = self.expire_if_due(key).unwrap_or(false);
+        let removed = self.transact(|t, e| {
+            e.remove(key)?;
+            Ok(t.remove(key)?.is_some())
+        });
+        removed.unwrap_or(false) && !expired
+    }
+
+    fn unlink(&self, key: &str) -> Option<String> {
+        if self.expire_if_due(key).unwrap_or(false) {
+            return None;
         }
+        let removed = self.transact(|t, e| {
+            e.remove(key)?;
+            Ok(t.remove(key)?)
+        });
+        removed.ok().and_then(Self::to_string_opt)
     }
 
     fn keys(&self) -> Vec<String> {
@@ -48,6 +129,16 @@ impl KVEngineStoreTrait for SledEngine {
             .collect()
     }
 
+    fn random_key(&self) -> Option<String> {
+        // The tree's length is not known up front, so this is reservoir sampling
+        self.tree
+            .iter()
+            .keys()
+            .filter_map(|r| r.ok())
+            .choose(&mut rand::thread_rng())
+            .and_then(|k| String::from_utf8(k.to_vec()).ok())
+    }
+
     fn len(&self) -> usize {
         self.tree.len()
     }
@@ -79,6 +170,9 @@ impl KVEngineStoreTrait for SledEngine {
         self.tree.len()
     }
     fn exists(&self, key: &str) -> bool {
+        if self.is_expired(key) {
+            return false;
+        }
         match self.tree.get(key) {
             Ok(opt) => opt.is_some(),
             Err(_) => false,
@@ -91,49 +185,166 @@ impl KVEngineStoreTrait for SledEngine {
     }
     fn increment(&self, key: &str, amount: Option<i64>) -> Result<i64> {
         let amt = amount.unwrap_or(1);
-        // get current
-        let current = match self.tree.get(key) {
-            Ok(Some(v)) => {
-                let s = String::from_utf8_lossy(&v).to_string();
-                s.parse::<i64>().map_err(|e| anyhow!("parse int error: {}", e))?
-            }
-            Ok(None) => 0,
-            Err(e) => return Err(anyhow!(e)),
-        };
-        let new = current + amt;
-        self.tree.insert(key.as_bytes(), new.to_string().as_bytes())?;
-        Ok(new)
+        let new = self.read_modify_write(key, |current| {
+            let current = match current {
+                Some(v) => v.parse::<i64>().map_err(|_| anyhow!(NOT_AN_INTEGER))?,
+                None => 0,
+            };
+            Ok(current.checked_add(amt).ok_or_else(|| anyhow!(OVERFLOW))?.to_string())
+        })?;
+        Ok(new.parse()?)
     }
 
     fn decrement(&self, key: &str, amount: Option<i64>) -> Result<i64> {
         let dec = amount.unwrap_or(1);
-        self.increment(key, Some(-dec))
+        let negated = dec.checked_neg().ok_or_else(|| anyhow!(OVERFLOW))?;
+        self.increment(key, Some(negated))
+    }
+
+    fn increment_limit(&self, key: &str, amount: i64, limit: i64) -> Result<(i64, bool)> {
+        self.expire_if_due(key)?;
+        // Retry the compare-and-swap until no other writer got in between
+        loop {
+            let old = self.tree.get(key)?;
+            let current = match &old {
+                Some(v) => String::from_utf8_lossy(v)
+                    .parse::<i64>()
+                    .map_err(|_| anyhow!(NOT_AN_INTEGER))?,
+                None => 0,
+            };
+            let new = current
+                .checked_add(amount)
+                .ok_or_else(|| anyhow!(OVERFLOW))?;
+            let swapped = self
+                .tree
+                .compare_and_swap(key, old, Some(new.to_string().as_bytes()))?;
+            if swapped.is_ok() {
+                return Ok((new, new <= limit));
+            }
+        }
     }
 
     fn append(&self, key: &str, value: &str) -> Result<String> {
-        let current = match self.tree.get(key) {
-            Ok(Some(v)) => String::from_utf8_lossy(&v).to_string(),
-            Ok(None) => String::new(),
-            Err(e) => return Err(anyhow!(e)),
-        };
-        let new = format!("{}{}", current, value);
-        self.tree.insert(key.as_bytes(), new.as_bytes())?;
-        Ok(new)
+        self.read_modify_write(key, |current| Ok(format!("{}{}", current.unwrap_or(""), value)))
     }
 
     fn prepend(&self, key: &str, value: &str) -> Result<String> {
-        let current = match self.tree.get(key) {
-            Ok(Some(v)) => String::from_utf8_lossy(&v).to_string(),
-            Ok(None) => String::new(),
-            Err(e) => return Err(anyhow!(e)),
+        self.read_modify_write(key, |current| Ok(format!("{}{}", value, current.unwrap_or(""))))
+    }
+
+    fn compare_and_swap(&self, key: &str, expected: &str, new: &str) -> Result<bool> {
+        self.expire_if_due(key)?;
+        // An empty `expected` matches a missing key (create-if-absent)
+        let old = if expected.is_empty() && !self.tree.contains_key(key)? {
+            None
+        } else {
+            Some(expected.as_bytes())
         };
-        let new = format!("{}{}", value, current);
-        self.tree.insert(key.as_bytes(), new.as_bytes())?;
-        Ok(new)
+        let swapped = self.tree.compare_and_swap(key, old, Some(new.as_bytes()))?;
+        Ok(swapped.is_ok())
+    }
+
+    fn set_many(&self, pairs: &[(String, String)]) -> Result<()> {
+        validate_batch(pairs)?;
+        // sled applies a batch atomically
+        let mut batch = sled::Batch::default();
+        let mut persist = sled::Batch::default();
+        for (key, value) in pairs {
+            batch.insert(key.as_bytes(), value.as_bytes());
+            persist.remove(key.as_bytes());
+        }
+        self.transact(|t, e| {
+            t.apply_batch(&batch)?;
+            e.apply_batch(&persist)?;
+            Ok(())
+        })
+    }
+
+    fn set_with_expiry(&self, key: String, value: String, deadline_ms: u64) -> Result<()> {
+        self.transact(|t, e| {
+            t.insert(key.as_bytes(), value.as_bytes())?;
+            e.insert(key.as_bytes(), &deadline_ms.to_be_bytes())?;
+            Ok(())
+        })
+    }
+
+    fn set_many_with_expiry(&self, entries: &[(String, String, Option<u64>)]) -> Result<()> {
+        validate_batch_keys(entries.iter().map(|(k, _, _)| k.as_str()))?;
+        let mut batch = sled::Batch::default();
+        let mut deadlines = sled::Batch::default();
+        for (key, value, deadline_ms) in entries {
+            batch.insert(key.as_bytes(), value.as_bytes());
+            match deadline_ms {
+                Some(deadline_ms) => deadlines.insert(key.as_bytes(), &deadline_ms.to_be_bytes()),
+                None => deadlines.remove(key.as_bytes()),
+            }
+        }
+        self.transact(|t, e| {
+            t.apply_batch(&batch)?;
+            e.apply_batch(&deadlines)?;
+            Ok(())
+        })
+    }
+
+    fn expiry(&self, key: &str) -> Option<u64> {
+        self.expiries.get(key).ok().flatten().and_then(|v| Self::decode_deadline(&v))
+    }
+
+    fn get_and_touch_expiry(&self, key: &str, deadline_ms: Option<u64>) -> Option<String> {
+        let value = self.transact(|t, e| {
+            let due = e
+                .get(key)?
+                .and_then(|v| Self::decode_deadline(&v))
+                .is_some_and(|d| d <= now_millis());
+            if due {
+                e.remove(key)?;
+                t.remove(key)?;
+                return Ok(None);
+            }
+            let Some(value) = t.get(key)? else {
+                return Ok(None);
+            };
+            match deadline_ms {
+                Some(deadline_ms) => e.insert(key.as_bytes(), &deadline_ms.to_be_bytes())?,
+                None => e.remove(key)?,
+            };
+            Ok(Some(value))
+        });
+        Self::to_string_opt(value.ok().flatten())
+    }
+
+    fn purge_expired(&self) -> Vec<String> {
+        let now = now_millis();
+        let due: Vec<String> = self
+            .expiries
+            .iter()
+            .filter_map(|r| r.ok())
+            .filter(|(_, v)| Self::decode_deadline(v).is_some_and(|d| d <= now))
+            .filter_map(|(k, _)| String::from_utf8(k.to_vec()).ok())
+            .collect();
+        due.into_iter()
+            .filter(|key| self.expire_if_due(key).unwrap_or(false))
+            .collect()
+    }
+
+    fn keyspace_stats(&self) -> KeyspaceStats {
+        // A single tree iterator; sled has no read lock to hold across it
+        let mut stats = KeyspaceStats::default();
+        for (key, value) in self.tree.iter().filter_map(|r| r.ok()) {
+            stats.record(&String::from_utf8_lossy(&key), &String::from_utf8_lossy(&value));
+        }
+        stats
+    }
+
+    fn for_each_entry(&self, visit: &mut dyn FnMut(&str, &str)) {
+        for (key, value) in self.tree.iter().filter_map(|r| r.ok()) {
+            visit(&String::from_utf8_lossy(&key), &String::from_utf8_lossy(&value));
+        }
     }
 
     fn truncate(&self) -> Result<()> {
         self.tree.clear()?;
+        self.expiries.clear()?;
         Ok(())
     }
 
@@ -145,4 +356,13 @@ impl KVEngineStoreTrait for SledEngine {
         self.db.flush()?;
         Ok(())
     }
+
+    fn compact(&self) -> Result<()> {
+        // Expired entries still occupy the log until something removes them
+        self.purge_expired();
+        // Flushing writes out the pending deletes, letting sled's segment
+        // cleaner rewrite and free the segments they emptied
+        self.db.flush()?;
+        Ok(())
+    }
 }
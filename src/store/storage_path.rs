@@ -0,0 +1,91 @@
+//! # Storage Path Checks
+//!
+//! Engines receive `storage_path` from the configuration. A persistent engine
+//! needs a directory it can write to; catching a bad path at startup gives a
+//! clear error instead of a failure deep inside the engine (or, for the
+//! in-memory engines, a setting that is silently ignored).
+
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+/// Name of the probe file written to check that a directory is writable.
+const WRITE_PROBE: &str = ".merkle_kv_write_probe";
+
+/// Make sure `path` is a writable directory, creating it (and any missing
+/// parents) first if needed.
+pub fn prepare_storage_dir(path: &str) -> Result<()> {
+    if path.is_empty() {
+        return Err(anyhow!("storage path is empty"));
+    }
+    let dir = Path::new(path);
+    check_is_dir(dir)?;
+    fs::create_dir_all(dir).map_err(|e| anyhow!("cannot create storage directory {}: {}", path, e))?;
+
+    let probe = dir.join(WRITE_PROBE);
+    fs::write(&probe, b"").map_err(|e| anyhow!("storage directory {} is not writable: {}", path, e))?;
+    fs::remove_file(&probe).map_err(|e| anyhow!("storage directory {} is not writable: {}", path, e))?;
+    Ok(())
+}
+
+/// Check the `storage_path` of an engine that keeps nothing on disk: the path
+/// is not created, but if it exists it must be a directory. An empty path is
+/// accepted.
+pub fn validate_storage_dir(path: &str) -> Result<()> {
+    if path.is_empty() {
+        return Ok(());
+    }
+    check_is_dir(Path::new(path))
+}
+
+fn check_is_dir(dir: &Path) -> Result<()> {
+    if dir.exists() && !dir.is_dir() {
+        return Err(anyhow!("storage path {} is not a directory", dir.display()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{KvEngine, RwLockEngine, SledEngine};
+
+    #[test]
+    fn test_prepare_creates_missing_directories() {
+        let temp = tempfile::tempdir().unwrap();
+        let nested = temp.path().join("a/b/c");
+        prepare_storage_dir(nested.to_str().unwrap()).unwrap();
+        assert!(nested.is_dir());
+        assert!(!nested.join(WRITE_PROBE).exists());
+        // An existing directory is fine too
+        prepare_storage_dir(nested.to_str().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_unusable_paths_are_rejected() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("file");
+        fs::write(&file, "x").unwrap();
+        let file = file.to_str().unwrap();
+        // Permission bits do not stop root, so a path below a plain file
+        // stands in for one that cannot be written
+        let below_file = format!("{}/data", file);
+
+        assert!(prepare_storage_dir("").is_err());
+        let err = prepare_storage_dir(file).unwrap_err().to_string();
+        assert!(err.contains("is not a directory"), "{}", err);
+        let err = prepare_storage_dir(&below_file).unwrap_err().to_string();
+        assert!(err.contains("cannot create storage directory"), "{}", err);
+
+        let err = SledEngine::new(&below_file).err().unwrap().to_string();
+        assert!(err.contains("cannot create storage directory"), "{}", err);
+        assert!(SledEngine::new(file).is_err());
+
+        // The in-memory engines only insist that an existing path is a directory
+        assert!(RwLockEngine::new(file).is_err());
+        assert!(KvEngine::new(file).is_err());
+        assert!(RwLockEngine::new("").is_ok());
+        assert!(KvEngine::new(temp.path().to_str().unwrap()).is_ok());
+        assert!(RwLockEngine::new(&format!("{}/missing", temp.path().display())).is_ok());
+    }
+}
@@ -1,20 +1,26 @@
 use anyhow::{anyhow, Context, Result};
 use log::{debug, info};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     future::Future,
+    ops::AddAssign,
     pin::Pin,
-    sync::Arc,
-    time::Duration,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     net::TcpStream,
-    sync::Mutex,
     time,
 };
 
+use crate::access::AccessTracker;
+use crate::change_event::ChangeEvent;
 use crate::config::Config;
+use crate::store::binary_value::BINARY_VALUE;
 use crate::store::merkle::MerkleTree;
 use crate::store::KVEngineStoreTrait;
 
@@ -24,34 +30,155 @@ const FANOUT: &[u8] =
 const DEFAULT_MAX_DEPTH: usize = 20;
 const DEFAULT_LEAF_THRESHOLD: usize = 200;
 
+/// What one `sync_once` run did. SYNC pulls: the remote is treated as the
+/// source of truth and only the local store is modified.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Local keys written or deleted to match the remote
+    pub keys_pulled: usize,
+    /// Remote keys modified; always 0 while SYNC is pull-only
+    pub keys_pushed: usize,
+    /// Pulled keys that held a different value on both sides
+    pub conflicts_resolved: usize,
+    /// Bytes of remote values written locally
+    pub bytes: usize,
+}
+
+impl SyncReport {
+    /// Keys changed on either side.
+    pub fn keys_reconciled(&self) -> usize {
+        self.keys_pulled + self.keys_pushed
+    }
+}
+
+impl AddAssign for SyncReport {
+    fn add_assign(&mut self, other: Self) {
+        self.keys_pulled += other.keys_pulled;
+        self.keys_pushed += other.keys_pushed;
+        self.conflicts_resolved += other.conflicts_resolved;
+        self.bytes += other.bytes;
+    }
+}
+
 pub struct SyncManager {
-    store: Arc<Mutex<Box<dyn KVEngineStoreTrait + Send + Sync>>>,
+    store: Arc<dyn KVEngineStoreTrait + Send + Sync>,
+    #[allow(dead_code)]
     sync_interval: Duration,
     max_depth: usize,
     leaf_threshold: usize,
+    /// Limit on connecting to a peer and on each reply; `None` waits forever
+    timeout: Option<Duration>,
 }
 
 impl SyncManager {
     pub fn new_with_shared_store(
         cfg: &Config,
-        store: Arc<Mutex<Box<dyn KVEngineStoreTrait + Send + Sync>>>,
+        store: Arc<dyn KVEngineStoreTrait + Send + Sync>,
     ) -> Self {
         Self {
             store,
             sync_interval: Duration::from_secs(cfg.sync_interval_seconds),
             max_depth: DEFAULT_MAX_DEPTH,
             leaf_threshold: DEFAULT_LEAF_THRESHOLD,
+            timeout: (cfg.sync.timeout_ms != 0).then(|| Duration::from_millis(cfg.sync.timeout_ms)),
         }
     }
 
-    /// One-shot: sync local with remote at host:port
-    pub async fn sync_once(&self, host: &str, port: u16) -> Result<()> {
+    /// One-shot: sync local with remote at host:port.
+    /// Returns what was reconciled.
+    pub async fn sync_once(&self, host: &str, port: u16) -> Result<SyncReport> {
         let addr = format!("{host}:{port}");
         info!("SYNC (recursive Merkle) → {}", addr);
         self.sync_prefix_recursive(&addr, String::new(), 0).await
     }
 
+    /// One-shot, read-only: list keys under `prefix` whose value differs
+    /// between the local store and the remote at host:port, including keys
+    /// present on only one side. Sorted; nothing is written.
+    pub async fn diff_once(&self, host: &str, port: u16, prefix: &str) -> Result<Vec<String>> {
+        let addr = format!("{host}:{port}");
+        info!("DIFF (recursive Merkle) → {} prefix={:?}", addr, prefix);
+        let mut keys = self.diff_prefix_recursive(&addr, prefix.to_string(), 0).await?;
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// Dry run of `sync_once`: count what it would change without writing.
+    /// SYNC only pulls, so every divergent key, including local keys the peer
+    /// lacks (which a real run deletes), counts as pulled and none as pushed.
+    pub async fn diff_only(&self, host: &str, port: u16) -> Result<SyncReport> {
+        let keys = self.diff_once(host, port, "").await?;
+        Ok(SyncReport {
+            keys_pulled: keys.len(),
+            ..SyncReport::default()
+        })
+    }
+
+    /// Pull from several peers at once. Keys that differ from any peer are
+    /// fetched from every reachable peer, and the value with the most recent
+    /// write timestamp wins; the local value is kept when `access` knows of a
+    /// write at least as recent. Unlike `sync_once`, a key missing from a peer
+    /// is not deleted locally, since a deletion carries no timestamp to
+    /// compare against.
+    ///
+    /// Nothing is changed unless a majority of `peers` is reachable. Returns
+    /// one report per peer, in order; pulled keys are credited to the peer
+    /// whose value won.
+    pub async fn sync_many(
+        &self,
+        peers: &[(String, u16)],
+        access: &AccessTracker,
+    ) -> Result<Vec<(String, Result<SyncReport>)>> {
+        let mut results = Vec::with_capacity(peers.len());
+        let mut divergent = BTreeSet::new();
+        for (host, port) in peers {
+            let result = self.diff_once(host, *port, "").await.map(|keys| {
+                divergent.extend(keys);
+                SyncReport::default()
+            });
+            results.push((format!("{host}:{port}"), result));
+        }
+
+        let reachable = results.iter().filter(|(_, r)| r.is_ok()).count();
+        if reachable <= peers.len() / 2 {
+            return Err(anyhow!("quorum not reached: {} of {} peers reachable", reachable, peers.len()));
+        }
+        info!("SYNC PEERS → {} divergent keys across {} peers", divergent.len(), reachable);
+
+        let store = &*self.store;
+        for key in divergent {
+            // (peer index, value, write timestamp) of the freshest value seen so far
+            let mut winner: Option<(usize, String, u64)> = None;
+            for (i, (addr, result)) in results.iter_mut().enumerate() {
+                if result.is_err() {
+                    continue;
+                }
+                match self.remote_dump_versioned(addr, &key).await {
+                    Ok(Some((value, ts))) if winner.as_ref().is_none_or(|(_, _, best)| ts > *best) => {
+                        winner = Some((i, value, ts));
+                    }
+                    Ok(_) => {}
+                    Err(e) => *result = Err(e),
+                }
+            }
+            let Some((i, value, ts)) = winner else { continue };
+            if store.get(&key).is_some() && access.last_write(&key).is_some_and(|local| local >= ts) {
+                continue;
+            }
+            if let Ok(report) = &mut results[i].1 {
+                let pulled = report.keys_pulled;
+                apply_remote(store, &key, Some(value), report);
+                if report.keys_pulled > pulled {
+                    access.touch_write_at(&key, ts);
+                }
+            }
+        }
+        Ok(results)
+    }
+
     /// sync loop
+    #[allow(dead_code)]
     pub async fn start_sync_loop(&self, host: String, port: u16) {
         let mut interval = time::interval(self.sync_interval);
         let addr = format!("{host}:{port}");
@@ -72,7 +199,7 @@ impl SyncManager {
         addr: &'a str,
         prefix: String,
         depth: usize,
-    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+    ) -> Pin<Box<dyn Future<Output = Result<SyncReport>> + Send + 'a>> {
         Box::pin(async move {
             // 1) Hash local/remote with prefix
             let local_hex = self.local_merkle_hex(&prefix).await?;
@@ -83,26 +210,82 @@ impl SyncManager {
 
             if local_hex == remote_hex {
                 // If equal → skip this branch.
-                return Ok(());
+                return Ok(SyncReport::default());
             }
 
             // 2) If at leaf (max depth) → reconcile directly
             if depth >= self.max_depth {
-                self.reconcile_leaf(addr, &prefix).await?;
-                return Ok(());
+                return self.reconcile_leaf(addr, &prefix).await;
             }
 
-            // 3) If not at leaf → split recursively by next character in FANOUT
+            // 3) The key equal to the prefix itself falls under no child branch
+            let mut report = if prefix.is_empty() {
+                SyncReport::default()
+            } else {
+                self.reconcile_key(addr, &prefix).await?
+            };
+
+            // 4) If not at leaf → split recursively by next character in FANOUT
             for &ch in FANOUT {
                 let mut sub = prefix.clone();
                 sub.push(ch as char);
-                self.sync_prefix_recursive(addr, sub, depth + 1).await?;
+                report += self.sync_prefix_recursive(addr, sub, depth + 1).await?;
             }
 
-            Ok(())
+            Ok(report)
         })
     }
 
+    /// Same descent as `sync_prefix_recursive`, collecting differing keys
+    /// instead of reconciling them.
+    fn diff_prefix_recursive<'a>(
+        &'a self,
+        addr: &'a str,
+        prefix: String,
+        depth: usize,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<String>>> + Send + 'a>> {
+        Box::pin(async move {
+            let local_hex = self.local_merkle_hex(&prefix).await?;
+            let remote_hex = self.remote_hash_hex(addr, &prefix).await?;
+            if local_hex == remote_hex {
+                return Ok(Vec::new());
+            }
+
+            if depth >= self.max_depth {
+                return self.diff_leaf(addr, &prefix).await;
+            }
+
+            let mut diffs = Vec::new();
+            if !prefix.is_empty() {
+                let remote = self.remote_get(addr, &prefix).await?;
+                if self.store.get(&prefix) != remote {
+                    diffs.push(prefix.clone());
+                }
+            }
+
+            for &ch in FANOUT {
+                let mut sub = prefix.clone();
+                sub.push(ch as char);
+                diffs.extend(self.diff_prefix_recursive(addr, sub, depth + 1).await?);
+            }
+
+            Ok(diffs)
+        })
+    }
+
+    /// Compare a whole prefix by fetching the remote keys and values and
+    /// diffing the two Merkle trees leaf by leaf.
+    async fn diff_leaf(&self, addr: &str, prefix: &str) -> Result<Vec<String>> {
+        let mut remote = MerkleTree::new();
+        for k in self.remote_scan_keys(addr, prefix).await? {
+            if let Some(v) = self.remote_get(addr, &k).await? {
+                remote.insert(&k, &v);
+            }
+        }
+        let (local, _map) = self.build_local_merkle_snapshot(prefix).await?;
+        Ok(local.diff_keys(&remote))
+    }
+
     // ─────────────────── Support function ───────────────────
 
     /// Compute Merkle root (hex) for a prefix on the LOCAL store (using existing MerkleTree).
@@ -121,7 +304,7 @@ impl SyncManager {
         let mut t = MerkleTree::new();
         let mut map = HashMap::new();
 
-        let store = self.store.lock().await;
+        let store = &*self.store;
         let keys = store.scan(prefix); 
         for k in keys {
             if let Some(v) = store.get(&k) {
@@ -132,7 +315,7 @@ impl SyncManager {
         Ok((t, map))
     }
     /// Reconcile a prefix by SCAN + GET from remote, then apply to local store.
-    async fn reconcile_leaf(&self, addr: &str, prefix: &str) -> Result<()> {
+    async fn reconcile_leaf(&self, addr: &str, prefix: &str) -> Result<SyncReport> {
         info!("RECONCILE prefix={:?}", prefix);
 
         let remote_keys = self.remote_scan_keys(addr, prefix).await?;
@@ -151,28 +334,31 @@ impl SyncManager {
             remote_map.insert(k.clone(), self.remote_get(addr, k).await?);
         }
 
-        let mut store = self.store.lock().await;
+        let store = &*self.store;
+        let mut report = SyncReport::default();
 
-        for (k, maybe_v) in &remote_map {
-            match maybe_v {
-                Some(v) => {
-                    let _ = store.set(k.clone(), v.clone());
-                }
-                None => {
-                    let _ = store.delete(k);
-                }
-            }
+        let local_keys = store.scan(prefix);
+        for (k, maybe_v) in remote_map {
+            apply_remote(store, &k, maybe_v, &mut report);
         }
 
-        let local_keys = store.scan(prefix);
         let remote_set: HashSet<&String> = remote_keys.iter().collect();
         for lk in local_keys {
             if !remote_set.contains(&lk) {
-                let _ = store.delete(&lk);
+                apply_remote(store, &lk, None, &mut report);
             }
         }
 
-        Ok(())
+        Ok(report)
+    }
+
+    /// Reconcile a single key by GET from remote.
+    async fn reconcile_key(&self, addr: &str, key: &str) -> Result<SyncReport> {
+        let remote = self.remote_get(addr, key).await?;
+        let store = &*self.store;
+        let mut report = SyncReport::default();
+        apply_remote(store, key, remote, &mut report);
+        Ok(report)
     }
 
     // ─────────────────── WIRE I/O (REMOTE) ───────────────────
@@ -185,12 +371,16 @@ impl SyncManager {
         };
         let line = self.send_and_read_line(addr, &cmd).await?;
       
-        let parts: Vec<&str> = line.trim_end().split_whitespace().collect();
+        let parts: Vec<&str> = line.split_whitespace().collect();
         if parts.is_empty() || parts[0] != "HASH" {
             return Err(anyhow!("unexpected HASH response: {}", line.trim_end()));
         }
        
         let hex = parts.last().unwrap().to_string();
+        if hex == "EMPTY" {
+            // Same sentinel as `local_merkle_hex` for a prefix with no keys
+            return Ok("0".repeat(64));
+        }
         if hex.len() != 64 {
             return Err(anyhow!("bad HASH hex length: {}", hex));
         }
@@ -201,9 +391,7 @@ impl SyncManager {
     async fn remote_scan_keys(&self, addr: &str, prefix: &str) -> Result<Vec<String>> {
         let cmd = format!("SCAN {prefix}\r\n");
         debug!("→ {} : {}", addr, cmd.trim_end());
-        let mut stream = TcpStream::connect(addr)
-            .await
-            .with_context(|| format!("connect {}", addr))?;
+        let mut stream = self.connect(addr).await?;
         stream
             .write_all(cmd.as_bytes())
             .await
@@ -213,7 +401,7 @@ impl SyncManager {
 
         // header
         let mut header = String::new();
-        let n = reader.read_line(&mut header).await?;
+        let n = self.read_line(&mut reader, &mut header).await?;
         if n == 0 {
             return Err(anyhow!("peer closed while reading SCAN header"));
         }
@@ -232,7 +420,7 @@ impl SyncManager {
         let mut keys = Vec::with_capacity(count);
         for _ in 0..count {
             let mut line = String::new();
-            let n = reader.read_line(&mut line).await?;
+            let n = self.read_line(&mut reader, &mut line).await?;
             if n == 0 {
                 return Err(anyhow!("peer closed while reading key list"));
             }
@@ -252,14 +440,43 @@ impl SyncManager {
         if let Some(rest) = line.strip_prefix("VALUE ") {
             return Ok(Some(rest.to_string()));
         }
+        if line.starts_with("ERROR WRONGTYPE") || line.strip_prefix("ERROR ") == Some(BINARY_VALUE) {
+            // Hashes and BSET values are not readable with GET; fetch the raw value via DUMP
+            return self.remote_dump(addr, key).await.map(Some);
+        }
         Err(anyhow!("unexpected GET response for {key}: {}", line))
     }
 
+    /// DUMP key → raw stored value (used for non-string types such as hashes)
+    async fn remote_dump(&self, addr: &str, key: &str) -> Result<String> {
+        self.remote_dump_versioned(addr, key)
+            .await?
+            .map(|(value, _)| value)
+            .ok_or_else(|| anyhow!("unexpected DUMP response for {key}: NOT_FOUND"))
+    }
+
+    /// DUMP key → raw stored value and its last write timestamp (unix nanos),
+    /// or `None` if the key does not exist remotely
+    async fn remote_dump_versioned(&self, addr: &str, key: &str) -> Result<Option<(String, u64)>> {
+        let cmd = format!("DUMP {key}\r\n");
+        let line = self.send_and_read_line(addr, &cmd).await?;
+        let line = line.trim_end();
+        if line == "NOT_FOUND" {
+            return Ok(None);
+        }
+        let payload = line
+            .strip_prefix("DUMP ")
+            .ok_or_else(|| anyhow!("unexpected DUMP response for {key}: {}", line))?;
+        let bytes = hex::decode(payload).context("invalid DUMP hex")?;
+        let ev = ChangeEvent::from_bincode(&bytes).context("invalid DUMP payload")?;
+        let val = ev.val.ok_or_else(|| anyhow!("DUMP payload for {key} has no value"))?;
+        let value = String::from_utf8(val).context("DUMP value is not UTF-8")?;
+        Ok(Some((value, ev.ts)))
+    }
+
     async fn send_and_read_line(&self, addr: &str, cmd: &str) -> Result<String> {
         debug!("→ {} : {}", addr, cmd.trim_end());
-        let mut stream = TcpStream::connect(addr)
-            .await
-            .with_context(|| format!("connect {}", addr))?;
+        let mut stream = self.connect(addr).await?;
         stream
             .write_all(cmd.as_bytes())
             .await
@@ -267,13 +484,112 @@ impl SyncManager {
 
         let mut reader = BufReader::new(stream);
         let mut line = String::new();
-        let n = reader.read_line(&mut line).await?;
+        let n = self.read_line(&mut reader, &mut line).await?;
         if n == 0 {
             return Err(anyhow!("peer closed connection"));
         }
         debug!("← {} : {}", addr, line.trim_end());
         Ok(line)
     }
+
+    async fn connect(&self, addr: &str) -> Result<TcpStream> {
+        self.within_timeout(async {
+            TcpStream::connect(addr)
+                .await
+                .with_context(|| format!("connect {}", addr))
+        })
+        .await
+    }
+
+    async fn read_line(&self, reader: &mut BufReader<TcpStream>, line: &mut String) -> Result<usize> {
+        self.within_timeout(async { Ok(reader.read_line(line).await?) }).await
+    }
+
+    /// Fail with `sync timeout` if `step` does not finish within the timeout,
+    /// so a silent or unreachable peer cannot stall the caller.
+    async fn within_timeout<T>(&self, step: impl Future<Output = Result<T>>) -> Result<T> {
+        match self.timeout {
+            Some(limit) => time::timeout(limit, step).await.map_err(|_| anyhow!("sync timeout"))?,
+            None => step.await,
+        }
+    }
+}
+
+/// Background anti-entropy: every `interval` (plus up to 10% jitter), pull
+/// from each peer in turn. A failing peer is logged and skipped until the
+/// next cycle. The manager is shared without a lock, so client `SYNC`
+/// commands run alongside the loop instead of waiting for it.
+///
+/// `writes` is bumped right before and right after each sync with a peer.
+pub async fn run_periodic_sync(
+    manager: Arc<SyncManager>,
+    peers: Vec<String>,
+    interval: Duration,
+    writes: Arc<AtomicU64>,
+) {
+    loop {
+        time::sleep(interval + jitter(interval)).await;
+
+        let mut reconciled = 0;
+        for peer in &peers {
+            let Some((host, port)) = parse_peer(peer) else {
+                log::warn!("background sync: invalid peer address {:?}", peer);
+                continue;
+            };
+            writes.fetch_add(1, Ordering::AcqRel);
+            let result = manager.sync_once(host, port).await;
+            writes.fetch_add(1, Ordering::AcqRel);
+            match result {
+                Ok(report) => reconciled += report.keys_reconciled(),
+                Err(e) => log::warn!("background sync with {} failed: {}", peer, e),
+            }
+        }
+        info!("background sync cycle reconciled {} keys across {} peers", reconciled, peers.len());
+    }
+}
+
+/// Make `key` match the remote value (`None` = absent remotely), recording
+/// the change in `report`.
+fn apply_remote(store: &dyn KVEngineStoreTrait, key: &str, remote: Option<String>, report: &mut SyncReport) {
+    let local = store.get(key);
+    match remote {
+        Some(v) if local.as_ref() != Some(&v) => {
+            let len = v.len();
+            if store.set(key.to_string(), v).is_ok() {
+                report.keys_pulled += 1;
+                report.bytes += len;
+                if local.is_some() {
+                    report.conflicts_resolved += 1;
+                }
+            }
+        }
+        Some(_) => {}
+        None => {
+            if store.delete(key) {
+                report.keys_pulled += 1;
+            }
+        }
+    }
+}
+
+/// Split a "host:port" peer address.
+fn parse_peer(peer: &str) -> Option<(&str, u16)> {
+    let (host, port) = peer.rsplit_once(':')?;
+    Some((host, port.parse().ok()?))
+}
+
+/// Random delay of up to 10% of `interval`, so nodes started together do not
+/// hit their peers in lockstep.
+fn jitter(interval: Duration) -> Duration {
+    let max_ms = (interval.as_millis() / 10) as u64;
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u64;
+    Duration::from_millis(seed % max_ms)
 }
 
 fn to_hex(bytes: &[u8]) -> String {
@@ -285,3 +601,25 @@ fn to_hex(bytes: &[u8]) -> String {
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_peer() {
+        assert_eq!(parse_peer("127.0.0.1:7379"), Some(("127.0.0.1", 7379)));
+        assert_eq!(parse_peer("node-b:80"), Some(("node-b", 80)));
+        assert_eq!(parse_peer("no-port"), None);
+        assert_eq!(parse_peer("host:notaport"), None);
+    }
+
+    #[test]
+    fn test_jitter_bounded() {
+        let interval = Duration::from_secs(10);
+        for _ in 0..100 {
+            assert!(jitter(interval) < Duration::from_secs(1));
+        }
+        assert_eq!(jitter(Duration::ZERO), Duration::ZERO);
+    }
+}
@@ -98,7 +98,7 @@ def test_sync(local_host, local_port, remote_host, remote_port, verify=False):
         # SYNC: local <- remote
         cmd = f"SYNC {remote_host} {remote_port}" + (" --verify" if verify else "")
         resp = send_cmd(sl, cmd)
-        assert resp == "OK", f"SYNC resp={resp!r}"
+        assert resp.startswith("SYNCED "), f"SYNC resp={resp!r}"
 
         # Sau SYNC: local đã có key của remote
         assert send_cmd(sl, "GET r:1") == "VALUE Ralpha"
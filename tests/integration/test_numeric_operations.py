@@ -62,7 +62,7 @@ class TestNumericOperations:
         # Try to increment
         response = connected_client.increment("text_key")
         assert "ERROR" in response
-        assert "not a valid number" in response
+        assert "not an integer" in response
         
         # Verify the value was not changed
         response = connected_client.get("text_key")
@@ -115,7 +115,7 @@ class TestNumericOperations:
         # Try to decrement
         response = connected_client.decrement("text_key2")
         assert "ERROR" in response
-        assert "not a valid number" in response
+        assert "not an integer" in response
         
         # Verify the value was not changed
         response = connected_client.get("text_key2")
@@ -734,6 +734,42 @@ async def test_malformed_mqtt_message_handling(unique_topic_prefix):
         if config1.exists():
             config1.unlink()
 
+@pytest.mark.asyncio
+async def test_wait_for_replica_acknowledgement(unique_topic_prefix):
+    """Test that WAIT counts a replica once it has applied the primary's writes."""
+    config1 = create_simple_replication_config(7398, "primary", unique_topic_prefix)
+    config2 = create_simple_replication_config(7399, "replica", unique_topic_prefix)
+
+    server1 = None
+    server2 = None
+
+    try:
+        # Without a replica nobody acknowledges and WAIT times out
+        server1 = await start_simple_server(config1)
+        await asyncio.sleep(8)
+        assert await execute_simple_command("127.0.0.1", 7398, "SET wait_key before") == "OK"
+        assert await execute_simple_command("127.0.0.1", 7398, "WAIT 1 500") == "WAIT 0"
+
+        server2 = await start_simple_server(config2)
+        await asyncio.sleep(8)
+
+        test_key = f"wait_test_{uuid.uuid4().hex[:8]}"
+        assert await execute_simple_command("127.0.0.1", 7398, f"SET {test_key} acked") == "OK"
+        result = await execute_simple_command("127.0.0.1", 7398, "WAIT 1 5000")
+        assert result == "WAIT 1", f"Expected WAIT 1, got {result}"
+
+        # The acknowledged write is readable on the replica
+        result = await _eventually_get_async(7399, test_key, "VALUE acked")
+        assert result == "VALUE acked"
+
+        print("✅ WAIT acknowledgement test passed")
+
+    finally:
+        cleanup_servers(server1, server2)
+        for config_file in [config1, config2]:
+            if config_file.exists():
+                config_file.unlink()
+
 if __name__ == "__main__":
     # Run specific test
     import sys